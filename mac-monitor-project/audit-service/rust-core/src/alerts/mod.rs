@@ -0,0 +1,251 @@
+//! 高风险事件实时告警
+//!
+//! 定时同步每 30 秒才上传一轮。risk_level 为 2 的记录 (扫描告警、DLP 命中等) 写库后由
+//! `Database::subscribe_critical` 立即通知，这里马上推送到告警接口 (失败按退避重试)，
+//! 同时记入最近告警列表，GUI 通过 IPC `get_alerts` (可带 `wait_ms` 长轮询) 获取。
+//! 原记录仍由定时同步上传，告警推送失败不影响数据完整性。
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::{CriticalRecord, Database};
+use crate::lifecycle::{ShutdownSignal, TaskTracker};
+use crate::metrics::Metrics;
+use crate::models::LogTable;
+use crate::uploader::{endpoints, Uploader};
+
+/// 首次推送失败后的重试间隔，用完后交给定时同步
+const RETRY_DELAYS: [Duration; 4] =
+    [Duration::from_millis(250), Duration::from_secs(1), Duration::from_secs(4), Duration::from_secs(15)];
+/// 保留的最近告警数
+const RECENT_CAPACITY: usize = 200;
+/// IPC 长轮询的最长等待
+pub const MAX_WAIT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertStatus {
+    Pending,
+    Delivered,
+    /// 重试用完或服务退出，原记录由定时同步上传
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    /// 本次运行内递增，GUI 据此增量获取
+    pub seq: u64,
+    /// 上报给服务端的告警 id，重试时不变，服务端可据此去重
+    pub alert_id: String,
+    pub table: LogTable,
+    pub record_id: String,
+    pub risk_level: i32,
+    pub raised_at: String,
+    pub record: serde_json::Value,
+    pub status: AlertStatus,
+    pub attempts: u32,
+}
+
+/// 推送到告警接口的内容
+#[derive(Serialize)]
+struct AlertUpload<'a> {
+    alert_id: &'a str,
+    table: LogTable,
+    record_id: &'a str,
+    risk_level: i32,
+    raised_at: &'a str,
+    record: &'a serde_json::Value,
+}
+
+/// IPC get_alerts 中的计数
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertStats {
+    /// 本次运行的告警数，也是最新告警的 seq
+    pub raised: u64,
+    pub delivered: u64,
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct Recent {
+    alerts: VecDeque<Alert>,
+    last_seq: u64,
+}
+
+pub struct AlertDispatcher {
+    uploader: Arc<Uploader>,
+    metrics: Arc<Metrics>,
+    clock: Arc<LogicalClock>,
+    tasks: Arc<TaskTracker>,
+    runtime_handle: Handle,
+    // 创建时即订阅，启动前写入的记录也不会漏掉
+    receiver: Mutex<Option<broadcast::Receiver<CriticalRecord>>>,
+    recent: Mutex<Recent>,
+    raised_cv: Condvar,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl AlertDispatcher {
+    pub fn new(
+        db: &Database,
+        uploader: Arc<Uploader>,
+        metrics: Arc<Metrics>,
+        clock: Arc<LogicalClock>,
+        tasks: Arc<TaskTracker>,
+        runtime_handle: Handle,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            uploader,
+            metrics,
+            clock,
+            tasks,
+            runtime_handle,
+            receiver: Mutex::new(Some(db.subscribe_critical())),
+            recent: Mutex::new(Recent::default()),
+            raised_cv: Condvar::new(),
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        })
+    }
+
+    /// 接收高风险记录并推送，收到停止信号后退出 (进行中的推送不再重试)
+    pub fn start(self: Arc<Self>, mut shutdown: ShutdownSignal) -> tokio::task::JoinHandle<()> {
+        let receiver = self.receiver.lock().unwrap().take();
+        tokio::spawn(async move {
+            let Some(mut receiver) = receiver else {
+                log::warn!("Alert dispatcher already started");
+                return;
+            };
+            loop {
+                let record = tokio::select! {
+                    record = receiver.recv() => record,
+                    _ = shutdown.wait() => break,
+                };
+                match record {
+                    Ok(record) => self.raise(record, &shutdown),
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("Alert dispatcher fell behind, {} critical records left to periodic sync", missed);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// `after` 之后的告警 (按 seq 升序)
+    pub fn alerts_after(&self, after: u64) -> Vec<Alert> {
+        let recent = self.recent.lock().unwrap();
+        recent.alerts.iter().filter(|a| a.seq > after).cloned().collect()
+    }
+
+    /// 没有新告警时阻塞等待，最多 `timeout` (不超过 `MAX_WAIT`)；只能在非异步线程调用
+    pub fn wait_after(&self, after: u64, timeout: Duration) -> Vec<Alert> {
+        let recent = self.recent.lock().unwrap();
+        let (recent, _) = self
+            .raised_cv
+            .wait_timeout_while(recent, timeout.min(MAX_WAIT), |r| r.last_seq <= after)
+            .unwrap();
+        recent.alerts.iter().filter(|a| a.seq > after).cloned().collect()
+    }
+
+    pub fn stats(&self) -> AlertStats {
+        AlertStats {
+            raised: self.recent.lock().unwrap().last_seq,
+            delivered: self.delivered.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn raise(self: &Arc<Self>, record: CriticalRecord, shutdown: &ShutdownSignal) {
+        let alert = {
+            let mut recent = self.recent.lock().unwrap();
+            recent.last_seq += 1;
+            let alert = Alert {
+                seq: recent.last_seq,
+                alert_id: uuid::Uuid::new_v4().to_string(),
+                table: record.table,
+                record_id: record.record_id,
+                risk_level: record.risk_level,
+                raised_at: format_ms(self.clock.now_ms()),
+                record: record.record,
+                status: AlertStatus::Pending,
+                attempts: 0,
+            };
+            if recent.alerts.len() >= RECENT_CAPACITY {
+                recent.alerts.pop_front();
+            }
+            recent.alerts.push_back(alert.clone());
+            alert
+        };
+        self.raised_cv.notify_all();
+        log::warn!("🚨 Critical {} record {} (risk {})", alert.table, alert.record_id, alert.risk_level);
+
+        let dispatcher = self.clone();
+        let shutdown = shutdown.clone();
+        let seq = alert.seq;
+        if !self.tasks.spawn(&self.runtime_handle, async move { dispatcher.deliver(alert, shutdown).await }) {
+            self.finish(seq, AlertStatus::Failed, 0);
+        }
+    }
+
+    async fn deliver(&self, alert: Alert, mut shutdown: ShutdownSignal) {
+        let upload = AlertUpload {
+            alert_id: &alert.alert_id,
+            table: alert.table,
+            record_id: &alert.record_id,
+            risk_level: alert.risk_level,
+            raised_at: &alert.raised_at,
+            record: &alert.record,
+        };
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match self.uploader.upload_data(endpoints::ALERT, &upload).await {
+                Ok(()) => {
+                    log::info!("Alert {} delivered after {} attempt(s)", alert.alert_id, attempts);
+                    self.finish(alert.seq, AlertStatus::Delivered, attempts);
+                    return;
+                }
+                Err(e) => e,
+            };
+            self.metrics.record_upload_failure(endpoints::ALERT);
+            let Some(delay) = RETRY_DELAYS.get(attempts as usize - 1) else {
+                log::warn!("Alert {} not delivered after {} attempts, left to periodic sync: {}", alert.alert_id, attempts, error);
+                self.finish(alert.seq, AlertStatus::Failed, attempts);
+                return;
+            };
+            log::warn!("Alert {} delivery failed (attempt {}), retrying in {:?}: {}", alert.alert_id, attempts, delay, error);
+            self.update(alert.seq, AlertStatus::Pending, attempts);
+            tokio::select! {
+                _ = tokio::time::sleep(*delay) => {}
+                _ = shutdown.wait() => {
+                    self.finish(alert.seq, AlertStatus::Failed, attempts);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn finish(&self, seq: u64, status: AlertStatus, attempts: u32) {
+        match status {
+            AlertStatus::Delivered => self.delivered.fetch_add(1, Ordering::Relaxed),
+            _ => self.failed.fetch_add(1, Ordering::Relaxed),
+        };
+        self.update(seq, status, attempts);
+    }
+
+    fn update(&self, seq: u64, status: AlertStatus, attempts: u32) {
+        let mut recent = self.recent.lock().unwrap();
+        if let Some(alert) = recent.alerts.iter_mut().find(|a| a.seq == seq) {
+            alert.status = status;
+            alert.attempts = attempts;
+        }
+    }
+}
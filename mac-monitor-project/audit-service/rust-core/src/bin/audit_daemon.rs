@@ -0,0 +1,382 @@
+//! 独立运行的审计服务与命令行工具
+//!
+//! `audit-daemon run --config <path>` 在前台运行与 Swift 宿主相同的服务 (数据库、同步、IPC)，
+//! 其余子命令通过 IPC socket 与正在运行的服务交互，用于 Linux / CI 等没有 GUI 宿主的环境。
+
+use audit_logic_core::evidence::{self, ExportFormat, ExportRequest};
+use audit_logic_core::ipc::{client, IpcResponse, SOCKET_PATH};
+use audit_logic_core::logging::{self, LoggingConfig};
+use audit_logic_core::models::{LogQuery, LogTable};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: audit-daemon [--socket <path>] <command> [options]
+
+Commands:
+  run --config <path>              Run the audit service in the foreground
+                                   (SIGTERM/SIGINT: stop, SIGHUP: reload config)
+  status                           Show service status and pending counts
+  sync-now [--timeout <secs>]      Upload pending logs now
+  query --table <t> [filters]      Print matching rows as JSON lines
+  export --table <t> --out <file> [--format jsonl|csv] [filters]
+                                   Write matching rows to a file
+  verify-chain                     Verify the tamper-evident log chain
+  evidence --out <name.tar.gz> [--tables <t>,<t>] [--since <time>] [--until <time>]
+           [--format jsonl|csv] [--no-screenshots]
+                                   Export a signed evidence bundle into the service's
+                                   evidence directory (default: all tables)
+  verify-bundle --bundle <file> [--public-key <hex>]
+                                   Check an evidence bundle's signature and file hashes
+                                   (runs locally, no service needed)
+  register --server-ip <ip> --server-port <port> --pin <pin>
+                                   Enroll this device with a management server
+  reenroll --pin <pin>             Request a new credential from the current server
+  unenroll [--force]               Unenroll and delete the stored credential
+                                   (--force: even if the server is unreachable)
+  enrollment                       Show enrollment status
+  network [--mode auto|constrained|normal]
+                                   Show upload bandwidth usage, or set the network mode
+  servers                          Show management servers with health, score and the active one
+  traffic                          Show stored / dropped / aggregated traffic record counts
+  usage [--day <YYYY-MM-DD>]       Show per-application foreground time (default: today)
+  alerts [--follow]                Show critical alerts raised since the service started
+                                   (--follow: keep waiting for new alerts)
+
+Tables: audit, behavior, screenshot, clipboard, file, peripheral, traffic_summary, usage_summary
+Filters: --since <time> --until <time> --unsent --limit <n>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<ExitCode, String> {
+    let mut args = Args::parse(args)?;
+    let socket = args.take("socket").unwrap_or_else(|| SOCKET_PATH.to_string());
+    let Some(command) = args.positional.first().cloned() else {
+        println!("{}", USAGE);
+        return Ok(ExitCode::FAILURE);
+    };
+
+    match command.as_str() {
+        "run" => {
+            let config = args.take("config").ok_or("run requires --config <path>")?;
+            args.finish()?;
+            run_daemon(Path::new(&config))
+        }
+        "status" => {
+            args.finish()?;
+            print_json(&client::call(&socket, "status", json!({}))?.payload);
+            Ok(ExitCode::SUCCESS)
+        }
+        "sync-now" => {
+            let timeout = args.take_parsed::<u64>("timeout")?.unwrap_or(60);
+            args.finish()?;
+            let response = client::request(&socket, "sync_now", json!({ "timeout_secs": timeout }))?;
+            report(response)
+        }
+        "query" => {
+            let query = args.query()?;
+            args.finish()?;
+            let rows = fetch_rows(&socket, &query)?;
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            for row in &rows {
+                writeln!(out, "{}", row).map_err(|e| e.to_string())?;
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        "export" => {
+            let out = args.take("out").ok_or("export requires --out <file>")?;
+            let format = args.take("format").unwrap_or_else(|| "jsonl".to_string());
+            let mut query = args.query()?;
+            query.limit = query.limit.or(Some(LogQuery::MAX_LIMIT));
+            args.finish()?;
+
+            let rows = fetch_rows(&socket, &query)?;
+            let text = match format.as_str() {
+                "jsonl" => rows.iter().map(|row| format!("{}\n", row)).collect(),
+                "csv" => evidence::to_csv(&rows),
+                other => return Err(format!("Unknown export format: {} (expected jsonl or csv)", other)),
+            };
+            std::fs::write(&out, text).map_err(|e| format!("Failed to write {}: {}", out, e))?;
+            println!("Exported {} {} rows to {}", rows.len(), query.table, out);
+            if rows.len() as u32 == query.effective_limit() {
+                eprintln!("warning: export hit the row limit ({}); narrow with --since/--until", query.effective_limit());
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        "verify-chain" => {
+            args.finish()?;
+            let response = client::request(&socket, "verify_chain", json!({}))?;
+            let intact = response.status == "ok";
+            print_json(&response.payload);
+            eprintln!("{}", response.message);
+            Ok(if intact { ExitCode::SUCCESS } else { ExitCode::from(2) })
+        }
+        "evidence" => {
+            let out = args.take("out").ok_or("evidence requires --out <file>")?;
+            let mut request = ExportRequest::new(out);
+            if let Some(tables) = args.take("tables") {
+                request.tables = tables.split(',').map(|t| t.trim().parse()).collect::<Result<_, String>>()?;
+            }
+            request.since = args.take("since");
+            request.until = args.take("until");
+            request.format = match args.take("format").as_deref() {
+                None | Some("jsonl") => ExportFormat::Jsonl,
+                Some("csv") => ExportFormat::Csv,
+                Some(other) => return Err(format!("Unknown export format: {} (expected jsonl or csv)", other)),
+            };
+            request.include_screenshots = !args.take_switch("no-screenshots");
+            args.finish()?;
+            let payload = serde_json::to_value(&request).map_err(|e| e.to_string())?;
+            let response = client::call(&socket, "export_evidence", payload)?;
+            print_json(&response.payload);
+            eprintln!("{}", response.message);
+            Ok(ExitCode::SUCCESS)
+        }
+        "verify-bundle" => {
+            let bundle = args.take("bundle").ok_or("verify-bundle requires --bundle <file>")?;
+            let public_key = args.take("public-key");
+            args.finish()?;
+            let report = evidence::verify_bundle(Path::new(&bundle), public_key.as_deref())?;
+            let intact = report.is_intact();
+            print_json(&Some(serde_json::to_value(&report).map_err(|e| e.to_string())?));
+            if public_key.is_none() {
+                eprintln!("note: no --public-key given; the signature is only checked against the key inside the bundle");
+            }
+            Ok(if intact { ExitCode::SUCCESS } else { ExitCode::from(2) })
+        }
+        "register" => {
+            let server_ip = args.take("server-ip").ok_or("register requires --server-ip")?;
+            let server_port = args.take("server-port").ok_or("register requires --server-port")?;
+            let pin = args.take("pin").ok_or("register requires --pin")?;
+            args.finish()?;
+            let payload = json!({
+                "server_ip": server_ip,
+                "server_port": server_port,
+                "pin": pin,
+            });
+            report(client::request(&socket, "register", payload)?)
+        }
+        "reenroll" => {
+            let pin = args.take("pin").ok_or("reenroll requires --pin")?;
+            args.finish()?;
+            report(client::request(&socket, "reenroll", json!({ "pin": pin }))?)
+        }
+        "unenroll" => {
+            let force = args.take_switch("force");
+            args.finish()?;
+            report(client::request(&socket, "unenroll", json!({ "force": force }))?)
+        }
+        "enrollment" => {
+            args.finish()?;
+            print_json(&client::call(&socket, "get_enrollment", json!({}))?.payload);
+            Ok(ExitCode::SUCCESS)
+        }
+        "network" => {
+            let mode = args.take("mode");
+            args.finish()?;
+            let response = match mode {
+                Some(mode) => client::call(&socket, "set_network_mode", json!({ "mode": mode }))?,
+                None => client::call(&socket, "get_bandwidth_status", json!({}))?,
+            };
+            print_json(&response.payload);
+            Ok(ExitCode::SUCCESS)
+        }
+        "servers" => {
+            args.finish()?;
+            print_json(&client::call(&socket, "get_servers", json!({}))?.payload);
+            Ok(ExitCode::SUCCESS)
+        }
+        "traffic" => {
+            args.finish()?;
+            print_json(&client::call(&socket, "get_traffic_stats", json!({}))?.payload);
+            Ok(ExitCode::SUCCESS)
+        }
+        "usage" => {
+            let day = args.take("day");
+            args.finish()?;
+            let payload = match day {
+                Some(day) => json!({ "day": day }),
+                None => json!({}),
+            };
+            print_json(&client::call(&socket, "get_app_usage", payload)?.payload);
+            Ok(ExitCode::SUCCESS)
+        }
+        "alerts" => {
+            let follow = args.take_switch("follow");
+            args.finish()?;
+            let response = client::call(&socket, "get_alerts", json!({}))?;
+            let payload = response.payload.unwrap_or(Value::Null);
+            if !follow {
+                print_json(&Some(payload));
+                return Ok(ExitCode::SUCCESS);
+            }
+            let mut after = payload["stats"]["raised"].as_u64().unwrap_or(0);
+            for alert in payload["alerts"].as_array().into_iter().flatten() {
+                println!("{}", alert);
+            }
+            loop {
+                let response = client::call(&socket, "get_alerts", json!({ "after": after, "wait_ms": 30_000 }))?;
+                let payload = response.payload.unwrap_or(Value::Null);
+                for alert in payload["alerts"].as_array().into_iter().flatten() {
+                    after = after.max(alert["seq"].as_u64().unwrap_or(0));
+                    println!("{}", alert);
+                }
+            }
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        other => Err(format!("Unknown command: {}\n\n{}", other, USAGE)),
+    }
+}
+
+fn run_daemon(config: &Path) -> Result<ExitCode, String> {
+    // 前台运行时同时输出到 stderr；配置文件中的 logging 段会覆盖
+    logging::init(&LoggingConfig { console: true, ..Default::default() })?;
+    audit_logic_core::start_service(config)?;
+
+    // 服务运行在库内部的运行时上，这里只用一个单线程运行时等待信号
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to create signal runtime: {}", e))?;
+    let (mut term, mut int, mut hup) = {
+        use tokio::signal::unix::{signal, SignalKind};
+        let _enter = rt.enter();
+        let open = |kind| signal(kind).map_err(|e| format!("Failed to install signal handler: {}", e));
+        (open(SignalKind::terminate())?, open(SignalKind::interrupt())?, open(SignalKind::hangup())?)
+    };
+
+    loop {
+        let reload = rt.block_on(async {
+            tokio::select! {
+                _ = term.recv() => false,
+                _ = int.recv() => false,
+                _ = hup.recv() => true,
+            }
+        });
+        if !reload {
+            break;
+        }
+        if let Err(e) = audit_logic_core::reload_service_config() {
+            log::error!("Config reload failed: {}", e);
+        }
+    }
+
+    match audit_logic_core::stop_service(audit_logic_core::DEFAULT_SHUTDOWN_TIMEOUT) {
+        Some(Err(e)) => {
+            eprintln!("warning: {}", e);
+            Ok(ExitCode::from(2))
+        }
+        _ => Ok(ExitCode::SUCCESS),
+    }
+}
+
+fn fetch_rows(socket: &str, query: &LogQuery) -> Result<Vec<Value>, String> {
+    let payload = serde_json::to_value(query).map_err(|e| e.to_string())?;
+    match client::call(socket, "query", payload)?.payload {
+        Some(Value::Array(rows)) => Ok(rows),
+        _ => Err("Unexpected query response".to_string()),
+    }
+}
+
+fn report(response: IpcResponse) -> Result<ExitCode, String> {
+    if response.payload.is_some() {
+        print_json(&response.payload);
+    }
+    if response.status == "ok" {
+        println!("{}", response.message);
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Err(response.message)
+    }
+}
+
+fn print_json(value: &Option<Value>) {
+    let value = value.as_ref().unwrap_or(&Value::Null);
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+/// `--name value` 形式的选项 (`--unsent` 等开关没有值) 与位置参数
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+const SWITCHES: &[&str] = &["unsent", "force", "follow", "no-screenshots"];
+
+impl Args {
+    fn parse(raw: Vec<String>) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut iter = raw.into_iter();
+        while let Some(arg) = iter.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+            if matches!(name, "help") {
+                positional.push(arg);
+                continue;
+            }
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None if SWITCHES.contains(&name) => (name.to_string(), None),
+                None => {
+                    let value = iter.next().ok_or_else(|| format!("--{} requires a value", name))?;
+                    (name.to_string(), Some(value))
+                }
+            };
+            options.insert(name, value);
+        }
+        Ok(Self { positional, options })
+    }
+
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name).flatten()
+    }
+
+    fn take_switch(&mut self, name: &str) -> bool {
+        self.options.remove(name).is_some()
+    }
+
+    fn take_parsed<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.take(name)
+            .map(|v| v.parse().map_err(|_| format!("Invalid value for --{}: {}", name, v)))
+            .transpose()
+    }
+
+    fn query(&mut self) -> Result<LogQuery, String> {
+        let table: LogTable = self.take("table").ok_or("--table is required")?.parse()?;
+        let mut query = LogQuery::new(table);
+        query.since = self.take("since");
+        query.until = self.take("until");
+        query.unsent_only = self.take_switch("unsent");
+        query.limit = self.take_parsed("limit")?;
+        Ok(query)
+    }
+
+    /// 拒绝未识别的选项与多余的位置参数
+    fn finish(self) -> Result<(), String> {
+        if let Some(extra) = self.positional.get(1) {
+            return Err(format!("Unexpected argument: {}", extra));
+        }
+        match self.options.keys().next() {
+            Some(name) => Err(format!("Unknown option: --{}", name)),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,9 +1,47 @@
-use sqlx::{sqlite::SqliteConnectOptions, Row, SqlitePool};
+pub mod chain;
+
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{Row, SqlitePool};
 use std::str::FromStr;
-use crate::models::{AuditLog, BehaviorLog, ClipboardLog, ScreenshotLog};
+use crate::models::{
+    AppUsage, AppUsageSession, AuditLog, BehaviorLog, ClipboardLog, FileLog, LogQuery, LogTable, PendingCounts, PeripheralLog,
+    RiskEventCounts, ScreenshotLog, TrafficSummary, UploadBatch, UploadSession, UsageSummary,
+};
+
+pub use self::chain::{ChainBreak, ChainReport};
+
+const AUDIT_COLUMNS: &str = "cpe_id, id, url, req_time, method_type, domain, process_name, risk_level, ip, mac, host_id, bytes";
+const BEHAVIOR_COLUMNS: &str = "id, proc, op_time, cpe_id, op_type, detail, risk_level, host_id, mac, ip";
+const SCREENSHOT_COLUMNS: &str =
+    "id, capture_time, cpe_id, image_path, ocr_text, risk_level, app_name, image_hash, host_id, mac, ip, redaction_labels, policy_decision";
+const CLIPBOARD_COLUMNS: &str =
+    "id, app_name, bundle_id, op_time, content, content_type, risk_level, cpe_id, host_id, mac, ip, policy_decision";
+const PERIPHERAL_COLUMNS: &str = "id, op_time, action, device_class, vendor_id, product_id, serial, name, volume_label, mount_point, risk_level, policy_decision, cpe_id, host_id, mac, ip";
+const TRAFFIC_SUMMARY_COLUMNS: &str = "id, bucket_start, bucket_secs, process_name, domain, method_type, path_template, first_seen, last_seen, request_count, bytes, risk_level, cpe_id, host_id, mac, ip";
+const USAGE_SUMMARY_COLUMNS: &str = "id, day, active_secs, apps, risk_events, cpe_id, host_id, mac, ip";
+const FILE_COLUMNS: &str =
+    "id, op_time, last_op_time, op_type, path, old_path, size, sha256, process_name, pid, event_count, risk_level, cpe_id, host_id, mac, ip";
+
+/// 需要立即告警的风险等级
+pub const CRITICAL_RISK_LEVEL: i32 = 2;
+/// 告警订阅方处理不及时时最多缓存的记录数，超出后最早的记录只走定时同步
+const CRITICAL_CHANNEL_CAPACITY: usize = 256;
+
+/// 已写入的高风险记录 (risk_level >= `CRITICAL_RISK_LEVEL`)
+#[derive(Debug, Clone)]
+pub struct CriticalRecord {
+    pub table: LogTable,
+    pub record_id: String,
+    pub risk_level: i32,
+    pub record: serde_json::Value,
+}
 
 pub struct Database {
     pool: SqlitePool,
+    // 串行化 "写记录 + 追加链项"，保证链项顺序与写入顺序一致
+    chain_lock: tokio::sync::Mutex<()>,
+    critical: tokio::sync::broadcast::Sender<CriticalRecord>,
 }
 
 impl Database {
@@ -13,12 +51,31 @@ impl Database {
 
         let pool = SqlitePool::connect_with(options).await?;
 
-        let db = Self { pool };
+        let (critical, _) = tokio::sync::broadcast::channel(CRITICAL_CHANNEL_CAPACITY);
+        let db = Self { pool, chain_lock: tokio::sync::Mutex::new(()), critical };
         db.init().await?;
 
         Ok(db)
     }
 
+    /// 订阅之后写入的高风险记录 (提交后通知)
+    pub fn subscribe_critical(&self) -> tokio::sync::broadcast::Receiver<CriticalRecord> {
+        self.critical.subscribe()
+    }
+
+    fn publish_critical<T: Serialize>(&self, table: LogTable, record_id: String, risk_level: i32, record: &T) {
+        if risk_level < CRITICAL_RISK_LEVEL || self.critical.receiver_count() == 0 {
+            return;
+        }
+        let record = serde_json::to_value(record).unwrap_or_default();
+        let _ = self.critical.send(CriticalRecord { table, record_id, risk_level, record });
+    }
+
+    /// 等待进行中的查询完成并关闭连接池，之后的查询都会失败
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
     async fn init(&self) -> Result<(), sqlx::Error> {
         // 创建流量审计表 (与服务端对齐)
         sqlx::query(
@@ -108,6 +165,147 @@ impl Database {
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_clipboard_uploaded ON clipboard_logs(is_uploaded)")
             .execute(&self.pool).await?;
 
+        // 创建文件操作日志表
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS file_logs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                op_time TEXT,
+                last_op_time TEXT,
+                op_type TEXT,
+                path TEXT,
+                old_path TEXT,
+                size INTEGER,
+                sha256 TEXT,
+                process_name TEXT,
+                pid INTEGER,
+                event_count INTEGER,
+                risk_level INTEGER,
+                cpe_id TEXT,
+                host_id TEXT,
+                mac TEXT,
+                ip TEXT,
+                is_uploaded INTEGER DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&self.pool).await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_file_uploaded ON file_logs(is_uploaded)")
+            .execute(&self.pool).await?;
+
+        // 创建外设日志表
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS peripheral_logs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                op_time TEXT,
+                action TEXT,
+                device_class TEXT,
+                vendor_id TEXT,
+                product_id TEXT,
+                serial TEXT,
+                name TEXT,
+                volume_label TEXT,
+                mount_point TEXT,
+                risk_level INTEGER,
+                policy_decision TEXT,
+                cpe_id TEXT,
+                host_id TEXT,
+                mac TEXT,
+                ip TEXT,
+                is_uploaded INTEGER DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&self.pool).await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_peripheral_uploaded ON peripheral_logs(is_uploaded)")
+            .execute(&self.pool).await?;
+
+        // 创建流量聚合表 (聚合模式下代替逐条的 monitor_log_traffic 记录)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS traffic_summaries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                bucket_start TEXT,
+                bucket_secs INTEGER,
+                process_name TEXT,
+                domain TEXT,
+                method_type TEXT,
+                path_template TEXT,
+                first_seen TEXT,
+                last_seen TEXT,
+                request_count INTEGER,
+                bytes INTEGER,
+                risk_level INTEGER,
+                cpe_id TEXT,
+                host_id TEXT,
+                mac TEXT,
+                ip TEXT,
+                is_uploaded INTEGER DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&self.pool).await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_traffic_summary_uploaded ON traffic_summaries(is_uploaded)")
+            .execute(&self.pool).await?;
+
+        // 前台应用使用时段 (本地统计用，不单独上传)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS app_usage_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                app_name TEXT,
+                bundle_id TEXT,
+                start_time TEXT,
+                end_time TEXT,
+                duration_ms INTEGER,
+                day TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&self.pool).await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_app_usage_day ON app_usage_sessions(day)")
+            .execute(&self.pool).await?;
+
+        // 每日使用汇总
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS usage_summaries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                day TEXT UNIQUE,
+                active_secs INTEGER,
+                apps TEXT,
+                risk_events TEXT,
+                cpe_id TEXT,
+                host_id TEXT,
+                mac TEXT,
+                ip TEXT,
+                is_uploaded INTEGER DEFAULT 0,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&self.pool).await?;
+
+        // 分块上传进度 (断点续传)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS upload_sessions (
+                sha256 TEXT PRIMARY KEY,
+                upload_id TEXT NOT NULL,
+                file_path TEXT,
+                size INTEGER,
+                offset INTEGER DEFAULT 0,
+                chunk_size INTEGER,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )"
+        ).execute(&self.pool).await?;
+
+        // 主备切换时各轮同步由哪台服务端接收
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS upload_batches (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                batch_id TEXT NOT NULL,
+                server_url TEXT NOT NULL,
+                endpoint TEXT NOT NULL,
+                records INTEGER NOT NULL,
+                accepted_at TEXT NOT NULL,
+                is_reported INTEGER DEFAULT 0
+            )"
+        ).execute(&self.pool).await?;
+
         // 数据库迁移：尝试添加新字段 (忽略已存在的错误)
         // Behavior Logs
         let _ = sqlx::query("ALTER TABLE behavior_logs ADD COLUMN host_id TEXT").execute(&self.pool).await;
@@ -118,20 +316,30 @@ impl Database {
         let _ = sqlx::query("ALTER TABLE monitor_log_traffic ADD COLUMN host_id TEXT").execute(&self.pool).await;
         let _ = sqlx::query("ALTER TABLE monitor_log_traffic ADD COLUMN mac TEXT").execute(&self.pool).await;
         let _ = sqlx::query("ALTER TABLE monitor_log_traffic ADD COLUMN ip TEXT").execute(&self.pool).await;
+        let _ = sqlx::query("ALTER TABLE monitor_log_traffic ADD COLUMN bytes INTEGER").execute(&self.pool).await;
 
         // Screenshot Logs
         let _ = sqlx::query("ALTER TABLE screenshot_logs ADD COLUMN host_id TEXT").execute(&self.pool).await;
         let _ = sqlx::query("ALTER TABLE screenshot_logs ADD COLUMN mac TEXT").execute(&self.pool).await;
         let _ = sqlx::query("ALTER TABLE screenshot_logs ADD COLUMN ip TEXT").execute(&self.pool).await;
         let _ = sqlx::query("ALTER TABLE screenshot_logs ADD COLUMN redaction_labels TEXT").execute(&self.pool).await;
+        let _ = sqlx::query("ALTER TABLE screenshot_logs ADD COLUMN policy_decision TEXT").execute(&self.pool).await;
+
+        // Clipboard Logs
+        let _ = sqlx::query("ALTER TABLE clipboard_logs ADD COLUMN policy_decision TEXT").execute(&self.pool).await;
+
+        // 防篡改哈希链
+        chain::create_table(&self.pool).await?;
 
         Ok(())
     }
 
     pub async fn save_audit_log(&self, log: &AuditLog) -> Result<(), sqlx::Error> {
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO monitor_log_traffic (id, cpe_id, url, req_time, method_type, domain, process_name, risk_level, ip, mac, host_id)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO monitor_log_traffic (id, cpe_id, url, req_time, method_type, domain, process_name, risk_level, ip, mac, host_id, bytes)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&log.id)
         .bind(&log.cpe_id)
@@ -144,13 +352,19 @@ impl Database {
         .bind(&log.ip)
         .bind(&log.mac)
         .bind(&log.host_id)
-        .execute(&self.pool)
+        .bind(log.bytes)
+        .execute(&mut *tx)
         .await?;
+        chain::append(&mut tx, LogTable::Audit, &log.id, &chain::content_hash(LogTable::Audit, log)).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::Audit, log.id.clone(), log.risk_level, log);
         Ok(())
     }
 
     pub async fn save_behavior_log(&self, log: &BehaviorLog) -> Result<(), sqlx::Error> {
-        sqlx::query(
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
             "INSERT INTO behavior_logs (proc, op_time, cpe_id, op_type, detail, risk_level, host_id, mac, ip)
              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
@@ -163,15 +377,22 @@ impl Database {
         .bind(&log.host_id)
         .bind(&log.mac)
         .bind(&log.ip)
-        .execute(&self.pool)
-        .await?;
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::Behavior, log);
+        chain::append(&mut tx, LogTable::Behavior, &row_id.to_string(), &hash).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::Behavior, row_id.to_string(), log.risk_level, log);
         Ok(())
     }
 
     pub async fn save_screenshot_log(&self, log: &ScreenshotLog) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "INSERT INTO screenshot_logs (capture_time, cpe_id, image_path, ocr_text, risk_level, app_name, image_hash, host_id, mac, ip, redaction_labels)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
+            "INSERT INTO screenshot_logs (capture_time, cpe_id, image_path, ocr_text, risk_level, app_name, image_hash, host_id, mac, ip, redaction_labels, policy_decision)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&log.capture_time)
         .bind(&log.cpe_id)
@@ -184,15 +405,23 @@ impl Database {
         .bind(&log.mac)
         .bind(&log.ip)
         .bind(&log.redaction_labels)
-        .execute(&self.pool)
-        .await?;
+        .bind(&log.policy_decision)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::Screenshot, log);
+        chain::append(&mut tx, LogTable::Screenshot, &row_id.to_string(), &hash).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::Screenshot, row_id.to_string(), log.risk_level, log);
         Ok(())
     }
 
     pub async fn save_clipboard_log(&self, log: &ClipboardLog) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "INSERT INTO clipboard_logs (app_name, bundle_id, op_time, content, content_type, risk_level, host_id, cpe_id, mac, ip)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
+            "INSERT INTO clipboard_logs (app_name, bundle_id, op_time, content, content_type, risk_level, host_id, cpe_id, mac, ip, policy_decision)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&log.app_name)
         .bind(&log.bundle_id)
@@ -204,47 +433,118 @@ impl Database {
         .bind(&log.cpe_id)
         .bind(&log.mac)
         .bind(&log.ip)
-        .execute(&self.pool)
-        .await?;
+        .bind(&log.policy_decision)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::Clipboard, log);
+        chain::append(&mut tx, LogTable::Clipboard, &row_id.to_string(), &hash).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::Clipboard, row_id.to_string(), log.risk_level, log);
         Ok(())
     }
 
-    pub async fn get_unsent_audit_logs(&self) -> Result<Vec<AuditLog>, sqlx::Error> {
-        let rows = sqlx::query(
-            r#"SELECT
-                cpe_id,
-                id,
-                url,
-                req_time,
-                method_type,
-                domain,
-                process_name,
-                risk_level,
-                ip,
-                mac,
-                host_id
-            FROM monitor_log_traffic WHERE is_uploaded = 0 LIMIT 1000"#
+    pub async fn save_file_log(&self, log: &FileLog) -> Result<(), sqlx::Error> {
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
+            "INSERT INTO file_logs (op_time, last_op_time, op_type, path, old_path, size, sha256, process_name, pid, event_count, risk_level, cpe_id, host_id, mac, ip)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
-        .fetch_all(&self.pool)
-        .await?;
+        .bind(&log.op_time)
+        .bind(&log.last_op_time)
+        .bind(&log.op_type)
+        .bind(&log.path)
+        .bind(&log.old_path)
+        .bind(log.size)
+        .bind(&log.sha256)
+        .bind(&log.process_name)
+        .bind(log.pid)
+        .bind(log.event_count)
+        .bind(log.risk_level)
+        .bind(&log.cpe_id)
+        .bind(&log.host_id)
+        .bind(&log.mac)
+        .bind(&log.ip)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::File, log);
+        chain::append(&mut tx, LogTable::File, &row_id.to_string(), &hash).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::File, row_id.to_string(), log.risk_level, log);
+        Ok(())
+    }
 
-        let mut logs = Vec::with_capacity(rows.len());
-        for row in rows {
-            logs.push(AuditLog {
-                cpe_id: row.try_get("cpe_id")?,
-                id: row.try_get("id")?,
-                url: row.try_get("url")?,
-                req_time: row.try_get("req_time")?,
-                method_type: row.try_get("method_type")?,
-                domain: row.try_get("domain")?,
-                process_name: row.try_get("process_name")?,
-                risk_level: row.try_get("risk_level")?,
-                ip: row.try_get("ip")?,
-                mac: row.try_get("mac")?,
-                host_id: row.try_get("host_id")?,
-            });
-        }
-        Ok(logs)
+    pub async fn save_traffic_summary(&self, summary: &TrafficSummary) -> Result<(), sqlx::Error> {
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
+            "INSERT INTO traffic_summaries (bucket_start, bucket_secs, process_name, domain, method_type, path_template, first_seen, last_seen, request_count, bytes, risk_level, cpe_id, host_id, mac, ip)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&summary.bucket_start)
+        .bind(summary.bucket_secs)
+        .bind(&summary.process_name)
+        .bind(&summary.domain)
+        .bind(&summary.method_type)
+        .bind(&summary.path_template)
+        .bind(&summary.first_seen)
+        .bind(&summary.last_seen)
+        .bind(summary.request_count)
+        .bind(summary.bytes)
+        .bind(summary.risk_level)
+        .bind(&summary.cpe_id)
+        .bind(&summary.host_id)
+        .bind(&summary.mac)
+        .bind(&summary.ip)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::TrafficSummary, summary);
+        chain::append(&mut tx, LogTable::TrafficSummary, &row_id.to_string(), &hash).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::TrafficSummary, row_id.to_string(), summary.risk_level, summary);
+        Ok(())
+    }
+
+    pub async fn save_peripheral_log(&self, log: &PeripheralLog) -> Result<(), sqlx::Error> {
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
+            "INSERT INTO peripheral_logs (op_time, action, device_class, vendor_id, product_id, serial, name, volume_label, mount_point, risk_level, policy_decision, cpe_id, host_id, mac, ip)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&log.op_time)
+        .bind(&log.action)
+        .bind(&log.device_class)
+        .bind(&log.vendor_id)
+        .bind(&log.product_id)
+        .bind(&log.serial)
+        .bind(&log.name)
+        .bind(&log.volume_label)
+        .bind(&log.mount_point)
+        .bind(log.risk_level)
+        .bind(&log.policy_decision)
+        .bind(&log.cpe_id)
+        .bind(&log.host_id)
+        .bind(&log.mac)
+        .bind(&log.ip)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::Peripheral, log);
+        chain::append(&mut tx, LogTable::Peripheral, &row_id.to_string(), &hash).await?;
+        tx.commit().await?;
+        self.publish_critical(LogTable::Peripheral, row_id.to_string(), log.risk_level, log);
+        Ok(())
+    }
+
+    pub async fn get_unsent_audit_logs(&self) -> Result<Vec<AuditLog>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM monitor_log_traffic WHERE is_uploaded = 0 LIMIT 1000", AUDIT_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(audit_log_from_row).collect()
     }
 
     pub async fn mark_audit_log_sent(&self, id: &str) -> Result<(), sqlx::Error> {
@@ -256,39 +556,10 @@ impl Database {
     }
 
     pub async fn get_unsent_behavior_logs(&self) -> Result<Vec<BehaviorLog>, sqlx::Error> {
-        let rows = sqlx::query(
-            r#"SELECT
-                id,
-                proc,
-                op_time,
-                cpe_id,
-                op_type,
-                detail,
-                risk_level,
-                host_id,
-                mac,
-                ip
-            FROM behavior_logs WHERE is_uploaded = 0 LIMIT 1000"#
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        let mut logs = Vec::with_capacity(rows.len());
-        for row in rows {
-            logs.push(BehaviorLog {
-                id: Some(row.try_get::<i64, _>("id")?),
-                proc: row.try_get("proc")?,
-                op_time: row.try_get("op_time")?,
-                cpe_id: row.try_get("cpe_id")?,
-                op_type: row.try_get("op_type")?,
-                detail: row.try_get("detail")?,
-                risk_level: row.try_get("risk_level")?,
-                host_id: row.try_get("host_id")?,
-                mac: row.try_get("mac")?,
-                ip: row.try_get("ip")?,
-            });
-        }
-        Ok(logs)
+        let rows = sqlx::query(&format!("SELECT {} FROM behavior_logs WHERE is_uploaded = 0 LIMIT 1000", BEHAVIOR_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(behavior_log_from_row).collect()
     }
 
     pub async fn mark_behavior_log_sent(&self, id: i64) -> Result<(), sqlx::Error> {
@@ -300,53 +571,305 @@ impl Database {
     }
 
     pub async fn get_unsent_screenshot_logs(&self) -> Result<Vec<ScreenshotLog>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM screenshot_logs WHERE is_uploaded = 0 LIMIT 1000", SCREENSHOT_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(screenshot_log_from_row).collect()
+    }
+
+    pub async fn mark_screenshot_log_sent(&self, hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE screenshot_logs SET is_uploaded = 1 WHERE image_hash = ?")
+            .bind(hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_upload_session(&self, sha256: &str) -> Result<Option<UploadSession>, sqlx::Error> {
+        let row = sqlx::query("SELECT sha256, upload_id, file_path, size, offset, chunk_size FROM upload_sessions WHERE sha256 = ?")
+            .bind(sha256)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| {
+            Ok(UploadSession {
+                sha256: row.try_get("sha256")?,
+                upload_id: row.try_get("upload_id")?,
+                file_path: row.try_get::<Option<String>, _>("file_path")?.unwrap_or_default(),
+                size: row.try_get("size")?,
+                offset: row.try_get("offset")?,
+                chunk_size: row.try_get("chunk_size")?,
+            })
+        })
+        .transpose()
+    }
+
+    pub async fn save_upload_session(&self, session: &UploadSession) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO upload_sessions (sha256, upload_id, file_path, size, offset, chunk_size, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)"
+        )
+        .bind(&session.sha256)
+        .bind(&session.upload_id)
+        .bind(&session.file_path)
+        .bind(session.size)
+        .bind(session.offset)
+        .bind(session.chunk_size)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn update_upload_offset(&self, sha256: &str, offset: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE upload_sessions SET offset = ?, updated_at = CURRENT_TIMESTAMP WHERE sha256 = ?")
+            .bind(offset)
+            .bind(sha256)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_upload_session(&self, sha256: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM upload_sessions WHERE sha256 = ?")
+            .bind(sha256)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn save_upload_batches(&self, batches: &[UploadBatch]) -> Result<(), sqlx::Error> {
+        if batches.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for batch in batches {
+            sqlx::query(
+                "INSERT INTO upload_batches (batch_id, server_url, endpoint, records, accepted_at) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(&batch.batch_id)
+            .bind(&batch.server_url)
+            .bind(&batch.endpoint)
+            .bind(batch.records)
+            .bind(&batch.accepted_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn get_unreported_upload_batches(&self) -> Result<Vec<UploadBatch>, sqlx::Error> {
         let rows = sqlx::query(
-            r#"SELECT
-                id,
-                capture_time,
-                cpe_id,
-                image_path,
-                ocr_text,
-                risk_level,
-                app_name,
-                image_hash,
-                host_id,
-                mac,
-                ip,
-                redaction_labels
-            FROM screenshot_logs WHERE is_uploaded = 0 LIMIT 1000"#
+            "SELECT id, batch_id, server_url, endpoint, records, accepted_at FROM upload_batches
+             WHERE is_reported = 0 ORDER BY id LIMIT 100"
         )
         .fetch_all(&self.pool)
         .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(UploadBatch {
+                    id: row.try_get("id")?,
+                    batch_id: row.try_get("batch_id")?,
+                    server_url: row.try_get("server_url")?,
+                    endpoint: row.try_get("endpoint")?,
+                    records: row.try_get("records")?,
+                    accepted_at: row.try_get("accepted_at")?,
+                })
+            })
+            .collect()
+    }
 
-        let mut logs = Vec::with_capacity(rows.len());
-        for row in rows {
-            logs.push(ScreenshotLog {
-                id: Some(row.try_get::<i64, _>("id")?),
-                capture_time: row.try_get("capture_time")?,
-                cpe_id: row.try_get("cpe_id")?,
-                image_path: row.try_get("image_path")?,
-                ocr_text: row.try_get::<Option<String>, _>("ocr_text")?,
-                risk_level: row.try_get("risk_level")?,
-                app_name: row.try_get("app_name")?,
-                image_hash: row.try_get("image_hash")?,
-                host_id: row.try_get("host_id")?,
-                mac: row.try_get("mac")?,
-                ip: row.try_get("ip")?,
-                redaction_labels: row.try_get::<Option<String>, _>("redaction_labels")?,
-            });
+    pub async fn mark_upload_batches_reported(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for id in ids {
+            sqlx::query("UPDATE upload_batches SET is_reported = 1 WHERE id = ?").bind(id).execute(&mut *tx).await?;
         }
-        Ok(logs)
+        tx.commit().await
     }
 
-    pub async fn mark_screenshot_log_sent(&self, hash: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE screenshot_logs SET is_uploaded = 1 WHERE image_hash = ?")
-            .bind(hash)
+    pub async fn save_app_usage_sessions(&self, sessions: &[AppUsageSession]) -> Result<(), sqlx::Error> {
+        if sessions.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for session in sessions {
+            sqlx::query(
+                "INSERT INTO app_usage_sessions (app_name, bundle_id, start_time, end_time, duration_ms, day)
+                 VALUES (?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&session.app_name)
+            .bind(&session.bundle_id)
+            .bind(&session.start_time)
+            .bind(&session.end_time)
+            .bind(session.duration_ms)
+            .bind(&session.day)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn get_app_usage_sessions(&self, day: &str) -> Result<Vec<AppUsageSession>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, app_name, bundle_id, start_time, end_time, duration_ms, day FROM app_usage_sessions WHERE day = ? ORDER BY start_time"
+        )
+        .bind(day)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(AppUsageSession {
+                    id: Some(row.try_get::<i64, _>("id")?),
+                    app_name: row.try_get("app_name")?,
+                    bundle_id: row.try_get::<Option<String>, _>("bundle_id")?,
+                    start_time: row.try_get("start_time")?,
+                    end_time: row.try_get("end_time")?,
+                    duration_ms: row.try_get("duration_ms")?,
+                    day: row.try_get("day")?,
+                })
+            })
+            .collect()
+    }
+
+    /// `before` 之前有使用记录但还没有汇总的日期
+    pub async fn get_unsummarized_usage_days(&self, before: &str) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT day FROM app_usage_sessions
+             WHERE day < ? AND day NOT IN (SELECT day FROM usage_summaries) ORDER BY day"
+        )
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(|row| row.try_get("day")).collect()
+    }
+
+    /// 一天内各应用的使用时间、截图数与风险事件数，按使用时间降序
+    pub async fn app_usage_for_day(&self, day: &str) -> Result<Vec<AppUsage>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"SELECT u.app_name AS app_name, MAX(u.bundle_id) AS bundle_id,
+                   SUM(u.duration_ms) AS duration_ms, COUNT(*) AS sessions,
+                   (SELECT COUNT(*) FROM screenshot_logs s
+                     WHERE s.app_name = u.app_name AND substr(s.capture_time, 1, 10) = ?1) AS screenshots,
+                   (SELECT COUNT(*) FROM screenshot_logs s
+                     WHERE s.app_name = u.app_name AND substr(s.capture_time, 1, 10) = ?1 AND s.risk_level >= 1)
+                   + (SELECT COUNT(*) FROM clipboard_logs c
+                     WHERE c.app_name = u.app_name AND substr(c.op_time, 1, 10) = ?1 AND c.risk_level >= 1) AS risk_events
+               FROM app_usage_sessions u WHERE u.day = ?1
+               GROUP BY u.app_name ORDER BY duration_ms DESC"#
+        )
+        .bind(day)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(AppUsage {
+                    app_name: row.try_get("app_name")?,
+                    bundle_id: row.try_get::<Option<String>, _>("bundle_id")?,
+                    active_secs: row.try_get::<i64, _>("duration_ms")? / 1000,
+                    sessions: row.try_get("sessions")?,
+                    screenshots: row.try_get("screenshots")?,
+                    risk_events: row.try_get("risk_events")?,
+                })
+            })
+            .collect()
+    }
+
+    /// 一天内各表 risk_level >= 1 的记录数
+    pub async fn risk_event_counts(&self, day: &str) -> Result<RiskEventCounts, sqlx::Error> {
+        let row = sqlx::query(
+            r#"SELECT
+                (SELECT COUNT(*) FROM monitor_log_traffic WHERE risk_level >= 1 AND substr(req_time, 1, 10) = ?1) AS traffic,
+                (SELECT COUNT(*) FROM behavior_logs WHERE risk_level >= 1 AND substr(op_time, 1, 10) = ?1) AS behavior,
+                (SELECT COUNT(*) FROM screenshot_logs WHERE risk_level >= 1 AND substr(capture_time, 1, 10) = ?1) AS screenshot,
+                (SELECT COUNT(*) FROM clipboard_logs WHERE risk_level >= 1 AND substr(op_time, 1, 10) = ?1) AS clipboard,
+                (SELECT COUNT(*) FROM file_logs WHERE risk_level >= 1 AND substr(op_time, 1, 10) = ?1) AS file,
+                (SELECT COUNT(*) FROM peripheral_logs WHERE risk_level >= 1 AND substr(op_time, 1, 10) = ?1) AS peripheral"#
+        )
+        .bind(day)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(RiskEventCounts {
+            traffic: row.try_get("traffic")?,
+            behavior: row.try_get("behavior")?,
+            screenshot: row.try_get("screenshot")?,
+            clipboard: row.try_get("clipboard")?,
+            file: row.try_get("file")?,
+            peripheral: row.try_get("peripheral")?,
+        })
+    }
+
+    pub async fn save_usage_summary(&self, summary: &UsageSummary) -> Result<(), sqlx::Error> {
+        let _chain = self.chain_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row_id = sqlx::query(
+            "INSERT INTO usage_summaries (day, active_secs, apps, risk_events, cpe_id, host_id, mac, ip)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&summary.day)
+        .bind(summary.active_secs)
+        .bind(&summary.apps)
+        .bind(&summary.risk_events)
+        .bind(&summary.cpe_id)
+        .bind(&summary.host_id)
+        .bind(&summary.mac)
+        .bind(&summary.ip)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        let hash = chain::content_hash(LogTable::UsageSummary, summary);
+        chain::append(&mut tx, LogTable::UsageSummary, &row_id.to_string(), &hash).await?;
+        tx.commit().await
+    }
+
+    pub async fn get_usage_summary(&self, day: &str) -> Result<Option<UsageSummary>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM usage_summaries WHERE day = ?", USAGE_SUMMARY_COLUMNS))
+            .bind(day)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(usage_summary_from_row).transpose()
+    }
+
+    pub async fn get_unsent_usage_summaries(&self) -> Result<Vec<UsageSummary>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM usage_summaries WHERE is_uploaded = 0 ORDER BY id LIMIT 100", USAGE_SUMMARY_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(usage_summary_from_row).collect()
+    }
+
+    pub async fn mark_usage_summary_sent(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE usage_summaries SET is_uploaded = 1 WHERE id = ?")
+            .bind(id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    /// 各表待上传的记录数
+    pub async fn count_unsent(&self) -> Result<PendingCounts, sqlx::Error> {
+        let row = sqlx::query(
+            r#"SELECT
+                (SELECT COUNT(*) FROM monitor_log_traffic WHERE is_uploaded = 0) AS audit,
+                (SELECT COUNT(*) FROM behavior_logs WHERE is_uploaded = 0) AS behavior,
+                (SELECT COUNT(*) FROM screenshot_logs WHERE is_uploaded = 0) AS screenshot,
+                (SELECT COUNT(*) FROM clipboard_logs WHERE is_uploaded = 0) AS clipboard,
+                (SELECT COUNT(*) FROM file_logs WHERE is_uploaded = 0) AS file,
+                (SELECT COUNT(*) FROM peripheral_logs WHERE is_uploaded = 0) AS peripheral,
+                (SELECT COUNT(*) FROM traffic_summaries WHERE is_uploaded = 0) AS traffic_summary,
+                (SELECT COUNT(*) FROM usage_summaries WHERE is_uploaded = 0) AS usage_summary"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(PendingCounts {
+            audit: row.try_get("audit")?,
+            behavior: row.try_get("behavior")?,
+            screenshot: row.try_get("screenshot")?,
+            clipboard: row.try_get("clipboard")?,
+            file: row.try_get("file")?,
+            peripheral: row.try_get("peripheral")?,
+            traffic_summary: row.try_get("traffic_summary")?,
+            usage_summary: row.try_get("usage_summary")?,
+        })
+    }
+
     pub async fn check_screenshot_exists(&self, hash: &str) -> Result<bool, sqlx::Error> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM screenshot_logs WHERE image_hash = ?")
             .bind(hash)
@@ -357,41 +880,10 @@ impl Database {
     }
 
     pub async fn get_unsent_clipboard_logs(&self) -> Result<Vec<ClipboardLog>, sqlx::Error> {
-        let rows = sqlx::query(
-            r#"SELECT
-                id,
-                app_name,
-                bundle_id,
-                op_time,
-                content,
-                content_type,
-                risk_level,
-                cpe_id,
-                host_id,
-                mac,
-                ip
-            FROM clipboard_logs WHERE is_uploaded = 0 LIMIT 20"#
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        let mut logs = Vec::with_capacity(rows.len());
-        for row in rows {
-            logs.push(ClipboardLog {
-                id: Some(row.try_get::<i64, _>("id")?),
-                app_name: row.try_get("app_name")?,
-                bundle_id: row.try_get("bundle_id")?,
-                op_time: row.try_get("op_time")?,
-                content: row.try_get("content")?,
-                content_type: row.try_get("content_type")?,
-                risk_level: row.try_get("risk_level")?,
-                cpe_id: row.try_get("cpe_id")?,
-                host_id: row.try_get("host_id")?,
-                mac: row.try_get("mac")?,
-                ip: row.try_get("ip")?,
-            });
-        }
-        Ok(logs)
+        let rows = sqlx::query(&format!("SELECT {} FROM clipboard_logs WHERE is_uploaded = 0 LIMIT 20", CLIPBOARD_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(clipboard_log_from_row).collect()
     }
 
     pub async fn mark_clipboard_log_sent(&self, id: i64) -> Result<(), sqlx::Error> {
@@ -402,41 +894,331 @@ impl Database {
         Ok(())
     }
 
+    pub async fn get_unsent_file_logs(&self) -> Result<Vec<FileLog>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM file_logs WHERE is_uploaded = 0 ORDER BY id LIMIT 500", FILE_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(file_log_from_row).collect()
+    }
+
+    pub async fn mark_file_log_sent(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE file_logs SET is_uploaded = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_unsent_traffic_summaries(&self) -> Result<Vec<TrafficSummary>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM traffic_summaries WHERE is_uploaded = 0 ORDER BY id LIMIT 500", TRAFFIC_SUMMARY_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(traffic_summary_from_row).collect()
+    }
+
+    pub async fn mark_traffic_summary_sent(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE traffic_summaries SET is_uploaded = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_unsent_peripheral_logs(&self) -> Result<Vec<PeripheralLog>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM peripheral_logs WHERE is_uploaded = 0 ORDER BY id LIMIT 500", PERIPHERAL_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(peripheral_log_from_row).collect()
+    }
+
+    pub async fn mark_peripheral_log_sent(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE peripheral_logs SET is_uploaded = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_all_clipboard_logs(&self) -> Result<Vec<ClipboardLog>, sqlx::Error> {
-        let rows = sqlx::query(
-            r#"SELECT
-                id,
-                app_name,
-                bundle_id,
-                op_time,
-                content,
-                content_type,
-                risk_level,
-                cpe_id,
-                host_id,
-                mac,
-                ip
-            FROM clipboard_logs ORDER BY op_time DESC LIMIT 100"#
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        let rows = sqlx::query(&format!("SELECT {} FROM clipboard_logs ORDER BY op_time DESC LIMIT 100", CLIPBOARD_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(clipboard_log_from_row).collect()
+    }
+
+    /// 按表、时间范围与上传状态查询，按时间升序返回 (CLI `query` / `export`)
+    pub async fn query_logs(&self, query: &LogQuery) -> Result<Vec<serde_json::Value>, sqlx::Error> {
+        let table = query.table;
+        let time = table.time_column();
+        let mut sql = format!("SELECT {} FROM {} WHERE 1 = 1", columns(table), table.table_name());
+        if query.since.is_some() {
+            sql.push_str(&format!(" AND {} >= ?", time));
+        }
+        if query.until.is_some() {
+            sql.push_str(&format!(" AND {} < ?", time));
+        }
+        if query.unsent_only {
+            sql.push_str(" AND is_uploaded = 0");
+        }
+        sql.push_str(&format!(" ORDER BY {}, rowid LIMIT {}", time, query.effective_limit()));
+        if let Some(offset) = query.offset {
+            sql.push_str(&format!(" OFFSET {}", offset));
+        }
+
+        let mut q = sqlx::query(&sql);
+        for bound in [&query.since, &query.until].into_iter().flatten() {
+            q = q.bind(bound);
+        }
+        let rows = q.fetch_all(&self.pool).await?;
+        rows.iter().map(|row| row_to_json(table, row)).collect()
+    }
+
+    /// 按链项顺序校验哈希链以及每条记录的内容
+    pub async fn verify_chain(&self) -> Result<ChainReport, sqlx::Error> {
+        let entries = sqlx::query("SELECT seq, table_name, row_id, content_hash, chain_hash FROM log_chain ORDER BY seq")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut report = ChainReport::default();
+        let mut prev = chain::GENESIS.to_string();
+        for entry in &entries {
+            let seq: i64 = entry.try_get("seq")?;
+            let table_name: String = entry.try_get("table_name")?;
+            let row_id: String = entry.try_get("row_id")?;
+            let content_hash: String = entry.try_get("content_hash")?;
+            let chain_hash: String = entry.try_get("chain_hash")?;
+            report.entries += 1;
 
-        let mut logs = Vec::with_capacity(rows.len());
-        for row in rows {
-            logs.push(ClipboardLog {
-                id: Some(row.try_get::<i64, _>("id")?),
-                app_name: row.try_get("app_name")?,
-                bundle_id: row.try_get("bundle_id")?,
-                op_time: row.try_get("op_time")?,
-                content: row.try_get("content")?,
-                content_type: row.try_get("content_type")?,
-                risk_level: row.try_get("risk_level")?,
-                cpe_id: row.try_get("cpe_id")?,
-                host_id: row.try_get("host_id")?,
-                mac: row.try_get("mac")?,
-                ip: row.try_get("ip")?,
-            });
+            let mut broken = |reason: &str| {
+                report.breaks.push(ChainBreak {
+                    seq,
+                    table: table_name.clone(),
+                    row_id: row_id.clone(),
+                    reason: reason.to_string(),
+                })
+            };
+            let Some(table) = LogTable::from_table_name(&table_name) else {
+                broken("unknown table");
+                prev = chain_hash;
+                continue;
+            };
+            if chain::link(&prev, table, &row_id, &content_hash) != chain_hash {
+                broken("chain link mismatch (entry modified, removed or reordered)");
+            }
+            match self.row_content_hash(table, &row_id).await? {
+                None => broken("record deleted"),
+                Some(actual) if actual != content_hash => broken("record modified"),
+                Some(_) => {}
+            }
+            // 以存储的哈希继续，单处损坏不会让后续所有项都报错
+            prev = chain_hash;
         }
-        Ok(logs)
+        report.head = entries.last().map(|e| e.try_get("chain_hash")).transpose()?;
+
+        for table in LogTable::ALL {
+            let id_expr = if table == LogTable::Audit { "id" } else { "CAST(id AS TEXT)" };
+            let row = sqlx::query(&format!(
+                "SELECT COUNT(*) AS count FROM {} WHERE {} NOT IN (SELECT row_id FROM log_chain WHERE table_name = ?)",
+                table.table_name(),
+                id_expr
+            ))
+            .bind(table.table_name())
+            .fetch_one(&self.pool)
+            .await?;
+            report.unchained_rows += row.try_get::<i64, _>("count")?;
+        }
+        Ok(report)
+    }
+
+    async fn row_content_hash(&self, table: LogTable, row_id: &str) -> Result<Option<String>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM {} WHERE id = ?", columns(table), table.table_name()))
+            .bind(row_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else { return Ok(None) };
+        let hash = match table {
+            LogTable::Audit => chain::content_hash(table, &audit_log_from_row(&row)?),
+            LogTable::Behavior => chain::content_hash(table, &behavior_log_from_row(&row)?),
+            LogTable::Screenshot => chain::content_hash(table, &screenshot_log_from_row(&row)?),
+            LogTable::Clipboard => chain::content_hash(table, &clipboard_log_from_row(&row)?),
+            LogTable::File => chain::content_hash(table, &file_log_from_row(&row)?),
+            LogTable::Peripheral => chain::content_hash(table, &peripheral_log_from_row(&row)?),
+            LogTable::TrafficSummary => chain::content_hash(table, &traffic_summary_from_row(&row)?),
+            LogTable::UsageSummary => chain::content_hash(table, &usage_summary_from_row(&row)?),
+        };
+        Ok(Some(hash))
+    }
+}
+
+fn columns(table: LogTable) -> &'static str {
+    match table {
+        LogTable::Audit => AUDIT_COLUMNS,
+        LogTable::Behavior => BEHAVIOR_COLUMNS,
+        LogTable::Screenshot => SCREENSHOT_COLUMNS,
+        LogTable::Clipboard => CLIPBOARD_COLUMNS,
+        LogTable::File => FILE_COLUMNS,
+        LogTable::Peripheral => PERIPHERAL_COLUMNS,
+        LogTable::TrafficSummary => TRAFFIC_SUMMARY_COLUMNS,
+        LogTable::UsageSummary => USAGE_SUMMARY_COLUMNS,
     }
 }
+
+fn row_to_json(table: LogTable, row: &SqliteRow) -> Result<serde_json::Value, sqlx::Error> {
+    fn to_json<T: Serialize>(log: T) -> serde_json::Value {
+        serde_json::to_value(log).unwrap_or_default()
+    }
+    Ok(match table {
+        LogTable::Audit => to_json(audit_log_from_row(row)?),
+        LogTable::Behavior => to_json(behavior_log_from_row(row)?),
+        LogTable::Screenshot => to_json(screenshot_log_from_row(row)?),
+        LogTable::Clipboard => to_json(clipboard_log_from_row(row)?),
+        LogTable::File => to_json(file_log_from_row(row)?),
+        LogTable::Peripheral => to_json(peripheral_log_from_row(row)?),
+        LogTable::TrafficSummary => to_json(traffic_summary_from_row(row)?),
+        LogTable::UsageSummary => to_json(usage_summary_from_row(row)?),
+    })
+}
+
+fn audit_log_from_row(row: &SqliteRow) -> Result<AuditLog, sqlx::Error> {
+    Ok(AuditLog {
+        cpe_id: row.try_get("cpe_id")?,
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        req_time: row.try_get("req_time")?,
+        method_type: row.try_get("method_type")?,
+        domain: row.try_get("domain")?,
+        process_name: row.try_get("process_name")?,
+        risk_level: row.try_get("risk_level")?,
+        ip: row.try_get("ip")?,
+        mac: row.try_get("mac")?,
+        host_id: row.try_get("host_id")?,
+        bytes: row.try_get::<Option<i64>, _>("bytes")?,
+    })
+}
+
+fn behavior_log_from_row(row: &SqliteRow) -> Result<BehaviorLog, sqlx::Error> {
+    Ok(BehaviorLog {
+        id: Some(row.try_get::<i64, _>("id")?),
+        proc: row.try_get("proc")?,
+        op_time: row.try_get("op_time")?,
+        cpe_id: row.try_get("cpe_id")?,
+        op_type: row.try_get("op_type")?,
+        detail: row.try_get("detail")?,
+        risk_level: row.try_get("risk_level")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+    })
+}
+
+fn screenshot_log_from_row(row: &SqliteRow) -> Result<ScreenshotLog, sqlx::Error> {
+    Ok(ScreenshotLog {
+        id: Some(row.try_get::<i64, _>("id")?),
+        capture_time: row.try_get("capture_time")?,
+        cpe_id: row.try_get("cpe_id")?,
+        image_path: row.try_get("image_path")?,
+        ocr_text: row.try_get::<Option<String>, _>("ocr_text")?,
+        risk_level: row.try_get("risk_level")?,
+        app_name: row.try_get("app_name")?,
+        image_hash: row.try_get("image_hash")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+        redaction_labels: row.try_get::<Option<String>, _>("redaction_labels")?,
+        policy_decision: row.try_get::<Option<String>, _>("policy_decision")?,
+    })
+}
+
+fn clipboard_log_from_row(row: &SqliteRow) -> Result<ClipboardLog, sqlx::Error> {
+    Ok(ClipboardLog {
+        id: Some(row.try_get::<i64, _>("id")?),
+        app_name: row.try_get("app_name")?,
+        bundle_id: row.try_get("bundle_id")?,
+        op_time: row.try_get("op_time")?,
+        content: row.try_get("content")?,
+        content_type: row.try_get("content_type")?,
+        risk_level: row.try_get("risk_level")?,
+        cpe_id: row.try_get("cpe_id")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+        policy_decision: row.try_get::<Option<String>, _>("policy_decision")?,
+    })
+}
+
+fn file_log_from_row(row: &SqliteRow) -> Result<FileLog, sqlx::Error> {
+    Ok(FileLog {
+        id: Some(row.try_get::<i64, _>("id")?),
+        op_time: row.try_get("op_time")?,
+        last_op_time: row.try_get("last_op_time")?,
+        op_type: row.try_get("op_type")?,
+        path: row.try_get("path")?,
+        old_path: row.try_get::<Option<String>, _>("old_path")?,
+        size: row.try_get::<Option<i64>, _>("size")?,
+        sha256: row.try_get::<Option<String>, _>("sha256")?,
+        process_name: row.try_get("process_name")?,
+        pid: row.try_get::<Option<i64>, _>("pid")?,
+        event_count: row.try_get("event_count")?,
+        risk_level: row.try_get("risk_level")?,
+        cpe_id: row.try_get("cpe_id")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+    })
+}
+
+fn traffic_summary_from_row(row: &SqliteRow) -> Result<TrafficSummary, sqlx::Error> {
+    Ok(TrafficSummary {
+        id: Some(row.try_get::<i64, _>("id")?),
+        bucket_start: row.try_get("bucket_start")?,
+        bucket_secs: row.try_get("bucket_secs")?,
+        process_name: row.try_get("process_name")?,
+        domain: row.try_get("domain")?,
+        method_type: row.try_get("method_type")?,
+        path_template: row.try_get("path_template")?,
+        first_seen: row.try_get("first_seen")?,
+        last_seen: row.try_get("last_seen")?,
+        request_count: row.try_get("request_count")?,
+        bytes: row.try_get("bytes")?,
+        risk_level: row.try_get("risk_level")?,
+        cpe_id: row.try_get("cpe_id")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+    })
+}
+
+fn usage_summary_from_row(row: &SqliteRow) -> Result<UsageSummary, sqlx::Error> {
+    Ok(UsageSummary {
+        id: Some(row.try_get::<i64, _>("id")?),
+        day: row.try_get("day")?,
+        active_secs: row.try_get("active_secs")?,
+        apps: row.try_get("apps")?,
+        risk_events: row.try_get("risk_events")?,
+        cpe_id: row.try_get("cpe_id")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+    })
+}
+
+fn peripheral_log_from_row(row: &SqliteRow) -> Result<PeripheralLog, sqlx::Error> {
+    Ok(PeripheralLog {
+        id: Some(row.try_get::<i64, _>("id")?),
+        op_time: row.try_get("op_time")?,
+        action: row.try_get("action")?,
+        device_class: row.try_get("device_class")?,
+        vendor_id: row.try_get::<Option<String>, _>("vendor_id")?,
+        product_id: row.try_get::<Option<String>, _>("product_id")?,
+        serial: row.try_get::<Option<String>, _>("serial")?,
+        name: row.try_get::<Option<String>, _>("name")?,
+        volume_label: row.try_get::<Option<String>, _>("volume_label")?,
+        mount_point: row.try_get::<Option<String>, _>("mount_point")?,
+        risk_level: row.try_get("risk_level")?,
+        policy_decision: row.try_get::<Option<String>, _>("policy_decision")?,
+        cpe_id: row.try_get("cpe_id")?,
+        host_id: row.try_get("host_id")?,
+        mac: row.try_get("mac")?,
+        ip: row.try_get("ip")?,
+    })
+}
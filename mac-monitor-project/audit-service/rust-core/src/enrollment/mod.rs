@@ -0,0 +1,187 @@
+//! 设备注册 (enrollment)
+//!
+//! 设备以真实身份 (序列号 / 机器 ID / 主机名 / 系统版本 / 网络地址) 加管理员下发的 PIN 向管理端注册，
+//! 换取设备 id 与长期凭据；凭据保存在 `store::CredentialStore` 中，之后所有登录都使用它。
+//! 支持重新注册 (沿用原设备 id 申请新凭据) 与注销。
+
+pub mod store;
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use self::store::CredentialStore;
+use crate::models::DeviceInfo;
+use crate::uploader::tls::TlsConfig;
+use crate::uploader::{DeviceCredential, EnrollRequest, Uploader};
+
+/// 保存在本地的注册结果 (含凭据，不通过 IPC 返回)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Enrollment {
+    pub device_id: String,
+    pub credential: String,
+    pub server_url: String,
+    pub serial_number: String,
+    pub enrolled_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+/// 注册状态 (IPC get_enrollment 返回)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnrollmentStatus {
+    pub enrolled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enrolled_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// 凭据存储位置
+    pub store: String,
+}
+
+pub struct Enroller {
+    uploader: Arc<Uploader>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    machine_id: Option<String>,
+    os_version: String,
+    store: Box<dyn CredentialStore>,
+    current: RwLock<Option<Enrollment>>,
+    // 串行化注册 / 注销
+    busy: tokio::sync::Mutex<()>,
+}
+
+impl Enroller {
+    /// 读取已保存的凭据并交给上传器；读取失败视为未注册
+    pub fn new(
+        uploader: Arc<Uploader>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        machine_id: Option<String>,
+        os_version: &str,
+        store: Box<dyn CredentialStore>,
+    ) -> Arc<Self> {
+        let current = match store.load() {
+            Ok(enrollment) => enrollment,
+            Err(e) => {
+                log::error!("Stored enrollment ignored: {}", e);
+                None
+            }
+        };
+        if let Some(enrollment) = &current {
+            if enrollment.server_url != uploader.base_url() {
+                log::warn!(
+                    "Device {} was enrolled with {}, but the configured server is {}",
+                    enrollment.device_id,
+                    enrollment.server_url,
+                    uploader.base_url()
+                );
+            }
+            uploader.set_credential(Some(credential_of(enrollment)));
+            log::info!("Device enrolled as {} ({})", enrollment.device_id, store.location());
+        }
+        Arc::new(Self {
+            uploader,
+            device_info,
+            machine_id,
+            os_version: os_version.to_string(),
+            store,
+            current: RwLock::new(current),
+            busy: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn status(&self) -> EnrollmentStatus {
+        let current = self.current.read().unwrap();
+        EnrollmentStatus {
+            enrolled: current.is_some(),
+            device_id: current.as_ref().map(|e| e.device_id.clone()),
+            server_url: current.as_ref().map(|e| e.server_url.clone()),
+            enrolled_at: current.as_ref().map(|e| e.enrolled_at.clone()),
+            expires_at: current.as_ref().and_then(|e| e.expires_at.clone()),
+            store: self.store.location(),
+        }
+    }
+
+    /// 指向新的管理端并注册；失败时恢复原地址与 TLS 配置
+    pub async fn register(&self, base_url: &str, tls: Option<TlsConfig>, pin: &str) -> Result<EnrollmentStatus, String> {
+        let (old_url, old_tls) = (self.uploader.base_url(), self.uploader.tls_config());
+        if let Some(tls) = tls {
+            self.uploader.configure_tls(tls)?;
+        }
+        self.uploader.set_base_url(base_url);
+        let result = self.enroll(pin).await;
+        if result.is_err() {
+            self.uploader.set_base_url(&old_url);
+            if self.uploader.tls_config() != old_tls {
+                let _ = self.uploader.configure_tls(old_tls);
+            }
+        }
+        result
+    }
+
+    /// 向当前管理端注册 (已注册时为重新注册，携带原设备 id)
+    pub async fn enroll(&self, pin: &str) -> Result<EnrollmentStatus, String> {
+        if pin.trim().is_empty() {
+            return Err("Enrollment requires a PIN".to_string());
+        }
+        let _busy = self.busy.lock().await;
+        let device = self.device_info.read().unwrap().clone();
+        let previous_device_id = self.current.read().unwrap().as_ref().map(|e| e.device_id.clone());
+        let request = EnrollRequest {
+            serial_number: device.pin.clone(),
+            machine_id: self.machine_id.clone(),
+            device_name: device.host_id,
+            os_version: self.os_version.clone(),
+            app_version: crate::APP_VERSION.to_string(),
+            mac: device.mac,
+            ip: device.ip,
+            pin: pin.trim().to_string(),
+            previous_device_id,
+        };
+        let data = self.uploader.enroll(&request).await?;
+
+        let enrollment = Enrollment {
+            device_id: data.device_id,
+            credential: data.credential,
+            server_url: self.uploader.base_url(),
+            serial_number: device.pin,
+            enrolled_at: self.uploader.clock().now_str(),
+            expires_at: data.expires_at,
+        };
+        self.store.save(&enrollment)?;
+        self.uploader.set_credential(Some(credential_of(&enrollment)));
+        log::info!(
+            "Device {} as {} with {}",
+            if request.previous_device_id.is_some() { "re-enrolled" } else { "enrolled" },
+            enrollment.device_id,
+            enrollment.server_url
+        );
+        *self.current.write().unwrap() = Some(enrollment);
+        Ok(self.status())
+    }
+
+    /// 通知管理端注销并删除本地凭据；`force` 时即使管理端不可达也删除
+    pub async fn unenroll(&self, force: bool) -> Result<EnrollmentStatus, String> {
+        let _busy = self.busy.lock().await;
+        let Some(device_id) = self.current.read().unwrap().as_ref().map(|e| e.device_id.clone()) else {
+            return Err("Device is not enrolled".to_string());
+        };
+        if let Err(e) = self.uploader.unenroll(&device_id).await {
+            if !force {
+                return Err(e);
+            }
+            log::warn!("Server unenrollment failed, removing local credential anyway: {}", e);
+        }
+        self.store.clear()?;
+        self.uploader.set_credential(None);
+        *self.current.write().unwrap() = None;
+        log::info!("Device {} unenrolled", device_id);
+        Ok(self.status())
+    }
+}
+
+fn credential_of(enrollment: &Enrollment) -> DeviceCredential {
+    DeviceCredential { device_id: enrollment.device_id.clone(), secret: enrollment.credential.clone() }
+}
@@ -0,0 +1,146 @@
+//! 设备凭据的本地存储：macOS 使用钥匙串，其他平台使用仅属主可读写的文件
+
+use std::path::{Path, PathBuf};
+
+use super::Enrollment;
+
+pub trait CredentialStore: Send + Sync {
+    /// 存储位置说明 (IPC 状态展示，不含凭据)
+    fn location(&self) -> String;
+
+    fn load(&self) -> Result<Option<Enrollment>, String>;
+
+    fn save(&self, enrollment: &Enrollment) -> Result<(), String>;
+
+    /// 删除凭据，不存在时也返回 Ok
+    fn clear(&self) -> Result<(), String>;
+}
+
+/// JSON 文件 (0600)，先写临时文件再重命名，避免写一半的凭据
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn location(&self) -> String {
+        format!("file:{}", self.path.display())
+    }
+
+    fn load(&self) -> Result<Option<Enrollment>, String> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|e| format!("Corrupt enrollment file {}: {}", self.path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        }
+    }
+
+    fn save(&self, enrollment: &Enrollment) -> Result<(), String> {
+        use std::io::Write;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let data = serde_json::to_vec_pretty(enrollment).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        file.write_all(&data)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("Failed to replace {}: {}", self.path.display(), e))
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove {}: {}", self.path.display(), e)),
+        }
+    }
+}
+
+/// 钥匙串通用密码项 (服务名 + 账户)，内容为 JSON
+#[cfg(target_os = "macos")]
+pub struct KeychainStore {
+    service: String,
+    account: String,
+}
+
+#[cfg(target_os = "macos")]
+impl KeychainStore {
+    pub const SERVICE: &'static str = "com.macmonitor.audit.enrollment";
+    const ACCOUNT: &'static str = "device";
+    // errSecItemNotFound
+    const NOT_FOUND: i32 = -25300;
+
+    pub fn new() -> Self {
+        Self { service: Self::SERVICE.to_string(), account: Self::ACCOUNT.to_string() }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Default for KeychainStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl CredentialStore for KeychainStore {
+    fn location(&self) -> String {
+        format!("keychain:{}", self.service)
+    }
+
+    fn load(&self) -> Result<Option<Enrollment>, String> {
+        match security_framework::passwords::get_generic_password(&self.service, &self.account) {
+            Ok(data) => serde_json::from_slice(&data).map(Some).map_err(|e| format!("Corrupt keychain enrollment: {}", e)),
+            Err(e) if e.code() == Self::NOT_FOUND => Ok(None),
+            Err(e) => Err(format!("Failed to read keychain: {}", e)),
+        }
+    }
+
+    fn save(&self, enrollment: &Enrollment) -> Result<(), String> {
+        let data = serde_json::to_vec(enrollment).map_err(|e| e.to_string())?;
+        security_framework::passwords::set_generic_password(&self.service, &self.account, &data)
+            .map_err(|e| format!("Failed to write keychain: {}", e))
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        match security_framework::passwords::delete_generic_password(&self.service, &self.account) {
+            Ok(()) => Ok(()),
+            Err(e) if e.code() == Self::NOT_FOUND => Ok(()),
+            Err(e) => Err(format!("Failed to delete keychain item: {}", e)),
+        }
+    }
+}
+
+/// 当前平台的存储：macOS 钥匙串，其他平台为 `path`
+#[allow(unused_variables)]
+pub fn platform_store(path: PathBuf) -> Box<dyn CredentialStore> {
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(KeychainStore::new())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        Box::new(FileStore::new(path))
+    }
+}
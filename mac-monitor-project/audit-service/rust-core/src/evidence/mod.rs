@@ -0,0 +1,584 @@
+//! 离线取证导出
+//!
+//! 把选定的表与时间范围导出为 JSONL / CSV，连同引用的截图文件打包成 tar.gz。
+//! 包内 `manifest.json` 记录每个文件的 SHA-256 与大小，`manifest.sig` 是设备密钥 (Ed25519)
+//! 对 manifest 原始字节的签名 (base64)。`verify_bundle` 不依赖数据库，调查人员可在任何机器上校验。
+//!
+//! IPC 没有身份认证，导出包只写到服务自己的导出目录 (0700)，文件名由调用方给出，
+//! 不接受路径，不覆盖已有文件，包文件权限为 0600。
+
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::Database;
+use crate::models::{DeviceInfo, LogQuery, LogTable};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const SIGNATURE_FILE: &str = "manifest.sig";
+const MANIFEST_VERSION: u32 = 1;
+const ALGORITHM: &str = "ed25519";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// IPC `export_evidence` 的载荷
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRequest {
+    /// 导出目录下的文件名 (tar.gz)，不能含路径，已存在时拒绝
+    pub out: String,
+    /// 缺省导出全部表
+    #[serde(default)]
+    pub tables: Vec<LogTable>,
+    /// 时间范围，含义同 `LogQuery`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// 导出截图表时附带本地截图文件
+    #[serde(default = "default_include_screenshots")]
+    pub include_screenshots: bool,
+}
+
+fn default_include_screenshots() -> bool {
+    true
+}
+
+impl ExportRequest {
+    pub fn new(out: impl Into<String>) -> Self {
+        Self {
+            out: out.into(),
+            tables: Vec::new(),
+            since: None,
+            until: None,
+            format: ExportFormat::default(),
+            include_screenshots: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleDevice {
+    pub cpe_id: String,
+    pub host_id: String,
+    pub mac: String,
+    pub ip: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleTable {
+    pub table: LogTable,
+    pub path: String,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub created_at: String,
+    pub device: BundleDevice,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    pub tables: Vec<BundleTable>,
+    /// 包内除 manifest 与签名外的全部文件
+    pub files: Vec<BundleFile>,
+    /// 记录中引用、但本地已不存在的截图
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_screenshots: Vec<String>,
+    pub algorithm: String,
+    /// 签名公钥 (hex)
+    pub public_key: String,
+}
+
+/// IPC `export_evidence` 的返回
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub tables: Vec<BundleTable>,
+    pub screenshots: usize,
+    pub missing_screenshots: usize,
+    pub bytes: u64,
+    pub public_key: String,
+}
+
+/// `verify_bundle` 的结果；`problems` 为空即完整且签名有效
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BundleReport {
+    pub signature_valid: bool,
+    pub files_checked: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<BundleDevice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    pub problems: Vec<String>,
+}
+
+impl BundleReport {
+    pub fn is_intact(&self) -> bool {
+        self.signature_valid && self.problems.is_empty()
+    }
+}
+
+/// 设备签名密钥，保存在仅属主可读写的文件中，首次导出时生成
+pub struct DeviceKey {
+    signing_key: SigningKey,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    algorithm: String,
+    secret_key: String,
+}
+
+impl DeviceKey {
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        match std::fs::read(path) {
+            Ok(data) => {
+                let file: KeyFile = serde_json::from_slice(&data)
+                    .map_err(|e| format!("Corrupt device key {}: {}", path.display(), e))?;
+                let secret: [u8; 32] = hex::decode(file.secret_key.trim())
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| format!("Corrupt device key {}: expected 32 hex-encoded bytes", path.display()))?;
+                Ok(Self { signing_key: SigningKey::from_bytes(&secret) })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut secret = [0u8; 32];
+                getrandom::getrandom(&mut secret).map_err(|e| format!("Failed to generate device key: {}", e))?;
+                let key = Self { signing_key: SigningKey::from_bytes(&secret) };
+                key.save(path)?;
+                log::info!("Generated evidence signing key {}", path.display());
+                Ok(key)
+            }
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().to_bytes())
+    }
+
+    pub fn sign(&self, message: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.signing_key.sign(message).to_bytes())
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let file = KeyFile { algorithm: ALGORITHM.to_string(), secret_key: hex::encode(self.signing_key.to_bytes()) };
+        let data = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+        // 不覆盖已有密钥
+        let mut out = create_private_file(path)?;
+        out.write_all(&data)
+            .and_then(|_| out.sync_all())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+pub struct EvidenceExporter {
+    db: Arc<Database>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+    key_path: PathBuf,
+    export_dir: PathBuf,
+    screenshot_dir: PathBuf,
+}
+
+impl EvidenceExporter {
+    pub fn new(
+        db: Arc<Database>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+        key_path: impl Into<PathBuf>,
+        export_dir: impl Into<PathBuf>,
+        screenshot_dir: impl Into<PathBuf>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            device_info,
+            clock,
+            key_path: key_path.into(),
+            export_dir: export_dir.into(),
+            screenshot_dir: screenshot_dir.into(),
+        })
+    }
+
+    pub fn export_dir(&self) -> &Path {
+        &self.export_dir
+    }
+
+    /// 导出目录下的包文件；只接受单个文件名 (不含目录、`..` 或绝对路径)
+    pub fn bundle_path(&self, name: &str) -> Result<PathBuf, String> {
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(std::path::Component::Normal(file)), None) if !name.contains(['/', '\\']) => Ok(self.export_dir.join(file)),
+            _ => Err(format!("Invalid bundle name '{}': expected a file name inside the export directory", name)),
+        }
+    }
+
+    /// 设备签名公钥 (hex)，调查人员据此校验导出包；没有密钥时生成
+    pub fn public_key(&self) -> Result<String, String> {
+        DeviceKey::load_or_create(&self.key_path).map(|key| key.public_key_hex())
+    }
+
+    /// 导出并签名到导出目录下的 `request.out`，先写临时文件，完成后再链接到目标名
+    pub async fn export(&self, request: &ExportRequest) -> Result<ExportSummary, String> {
+        let out = self.bundle_path(request.out.trim())?;
+        if out.exists() {
+            return Err(format!("{} already exists", out.display()));
+        }
+        let key = DeviceKey::load_or_create(&self.key_path)?;
+        let mut tables: Vec<LogTable> = Vec::new();
+        for table in if request.tables.is_empty() { LogTable::ALL.to_vec() } else { request.tables.clone() } {
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+
+        let mut data = Vec::new();
+        for table in tables {
+            let rows = self.fetch_rows(table, request).await?;
+            data.push((table, rows));
+        }
+
+        let device = {
+            let info = self.device_info.read().unwrap();
+            BundleDevice { cpe_id: info.cpe_id.clone(), host_id: info.host_id.clone(), mac: info.mac.clone(), ip: info.ip.clone() }
+        };
+        let created_ms = self.clock.now_ms();
+        let bundle = BundleWriter {
+            request: request.clone(),
+            device,
+            created_ms,
+            screenshot_dir: self.screenshot_dir.clone(),
+            key,
+        };
+        let export_dir = self.export_dir.clone();
+        tokio::task::spawn_blocking(move || {
+            create_private_dir(&export_dir)?;
+            bundle.write(&out, data)
+        })
+            .await
+            .map_err(|e| format!("Export task failed: {}", e))?
+    }
+
+    /// 按页读取时间范围内的全部记录
+    async fn fetch_rows(&self, table: LogTable, request: &ExportRequest) -> Result<Vec<Value>, String> {
+        let mut rows = Vec::new();
+        let mut query = LogQuery::new(table);
+        query.since = request.since.clone();
+        query.until = request.until.clone();
+        query.limit = Some(LogQuery::MAX_LIMIT);
+        loop {
+            query.offset = Some(rows.len() as u32);
+            let page = self.db.query_logs(&query).await.map_err(|e| e.to_string())?;
+            let done = page.len() < LogQuery::MAX_LIMIT as usize;
+            rows.extend(page);
+            if done {
+                return Ok(rows);
+            }
+        }
+    }
+}
+
+struct BundleWriter {
+    request: ExportRequest,
+    device: BundleDevice,
+    created_ms: i64,
+    screenshot_dir: PathBuf,
+    key: DeviceKey,
+}
+
+impl BundleWriter {
+    fn write(self, out: &Path, data: Vec<(LogTable, Vec<Value>)>) -> Result<ExportSummary, String> {
+        let file_name = out.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let tmp = out.with_file_name(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+        // 硬链接在目标已存在时失败，不会覆盖其他文件
+        let result = self.write_archive(&tmp, data).and_then(|summary| {
+            std::fs::hard_link(&tmp, out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+            Ok(summary)
+        });
+        let _ = std::fs::remove_file(&tmp);
+        let mut summary = result?;
+        summary.path = out.display().to_string();
+        summary.bytes = std::fs::metadata(out).map(|m| m.len()).unwrap_or_default();
+        log::info!("Exported evidence bundle {} ({} bytes)", summary.path, summary.bytes);
+        Ok(summary)
+    }
+
+    fn write_archive(&self, path: &Path, data: Vec<(LogTable, Vec<Value>)>) -> Result<ExportSummary, String> {
+        let file = create_private_file(path)?;
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut files = Vec::new();
+        let mut tables = Vec::new();
+        let mut screenshots = Vec::new();
+        let mut missing = Vec::new();
+
+        for (table, rows) in &data {
+            let name = format!("data/{}.{}", table, self.request.format.extension());
+            let content = match self.request.format {
+                ExportFormat::Jsonl => rows.iter().map(|row| format!("{}\n", row)).collect::<String>(),
+                ExportFormat::Csv => to_csv(rows),
+            };
+            files.push(self.append(&mut builder, &name, content.as_bytes())?);
+            tables.push(BundleTable { table: *table, path: name, rows: rows.len() });
+            if *table == LogTable::Screenshot && self.request.include_screenshots {
+                screenshots.extend(rows.iter().filter_map(|row| row["image_path"].as_str()).filter(|p| !p.is_empty()));
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut seen = HashSet::new();
+        for image_path in screenshots {
+            if !seen.insert(image_path) {
+                continue;
+            }
+            let Some(local) = self.resolve_screenshot(image_path) else {
+                missing.push(image_path.to_string());
+                continue;
+            };
+            let content = std::fs::read(&local).map_err(|e| format!("Failed to read {}: {}", local.display(), e))?;
+            let file_name = local.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let mut name = format!("screenshots/{}", file_name);
+            // 不同目录下的同名文件
+            if !names.insert(name.clone()) {
+                name = format!("screenshots/{}-{}", names.len(), file_name);
+                names.insert(name.clone());
+            }
+            files.push(self.append(&mut builder, &name, &content)?);
+        }
+
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            created_at: format_ms(self.created_ms),
+            device: self.device.clone(),
+            since: self.request.since.clone(),
+            until: self.request.until.clone(),
+            tables: tables.clone(),
+            files,
+            missing_screenshots: missing.clone(),
+            algorithm: ALGORITHM.to_string(),
+            public_key: self.key.public_key_hex(),
+        };
+        let manifest_bytes = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+        self.append(&mut builder, MANIFEST_FILE, &manifest_bytes)?;
+        self.append(&mut builder, SIGNATURE_FILE, self.key.sign(&manifest_bytes).as_bytes())?;
+
+        let encoder = builder.into_inner().map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        let file = encoder.finish().map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        file.sync_all().map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        Ok(ExportSummary {
+            path: String::new(),
+            tables,
+            screenshots: manifest.files.iter().filter(|f| f.path.starts_with("screenshots/")).count(),
+            missing_screenshots: missing.len(),
+            bytes: 0,
+            public_key: manifest.public_key,
+        })
+    }
+
+    fn append<W: Write>(&self, builder: &mut tar::Builder<W>, name: &str, content: &[u8]) -> Result<BundleFile, String> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime((self.created_ms / 1000).max(0) as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, content)
+            .map_err(|e| format!("Failed to add {} to bundle: {}", name, e))?;
+        Ok(BundleFile { path: name.to_string(), sha256: hex::encode(Sha256::digest(content)), size: content.len() as u64 })
+    }
+
+    /// 同 `SyncService`：原路径不存在时按文件名到截图目录下找
+    fn resolve_screenshot(&self, image_path: &str) -> Option<PathBuf> {
+        let path = Path::new(image_path);
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        let candidate = self.screenshot_dir.join(path.file_name()?);
+        candidate.is_file().then_some(candidate)
+    }
+}
+
+/// 仅属主可访问的目录 (0700)
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))
+}
+
+/// 新建仅属主可读写的文件 (0600)，已存在时失败
+fn create_private_file(path: &Path) -> Result<std::fs::File, String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+}
+
+/// 校验导出包：签名、每个文件的 SHA-256 与大小，以及包内没有未列出的文件。
+/// 给出 `trusted_public_key` (hex) 时要求由该密钥签名，否则只能证明包与其自带公钥一致。
+pub fn verify_bundle(path: &Path, trusted_public_key: Option<&str>) -> Result<BundleReport, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let mut report = BundleReport::default();
+    let mut hashes: BTreeMap<String, (String, u64)> = BTreeMap::new();
+    let mut manifest_bytes = None;
+    let mut signature = None;
+
+    let entries = archive.entries().map_err(|e| format!("Invalid bundle {}: {}", path.display(), e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Invalid bundle {}: {}", path.display(), e))?;
+        let name = entry.path().map_err(|e| format!("Invalid bundle entry: {}", e))?.to_string_lossy().into_owned();
+        match name.as_str() {
+            MANIFEST_FILE | SIGNATURE_FILE => {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+                let slot = if name == MANIFEST_FILE { &mut manifest_bytes } else { &mut signature };
+                if slot.replace(content).is_some() {
+                    report.problems.push(format!("Duplicate {}", name));
+                }
+            }
+            _ => {
+                let mut hasher = Sha256::new();
+                let size = std::io::copy(&mut entry, &mut hasher).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+                if hashes.insert(name.clone(), (hex::encode(hasher.finalize()), size)).is_some() {
+                    report.problems.push(format!("Duplicate entry {}", name));
+                }
+            }
+        }
+    }
+
+    let Some(manifest_bytes) = manifest_bytes else {
+        report.problems.push(format!("Missing {}", MANIFEST_FILE));
+        return Ok(report);
+    };
+    let manifest: Manifest = match serde_json::from_slice(&manifest_bytes) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            report.problems.push(format!("Invalid {}: {}", MANIFEST_FILE, e));
+            return Ok(report);
+        }
+    };
+    report.public_key = Some(manifest.public_key.clone());
+    report.device = Some(manifest.device.clone());
+    report.created_at = Some(manifest.created_at.clone());
+
+    let key = trusted_public_key.map(str::trim).unwrap_or(&manifest.public_key);
+    match signature {
+        _ if !key.eq_ignore_ascii_case(&manifest.public_key) => {
+            report.problems.push("Bundle is signed by a different key than the trusted one".to_string());
+        }
+        Some(signature) => match check_signature(key, &manifest_bytes, &signature) {
+            Ok(()) => report.signature_valid = true,
+            Err(e) => report.problems.push(e),
+        },
+        None => report.problems.push(format!("Missing {}", SIGNATURE_FILE)),
+    }
+
+    for file in &manifest.files {
+        match hashes.remove(&file.path) {
+            None => report.problems.push(format!("Missing file {}", file.path)),
+            Some((sha256, size)) if sha256 != file.sha256 || size != file.size => {
+                report.problems.push(format!("File {} does not match the manifest", file.path));
+            }
+            Some(_) => report.files_checked += 1,
+        }
+    }
+    report.problems.extend(hashes.into_keys().map(|name| format!("File {} is not listed in the manifest", name)));
+    Ok(report)
+}
+
+fn check_signature(public_key_hex: &str, message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let key: [u8; 32] = hex::decode(public_key_hex)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Invalid Ed25519 public key".to_string())?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|e| format!("Invalid Ed25519 public key: {}", e))?;
+    let signature = base64::engine::general_purpose::STANDARD
+        .decode(String::from_utf8_lossy(signature).trim())
+        .map_err(|e| format!("Invalid signature encoding: {}", e))?;
+    let signature = ed25519_dalek::Signature::from_slice(&signature).map_err(|e| format!("Invalid Ed25519 signature: {}", e))?;
+    key.verify_strict(message, &signature).map_err(|_| "Manifest signature is invalid".to_string())
+}
+
+/// 列为所有行中出现过的字段 (按字母序)，嵌套值按 JSON 文本写入
+pub fn to_csv(rows: &[Value]) -> String {
+    let mut columns: Vec<&str> = rows
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|row| row.keys().map(String::as_str))
+        .collect();
+    columns.sort_unstable();
+    columns.dedup();
+
+    let mut out = String::new();
+    out.push_str(&columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","));
+    out.push('\n');
+    for row in rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|c| match row.get(*c) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => csv_field(s),
+                Some(other) => csv_field(&other.to_string()),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use super::{FileEvent, FileOp};
+
+/// 合并后的一批同类事件
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileBatch {
+    pub op: FileOp,
+    pub path: String,
+    pub old_path: Option<String>,
+    pub process_name: String,
+    pub pid: Option<i64>,
+    pub size: Option<i64>,
+    pub first_ms: i64,
+    pub last_ms: i64,
+    pub count: i64,
+}
+
+impl FileBatch {
+    fn new(event: FileEvent, now_ms: i64) -> Self {
+        let time = event.time_ms.unwrap_or(now_ms);
+        Self {
+            op: event.op,
+            path: event.path,
+            old_path: event.old_path,
+            process_name: event.process_name,
+            pid: event.pid,
+            size: event.size,
+            first_ms: time,
+            last_ms: time,
+            count: 1,
+        }
+    }
+
+    fn merge(&mut self, event: FileEvent, now_ms: i64) {
+        self.last_ms = event.time_ms.unwrap_or(now_ms).max(self.last_ms);
+        self.count += 1;
+        if event.size.is_some() {
+            self.size = event.size;
+        }
+        if event.pid.is_some() {
+            self.pid = event.pid;
+        }
+    }
+}
+
+type Key = (String, FileOp, String);
+
+/// 同一进程对同一路径的同类事件在窗口期内合并为一条记录
+///
+/// 创建后紧接着的写入并入创建事件 (编辑器保存、下载等通常表现为 create + 多次 modify)。
+pub struct Batcher {
+    window_ms: i64,
+    max_pending: usize,
+    pending: HashMap<Key, FileBatch>,
+}
+
+impl Batcher {
+    pub fn new(window_ms: i64, max_pending: usize) -> Self {
+        Self { window_ms: window_ms.max(0), max_pending: max_pending.max(1), pending: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// 加入一个事件；待合并的条目超过上限时返回需要立即写入的批次
+    pub fn push(&mut self, event: FileEvent, now_ms: i64) -> Vec<FileBatch> {
+        if self.window_ms == 0 {
+            return vec![FileBatch::new(event, now_ms)];
+        }
+
+        let mut key = (event.path.clone(), event.op, event.process_name.clone());
+        if event.op == FileOp::Modify {
+            let create_key = (event.path.clone(), FileOp::Create, event.process_name.clone());
+            if self.pending.contains_key(&create_key) {
+                key = create_key;
+            }
+        }
+        match self.pending.get_mut(&key) {
+            Some(batch) => batch.merge(event, now_ms),
+            None => {
+                self.pending.insert(key, FileBatch::new(event, now_ms));
+            }
+        }
+
+        if self.pending.len() > self.max_pending {
+            self.drain_all()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// 取出窗口期已结束的批次，按首次事件时间排序
+    pub fn drain_due(&mut self, now_ms: i64) -> Vec<FileBatch> {
+        let window = self.window_ms;
+        let due: Vec<Key> = self
+            .pending
+            .iter()
+            .filter(|(_, batch)| now_ms - batch.first_ms >= window)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut batches: Vec<FileBatch> = due.iter().filter_map(|key| self.pending.remove(key)).collect();
+        batches.sort_by_key(|b| b.first_ms);
+        batches
+    }
+
+    pub fn drain_all(&mut self) -> Vec<FileBatch> {
+        let mut batches: Vec<FileBatch> = self.pending.drain().map(|(_, batch)| batch).collect();
+        batches.sort_by_key(|b| b.first_ms);
+        batches
+    }
+}
@@ -0,0 +1,171 @@
+//! Linux 文件事件源：inotify 递归监听配置的目录
+//!
+//! inotify 不提供操作进程，事件的 process_name 为 "unknown"；需要进程归属时改用 fanotify
+//! (需要 CAP_SYS_ADMIN) 或通过 IPC `log_file_event` 由外部采集器提交。
+
+use inotify::{EventMask, Inotify, WatchMask};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{FileAuditor, FileEvent, FileOp};
+use crate::lifecycle::ShutdownSignal;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// MOVED_FROM 之后在该时间内没有等到配对的 MOVED_TO，视为移出监听范围 (按删除记录)
+const RENAME_PAIR_TIMEOUT: Duration = Duration::from_millis(500);
+
+fn watch_mask() -> WatchMask {
+    WatchMask::CREATE | WatchMask::CLOSE_WRITE | WatchMask::MOVED_FROM | WatchMask::MOVED_TO | WatchMask::DELETE
+}
+
+/// 在独立线程上监听 `paths`，直到收到停止信号
+pub fn start(paths: &[String], auditor: Arc<FileAuditor>, shutdown: ShutdownSignal) -> Result<JoinHandle<()>, String> {
+    let inotify = Inotify::init().map_err(|e| format!("Failed to initialize inotify: {}", e))?;
+    let mut watcher = Watcher { inotify, dirs: HashMap::new(), moves: HashMap::new(), auditor };
+    for path in paths {
+        let root = Path::new(path);
+        if !root.is_dir() {
+            log::warn!("File audit watch path {} is not a directory, skipped", path);
+            continue;
+        }
+        watcher.watch_tree(root);
+    }
+    log::info!("File audit watching {} directories under {:?}", watcher.dirs.len(), paths);
+
+    std::thread::Builder::new()
+        .name("file-audit-inotify".to_string())
+        .spawn(move || watcher.run(shutdown))
+        .map_err(|e| format!("Failed to start inotify thread: {}", e))
+}
+
+struct Watcher {
+    inotify: Inotify,
+    /// watch descriptor -> 目录
+    dirs: HashMap<i32, PathBuf>,
+    /// rename cookie -> (原路径, 收到 MOVED_FROM 的时间)
+    moves: HashMap<u32, (PathBuf, Instant)>,
+    auditor: Arc<FileAuditor>,
+}
+
+impl Watcher {
+    fn run(mut self, shutdown: ShutdownSignal) {
+        let mut buffer = vec![0u8; 64 * 1024];
+        while !shutdown.is_triggered() {
+            let events: Vec<(i32, EventMask, u32, Option<PathBuf>)> = match self.inotify.read_events(&mut buffer) {
+                Ok(events) => events
+                    .map(|e| (e.wd.get_watch_descriptor_id(), e.mask, e.cookie, e.name.map(|n: &OsStr| PathBuf::from(n))))
+                    .collect(),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => Vec::new(),
+                Err(e) => {
+                    log::error!("inotify read failed, file audit stopped: {}", e);
+                    return;
+                }
+            };
+            let idle = events.is_empty();
+            for (wd, mask, cookie, name) in events {
+                self.handle(wd, mask, cookie, name);
+            }
+            self.expire_moves();
+            if idle {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+        log::info!("File audit inotify watcher stopped");
+    }
+
+    fn handle(&mut self, wd: i32, mask: EventMask, cookie: u32, name: Option<PathBuf>) {
+        if mask.contains(EventMask::Q_OVERFLOW) {
+            log::warn!("inotify queue overflowed, some file events were lost");
+            return;
+        }
+        if mask.contains(EventMask::IGNORED) {
+            self.dirs.remove(&wd);
+            return;
+        }
+        let (Some(dir), Some(name)) = (self.dirs.get(&wd), name) else {
+            return;
+        };
+        let path = dir.join(name);
+        let is_dir = mask.contains(EventMask::ISDIR);
+
+        if is_dir {
+            // 目录本身不记录，只维护监听；移入的目录中已有的文件不逐个补记
+            if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                self.watch_tree(&path);
+            }
+            return;
+        }
+
+        if mask.contains(EventMask::MOVED_FROM) {
+            self.moves.insert(cookie, (path, Instant::now()));
+            return;
+        }
+        let event = if mask.contains(EventMask::MOVED_TO) {
+            match self.moves.remove(&cookie) {
+                Some((old_path, _)) => {
+                    let mut event = FileEvent::new(FileOp::Rename, path.to_string_lossy());
+                    event.old_path = Some(old_path.to_string_lossy().into_owned());
+                    event
+                }
+                // 从监听范围外移入
+                None => FileEvent::new(FileOp::Create, path.to_string_lossy()),
+            }
+        } else if mask.contains(EventMask::CREATE) {
+            FileEvent::new(FileOp::Create, path.to_string_lossy())
+        } else if mask.contains(EventMask::CLOSE_WRITE) {
+            FileEvent::new(FileOp::Modify, path.to_string_lossy())
+        } else if mask.contains(EventMask::DELETE) {
+            FileEvent::new(FileOp::Delete, path.to_string_lossy())
+        } else {
+            return;
+        };
+        self.submit(event);
+    }
+
+    fn expire_moves(&mut self) {
+        let expired: Vec<u32> = self
+            .moves
+            .iter()
+            .filter(|(_, (_, at))| at.elapsed() >= RENAME_PAIR_TIMEOUT)
+            .map(|(cookie, _)| *cookie)
+            .collect();
+        for cookie in expired {
+            if let Some((path, _)) = self.moves.remove(&cookie) {
+                self.submit(FileEvent::new(FileOp::Delete, path.to_string_lossy()));
+            }
+        }
+    }
+
+    fn submit(&self, event: FileEvent) {
+        if let Err(e) = self.auditor.submit(event) {
+            log::warn!("File event rejected: {}", e);
+        }
+    }
+
+    fn watch_tree(&mut self, root: &Path) {
+        let mut stack = vec![root.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            match self.inotify.watches().add(&dir, watch_mask()) {
+                Ok(wd) => {
+                    self.dirs.insert(wd.get_watch_descriptor_id(), dir.clone());
+                }
+                Err(e) => {
+                    log::warn!("Cannot watch {}: {}", dir.display(), e);
+                    continue;
+                }
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+            for entry in entries.flatten() {
+                // 不跟随符号链接，避免循环
+                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    stack.push(entry.path());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! 文件操作审计：创建 / 修改 / 重命名 / 删除 / 拷贝到移动存储
+//!
+//! 事件来源：macOS 由 Swift 宿主 (EndpointSecurity) 通过 FFI `log_file_event` 提交，
+//! Linux 由内置的 inotify 监听 (`linux::InotifyProducer`) 提交，也可通过 IPC `log_file_event` 提交。
+//! 事件先按策略中的路径 glob 过滤，再在窗口期内合并，最后计算哈希写入 `file_logs`。
+
+pub mod batcher;
+#[cfg(target_os = "linux")]
+pub mod linux;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use self::batcher::{Batcher, FileBatch};
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::Database;
+use crate::lifecycle::ShutdownSignal;
+use crate::models::{DeviceInfo, FileLog, PolicyConfig};
+
+const DEFAULT_BATCH_WINDOW_MS: u64 = 2_000;
+const DEFAULT_HASH_MAX_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_MAX_PENDING: usize = 10_000;
+const DEFAULT_REMOVABLE_PREFIXES: [&str; 3] = ["/Volumes/", "/media/", "/run/media/"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOp {
+    Create,
+    Modify,
+    Rename,
+    Delete,
+    CopyToRemovable,
+}
+
+impl FileOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileOp::Create => "create",
+            FileOp::Modify => "modify",
+            FileOp::Rename => "rename",
+            FileOp::Delete => "delete",
+            FileOp::CopyToRemovable => "copy_to_removable",
+        }
+    }
+}
+
+/// 生产者提交的原始事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEvent {
+    pub op: FileOp,
+    pub path: String,
+    /// 重命名前的路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    #[serde(default = "unknown_process")]
+    pub process_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i64>,
+    /// 缺省时写入前读取文件大小
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+    /// 事件时间 (逻辑时钟毫秒)，缺省为提交时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<i64>,
+}
+
+fn unknown_process() -> String {
+    "unknown".to_string()
+}
+
+impl FileEvent {
+    pub fn new(op: FileOp, path: impl Into<String>) -> Self {
+        Self { op, path: path.into(), old_path: None, process_name: unknown_process(), pid: None, size: None, time_ms: None }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileAuditConfig {
+    /// Linux 上由 inotify 递归监听的目录；macOS 由 Swift 宿主提交事件，忽略此项
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_paths: Vec<String>,
+    /// 合并窗口 (毫秒)，缺省 2000，0 表示不合并
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_window_ms: Option<u64>,
+    /// 超过该大小的文件不计算哈希，缺省 64 MiB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_max_bytes: Option<u64>,
+    /// 移动存储挂载点前缀，缺省 /Volumes/、/media/、/run/media/
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removable_prefixes: Option<Vec<String>>,
+}
+
+impl FileAuditConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn is_removable(&self, path: &str) -> bool {
+        match &self.removable_prefixes {
+            Some(prefixes) => prefixes.iter().any(|p| path.starts_with(p.as_str())),
+            None => DEFAULT_REMOVABLE_PREFIXES.iter().any(|p| path.starts_with(p)),
+        }
+    }
+}
+
+/// 策略中的路径 glob：`*` 不跨目录，`**` 跨任意层目录；include 为空表示全部
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, String> {
+        let include = if include.is_empty() { None } else { Some(build_globset(include)?) };
+        Ok(Self { include, exclude: build_globset(exclude)? })
+    }
+
+    pub fn allows(&self, path: &str) -> bool {
+        if self.exclude.is_match(path) {
+            return false;
+        }
+        self.include.as_ref().is_none_or(|include| include.is_match(path))
+    }
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = globset::GlobBuilder::new(pattern.trim())
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid path glob '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// 校验策略中的 glob (策略下发时调用)
+pub fn validate_globs(patterns: &[String]) -> Result<(), String> {
+    patterns.iter().try_for_each(|p| Glob::new(p.trim()).map(|_| ()).map_err(|e| format!("'{}': {}", p, e)))
+}
+
+struct CachedFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    filter: Arc<FileFilter>,
+}
+
+/// 文件事件的过滤、合并与落库
+pub struct FileAuditor {
+    db: Arc<Database>,
+    policy: Arc<RwLock<PolicyConfig>>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+    config: FileAuditConfig,
+    batcher: Mutex<Batcher>,
+    // 超出待合并上限时提前结束窗口的批次，等下一次 flush 写入
+    ready: Mutex<Vec<FileBatch>>,
+    filter: Mutex<Option<CachedFilter>>,
+    closed: AtomicBool,
+}
+
+impl FileAuditor {
+    pub fn new(
+        db: Arc<Database>,
+        policy: Arc<RwLock<PolicyConfig>>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+        config: FileAuditConfig,
+    ) -> Arc<Self> {
+        let window = config.batch_window_ms.unwrap_or(DEFAULT_BATCH_WINDOW_MS) as i64;
+        Arc::new(Self {
+            db,
+            policy,
+            device_info,
+            clock,
+            config,
+            batcher: Mutex::new(Batcher::new(window, DEFAULT_MAX_PENDING)),
+            ready: Mutex::new(Vec::new()),
+            filter: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn config(&self) -> &FileAuditConfig {
+        &self.config
+    }
+
+    /// 提交一个事件；返回 false 表示被策略过滤
+    pub fn submit(&self, mut event: FileEvent) -> Result<bool, String> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err("Service is shutting down".to_string());
+        }
+        if event.path.trim().is_empty() {
+            return Err("File event has an empty path".to_string());
+        }
+        let filter = self.current_filter()?;
+        let old_allowed = event.old_path.as_deref().is_some_and(|p| filter.allows(p));
+        if !filter.allows(&event.path) && !old_allowed {
+            return Ok(false);
+        }
+
+        if matches!(event.op, FileOp::Create | FileOp::Modify) && self.config.is_removable(&event.path) {
+            event.op = FileOp::CopyToRemovable;
+        }
+        let overflow = self.batcher.lock().unwrap().push(event, self.clock.now_ms());
+        if !overflow.is_empty() {
+            log::warn!("File event backlog exceeded {} entries, flushing early", DEFAULT_MAX_PENDING);
+            self.ready.lock().unwrap().extend(overflow);
+        }
+        Ok(true)
+    }
+
+    /// 停止接收新事件 (退出流程)，已接收的由 `flush(true)` 写入
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// 待写入的事件数 (含窗口期内合并中的)
+    pub fn pending(&self) -> usize {
+        self.batcher.lock().unwrap().len() + self.ready.lock().unwrap().len()
+    }
+
+    /// 定期写入窗口期已结束的批次，停止时写入全部
+    pub fn start(self: Arc<Self>, mut shutdown: ShutdownSignal) -> tokio::task::JoinHandle<()> {
+        let window = self.config.batch_window_ms.unwrap_or(DEFAULT_BATCH_WINDOW_MS);
+        let tick = Duration::from_millis((window / 2).clamp(100, 1_000));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tick);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
+                self.flush(false).await;
+            }
+            self.flush(true).await;
+        })
+    }
+
+    /// 写入批次，`all` 为 true 时不等待窗口结束；返回写入的记录数
+    pub async fn flush(&self, all: bool) -> usize {
+        let mut batches = std::mem::take(&mut *self.ready.lock().unwrap());
+        {
+            let mut batcher = self.batcher.lock().unwrap();
+            batches.extend(if all { batcher.drain_all() } else { batcher.drain_due(self.clock.now_ms()) });
+        }
+
+        let mut written = 0;
+        for batch in batches {
+            let log = self.to_log(batch).await;
+            match self.db.save_file_log(&log).await {
+                Ok(()) => written += 1,
+                Err(e) => log::error!("Failed to save file log for {}: {}", log.path, e),
+            }
+        }
+        if written > 0 {
+            log::debug!("Saved {} file logs", written);
+        }
+        written
+    }
+
+    fn current_filter(&self) -> Result<Arc<FileFilter>, String> {
+        let policy = self.policy.read().unwrap();
+        let mut cached = self.filter.lock().unwrap();
+        if let Some(c) = cached.as_ref() {
+            if c.include == policy.file_include_paths && c.exclude == policy.file_exclude_paths {
+                return Ok(c.filter.clone());
+            }
+        }
+        let filter = Arc::new(FileFilter::new(&policy.file_include_paths, &policy.file_exclude_paths)?);
+        *cached = Some(CachedFilter {
+            include: policy.file_include_paths.clone(),
+            exclude: policy.file_exclude_paths.clone(),
+            filter: filter.clone(),
+        });
+        Ok(filter)
+    }
+
+    async fn to_log(&self, batch: FileBatch) -> FileLog {
+        let hash_max = self.config.hash_max_bytes.unwrap_or(DEFAULT_HASH_MAX_BYTES);
+        let (size, sha256) = if matches!(batch.op, FileOp::Delete) {
+            (batch.size, None)
+        } else {
+            let path = batch.path.clone();
+            tokio::task::spawn_blocking(move || inspect_file(Path::new(&path), hash_max))
+                .await
+                .unwrap_or((None, None))
+        };
+        let device = self.device_info.read().unwrap().clone();
+        FileLog {
+            id: None,
+            op_time: format_ms(batch.first_ms),
+            last_op_time: format_ms(batch.last_ms),
+            op_type: batch.op.as_str().to_string(),
+            path: batch.path,
+            old_path: batch.old_path,
+            size: size.or(batch.size),
+            sha256,
+            process_name: batch.process_name,
+            pid: batch.pid,
+            event_count: batch.count,
+            risk_level: if batch.op == FileOp::CopyToRemovable { 1 } else { 0 },
+            cpe_id: device.cpe_id,
+            host_id: device.host_id,
+            mac: device.mac,
+            ip: device.ip,
+        }
+    }
+}
+
+/// 文件大小与 SHA-256；文件已不存在或超过大小上限时不计算哈希
+fn inspect_file(path: &Path, hash_max: u64) -> (Option<i64>, Option<String>) {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return (None, None);
+    };
+    if !metadata.is_file() {
+        return (None, None);
+    }
+    let size = Some(metadata.len() as i64);
+    if metadata.len() > hash_max {
+        return (size, None);
+    }
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return (size, None);
+    };
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(_) => return (size, None),
+        }
+    }
+    (size, Some(hex::encode(hasher.finalize())))
+}
@@ -0,0 +1,31 @@
+//! IPC 客户端：一个连接发送一条命令，读到服务端关闭连接为止即为完整响应
+
+use interprocess::local_socket::LocalSocketStream;
+use std::io::{Read, Write};
+
+use super::{IpcCommand, IpcResponse};
+
+pub fn request(socket_path: &str, command: &str, payload: serde_json::Value) -> Result<IpcResponse, String> {
+    let mut stream = LocalSocketStream::connect(socket_path)
+        .map_err(|e| format!("Cannot connect to audit service at {}: {}", socket_path, e))?;
+
+    let body = serde_json::to_vec(&IpcCommand { command: command.to_string(), payload })
+        .map_err(|e| format!("Failed to encode command: {}", e))?;
+    // 服务端单次 read 读取命令，需一次写完
+    stream.write_all(&body).map_err(|e| format!("Failed to send command: {}", e))?;
+    stream.flush().map_err(|e| format!("Failed to send command: {}", e))?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).map_err(|e| format!("Failed to read response: {}", e))?;
+    serde_json::from_slice(&response).map_err(|e| format!("Invalid response from audit service: {}", e))
+}
+
+/// 同 `request`，status 不是 ok 时返回 message 作为错误
+pub fn call(socket_path: &str, command: &str, payload: serde_json::Value) -> Result<IpcResponse, String> {
+    let response = request(socket_path, command, payload)?;
+    if response.status == "ok" {
+        Ok(response)
+    } else {
+        Err(response.message)
+    }
+}
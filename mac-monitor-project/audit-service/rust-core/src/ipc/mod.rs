@@ -1,98 +1,285 @@
+pub mod client;
+
 use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
 use serde::{Deserialize, Serialize};
-use std::io::{self, Read, Write};
-use std::sync::Arc;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use tokio::runtime::Handle;
+use crate::clock::LogicalClock;
 use crate::db::Database;
+use crate::files::{FileAuditor, FileEvent};
+use crate::peripherals::{PeripheralEvent, PeripheralMonitor};
+use crate::session::{SessionEvent, SessionTracker};
+use crate::usage::{local_day, ForegroundEvent, UsageTracker};
+use crate::alerts::AlertDispatcher;
+use crate::evidence::{verify_bundle, EvidenceExporter, ExportRequest};
+use crate::enrollment::Enroller;
+use crate::lifecycle::TaskTracker;
+use crate::metrics::Metrics;
+use crate::policy::PolicyStore;
+use crate::traffic::aggregate::TrafficAggregator;
+use crate::traffic::TrafficFilter;
+use crate::updater::Updater;
+use crate::uploader::bandwidth::NetworkMode;
+use crate::uploader::sync::SyncService;
+use crate::uploader::tls::{build_base_url, TlsConfig};
 use crate::uploader::Uploader;
-use crate::models::AuditLog;
+use crate::models::{AuditLog, LogQuery};
+
+pub const SOCKET_PATH: &str = "/tmp/mac_monitor_audit.sock";
+/// 未指定配置文件时 register 写回的位置 (与 Swift 宿主一致)
+pub const DEFAULT_CONFIG_PATH: &str = "/Users/adolf/Desktop/code/clash/mac-monitor-project/audit-service/config.json";
+// 注册 / 注销需要访问管理端
+const ENROLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+// socket 为 0777 供 GUI 连接；改变管理状态的命令只接受 root 或服务自身用户
+const ADMIN_COMMANDS: &[&str] = &["register", "reenroll", "unenroll", "rollback_policy", "set_network_mode"];
+// get_alerts 长轮询的最长等待，防止客户端让连接线程无限期阻塞
+const MAX_ALERT_WAIT_MS: u64 = 60_000;
 
-#[derive(Debug, Deserialize)]
-struct IpcCommand {
-    command: String,
-    payload: serde_json::Value,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IpcCommand {
+    pub command: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
 }
 
-#[derive(Debug, Serialize)]
-struct IpcResponse {
-    status: String,
-    message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    payload: Option<serde_json::Value>,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IpcResponse {
+    pub status: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
+}
+
+/// GUI 宿主提供的打码开关回调；独立守护进程没有宿主，不注册
+pub type RedactionCallback = extern "C" fn(enabled: bool);
+
+static REDACTION_CALLBACK: RwLock<Option<RedactionCallback>> = RwLock::new(None);
+
+pub fn set_redaction_callback(callback: Option<RedactionCallback>) {
+    *REDACTION_CALLBACK.write().unwrap() = callback;
 }
 
 pub struct IpcServer {
     db: Arc<Database>,
     uploader: Arc<Uploader>,
+    clock: Arc<LogicalClock>,
+    policy: Arc<PolicyStore>,
+    traffic: TrafficFilter,
+    updater: Arc<Updater>,
+    metrics: Arc<Metrics>,
+    sync: Arc<SyncService>,
+    tasks: Arc<TaskTracker>,
     runtime_handle: Handle,
+    socket_path: String,
+    config_path: PathBuf,
+    files: Option<Arc<FileAuditor>>,
+    peripherals: Option<Arc<PeripheralMonitor>>,
+    session: Option<Arc<SessionTracker>>,
+    usage: Option<Arc<UsageTracker>>,
+    alerts: Option<Arc<AlertDispatcher>>,
+    evidence: Option<Arc<EvidenceExporter>>,
+    aggregator: Option<Arc<TrafficAggregator>>,
+    enroller: Option<Arc<Enroller>>,
+}
+
+/// 停止 IPC 服务
+pub struct IpcHandle {
+    stopped: Arc<AtomicBool>,
+    socket_path: String,
+}
+
+impl IpcHandle {
+    /// 标记停止并唤醒阻塞在 accept 上的线程，随后删除 socket 文件
+    pub fn stop(&self) {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let _ = LocalSocketStream::connect(self.socket_path.as_str());
+        if let Err(e) = std::fs::remove_file(&self.socket_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to remove IPC socket {}: {}", self.socket_path, e);
+            }
+        }
+    }
 }
 
 impl IpcServer {
-    pub fn new(db: Arc<Database>, uploader: Arc<Uploader>, runtime_handle: Handle) -> Self {
-        Self { db, uploader, runtime_handle }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        db: Arc<Database>,
+        uploader: Arc<Uploader>,
+        clock: Arc<LogicalClock>,
+        policy: Arc<PolicyStore>,
+        updater: Arc<Updater>,
+        metrics: Arc<Metrics>,
+        sync: Arc<SyncService>,
+        tasks: Arc<TaskTracker>,
+        runtime_handle: Handle,
+    ) -> Self {
+        Self {
+            db,
+            uploader,
+            clock,
+            traffic: TrafficFilter::new(policy.shared()),
+            policy,
+            updater,
+            metrics,
+            sync,
+            tasks,
+            runtime_handle,
+            socket_path: SOCKET_PATH.to_string(),
+            config_path: PathBuf::from(DEFAULT_CONFIG_PATH),
+            files: None,
+            peripherals: None,
+            session: None,
+            usage: None,
+            alerts: None,
+            evidence: None,
+            aggregator: None,
+            enroller: None,
+        }
     }
 
-    pub fn start(self) {
-        std::thread::spawn(move || {
-            let name = "/tmp/mac_monitor_audit.sock";
+    /// 监听其他路径 (缺省 /tmp/mac_monitor_audit.sock)
+    pub fn with_socket_path(mut self, path: impl Into<String>) -> Self {
+        self.socket_path = path.into();
+        self
+    }
 
-            // Try to remove old socket file
-            if let Err(e) = std::fs::remove_file(name) {
-                if e.kind() != std::io::ErrorKind::NotFound {
-                    eprintln!("⚠️  Warning: Failed to remove old socket file: {}", e);
-                    eprintln!("    If the socket is owned by root, run: sudo rm -f {}", name);
-                    eprintln!("    Attempting to bind anyway...");
-                }
+    /// register 命令写回的配置文件
+    pub fn with_config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = path.into();
+        self
+    }
+
+    /// 处理 register / reenroll / unenroll / get_enrollment
+    pub fn with_enroller(mut self, enroller: Arc<Enroller>) -> Self {
+        self.enroller = Some(enroller);
+        self
+    }
+
+    /// 接收 `log_file_event` 提交的文件事件
+    pub fn with_file_auditor(mut self, files: Arc<FileAuditor>) -> Self {
+        self.files = Some(files);
+        self
+    }
+
+    /// 接收 `log_peripheral_event` 提交的外设事件
+    pub fn with_peripheral_monitor(mut self, peripherals: Arc<PeripheralMonitor>) -> Self {
+        self.peripherals = Some(peripherals);
+        self
+    }
+
+    /// 接收 `log_session_event` 提交的会话事件，并通过 `get_session_state` 查询锁屏状态
+    pub fn with_session_tracker(mut self, session: Arc<SessionTracker>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// 接收 `log_foreground_event` 提交的前台应用事件，并通过 `get_app_usage` 查询每日使用时长
+    pub fn with_usage_tracker(mut self, usage: Arc<UsageTracker>) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// 通过 `get_alerts` 向 GUI 提供高风险告警
+    pub fn with_alert_dispatcher(mut self, alerts: Arc<AlertDispatcher>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// 处理 `export_evidence` 取证导出
+    pub fn with_evidence_exporter(mut self, evidence: Arc<EvidenceExporter>) -> Self {
+        self.evidence = Some(evidence);
+        self
+    }
+
+    pub fn with_traffic_aggregator(mut self, aggregator: Arc<TrafficAggregator>) -> Self {
+        self.aggregator = Some(aggregator);
+        self
+    }
+
+    /// 绑定 socket 并在后台线程处理请求；绑定失败时返回错误而不是 panic
+    pub fn start(self) -> Result<IpcHandle, String> {
+        let socket_path = self.socket_path.clone();
+        let name = socket_path.as_str();
+
+        // Try to remove old socket file
+        if let Err(e) = std::fs::remove_file(name) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!(
+                    "Failed to remove old socket file {} ({}); if it is owned by root, run: sudo rm -f {}. Attempting to bind anyway",
+                    name, e, name
+                );
             }
+        }
 
-            let listener = match LocalSocketListener::bind(name) {
-                Ok(l) => l,
-                Err(e) => {
-                    eprintln!("❌ FATAL: Failed to bind IPC socket at {}: {}", name, e);
-                    eprintln!("    This usually means the socket file already exists and is owned by root.");
-                    eprintln!("    Please run: sudo rm -f {}", name);
-                    eprintln!("    Then restart the AuditService.");
-                    panic!("Failed to bind IPC socket: {}", e);
-                }
-            };
+        let listener = LocalSocketListener::bind(name).map_err(|e| {
+            format!(
+                "Failed to bind IPC socket at {}: {}. The socket file probably exists and is owned by root; run: sudo rm -f {} and restart the AuditService",
+                name, e, name
+            )
+        })?;
 
-            // Set permissions to 777 so GUI (non-root) can connect
-            use std::os::unix::fs::PermissionsExt;
-            if let Ok(metadata) = std::fs::metadata(name) {
-                let mut perms = metadata.permissions();
-                perms.set_mode(0o777);
-                if let Err(e) = std::fs::set_permissions(name, perms) {
-                    eprintln!("Failed to set IPC socket permissions: {}", e);
-                }
+        // Set permissions to 777 so GUI (non-root) can connect
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = std::fs::metadata(name) {
+            let mut perms = metadata.permissions();
+            perms.set_mode(0o777);
+            if let Err(e) = std::fs::set_permissions(name, perms) {
+                log::warn!("Failed to set IPC socket permissions: {}", e);
             }
+        }
 
-            println!("IPC server listening on {}", name);
+        log::info!("IPC server listening on {}", name);
 
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop_flag = stopped.clone();
+        let server = Arc::new(self);
+        std::thread::spawn(move || {
             for stream in listener.incoming() {
+                if stop_flag.load(Ordering::SeqCst) {
+                    break;
+                }
                 match stream {
                     Ok(stream) => {
-                        self.handle_client(stream);
+                        // 每个连接单独一个线程，sync_now / query 等耗时命令不阻塞流量上报
+                        let server = server.clone();
+                        std::thread::spawn(move || server.handle_client(stream));
                     }
                     Err(e) => {
-                        eprintln!("IPC connection failed: {}", e);
+                        log::warn!("IPC connection failed: {}", e);
                     }
                 }
             }
+            log::info!("IPC server stopped");
         });
+
+        Ok(IpcHandle { stopped, socket_path })
     }
 
     fn handle_client(&self, mut stream: LocalSocketStream) {
-        println!("DEBUG: handle_client start");
         let mut buffer = [0u8; 65536];
         match stream.read(&mut buffer) {
             Ok(n) if n > 0 => {
-                println!("DEBUG: Read {} bytes", n);
                 let cmd_str = String::from_utf8_lossy(&buffer[..n]);
-                println!("Received IPC command: {}", cmd_str);
 
+                // 载荷可能包含 PIN 等敏感信息，只记录命令名
+                let caller = peer_uid(&stream);
                 let response = match serde_json::from_str::<IpcCommand>(&cmd_str) {
+                    Ok(cmd) if ADMIN_COMMANDS.contains(&cmd.command.as_str()) && !is_privileged(caller) => {
+                        log::warn!("Rejected IPC command {} from unprivileged caller (uid {:?})", cmd.command, caller);
+                        IpcResponse {
+                            status: "error".to_string(),
+                            message: format!("Permission denied: {} requires root", cmd.command),
+                            payload: None,
+                        }
+                    }
                     Ok(cmd) => {
-                        println!("DEBUG: Command parsed, processing...");
+                        log::debug!("Received IPC command: {} ({} bytes)", cmd.command, n);
                         self.process_command(cmd)
                     },
                     Err(e) => IpcResponse {
@@ -101,101 +288,180 @@ impl IpcServer {
                         payload: None,
                     },
                 };
-                
-                println!("DEBUG: Sending response...");
+
                 let response_json = serde_json::to_string(&response).unwrap_or_else(|_| "{\"status\":\"error\"}".to_string());
                 let _ = stream.write_all(response_json.as_bytes());
                 let _ = stream.flush();
-                println!("DEBUG: Response sent.");
+                log::debug!("IPC response sent: {}", response.status);
             }
-            Ok(_) => println!("IPC client closed connection or sent empty data"),
-            Err(e) => eprintln!("Failed to read from IPC stream: {}", e),
+            Ok(_) => log::debug!("IPC client closed connection or sent empty data"),
+            Err(e) => log::warn!("Failed to read from IPC stream: {}", e),
         }
-        println!("DEBUG: handle_client end");
     }
 
     fn process_command(&self, cmd: IpcCommand) -> IpcResponse {
         match cmd.command.as_str() {
             "register" => {
-                let uploader = self.uploader.clone();
+                let Some(enroller) = self.enroller.clone() else {
+                    return IpcResponse { status: "error".to_string(), message: "Enrollment is not enabled".to_string(), payload: None };
+                };
                 let server_ip = cmd.payload["server_ip"].as_str().unwrap_or("").to_string();
                 let server_port = cmd.payload["server_port"].as_str().unwrap_or("").to_string();
-                let _cpe_id = cmd.payload["cpe_id"].as_str().unwrap_or("").to_string();
                 let pin = cmd.payload["pin"].as_str().unwrap_or("").to_string();
 
-                if server_ip.is_empty() || server_port.is_empty() {
+                if server_ip.is_empty() || server_port.is_empty() || pin.is_empty() {
                     return IpcResponse {
                         status: "error".to_string(),
-                        message: "Missing server_ip or server_port".to_string(),
+                        message: "Missing server_ip, server_port or pin".to_string(),
                         payload: None,
                     };
                 }
 
-                println!("Registering device via IPC: {}:{}", server_ip, server_port);
-
-                let base_url = format!("http://{}:{}", server_ip, server_port);
-                let app_code = "mac_monitor".to_string();
-                let app_secret = pin.clone();
-                let serial_number = "MAC_SN_123456".to_string(); // TODO: 获取真实序列号
-
-                uploader.update_config(&app_code, &app_secret, &base_url, &serial_number);
+                // 可选的 TLS 信任配置：{ ca_bundle_path, spki_pins, client_cert_path, client_key_path }
+                let tls = match cmd.payload.get("tls").filter(|v| !v.is_null()) {
+                    Some(value) => match serde_json::from_value::<TlsConfig>(value.clone()) {
+                        Ok(tls) => Some(tls),
+                        Err(e) => {
+                            return IpcResponse {
+                                status: "error".to_string(),
+                                message: format!("Invalid tls config: {}", e),
+                                payload: None,
+                            };
+                        }
+                    },
+                    None => None,
+                };
 
-                // 尝试保存到配置文件 (硬编码路径需注意，但在本项目中是一致的)
-                let config_path = "/Users/adolf/Desktop/code/clash/mac-monitor-project/audit-service/config.json";
-                if let Ok(config_str) = std::fs::read_to_string(config_path) {
-                    if let Ok(mut json_val) = serde_json::from_str::<serde_json::Value>(&config_str) {
-                        json_val["server"]["url"] = serde_json::Value::String(base_url);
-                        json_val["server"]["app_code"] = serde_json::Value::String(app_code);
-                        json_val["server"]["app_secret"] = serde_json::Value::String(app_secret);
+                log::info!("Registering device via IPC: {}:{}", server_ip, server_port);
 
-                        if let Ok(new_config_str) = serde_json::to_string_pretty(&json_val) {
-                            let _ = std::fs::write(config_path, new_config_str);
-                        }
+                let base_url = build_base_url(&server_ip, &server_port);
+                let (tx, rx) = std::sync::mpsc::channel();
+                let register_url = base_url.clone();
+                let register_tls = tls.clone();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(enroller.register(&register_url, register_tls, &pin).await);
+                });
+                let status = match rx.recv_timeout(ENROLL_TIMEOUT) {
+                    Ok(Ok(status)) => status,
+                    Ok(Err(e)) => {
+                        return IpcResponse {
+                            status: "error".to_string(),
+                            message: e,
+                            payload: Some(serde_json::to_value(self.uploader.connection_status()).unwrap_or_default()),
+                        };
                     }
+                    Err(_) => return IpcResponse { status: "error".to_string(), message: "Enrollment timeout".to_string(), payload: None },
+                };
+
+                // 服务端地址写回配置文件；凭据只保存在凭据存储中
+                let written = crate::write_server_config(&self.config_path, &base_url, tls.as_ref());
+                if let Err(e) = written {
+                    return IpcResponse {
+                        status: "error".to_string(),
+                        message: format!("Device registered but config.json was not updated: {}", e),
+                        payload: Some(serde_json::to_value(status).unwrap_or_default()),
+                    };
                 }
 
                 IpcResponse {
                     status: "ok".to_string(),
                     message: "Registration successful".to_string(),
-                    payload: None,
+                    payload: Some(serde_json::to_value(status).unwrap_or_default()),
+                }
+            }
+            "reenroll" | "unenroll" => {
+                let Some(enroller) = self.enroller.clone() else {
+                    return IpcResponse { status: "error".to_string(), message: "Enrollment is not enabled".to_string(), payload: None };
+                };
+                let pin = cmd.payload["pin"].as_str().unwrap_or("").to_string();
+                let force = cmd.payload["force"].as_bool().unwrap_or(false);
+                let reenroll = cmd.command == "reenroll";
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let result = if reenroll { enroller.enroll(&pin).await } else { enroller.unenroll(force).await };
+                    let _ = tx.send(result);
+                });
+                match rx.recv_timeout(ENROLL_TIMEOUT) {
+                    Ok(Ok(status)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: if reenroll { "Re-enrollment successful" } else { "Device unenrolled" }.to_string(),
+                        payload: Some(serde_json::to_value(status).unwrap_or_default()),
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "Enrollment timeout".to_string(), payload: None },
                 }
             }
+            "get_enrollment" => match &self.enroller {
+                Some(enroller) => IpcResponse {
+                    status: "ok".to_string(),
+                    message: "Success".to_string(),
+                    payload: Some(serde_json::to_value(enroller.status()).unwrap_or_default()),
+                },
+                None => IpcResponse { status: "error".to_string(), message: "Enrollment is not enabled".to_string(), payload: None },
+            },
             "login" => {
                 let uploader = self.uploader.clone();
                 let (tx, rx) = std::sync::mpsc::channel();
 
-                println!("Processing login via IPC...");
+                log::info!("Processing login via IPC...");
 
                 self.runtime_handle.spawn(async move {
-                    // 调用 uploader 的 heartbeat 或类似逻辑来获取 token
-                    // 这里我们假设 heartbeat 会触发 get_token
-                    let result = uploader.heartbeat("0.1.0").await;
-                    let _ = tx.send(result);
+                    let _ = tx.send(uploader.login().await);
                 });
 
                 match rx.recv_timeout(std::time::Duration::from_secs(15)) {
-                    Ok(Ok(resp)) => {
-                        // 返回包含 token 的 payload
-                        IpcResponse {
-                            status: "ok".to_string(),
-                            message: "Login successful".to_string(),
-                            payload: Some(serde_json::json!({
-                                "token": "mock_token_via_heartbeat" // 实际上 get_token 已经把 token 存入 uploader 了
-                            })),
-                        }
+                    Ok(Ok(auth)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: "Login successful".to_string(),
+                        payload: Some(serde_json::to_value(auth).unwrap_or_default()),
+                    },
+                    Ok(Err(e)) => IpcResponse {
+                        status: "error".to_string(),
+                        message: e,
+                        payload: Some(serde_json::to_value(self.uploader.auth_status()).unwrap_or_default()),
                     },
-                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e, payload: None },
                     Err(_) => IpcResponse { status: "error".to_string(), message: "Login timeout".to_string(), payload: None },
                 }
             }
             "logout" => {
-                println!("Processing logout...");
+                log::info!("Processing logout...");
+                self.uploader.logout();
                 IpcResponse {
                     status: "ok".to_string(),
                     message: "Logged out".to_string(),
-                    payload: None,
+                    payload: Some(serde_json::to_value(self.uploader.auth_status()).unwrap_or_default()),
                 }
             }
+            "get_traffic_stats" => IpcResponse {
+                status: "ok".to_string(),
+                message: "Success".to_string(),
+                payload: Some(serde_json::json!({
+                    "filter": self.traffic.stats(),
+                    "aggregation": self.aggregator.as_ref().map(|a| a.stats()),
+                })),
+            },
+            "get_bandwidth_status" => IpcResponse {
+                status: "ok".to_string(),
+                message: "Success".to_string(),
+                payload: Some(serde_json::to_value(self.uploader.bandwidth().status()).unwrap_or_default()),
+            },
+            // {"mode": "auto|constrained|normal"}：手动指定受限网络，auto 时按宿主检测结果
+            "set_network_mode" => match cmd.payload["mode"].as_str().unwrap_or("").parse::<NetworkMode>() {
+                Ok(mode) => {
+                    self.uploader.bandwidth().set_mode(mode);
+                    IpcResponse {
+                        status: "ok".to_string(),
+                        message: "Network mode updated".to_string(),
+                        payload: Some(serde_json::to_value(self.uploader.bandwidth().status()).unwrap_or_default()),
+                    }
+                }
+                Err(e) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+            },
+            "get_auth_status" => IpcResponse {
+                status: "ok".to_string(),
+                message: "Success".to_string(),
+                payload: Some(serde_json::to_value(self.uploader.auth_status()).unwrap_or_default()),
+            },
             "get_pops" => {
                 let uploader = self.uploader.clone();
                 let (tx, rx) = std::sync::mpsc::channel();
@@ -231,6 +497,30 @@ impl IpcServer {
                     Err(_) => IpcResponse { status: "error".to_string(), message: "Timeout".to_string(), payload: None },
                 }
             }
+            "start_update" => {
+                if self.updater.is_running() {
+                    return IpcResponse {
+                        status: "error".to_string(),
+                        message: "Update already in progress".to_string(),
+                        payload: Some(serde_json::to_value(self.updater.progress()).unwrap_or_default()),
+                    };
+                }
+                // 下载可能耗时较长，后台执行，进度通过 get_update_status 查询
+                let updater = self.updater.clone();
+                self.runtime_handle.spawn(async move {
+                    let _ = updater.run().await;
+                });
+                IpcResponse {
+                    status: "ok".to_string(),
+                    message: "Update started".to_string(),
+                    payload: None,
+                }
+            }
+            "get_update_status" => IpcResponse {
+                status: "ok".to_string(),
+                message: "Success".to_string(),
+                payload: Some(serde_json::to_value(self.updater.progress()).unwrap_or_default()),
+            },
             "get_cert" => {
                 let uploader = self.uploader.clone();
                 let (tx, rx) = std::sync::mpsc::channel();
@@ -263,30 +553,201 @@ impl IpcServer {
                     Err(_) => IpcResponse { status: "error".to_string(), message: "Timeout".to_string(), payload: None },
                 }
             }
+            "get_connection_status" => {
+                let status = self.uploader.connection_status();
+                let (status_str, message) = match &status.last_error {
+                    Some(e) if e.tls => ("error", e.message.clone()),
+                    _ if !status.https => ("ok", "Connection is not encrypted (HTTP)".to_string()),
+                    _ => ("ok", "OK".to_string()),
+                };
+                IpcResponse {
+                    status: status_str.to_string(),
+                    message,
+                    payload: Some(serde_json::to_value(status).unwrap_or_default()),
+                }
+            }
+            "get_servers" => IpcResponse {
+                status: "ok".to_string(),
+                message: "Success".to_string(),
+                payload: Some(serde_json::json!({ "servers": self.uploader.server_status() })),
+            },
+            "status" => {
+                let metrics = self.metrics.clone();
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(metrics.status().await);
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(5)) {
+                    Ok(Ok(status)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: "Success".to_string(),
+                        payload: Some(serde_json::to_value(status).unwrap_or_default()),
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "Timeout".to_string(), payload: None },
+                }
+            }
+            "get_log_levels" => match crate::logging::levels() {
+                Some(levels) => IpcResponse {
+                    status: "ok".to_string(),
+                    message: "Success".to_string(),
+                    payload: Some(serde_json::json!({ "levels": levels.to_string() })),
+                },
+                None => IpcResponse { status: "error".to_string(), message: "Logging is not initialized".to_string(), payload: None },
+            },
+            "set_log_level" => {
+                // {"spec": "info,audit_logic_core::uploader=debug"} 替换全部配置，
+                // 或 {"module": "audit_logic_core::ipc", "level": "debug"} 只修改一个模块 (缺省 module 为全局)
+                let result = match (cmd.payload["spec"].as_str(), cmd.payload["level"].as_str()) {
+                    (Some(spec), _) => crate::logging::set_levels(spec),
+                    (None, Some(level)) => crate::logging::set_module_level(cmd.payload["module"].as_str(), level),
+                    (None, None) => Err("Missing spec or level".to_string()),
+                };
+                match result {
+                    Ok(levels) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: format!("Log levels set to {}", levels),
+                        payload: Some(serde_json::json!({ "levels": levels.to_string() })),
+                    },
+                    Err(e) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                }
+            }
+            "sync_now" => {
+                // 立即执行一轮上传 (与定时同步互斥)，返回本轮之后仍待上传的数量
+                let sync = self.sync.clone();
+                let db = self.db.clone();
+                let timeout = cmd.payload["timeout_secs"].as_u64().unwrap_or(60);
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let result = sync.sync_logs().await;
+                    let pending = db.count_unsent().await.ok();
+                    let _ = tx.send((result, pending));
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(timeout)) {
+                    Ok((result, pending)) => IpcResponse {
+                        status: if result.is_ok() { "ok" } else { "error" }.to_string(),
+                        message: result.err().unwrap_or_else(|| "Sync completed".to_string()),
+                        payload: pending.map(|p| serde_json::json!({ "pending": p })),
+                    },
+                    Err(_) => IpcResponse {
+                        status: "error".to_string(),
+                        message: "Sync still running after timeout".to_string(),
+                        payload: None,
+                    },
+                }
+            }
+            "query" => {
+                let query = match serde_json::from_value::<LogQuery>(cmd.payload) {
+                    Ok(query) => query,
+                    Err(e) => {
+                        return IpcResponse { status: "error".to_string(), message: format!("Invalid query: {}", e), payload: None };
+                    }
+                };
+                let db = self.db.clone();
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(db.query_logs(&query).await);
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(30)) {
+                    Ok(Ok(rows)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: format!("{} rows", rows.len()),
+                        payload: Some(serde_json::Value::Array(rows)),
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e.to_string(), payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "DB query timeout".to_string(), payload: None },
+                }
+            }
+            "verify_chain" => {
+                let db = self.db.clone();
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(db.verify_chain().await);
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(120)) {
+                    Ok(Ok(report)) => IpcResponse {
+                        status: if report.is_intact() { "ok" } else { "error" }.to_string(),
+                        message: if report.is_intact() {
+                            format!("Chain intact ({} entries)", report.entries)
+                        } else {
+                            format!("Chain broken at {} of {} entries", report.breaks.len(), report.entries)
+                        },
+                        payload: Some(serde_json::to_value(report).unwrap_or_default()),
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e.to_string(), payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "Verification timeout".to_string(), payload: None },
+                }
+            }
+            "get_logical_time" => {
+                // 供流量代理 / GUI 以本服务为时间源校准各自的逻辑时钟
+                IpcResponse {
+                    status: "ok".to_string(),
+                    message: "Success".to_string(),
+                    payload: Some(serde_json::json!({
+                        "logical_ms": self.clock.now_ms(),
+                        "offset_ms": self.clock.offset_ms(),
+                        "synced": self.clock.is_synced(),
+                    })),
+                }
+            }
+            "get_policy" => IpcResponse {
+                status: "ok".to_string(),
+                message: "Success".to_string(),
+                payload: Some(serde_json::json!({
+                    "current": self.policy.current(),
+                    "previous": self.policy.previous(),
+                })),
+            },
+            "rollback_policy" => {
+                let policy = self.policy.clone();
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(policy.rollback().await);
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(5)) {
+                    Ok(Ok(version)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: format!("Policy rolled back to {}", version),
+                        payload: None,
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "Timeout".to_string(), payload: None },
+                }
+            }
             "log_traffic" => {
                 match serde_json::from_value::<AuditLog>(cmd.payload) {
-                    Ok(log) => {
-                        // Traffic Filtering Logic
-                        // Only record traffic for *.github.com and *.google.com
-                        let domain = log.domain.to_lowercase();
-                        let is_allowed = domain == "github.com" || domain.ends_with(".github.com") ||
-                                         domain == "google.com" || domain.ends_with(".google.com");
-
-                        if !is_allowed {
-                            println!("DEBUG: Ignoring traffic for domain: {}", domain);
+                    Ok(mut log) => {
+                        // 按策略 traffic_rules 过滤，命中的规则可提升风险等级
+                        if !self.traffic.check(&mut log) {
+                            log::debug!("Ignoring traffic for domain: {}", log.domain);
                             return IpcResponse {
                                 status: "ok".to_string(),
                                 message: "Log ignored (filtered)".to_string(),
                                 payload: None,
                             };
                         }
+                        // 聚合模式下并入时间桶，带风险等级的请求仍保存完整记录
+                        if self.aggregator.as_ref().is_some_and(|a| a.absorb(&log)) {
+                            return IpcResponse {
+                                status: "ok".to_string(),
+                                message: "Log aggregated".to_string(),
+                                payload: None,
+                            };
+                        }
 
                         let db = self.db.clone();
-                        self.runtime_handle.spawn(async move {
+                        let queued = self.tasks.spawn(&self.runtime_handle, async move {
                             if let Err(e) = db.save_audit_log(&log).await {
-                                eprintln!("Failed to save audit log via IPC: {}", e);
+                                log::error!("Failed to save audit log via IPC: {}", e);
                             }
                         });
+                        if !queued {
+                            return IpcResponse {
+                                status: "error".to_string(),
+                                message: "Service is shutting down".to_string(),
+                                payload: None,
+                            };
+                        }
 
                         IpcResponse {
                             status: "ok".to_string(),
@@ -301,6 +762,184 @@ impl IpcServer {
                     }
                 }
             }
+            "log_file_event" => {
+                // 载荷为单个事件或事件数组 (外部采集器批量提交)
+                let Some(files) = &self.files else {
+                    return IpcResponse { status: "error".to_string(), message: "File audit is not enabled".to_string(), payload: None };
+                };
+                let events = match cmd.payload {
+                    serde_json::Value::Array(items) => items.into_iter().map(serde_json::from_value::<FileEvent>).collect(),
+                    single => serde_json::from_value::<FileEvent>(single).map(|e| vec![e]),
+                };
+                let events = match events {
+                    Ok(events) => events,
+                    Err(e) => {
+                        return IpcResponse { status: "error".to_string(), message: format!("Invalid FileEvent payload: {}", e), payload: None };
+                    }
+                };
+                let (mut accepted, mut filtered) = (0, 0);
+                for event in events {
+                    match files.submit(event) {
+                        Ok(true) => accepted += 1,
+                        Ok(false) => filtered += 1,
+                        Err(e) => return IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    }
+                }
+                IpcResponse {
+                    status: "ok".to_string(),
+                    message: format!("{} events queued, {} filtered", accepted, filtered),
+                    payload: Some(serde_json::json!({ "accepted": accepted, "filtered": filtered })),
+                }
+            }
+            "log_peripheral_event" => {
+                let Some(peripherals) = &self.peripherals else {
+                    return IpcResponse { status: "error".to_string(), message: "Peripheral audit is not enabled".to_string(), payload: None };
+                };
+                match serde_json::from_value::<PeripheralEvent>(cmd.payload) {
+                    Ok(event) => {
+                        if !peripherals.record(event) {
+                            return IpcResponse { status: "error".to_string(), message: "Service is shutting down".to_string(), payload: None };
+                        }
+                        IpcResponse { status: "ok".to_string(), message: "Event queued".to_string(), payload: None }
+                    }
+                    Err(e) => IpcResponse {
+                        status: "error".to_string(),
+                        message: format!("Invalid PeripheralEvent payload: {}", e),
+                        payload: None,
+                    },
+                }
+            }
+            "log_session_event" => {
+                let Some(session) = &self.session else {
+                    return IpcResponse { status: "error".to_string(), message: "Session audit is not enabled".to_string(), payload: None };
+                };
+                match serde_json::from_value::<SessionEvent>(cmd.payload) {
+                    Ok(event) => {
+                        if !session.record(event) {
+                            return IpcResponse { status: "error".to_string(), message: "Service is shutting down".to_string(), payload: None };
+                        }
+                        IpcResponse { status: "ok".to_string(), message: "Event queued".to_string(), payload: None }
+                    }
+                    Err(e) => IpcResponse {
+                        status: "error".to_string(),
+                        message: format!("Invalid SessionEvent payload: {}", e),
+                        payload: None,
+                    },
+                }
+            }
+            "get_session_state" => {
+                let Some(session) = &self.session else {
+                    return IpcResponse { status: "error".to_string(), message: "Session audit is not enabled".to_string(), payload: None };
+                };
+                let state = session.state();
+                let mut payload = serde_json::to_value(&state).unwrap();
+                payload["capture_paused"] = serde_json::Value::Bool(state.capture_paused());
+                IpcResponse { status: "ok".to_string(), message: "Success".to_string(), payload: Some(payload) }
+            }
+            "log_foreground_event" => {
+                let Some(usage) = &self.usage else {
+                    return IpcResponse { status: "error".to_string(), message: "App usage tracking is not enabled".to_string(), payload: None };
+                };
+                match serde_json::from_value::<ForegroundEvent>(cmd.payload) {
+                    Ok(event) => match usage.record(event) {
+                        Ok(true) => IpcResponse { status: "ok".to_string(), message: "Event queued".to_string(), payload: None },
+                        Ok(false) => IpcResponse { status: "error".to_string(), message: "Service is shutting down".to_string(), payload: None },
+                        Err(e) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    },
+                    Err(e) => IpcResponse {
+                        status: "error".to_string(),
+                        message: format!("Invalid ForegroundEvent payload: {}", e),
+                        payload: None,
+                    },
+                }
+            }
+            "get_app_usage" => {
+                let Some(usage) = self.usage.clone() else {
+                    return IpcResponse { status: "error".to_string(), message: "App usage tracking is not enabled".to_string(), payload: None };
+                };
+                let day = match cmd.payload["day"].as_str() {
+                    Some(day) if chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d").is_err() => {
+                        return IpcResponse { status: "error".to_string(), message: format!("Invalid day '{}', expected YYYY-MM-DD", day), payload: None };
+                    }
+                    Some(day) => day.to_string(),
+                    None => local_day(self.clock.now_ms()),
+                };
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(usage.report(&day).await);
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(10)) {
+                    Ok(Ok(report)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: format!("{} apps, {}s active", report.apps.len(), report.active_secs),
+                        payload: Some(serde_json::to_value(report).unwrap_or_default()),
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "DB query timeout".to_string(), payload: None },
+                }
+            }
+            "get_alerts" => {
+                let Some(alerts) = &self.alerts else {
+                    return IpcResponse { status: "error".to_string(), message: "Alerts are not enabled".to_string(), payload: None };
+                };
+                let after = cmd.payload["after"].as_u64().unwrap_or(0);
+                // wait_ms > 0 时长轮询：没有新告警就等到有或超时 (最多 MAX_ALERT_WAIT_MS)
+                let list = match cmd.payload["wait_ms"].as_u64().filter(|ms| *ms > 0).map(|ms| ms.min(MAX_ALERT_WAIT_MS)) {
+                    Some(wait_ms) => alerts.wait_after(after, std::time::Duration::from_millis(wait_ms)),
+                    None => alerts.alerts_after(after),
+                };
+                IpcResponse {
+                    status: "ok".to_string(),
+                    message: format!("{} alerts", list.len()),
+                    payload: Some(serde_json::json!({ "alerts": list, "stats": alerts.stats() })),
+                }
+            }
+            "export_evidence" => {
+                let Some(evidence) = self.evidence.clone() else {
+                    return IpcResponse { status: "error".to_string(), message: "Evidence export is not enabled".to_string(), payload: None };
+                };
+                let request = match serde_json::from_value::<ExportRequest>(cmd.payload) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        return IpcResponse { status: "error".to_string(), message: format!("Invalid export request: {}", e), payload: None };
+                    }
+                };
+                let (tx, rx) = std::sync::mpsc::channel();
+                self.runtime_handle.spawn(async move {
+                    let _ = tx.send(evidence.export(&request).await);
+                });
+                match rx.recv_timeout(std::time::Duration::from_secs(600)) {
+                    Ok(Ok(summary)) => IpcResponse {
+                        status: "ok".to_string(),
+                        message: format!("Exported {} tables and {} screenshots to {}", summary.tables.len(), summary.screenshots, summary.path),
+                        payload: Some(serde_json::to_value(summary).unwrap_or_default()),
+                    },
+                    Ok(Err(e)) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                    Err(_) => IpcResponse { status: "error".to_string(), message: "Export timeout".to_string(), payload: None },
+                }
+            }
+            "verify_evidence" => {
+                let Some(evidence) = &self.evidence else {
+                    return IpcResponse { status: "error".to_string(), message: "Evidence export is not enabled".to_string(), payload: None };
+                };
+                // 只校验导出目录下的包，不替调用方读取任意文件
+                let path = match evidence.bundle_path(cmd.payload["name"].as_str().unwrap_or_default()) {
+                    Ok(path) => path,
+                    Err(e) => return IpcResponse { status: "error".to_string(), message: e, payload: None },
+                };
+                match verify_bundle(&path, cmd.payload["public_key"].as_str()) {
+                    Ok(report) => IpcResponse {
+                        status: if report.is_intact() { "ok" } else { "error" }.to_string(),
+                        message: if report.is_intact() {
+                            format!("Bundle intact ({} files)", report.files_checked)
+                        } else {
+                            format!("Bundle verification failed: {}", report.problems.join("; "))
+                        },
+                        payload: Some(serde_json::to_value(report).unwrap_or_default()),
+                    },
+                    Err(e) => IpcResponse { status: "error".to_string(), message: e, payload: None },
+                }
+            }
             "get_screenshot_logs" => {
                 let db = self.db.clone();
                 let (tx, rx) = std::sync::mpsc::channel();
@@ -341,16 +980,17 @@ impl IpcServer {
             }
             "set_redaction_status" => {
                 let enabled = cmd.payload["enabled"].as_bool().unwrap_or(true);
-                println!("Updating redaction status via IPC: {}", enabled);
-
-                // Call Swift FFI
-                extern "C" {
-                    fn update_redaction_status(enabled: bool);
-                }
+                log::info!("Updating redaction status via IPC: {}", enabled);
 
-                unsafe {
-                    update_redaction_status(enabled);
-                }
+                // 由 GUI 宿主 (Swift) 处理
+                let Some(callback) = *REDACTION_CALLBACK.read().unwrap() else {
+                    return IpcResponse {
+                        status: "error".to_string(),
+                        message: "Redaction is handled by the GUI host, which is not attached".to_string(),
+                        payload: None,
+                    };
+                };
+                callback(enabled);
 
                 IpcResponse {
                     status: "ok".to_string(),
@@ -366,3 +1006,39 @@ impl IpcServer {
         }
     }
 }
+
+/// 调用方是 root 或与服务同一用户
+fn is_privileged(uid: Option<u32>) -> bool {
+    uid.is_some_and(|uid| uid == 0 || uid == unsafe { libc::geteuid() })
+}
+
+/// 对端进程的 uid；取不到时按非特权调用方处理
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &LocalSocketStream) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+    let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (rc == 0).then_some(cred.uid)
+}
+
+#[cfg(target_os = "macos")]
+fn peer_uid(stream: &LocalSocketStream) -> Option<u32> {
+    use std::os::unix::io::AsRawFd;
+    let (mut uid, mut gid) = (0, 0);
+    let rc = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    (rc == 0).then_some(uid)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn peer_uid(_stream: &LocalSocketStream) -> Option<u32> {
+    None
+}
@@ -4,298 +4,827 @@ pub mod uploader;
 pub mod clock;
 pub mod ipc;
 pub mod scanner;
-
-use std::ffi::CStr;
+pub mod policy;
+pub mod updater;
+pub mod metrics;
+pub mod logging;
+pub mod lifecycle;
+pub mod files;
+pub mod peripherals;
+pub mod session;
+pub mod enrollment;
+pub mod traffic;
+pub mod usage;
+pub mod alerts;
+pub mod evidence;
+
+/// 当前版本 (心跳上报 / 更新比较)
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
-use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
 use image::{ImageBuffer, Rgba, DynamicImage};
 use sha2::{Sha256, Digest};
 use std::io::Cursor;
-use chrono::Local;
 
 use crate::db::Database;
-use crate::models::{AuditLog, BehaviorLog, ClipboardLog, ScreenshotLog};
+use crate::enrollment::Enroller;
+use crate::files::{FileAuditConfig, FileAuditor, FileEvent};
+use crate::traffic::aggregate::TrafficAggregator;
+use crate::peripherals::{PeripheralConfig, PeripheralEvent, PeripheralMonitor};
+use crate::session::{SessionConfig, SessionEvent, SessionTracker};
+use crate::usage::{ForegroundEvent, UsageTracker};
+use crate::alerts::AlertDispatcher;
+use crate::evidence::EvidenceExporter;
+use crate::models::{ClipboardLog, ScreenshotLog};
+use crate::uploader::bandwidth::BandwidthConfig;
+use crate::uploader::servers::FailoverConfig;
+use crate::uploader::tls::{build_base_url, TlsConfig};
 use crate::uploader::Uploader;
+use crate::updater::{UpdateConfig, Updater};
 use crate::uploader::sync::SyncService;
 use crate::clock::LogicalClock;
-use crate::ipc::IpcServer;
+use crate::ipc::{IpcHandle, IpcServer};
+use crate::lifecycle::{Shutdown, TaskTracker};
+use crate::metrics::prometheus::MetricsConfig;
+use crate::metrics::Metrics;
+use crate::logging::LoggingConfig;
+use crate::policy::capture::{CaptureAction, CaptureEvaluator};
+use crate::policy::PolicyStore;
+use mac_monitor_common::identity::{platform_identity, IdentityMonitor};
 use serde::{Deserialize, Serialize};
 use std::fs;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct Config {
     server: ServerConfig,
     storage: StorageConfig,
+    #[serde(default)]
+    update: UpdateConfig,
+    /// 可选的 Prometheus 指标导出
+    #[serde(default, skip_serializing_if = "MetricsConfig::is_default")]
+    metrics: MetricsConfig,
+    #[serde(default, skip_serializing_if = "LoggingConfig::is_default")]
+    logging: LoggingConfig,
+    #[serde(default, skip_serializing_if = "IpcConfig::is_default")]
+    ipc: IpcConfig,
+    /// 文件操作审计 (监听目录、合并窗口、哈希上限)
+    #[serde(default, skip_serializing_if = "FileAuditConfig::is_default")]
+    files: FileAuditConfig,
+    /// 外设接入 / 拔出审计
+    #[serde(default, skip_serializing_if = "PeripheralConfig::is_default")]
+    peripherals: PeripheralConfig,
+    /// 登录 / 锁屏 / 睡眠等会话事件
+    #[serde(default, skip_serializing_if = "SessionConfig::is_default")]
+    session: SessionConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+struct IpcConfig {
+    /// 缺省 /tmp/mac_monitor_audit.sock；同一台机器运行多个实例 (如 CI) 时需各自指定
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    socket_path: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl IpcConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 struct ServerConfig {
     url: String,
+    /// 备用服务端，按顺序在当前服务端连续失败时切换
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    standby_urls: Vec<String>,
+    /// 切换与切回条件
+    #[serde(default, skip_serializing_if = "FailoverConfig::is_default")]
+    failover: FailoverConfig,
     app_code: String,
     app_secret: String,
+    /// 私有 CA / 证书指纹 / 设备证书，缺省使用系统内置根证书
+    #[serde(default, skip_serializing_if = "TlsConfig::is_default")]
+    tls: TlsConfig,
+    /// 请求体超过该大小 (字节) 时按协商的编码压缩
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compression_min_bytes: Option<usize>,
+    /// 元数据 / 文件上传限速与计费网络检测，缺省不限速
+    #[serde(default, skip_serializing_if = "BandwidthConfig::is_default")]
+    bandwidth: BandwidthConfig,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 struct StorageConfig {
     screenshot_dir: String,
     database_path: String,
+    /// 缺省时放在数据库同目录下的 policy_cache.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    policy_cache_path: Option<String>,
+    /// 设备凭据文件 (macOS 使用钥匙串，不读此项)；缺省放在数据库同目录下的 enrollment.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    enrollment_path: Option<String>,
+    /// 取证导出包的签名密钥；缺省放在数据库同目录下的 evidence_key.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    evidence_key_path: Option<String>,
+    /// 取证导出包的输出目录 (0700)；缺省为数据库同目录下的 evidence/
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    evidence_dir: Option<String>,
+}
+
+impl StorageConfig {
+    fn policy_cache_path(&self) -> std::path::PathBuf {
+        match &self.policy_cache_path {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::path::Path::new(&self.database_path)
+                .with_file_name("policy_cache.json"),
+        }
+    }
+
+    fn enrollment_path(&self) -> std::path::PathBuf {
+        match &self.enrollment_path {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::path::Path::new(&self.database_path).with_file_name("enrollment.json"),
+        }
+    }
+
+    fn evidence_key_path(&self) -> std::path::PathBuf {
+        match &self.evidence_key_path {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::path::Path::new(&self.database_path).with_file_name("evidence_key.json"),
+        }
+    }
+
+    fn evidence_dir(&self) -> std::path::PathBuf {
+        match &self.evidence_dir {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::path::Path::new(&self.database_path).with_file_name("evidence"),
+        }
+    }
 }
 
+// 无 netlink 等事件源的平台上，按此间隔轮询网络变化
+const IDENTITY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
 lazy_static::lazy_static! {
     static ref RUNTIME: Runtime = Runtime::new().expect("Failed to create Tokio runtime");
 }
 
-static SERVICE_CONTEXT: OnceCell<Arc<ServiceContext>> = OnceCell::const_new();
+/// 运行时状态，`shutdown_audit_core` 之后清空，可再次调用 `init_audit_core` 重新初始化
+static SERVICE_CONTEXT: RwLock<Option<Arc<ServiceContext>>> = RwLock::new(None);
+// 串行化 init / shutdown / reload
+static LIFECYCLE_LOCK: Mutex<()> = Mutex::new(());
+// 最近一次 FFI 调用失败的原因，供宿主通过 audit_core_last_error 读取
+static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+const CONFIG_PATH: &str = ipc::DEFAULT_CONFIG_PATH;
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 struct ServiceContext {
     db: Arc<Database>,
     uploader: Arc<Uploader>,
     clock: Arc<LogicalClock>,
-    config: Config,
-    policy: Arc<RwLock<models::PolicyConfig>>,
-    device_info: models::DeviceInfo,
+    config: RwLock<Config>,
+    capture: CaptureEvaluator,
+    device_info: Arc<RwLock<models::DeviceInfo>>,
+    identity: IdentityMonitor,
+    enrollment: Arc<Enroller>,
+    config_path: PathBuf,
+    sync: Arc<SyncService>,
+    sync_task: Mutex<Option<JoinHandle<()>>>,
+    files: Arc<FileAuditor>,
+    files_task: Mutex<Option<JoinHandle<()>>>,
+    traffic: Arc<TrafficAggregator>,
+    traffic_task: Mutex<Option<JoinHandle<()>>>,
+    peripherals: Arc<PeripheralMonitor>,
+    session: Arc<SessionTracker>,
+    usage: Arc<UsageTracker>,
+    usage_task: Mutex<Option<JoinHandle<()>>>,
+    alerts: Arc<AlertDispatcher>,
+    alerts_task: Mutex<Option<JoinHandle<()>>>,
+    ipc: IpcHandle,
+    tasks: Arc<TaskTracker>,
+    shutdown: Shutdown,
+}
+
+fn load_config(path: &Path) -> Result<Config, String> {
+    let config_str = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+    serde_json::from_str(&config_str).map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))
+}
+
+/// 注册成功后把服务端地址 (及 TLS 配置) 写回 config.json
+///
+/// 只改 `server.url` / `server.tls`，其余键 (包括 `Config` 未建模的) 原样保留；
+/// 先写临时文件再重命名，写到一半时崩溃不会留下损坏的配置。
+pub(crate) fn write_server_config(path: &Path, base_url: &str, tls: Option<&TlsConfig>) -> Result<(), String> {
+    let config_str = fs::read_to_string(path).map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+    let mut json_val: serde_json::Value =
+        serde_json::from_str(&config_str).map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?;
+    json_val["server"]["url"] = serde_json::Value::String(base_url.to_string());
+    if let Some(tls) = tls {
+        json_val["server"]["tls"] = serde_json::to_value(tls).map_err(|e| e.to_string())?;
+    }
+    let new_config_str = serde_json::to_string_pretty(&json_val).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, new_config_str).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
 }
 
-async fn init_service_context() -> Arc<ServiceContext> {
+async fn init_service_context(config_path: &Path) -> Result<Arc<ServiceContext>, String> {
     // 1. 加载配置
-    let config_path = "/Users/adolf/Desktop/code/clash/mac-monitor-project/audit-service/config.json";
-    let config_str = fs::read_to_string(config_path).expect("Failed to read config.json");
-    let config: Config = serde_json::from_str(&config_str).expect("Failed to parse config.json");
+    let config = load_config(config_path)?;
+    if !config.logging.is_default() {
+        if let Err(e) = logging::init(&config.logging) {
+            log::error!("Invalid logging configuration, keeping defaults: {}", e);
+        }
+    }
 
     // 2. 初始化数据库
-    let _ = std::fs::create_dir_all(std::path::Path::new(&config.storage.database_path).parent().unwrap());
+    let db_path = std::path::Path::new(&config.storage.database_path);
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create database dir {:?}: {}", parent, e))?;
+    }
     let db = Database::new(&format!("sqlite://{}", config.storage.database_path))
         .await
-        .expect("Failed to init DB");
-
-    // 3. 获取真实设备信息
-    let mut sys = sysinfo::System::new_all();
-    sys.refresh_all();
-
-    let host_name = sysinfo::System::host_name().unwrap_or_else(|| "Unknown-Mac".to_string());
-    let mac_addr = get_local_mac();
-    let ip_addr = get_local_ip();
-    let serial_number = get_macos_serial_number();
-
-    let device_info = models::DeviceInfo {
-        pin: serial_number.clone(),
-        host_id: host_name.clone(),
-        cpe_id: serial_number.clone(), // 使用 clone 避免所有权转移
-        mac: mac_addr,
-        ip: ip_addr,
-    };
+        .map_err(|e| format!("Failed to init DB {}: {}", config.storage.database_path, e))?;
+
+    // 3. 获取真实设备信息，并在网络变化时刷新
+    let identity = IdentityMonitor::new(platform_identity());
+    let snapshot = identity.current();
+    let serial_number = snapshot.serial_number.clone();
+    let device_info = Arc::new(RwLock::new(models::DeviceInfo::from_identity(&snapshot)));
+
+    // 所有组件共用同一个逻辑时钟，由心跳的服务端时间校准
+    let clock = Arc::new(LogicalClock::new());
 
     // 4. 初始化上传器
     let uploader = Arc::new(Uploader::new(
         &config.server.app_code,
         &config.server.app_secret,
         &config.server.url,
-        &serial_number
+        &serial_number,
+        clock.clone(),
     ));
+    uploader.set_device_profile(&snapshot.host_name, &snapshot.os_version);
+    if !config.server.standby_urls.is_empty() {
+        uploader.configure_servers(&config.server.url, &config.server.standby_urls, config.server.failover.clone());
+    }
+    if let Some(min_bytes) = config.server.compression_min_bytes {
+        uploader.set_compression_threshold(min_bytes);
+    }
+    uploader.bandwidth().configure(&config.server.bandwidth);
+    if !config.server.tls.is_default() {
+        // 配置无效时上传器拒绝所有请求，错误通过 IPC get_connection_status 暴露
+        if let Err(e) = uploader.configure_tls(config.server.tls.clone()) {
+            log::error!("Management server TLS configuration rejected: {}", e);
+        }
+    }
+
+    // 已注册的设备使用保存的凭据登录
+    let enrollment = Enroller::new(
+        uploader.clone(),
+        device_info.clone(),
+        snapshot.machine_id.clone(),
+        &snapshot.os_version,
+        enrollment::store::platform_store(config.storage.enrollment_path()),
+    );
 
-    let clock = Arc::new(LogicalClock::new());
     let db_arc = Arc::new(db);
 
-    // 初始化默认策略
-    let policy = Arc::new(RwLock::new(models::PolicyConfig {
-        process_blacklist: vec!["clash".to_string(), "v2ray".to_string(), "clash-meta".to_string(), "proxyman".to_string()],
-        app_blacklist: vec!["clash".to_string(), "v2ray".to_string(), "proxyman".to_string()],
-    }));
+    // 加载本地缓存的策略 (在首次心跳之前生效)，无缓存时使用内置策略
+    let policy = Arc::new(PolicyStore::load(
+        config.storage.policy_cache_path(),
+        db_arc.clone(),
+        device_info.clone(),
+        clock.clone(),
+    ));
 
-    // 5. 初始化背景同步服务
-    let sync_service = SyncService::new(
+    let metrics = Arc::new(Metrics::new(
+        db_arc.clone(),
+        clock.clone(),
+        std::path::PathBuf::from(&config.storage.database_path),
+        std::path::PathBuf::from(&config.storage.screenshot_dir),
+    ));
+
+    let updater = Arc::new(Updater::new(
+        uploader.clone(),
+        config.update.clone(),
+        std::path::Path::new(&config.storage.database_path).with_file_name("updates"),
+    ));
+    let tasks = TaskTracker::new();
+
+    let sync = Arc::new(SyncService::new(
         db_arc.clone(),
         uploader.clone(),
         clock.clone(),
         policy.clone(),
         device_info.clone(),
         config.storage.screenshot_dir.clone(),
+        metrics.clone(),
+    ));
+
+    let files = FileAuditor::new(
+        db_arc.clone(),
+        policy.shared(),
+        device_info.clone(),
+        clock.clone(),
+        config.files.clone(),
     );
-    sync_service.start();
 
-    // 6. 启动 IPC 服务
-    let ipc_server = IpcServer::new(db_arc.clone(), uploader.clone(), RUNTIME.handle().clone());
-    ipc_server.start();
+    let traffic = TrafficAggregator::new(db_arc.clone(), policy.shared(), clock.clone());
 
-    Arc::new(ServiceContext {
+    let peripherals = PeripheralMonitor::new(
+        db_arc.clone(),
+        policy.shared(),
+        device_info.clone(),
+        clock.clone(),
+        tasks.clone(),
+        RUNTIME.handle().clone(),
+    );
+
+    let session = SessionTracker::new(
+        db_arc.clone(),
+        device_info.clone(),
+        clock.clone(),
+        tasks.clone(),
+        RUNTIME.handle().clone(),
+    );
+
+    let usage = UsageTracker::new(
+        db_arc.clone(),
+        device_info.clone(),
+        clock.clone(),
+        tasks.clone(),
+        RUNTIME.handle().clone(),
+    );
+    let alerts = AlertDispatcher::new(
+        &db_arc,
+        uploader.clone(),
+        metrics.clone(),
+        clock.clone(),
+        tasks.clone(),
+        RUNTIME.handle().clone(),
+    );
+
+    let evidence = EvidenceExporter::new(
+        db_arc.clone(),
+        device_info.clone(),
+        clock.clone(),
+        config.storage.evidence_key_path(),
+        config.storage.evidence_dir(),
+        &config.storage.screenshot_dir,
+    );
+
+    let usage_listener = usage.clone();
+    session.on_pause_change(move |paused, time_ms| usage_listener.set_away(paused, time_ms));
+
+    // 5. 启动 IPC 服务 (绑定失败时直接返回错误，此时尚未启动任何后台任务)
+    let mut ipc_server = IpcServer::new(
+        db_arc.clone(),
+        uploader.clone(),
+        clock.clone(),
+        policy.clone(),
+        updater,
+        metrics.clone(),
+        sync.clone(),
+        tasks.clone(),
+        RUNTIME.handle().clone(),
+    )
+    .with_config_path(config_path)
+    .with_enroller(enrollment.clone())
+    .with_file_auditor(files.clone())
+    .with_traffic_aggregator(traffic.clone())
+    .with_peripheral_monitor(peripherals.clone())
+    .with_session_tracker(session.clone())
+    .with_usage_tracker(usage.clone())
+    .with_alert_dispatcher(alerts.clone())
+    .with_evidence_exporter(evidence);
+    if let Some(socket_path) = &config.ipc.socket_path {
+        ipc_server = ipc_server.with_socket_path(socket_path.as_str());
+    }
+    let ipc = match ipc_server.start() {
+        Ok(ipc) => ipc,
+        Err(e) => {
+            db_arc.close().await;
+            return Err(e);
+        }
+    };
+
+    // 6. 启动后台任务：身份监听、指标导出、同步服务
+    let device_info_watch = device_info.clone();
+    identity.watch(IDENTITY_POLL_INTERVAL, move |snapshot| {
+        let mut info = device_info_watch.write().unwrap();
+        info.refresh_network(snapshot);
+        log::info!("Device info refreshed after network change: ip={}, mac={}", info.ip, info.mac);
+    });
+
+    let shutdown = Shutdown::new();
+    metrics::prometheus::start_exporter(metrics.clone(), &config.metrics, shutdown.signal());
+    let sync_task = sync.clone().start(shutdown.signal());
+    let files_task = files.clone().start(shutdown.signal());
+    let traffic_task = traffic.clone().start(shutdown.signal());
+    let usage_task = usage.clone().start(shutdown.signal());
+    let alerts_task = alerts.clone().start(shutdown.signal());
+    start_file_watcher(&config.files, &files, &shutdown);
+    start_peripheral_producers(&config.peripherals, &peripherals, &shutdown);
+    start_session_producers(&config.session, &session, &shutdown);
+    start_metered_detection(&config.server.bandwidth, &uploader, &shutdown);
+
+    let capture = CaptureEvaluator::new(policy.shared());
+
+    Ok(Arc::new(ServiceContext {
         db: db_arc,
         uploader,
         clock,
-        config,
-        policy,
+        config: RwLock::new(config),
+        capture,
         device_info,
-    })
-}
-
-fn get_macos_serial_number() -> String {
-    use std::process::Command;
-    let output = Command::new("ioreg")
-        .args(&["-c", "IOPlatformExpertDevice", "-d", "2"])
-        .output();
-
-    match output {
-        Ok(o) => {
-            let s = String::from_utf8_lossy(&o.stdout);
-            for line in s.lines() {
-                if line.contains("IOPlatformSerialNumber") {
-                    let parts: Vec<&str> = line.split('=').collect();
-                    if parts.len() == 2 {
-                        return parts[1].trim().trim_matches(|c| c == '"' || c == ' ').to_string();
-                    }
-                }
-            }
-        },
-        Err(e) => log::error!("Failed to execute ioreg: {}", e),
-    }
-    "UNKNOWN_SERIAL".to_string()
-}
-
-fn get_primary_interface_name() -> Option<String> {
-    use std::process::Command;
-    let output = Command::new("route")
-        .args(&["-n", "get", "default"])
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
-    }
-
-    let s = String::from_utf8_lossy(&output.stdout);
-    for line in s.lines() {
-        let line = line.trim();
-        if let Some(rest) = line.strip_prefix("interface:") {
-            let iface = rest.trim();
-            if !iface.is_empty() {
-                log::info!("Primary network interface detected: {}", iface);
-                return Some(iface.to_string());
-            }
-        }
+        identity,
+        enrollment,
+        config_path: config_path.to_path_buf(),
+        sync,
+        sync_task: Mutex::new(Some(sync_task)),
+        files,
+        files_task: Mutex::new(Some(files_task)),
+        traffic,
+        traffic_task: Mutex::new(Some(traffic_task)),
+        peripherals,
+        session,
+        usage,
+        usage_task: Mutex::new(Some(usage_task)),
+        alerts,
+        alerts_task: Mutex::new(Some(alerts_task)),
+        ipc,
+        tasks,
+        shutdown,
+    }))
+}
+
+#[cfg(target_os = "linux")]
+fn start_file_watcher(config: &FileAuditConfig, files: &Arc<FileAuditor>, shutdown: &Shutdown) {
+    if config.watch_paths.is_empty() {
+        return;
+    }
+    // 监听失败不影响其他功能，IPC / FFI 提交的事件仍会记录
+    if let Err(e) = files::linux::start(&config.watch_paths, files.clone(), shutdown.signal()) {
+        log::error!("File audit watcher not started: {}", e);
     }
-    None
 }
 
-fn get_ip_for_interface(interface: &str) -> Option<String> {
-    use std::process::Command;
-    let output = Command::new("ipconfig")
-        .args(&["getifaddr", interface])
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
+#[cfg(not(target_os = "linux"))]
+fn start_file_watcher(config: &FileAuditConfig, _files: &Arc<FileAuditor>, _shutdown: &Shutdown) {
+    if !config.watch_paths.is_empty() {
+        log::warn!("files.watch_paths is only supported on Linux; file events come from the host via log_file_event");
     }
-    let ip = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if ip.is_empty() {
-        None
-    } else {
-        Some(ip)
+}
+
+#[cfg(target_os = "linux")]
+fn start_metered_detection(config: &BandwidthConfig, uploader: &Arc<Uploader>, shutdown: &Shutdown) {
+    if config.detect_metered {
+        uploader::bandwidth::networkmanager::start(uploader.clone(), shutdown.signal());
     }
 }
 
-fn get_mac_for_interface(interface: &str) -> Option<String> {
-    use std::process::Command;
-    let output = Command::new("ifconfig")
-        .arg(interface)
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
+#[cfg(not(target_os = "linux"))]
+fn start_metered_detection(config: &BandwidthConfig, _uploader: &Arc<Uploader>, _shutdown: &Shutdown) {
+    if config.detect_metered {
+        log::warn!("server.bandwidth.detect_metered is only supported on Linux; the host reports constrained networks via audit_core_set_network_constrained");
     }
+}
 
-    let s = String::from_utf8_lossy(&output.stdout);
-    for line in s.lines() {
-        let line = line.trim();
-        if let Some(rest) = line.strip_prefix("ether ") {
-            let mac = rest.split_whitespace().next().unwrap_or("").trim();
-            if !mac.is_empty() {
-                return Some(mac.to_string());
-            }
+fn start_peripheral_producers(config: &PeripheralConfig, monitor: &Arc<PeripheralMonitor>, shutdown: &Shutdown) {
+    let mut producers: Vec<Box<dyn peripherals::PeripheralProducer>> = Vec::new();
+    #[cfg(target_os = "linux")]
+    if config.watch_udev {
+        producers.push(Box::new(peripherals::udev::UdevProducer::default()));
+    }
+    #[cfg(not(target_os = "linux"))]
+    if config.watch_udev {
+        log::warn!("peripherals.watch_udev is only supported on Linux; peripheral events come from the host via log_peripheral_event");
+    }
+    for producer in producers {
+        let name = producer.name();
+        if let Err(e) = producer.start(monitor.clone(), shutdown.signal()) {
+            log::error!("Peripheral producer {} not started: {}", name, e);
         }
     }
-    None
 }
 
-fn get_local_ip() -> String {
-    if let Some(interface) = get_primary_interface_name() {
-        if let Some(ip) = get_ip_for_interface(&interface) {
-            log::info!("Local IP resolved via {}: {}", interface, ip);
-            return ip;
+fn start_session_producers(config: &SessionConfig, tracker: &Arc<SessionTracker>, shutdown: &Shutdown) {
+    let mut producers: Vec<Box<dyn session::SessionProducer>> = Vec::new();
+    #[cfg(target_os = "linux")]
+    if config.watch_logind {
+        producers.push(Box::new(session::logind::LogindProducer));
+    }
+    #[cfg(not(target_os = "linux"))]
+    if config.watch_logind {
+        log::warn!("session.watch_logind is only supported on Linux; session events come from the host via log_session_event");
+    }
+    for producer in producers {
+        let name = producer.name();
+        if let Err(e) = producer.start(tracker.clone(), shutdown.signal()) {
+            log::error!("Session producer {} not started: {}", name, e);
         }
     }
+}
 
-    // Fallback: derive local IP from a UDP socket without sending packets.
-    use std::net::UdpSocket;
-    match UdpSocket::bind("0.0.0.0:0") {
-        Ok(socket) => {
-            if socket.connect("8.8.8.8:80").is_ok() {
-                if let Ok(addr) = socket.local_addr() {
-                    log::info!("Local IP resolved via UDP fallback: {}", addr.ip());
-                    return addr.ip().to_string();
-                }
+impl ServiceContext {
+    /// 依次停止接收新数据、停止后台任务、等待写库完成、最后同步一次、关闭数据库
+    ///
+    /// 所有步骤共享 `timeout`；超时的步骤被跳过并记入返回的错误，不会阻止后续步骤。
+    async fn shutdown(&self, timeout: Duration) -> Result<(), String> {
+        let deadline = Instant::now() + timeout;
+        let remaining = || deadline.saturating_duration_since(Instant::now());
+        let mut problems = Vec::new();
+
+        // 1. 拒绝新的 FFI / IPC 写入，通知后台任务退出
+        self.tasks.close();
+        self.files.close();
+        self.traffic.close();
+        self.ipc.stop();
+        self.shutdown.trigger();
+        self.identity.stop();
+
+        // 2. 等待同步循环结束当前一轮，避免与最后一次同步并发
+        let sync_task = self.sync_task.lock().unwrap().take();
+        if let Some(mut task) = sync_task {
+            if tokio::time::timeout(remaining(), &mut task).await.is_err() {
+                task.abort();
+                problems.push("sync loop did not stop in time".to_string());
+            }
+        }
+        // 文件审计循环退出前写入所有合并中的事件
+        let files_task = self.files_task.lock().unwrap().take();
+        if let Some(mut task) = files_task {
+            if tokio::time::timeout(remaining(), &mut task).await.is_err() {
+                task.abort();
+                problems.push(format!("{} file events were not saved in time", self.files.pending()));
+            }
+        }
+        let traffic_task = self.traffic_task.lock().unwrap().take();
+        if let Some(mut task) = traffic_task {
+            if tokio::time::timeout(remaining(), &mut task).await.is_err() {
+                task.abort();
+                problems.push(format!("{} traffic summaries were not saved in time", self.traffic.pending()));
+            }
+        }
+        // 写入进行中的前台使用时段
+        let usage_task = self.usage_task.lock().unwrap().take();
+        if let Some(mut task) = usage_task {
+            if tokio::time::timeout(remaining(), &mut task).await.is_err() {
+                task.abort();
+                problems.push("app usage session was not saved in time".to_string());
+            }
+        }
+        // 告警循环随停止信号退出，进行中的推送在第 3 步等待
+        let alerts_task = self.alerts_task.lock().unwrap().take();
+        if let Some(mut task) = alerts_task {
+            if tokio::time::timeout(remaining(), &mut task).await.is_err() {
+                task.abort();
+                problems.push("alert dispatcher did not stop in time".to_string());
+            }
+        }
+        let alert_stats = self.alerts.stats();
+        if alert_stats.raised > 0 {
+            log::info!(
+                "Alerts this run: {} raised, {} delivered, {} failed",
+                alert_stats.raised, alert_stats.delivered, alert_stats.failed
+            );
+        }
+
+        // 3. 等待已提交的写库任务
+        if !self.tasks.wait_idle(remaining()).await {
+            problems.push(format!("{} pending writes did not finish", self.tasks.active()));
+        }
+
+        // 4. 最后一次同步
+        match tokio::time::timeout(remaining(), self.sync.sync_logs()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => problems.push(format!("final sync failed: {}", e)),
+            Err(_) => problems.push("final sync timed out".to_string()),
+        }
+
+        // 5. 关闭数据库
+        self.db.close().await;
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    /// 重新读取 config.json 并应用服务端连接与日志配置；存储路径、更新、指标导出需重启后生效
+    fn reload(&self) -> Result<(), String> {
+        let mut new_config = load_config(&self.config_path)?;
+        let mut config = self.config.write().unwrap();
+
+        let (old, new) = (&config.server, &new_config.server);
+        if old.url != new.url || old.app_code != new.app_code || old.app_secret != new.app_secret {
+            let serial_number = self.device_info.read().unwrap().pin.clone();
+            self.uploader.update_config(&new.app_code, &new.app_secret, &new.url, &serial_number);
+            log::info!("Management server changed to {}", new.url);
+        }
+        if old.standby_urls != new.standby_urls || old.failover != new.failover {
+            self.uploader.configure_servers(&new.url, &new.standby_urls, new.failover.clone());
+            log::info!("Standby management servers changed to {:?}", new.standby_urls);
+        }
+        if old.compression_min_bytes != new.compression_min_bytes {
+            self.uploader.set_compression_threshold(
+                new.compression_min_bytes.unwrap_or(uploader::compression::DEFAULT_MIN_BYTES),
+            );
+        }
+        if old.tls != new.tls {
+            self.uploader.configure_tls(new.tls.clone())?;
+        }
+        if old.bandwidth != new.bandwidth {
+            self.uploader.bandwidth().configure(&new.bandwidth);
+            if old.bandwidth.detect_metered != new.bandwidth.detect_metered {
+                log::warn!("server.bandwidth.detect_metered changed; it takes effect after restart");
             }
         }
-        Err(e) => log::error!("Failed to bind UDP socket for IP detection: {}", e),
+        if config.logging != new_config.logging {
+            logging::init(&new_config.logging)?;
+        }
+
+        let restart_required = [
+            ("storage", config.storage != new_config.storage),
+            ("update", config.update != new_config.update),
+            ("metrics", config.metrics != new_config.metrics),
+            ("ipc", config.ipc != new_config.ipc),
+            ("files", config.files != new_config.files),
+            ("peripherals", config.peripherals != new_config.peripherals),
+            ("session", config.session != new_config.session),
+        ];
+        for (section, _) in restart_required.iter().filter(|(_, changed)| *changed) {
+            log::warn!("Config section '{}' changed; it takes effect after restart", section);
+        }
+        // 未生效的部分保持运行中的值，避免与已启动的组件不一致
+        new_config.storage = config.storage.clone();
+        new_config.update = config.update.clone();
+        new_config.metrics = config.metrics.clone();
+        new_config.ipc = config.ipc.clone();
+        new_config.files = config.files.clone();
+        new_config.peripherals = config.peripherals.clone();
+        new_config.session = config.session.clone();
+        *config = new_config;
+        log::info!("Configuration reloaded from {}", self.config_path.display());
+        Ok(())
     }
-    log::warn!("Local IP fallback to 127.0.0.1");
-    "127.0.0.1".to_string()
 }
 
-fn get_local_mac() -> String {
-    if let Some(interface) = get_primary_interface_name() {
-        if let Some(mac) = get_mac_for_interface(&interface) {
-            log::info!("Local MAC resolved via {}: {}", interface, mac);
-            return mac;
+fn service_context() -> Option<Arc<ServiceContext>> {
+    SERVICE_CONTEXT.read().unwrap().clone()
+}
+
+fn set_last_error(message: String) {
+    log::error!("{}", message);
+    *LAST_ERROR.lock().unwrap() = Some(message);
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// 按配置文件启动服务 (FFI 宿主与独立守护进程共用)，阻塞直到初始化完成；已在运行时直接返回
+///
+/// 不能在 tokio 运行时内调用。
+pub fn start_service(config_path: &Path) -> Result<(), String> {
+    let _guard = LIFECYCLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if service_context().is_some() {
+        log::warn!("Audit core already initialized");
+        return Ok(());
+    }
+
+    log::info!("🚀 Audit Core initializing from {}...", config_path.display());
+    let result = std::panic::catch_unwind(|| RUNTIME.block_on(init_service_context(config_path)));
+    match result {
+        Ok(Ok(ctx)) => {
+            *SERVICE_CONTEXT.write().unwrap() = Some(ctx);
+            log::info!("✅ Audit Logic Core initialized successfully");
+            Ok(())
         }
+        Ok(Err(e)) => Err(format!("Audit Core initialization failed: {}", e)),
+        Err(panic) => Err(format!("Audit Core initialization panicked: {}", panic_message(panic))),
     }
+}
 
-    mac_address::get_mac_address()
-        .unwrap_or(None)
-        .map(|m| {
-            let mac = m.to_string();
-            log::info!("Local MAC resolved via mac_address: {}", mac);
-            mac
-        })
-        .unwrap_or_else(|| {
-            log::warn!("Local MAC fallback to 00:00:00:00:00:00");
-            "00:00:00:00:00:00".to_string()
-        })
+/// 停止服务：停止同步与 IPC，等待已提交的数据写入，尝试最后一次同步后关闭数据库
+///
+/// 未运行时返回 `None`；`Some(Err)` 表示已停止但有步骤失败或超时。
+pub fn stop_service(timeout: Duration) -> Option<Result<(), String>> {
+    let _guard = LIFECYCLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let ctx = SERVICE_CONTEXT.write().unwrap().take()?;
+
+    log::info!("🛑 Audit Core shutting down (timeout {:?})...", timeout);
+    let result = RUNTIME.block_on(ctx.shutdown(timeout));
+    match &result {
+        Ok(()) => log::info!("Audit Core stopped"),
+        Err(e) => log::warn!("Audit Core stopped with errors: {}", e),
+    }
+    log::logger().flush();
+    Some(result)
 }
 
-async fn get_service_context() -> Arc<ServiceContext> {
-    SERVICE_CONTEXT
-        .get_or_init(|| async { init_service_context().await })
-        .await
-        .clone()
+/// 重新读取启动时使用的配置文件
+pub fn reload_service_config() -> Result<(), String> {
+    let _guard = LIFECYCLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let ctx = service_context().ok_or_else(|| "Audit core is not running".to_string())?;
+    ctx.reload()
 }
 
+/// 初始化审计核心 (阻塞直到完成)
+///
+/// 返回 0 表示成功 (已初始化时也返回 0)，-1 表示失败，原因通过 `audit_core_last_error` 获取。
 #[no_mangle]
-pub fn init_audit_core() {
-    // initialize file logging for debugging
-    use simplelog::*;
-    let _ = WriteLogger::init(
-        LevelFilter::Debug,
-        Config::default(),
-        std::fs::File::create("/tmp/mac_monitor_audit_service.log").unwrap()
-    );
+pub extern "C" fn init_audit_core() -> i32 {
+    // 先按默认配置输出日志，读取 config.json 后再按其中的 logging 配置调整
+    if logging::levels().is_none() {
+        if let Err(e) = logging::init(&LoggingConfig::default()) {
+            eprintln!("Failed to initialize logging: {}", e);
+        }
+    }
 
-    log::info!("🚀 Audit Core initializing...");
+    match start_service(Path::new(CONFIG_PATH)) {
+        Ok(()) => {
+            *LAST_ERROR.lock().unwrap() = None;
+            0
+        }
+        Err(e) => {
+            set_last_error(format!("❌ {}", e));
+            -1
+        }
+    }
+}
 
-    RUNTIME.spawn(async {
-        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| async {
-            get_service_context().await
-        })) {
-            Ok(future) => {
-                future.await;
-                log::info!("✅ Audit Logic Core initialized successfully");
-            }
-            Err(e) => {
-                log::error!("❌ Audit Core Initialization PANIC: {:?}", e);
-            }
+/// 停止审计核心，见 `stop_service`
+///
+/// `timeout_ms` 为整个过程的上限，0 表示使用默认值 (10 秒)。
+/// 返回 0 表示正常停止，1 表示未初始化，2 表示已停止但有步骤失败或超时 (见 `audit_core_last_error`)。
+#[no_mangle]
+pub extern "C" fn shutdown_audit_core(timeout_ms: u32) -> i32 {
+    let timeout = if timeout_ms == 0 { DEFAULT_SHUTDOWN_TIMEOUT } else { Duration::from_millis(timeout_ms as u64) };
+    match stop_service(timeout) {
+        None => {
+            log::warn!("shutdown_audit_core called but audit core is not running");
+            1
         }
-    });
+        Some(Ok(())) => 0,
+        Some(Err(e)) => {
+            set_last_error(format!("Audit Core stopped with errors: {}", e));
+            2
+        }
+    }
 }
 
+/// 重新加载 config.json，返回 0 成功，-1 失败 (原因见 `audit_core_last_error`)
+#[no_mangle]
+pub extern "C" fn reload_audit_core_config() -> i32 {
+    match reload_service_config() {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(format!("Failed to reload config: {}", e));
+            -1
+        }
+    }
+}
+
+/// 注册 IPC `set_redaction_status` 的处理函数 (由 GUI 宿主在 `init_audit_core` 之前调用)
+#[no_mangle]
+pub extern "C" fn register_redaction_callback(callback: Option<ipc::RedactionCallback>) {
+    ipc::set_redaction_callback(callback);
+}
+
+/// 最近一次失败的原因，无错误时返回空指针；返回的字符串需用 `audit_core_free_string` 释放
+#[no_mangle]
+pub extern "C" fn audit_core_last_error() -> *mut c_char {
+    match LAST_ERROR.lock().unwrap().as_deref() {
+        Some(message) => CString::new(message.replace('\0', " ")).map(CString::into_raw).unwrap_or(std::ptr::null_mut()),
+        None => std::ptr::null_mut(),
+    }
+}
+
+// SAFETY: 由 Swift 宿主调用，ptr 为 null，或是本库返回且尚未释放的字符串
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn audit_core_free_string(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        unsafe { drop(CString::from_raw(ptr)) };
+    }
+}
+
+// SAFETY: 由 Swift 宿主调用，ptr/len 指向调用期间有效的图像缓冲区；字符串参数为 null 或以 NUL 结尾
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn analyze_enhanced_image(
     ptr: *const u8,
@@ -307,14 +836,14 @@ pub extern "C" fn analyze_enhanced_image(
     ocr_text: *const c_char,
     redaction_labels: *const c_char
 ) {
-    eprintln!(
+    log::debug!(
         "analyze_enhanced_image called: len={}, width={}, height={}",
         len,
         width,
         height
     );
     if ptr.is_null() || len == 0 {
-        eprintln!("analyze_enhanced_image: null ptr or zero len");
+        log::warn!("analyze_enhanced_image: null ptr or zero len");
         return;
     }
 
@@ -341,10 +870,19 @@ pub extern "C" fn analyze_enhanced_image(
     let raw_data = unsafe { std::slice::from_raw_parts(ptr, len) };
     let data_vec = raw_data.to_vec();
 
-    RUNTIME.spawn(async move {
-        let ctx = get_service_context().await;
-        // ... (rest of the logic)
-        // Ensure redaction_labels is passed to ScreenshotLog construction below
+    let Some(ctx) = service_context() else {
+        log::warn!("Audit core is not running, screenshot of {} dropped", app_name_str);
+        return;
+    };
+    // 锁屏 / 睡眠 / 切到其他用户期间不采集
+    if ctx.session.capture_paused() {
+        log::debug!("Screen is locked, screenshot of {} dropped", app_name_str);
+        return;
+    }
+    let save_dir = ctx.config.read().unwrap().storage.screenshot_dir.clone();
+    let tasks = ctx.tasks.clone();
+
+    let accepted = tasks.spawn(RUNTIME.handle(), async move {
 
         // 1. 构建 ImageBuffer (Swift 传过来的是 RGBA)
         let width_usize = width as usize;
@@ -412,59 +950,99 @@ pub extern "C" fn analyze_enhanced_image(
                 }
             }
 
-            // 4. 将图片编码为 JPEG (压缩以减小体积)
+            // 4. 按策略 (工作时间 / 应用采集模式 / 采集间隔) 判定是否保存
+            let decision = ctx.capture.screenshot(&app_name_str, is_sensitive, ctx.clock.now_ms());
+            let device = ctx.device_info.read().unwrap().clone();
+            match decision.action {
+                CaptureAction::Drop => {
+                    log::info!("Screenshot of {} dropped by policy ({})", app_name_str, decision.reason);
+                    return;
+                }
+                CaptureAction::Downgrade => {
+                    // 降级：仅记录元数据，不保存图片与 OCR 文本
+                    let log = ScreenshotLog {
+                        id: None,
+                        capture_time: ctx.clock.now_str(),
+                        app_name: app_name_str,
+                        image_path: String::new(),
+                        image_hash: hash_string,
+                        risk_level: if is_sensitive { 1 } else { 0 },
+                        ocr_text: None,
+                        host_id: device.host_id,
+                        cpe_id: device.cpe_id,
+                        mac: device.mac,
+                        ip: device.ip,
+                        redaction_labels: redaction_labels_str,
+                        policy_decision: Some(decision.label()),
+                    };
+                    if let Err(e) = ctx.db.save_screenshot_log(&log).await {
+                        log::error!("Failed to save screenshot log to DB: {}", e);
+                    } else {
+                        log::info!("Screenshot of {} downgraded to metadata ({})", log.app_name, decision.reason);
+                    }
+                    return;
+                }
+                CaptureAction::Capture => {}
+            }
+
+            // 5. 将图片编码为 JPEG (压缩以减小体积)
             let mut jpeg_data = Vec::new();
             let mut cursor = Cursor::new(&mut jpeg_data);
             // 设置 80% 质量
             if let Err(e) = dynamic_image.write_to(&mut cursor, image::ImageFormat::Jpeg) {
-                eprintln!("Failed to encode image to JPEG: {}", e);
+                log::error!("Failed to encode image to JPEG: {}", e);
                 return;
             }
 
-            // 5. 保存图片到本地
+            // 6. 保存图片到本地
             let filename = format!("{}.jpg", hash_string);
-            let save_dir = ctx.config.storage.screenshot_dir.as_str();
             let save_path = format!("{}/{}", save_dir, filename);
-            if let Err(e) = std::fs::create_dir_all(save_dir) {
-                eprintln!("Failed to create screenshot dir {}: {}", save_dir, e);
+            if let Err(e) = std::fs::create_dir_all(&save_dir) {
+                log::error!("Failed to create screenshot dir {}: {}", save_dir, e);
                 return;
             }
 
             if let Err(e) = std::fs::write(&save_path, &jpeg_data) {
-                eprintln!("Failed to save image file: {}", e);
+                log::error!("Failed to save image file: {}", e);
                 return;
             } else {
-                eprintln!("Screenshot written to {}", save_path);
+                log::debug!("Screenshot written to {}", save_path);
             }
 
-            // 6. 创建日志记录
+            // 7. 创建日志记录
             let log = ScreenshotLog {
                 id: None,
-                capture_time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                capture_time: ctx.clock.now_str(),
                 app_name: app_name_str,
                 image_path: save_path.clone(),
                 image_hash: hash_string,
                 risk_level: if is_sensitive { 1 } else { 0 },
                 ocr_text: ocr_text_str,
-                host_id: ctx.device_info.host_id.clone(),
-                cpe_id: ctx.device_info.cpe_id.clone(),
-                mac: ctx.device_info.mac.clone(),
-                ip: ctx.device_info.ip.clone(),
+                host_id: device.host_id,
+                cpe_id: device.cpe_id,
+                mac: device.mac,
+                ip: device.ip,
                 redaction_labels: redaction_labels_str,
+                policy_decision: Some(decision.label()),
             };
 
             if let Err(e) = ctx.db.save_screenshot_log(&log).await {
-                eprintln!("Failed to save screenshot log to DB: {}", e);
+                log::error!("Failed to save screenshot log to DB: {}", e);
             } else {
-                eprintln!("📸 Screenshot saved: {} (Sensitive: {})", log.app_name, is_sensitive);
+                log::info!("📸 Screenshot saved: {} (Sensitive: {})", log.app_name, is_sensitive);
             }
         } else {
-            eprintln!("Failed to build ImageBuffer from raw pixels");
+            log::error!("Failed to build ImageBuffer from raw pixels");
         }
     });
+    if !accepted {
+        log::warn!("Audit core is shutting down, screenshot dropped");
+    }
 }
 
 
+// SAFETY: 由 Swift 宿主调用，字符串参数为 null 或以 NUL 结尾，调用期间有效
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn log_clipboard_event(
     app_name: *const c_char,
@@ -497,20 +1075,38 @@ pub extern "C" fn log_clipboard_event(
         "text/plain".to_string()
     };
 
-    RUNTIME.spawn(async move {
-        let ctx = get_service_context().await;
+    let Some(ctx) = service_context() else {
+        log::warn!("Audit core is not running, clipboard event from {} dropped", app_name_str);
+        return;
+    };
+    let tasks = ctx.tasks.clone();
+
+    let accepted = tasks.spawn(RUNTIME.handle(), async move {
+        let decision = ctx.capture.clipboard(&app_name_str, &bundle_id_str, risk_level, ctx.clock.now_ms());
+        let content_str = match decision.action {
+            CaptureAction::Capture => content_str,
+            // 降级：保留事件本身，不保存剪贴板内容
+            CaptureAction::Downgrade => format!("[REDACTED {} chars]", content_str.chars().count()),
+            CaptureAction::Drop => {
+                log::info!("Clipboard event from {} dropped by policy ({})", app_name_str, decision.reason);
+                return;
+            }
+        };
+
+        let device = ctx.device_info.read().unwrap().clone();
         let log = ClipboardLog {
             id: None,
             app_name: app_name_str,
             bundle_id: bundle_id_str,
-            op_time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            op_time: ctx.clock.now_str(),
             content: content_str,
             content_type: content_type_str,
             risk_level,
-            cpe_id: ctx.device_info.cpe_id.clone(),
-            host_id: ctx.device_info.host_id.clone(),
-            mac: ctx.device_info.mac.clone(),
-            ip: ctx.device_info.ip.clone(),
+            cpe_id: device.cpe_id,
+            host_id: device.host_id,
+            mac: device.mac,
+            ip: device.ip,
+            policy_decision: Some(decision.label()),
         };
 
         if let Err(e) = ctx.db.save_clipboard_log(&log).await {
@@ -519,78 +1115,198 @@ pub extern "C" fn log_clipboard_event(
             log::info!("📋 Clipboard event logged: {} ({})", log.app_name, log.bundle_id);
         }
     });
+    if !accepted {
+        log::warn!("Audit core is shutting down, clipboard event dropped");
+    }
+}
+
+/// 提交一个文件操作事件 (JSON，字段见 `files::FileEvent`)，由 EndpointSecurity 等事件源调用
+///
+/// 返回 0 表示已接收，1 表示被策略过滤，-1 表示失败 (原因见 `audit_core_last_error`)。
+// SAFETY: 由 Swift 宿主调用，event_json 为 null 或以 NUL 结尾，调用期间有效
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn log_file_event(event_json: *const c_char) -> i32 {
+    if event_json.is_null() {
+        set_last_error("log_file_event called with a null event".to_string());
+        return -1;
+    }
+    let json = unsafe { CStr::from_ptr(event_json).to_string_lossy().into_owned() };
+    let event = match serde_json::from_str::<FileEvent>(&json) {
+        Ok(event) => event,
+        Err(e) => {
+            set_last_error(format!("Invalid file event: {}", e));
+            return -1;
+        }
+    };
+    let Some(ctx) = service_context() else {
+        log::warn!("Audit core is not running, file event for {} dropped", event.path);
+        return -1;
+    };
+    match ctx.files.submit(event) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            set_last_error(format!("File event rejected: {}", e));
+            -1
+        }
+    }
+}
+
+/// 提交一个外设接入 / 拔出事件 (JSON，字段见 `peripherals::PeripheralEvent`)，由 IOKit 等事件源调用
+///
+/// 返回 0 表示已接收，-1 表示失败 (原因见 `audit_core_last_error`)。
+// SAFETY: 由 Swift 宿主调用，event_json 为 null 或以 NUL 结尾，调用期间有效
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn log_peripheral_event(event_json: *const c_char) -> i32 {
+    if event_json.is_null() {
+        set_last_error("log_peripheral_event called with a null event".to_string());
+        return -1;
+    }
+    let json = unsafe { CStr::from_ptr(event_json).to_string_lossy().into_owned() };
+    let event = match serde_json::from_str::<PeripheralEvent>(&json) {
+        Ok(event) => event,
+        Err(e) => {
+            set_last_error(format!("Invalid peripheral event: {}", e));
+            return -1;
+        }
+    };
+    let Some(ctx) = service_context() else {
+        log::warn!("Audit core is not running, {} {} event dropped", event.device_class, event.action.as_str());
+        return -1;
+    };
+    if ctx.peripherals.record(event) {
+        0
+    } else {
+        set_last_error("Audit core is shutting down, peripheral event dropped".to_string());
+        -1
+    }
+}
+
+/// 提交一个会话事件 (JSON，字段见 `session::SessionEvent`)，由宿主的锁屏 / 睡眠 / 用户切换通知调用
+///
+/// 返回 0 表示已接收，-1 表示失败 (原因见 `audit_core_last_error`)。
+// SAFETY: 由 Swift 宿主调用，event_json 为 null 或以 NUL 结尾，调用期间有效
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn log_session_event(event_json: *const c_char) -> i32 {
+    if event_json.is_null() {
+        set_last_error("log_session_event called with a null event".to_string());
+        return -1;
+    }
+    let json = unsafe { CStr::from_ptr(event_json).to_string_lossy().into_owned() };
+    let event = match serde_json::from_str::<SessionEvent>(&json) {
+        Ok(event) => event,
+        Err(e) => {
+            set_last_error(format!("Invalid session event: {}", e));
+            return -1;
+        }
+    };
+    let Some(ctx) = service_context() else {
+        log::warn!("Audit core is not running, {} event dropped", event.kind.op_type());
+        return -1;
+    };
+    if ctx.session.record(event) {
+        0
+    } else {
+        set_last_error("Audit core is shutting down, session event dropped".to_string());
+        -1
+    }
+}
+
+/// 提交一个前台应用事件 (JSON，字段见 `usage::ForegroundEvent`)，由宿主的前台切换 / 空闲检测调用
+// SAFETY: 由 Swift 宿主调用，event_json 为 null 或以 NUL 结尾，调用期间有效
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn log_foreground_event(event_json: *const c_char) -> i32 {
+    if event_json.is_null() {
+        set_last_error("log_foreground_event called with a null event".to_string());
+        return -1;
+    }
+    let json = unsafe { CStr::from_ptr(event_json).to_string_lossy().into_owned() };
+    let event = match serde_json::from_str::<ForegroundEvent>(&json) {
+        Ok(event) => event,
+        Err(e) => {
+            set_last_error(format!("Invalid foreground event: {}", e));
+            return -1;
+        }
+    };
+    let Some(ctx) = service_context() else {
+        log::warn!("Audit core is not running, foreground event dropped");
+        return -1;
+    };
+    match ctx.usage.record(event) {
+        Ok(true) => 0,
+        Ok(false) => {
+            set_last_error("Audit core is shutting down, foreground event dropped".to_string());
+            -1
+        }
+        Err(e) => {
+            set_last_error(e);
+            -1
+        }
+    }
 }
 
+/// 当前是否暂停截图 (锁屏 / 睡眠 / 切到其他用户)；宿主据此跳过采集，未运行时返回 false
+#[no_mangle]
+pub extern "C" fn audit_core_capture_paused() -> bool {
+    service_context().map(|ctx| ctx.session.capture_paused()).unwrap_or(false)
+}
+
+/// 宿主检测到的网络状态 (macOS 低数据模式 / 计费网络)；受限时截图推迟上传
+#[no_mangle]
+pub extern "C" fn audit_core_set_network_constrained(constrained: bool) {
+    if let Some(ctx) = service_context() {
+        ctx.uploader.bandwidth().set_detected(constrained);
+    }
+}
+
+/// 策略要求的截图间隔 (秒)，0 表示未限制；客户端据此调整截图频率
+#[no_mangle]
+pub extern "C" fn get_screenshot_interval_secs() -> u32 {
+    match service_context() {
+        Some(ctx) => ctx.capture.screenshot_interval_secs().unwrap_or(0),
+        None => 0,
+    }
+}
+
+/// 向管理端注册设备：以设备身份与 PIN 换取设备 id 和凭据，成功后把服务端地址写回 config.json
+///
+/// `cpe_id` 保留以兼容旧的调用方，设备标识取自本机序列号。阻塞直到注册完成；
+/// 失败时返回 false，原因见 `audit_core_last_error`。
 #[no_mangle]
 pub extern "C" fn register_device(
     server_ip: *const c_char,
     server_port: *const c_char,
-    cpe_id: *const c_char,
+    _cpe_id: *const c_char,
     pin: *const c_char
 ) -> bool {
-    // 1. Convert C strings to Rust strings
-    let server_ip = if !server_ip.is_null() {
-        unsafe { CStr::from_ptr(server_ip).to_string_lossy().into_owned() }
-    } else { return false; };
-
-    let server_port = if !server_port.is_null() {
-        unsafe { CStr::from_ptr(server_port).to_string_lossy().into_owned() }
-    } else { return false; };
-
-    let cpe_id = if !cpe_id.is_null() {
-        unsafe { CStr::from_ptr(cpe_id).to_string_lossy().into_owned() }
-    } else { return false; };
-
-    let pin = if !pin.is_null() {
-        unsafe { CStr::from_ptr(pin).to_string_lossy().into_owned() }
-    } else { return false; };
-
-    log::info!("Registering device: IP={}, Port={}, CPE={}, PIN={}", server_ip, server_port, cpe_id, pin);
-
-    // 2. Construct Base URL
-    let base_url = format!("http://{}:{}", server_ip, server_port);
-    let app_code = "mac_monitor".to_string(); // Default app code
-    let app_secret = pin.clone(); // Use PIN as secret for now
-
-    // 3. Update ServiceContext
-    let rt = &RUNTIME;
-    let res = rt.block_on(async {
-        let ctx = get_service_context().await;
-
-        // Update Uploader config
-        ctx.uploader.update_config(&app_code, &app_secret, &base_url, &ctx.device_info.pin);
-
-        // Persist to config.json
-        let config_path = "/Users/adolf/Desktop/code/clash/mac-monitor-project/audit-service/config.json";
-
-        // Create new config object
-        let new_config = Config {
-            server: ServerConfig {
-                url: base_url.clone(),
-                app_code: app_code.clone(),
-                app_secret: app_secret.clone(),
-            },
-            storage: StorageConfig {
-                screenshot_dir: "/Users/adolf/Desktop/mac-monitor/screenshots".to_string(), // Keep default or read from existing
-                database_path: ctx.config.storage.database_path.clone(),
-            }
-        };
-
-        match serde_json::to_string_pretty(&new_config) {
-            Ok(json) => {
-                if let Err(e) = fs::write(config_path, json) {
-                    log::error!("Failed to write config.json: {}", e);
-                    return false;
-                }
-            }
-            Err(e) => {
-                log::error!("Failed to serialize config: {}", e);
-                return false;
-            }
-        }
+    let arg = |ptr: *const c_char| {
+        (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() })
+    };
+    let (Some(server_ip), Some(server_port), Some(pin)) = (arg(server_ip), arg(server_port), arg(pin)) else {
+        set_last_error("register_device called with a null argument".to_string());
+        return false;
+    };
+    let Some(ctx) = service_context() else {
+        set_last_error("Cannot register device: audit core is not running".to_string());
+        return false;
+    };
 
-        true
-    });
+    log::info!("Registering device with {}:{}", server_ip, server_port);
+    let base_url = build_base_url(&server_ip, &server_port);
+    if let Err(e) = RUNTIME.block_on(ctx.enrollment.register(&base_url, None, &pin)) {
+        set_last_error(format!("Device registration failed: {}", e));
+        return false;
+    }
 
-    res
+    // 只写回服务端地址，凭据已保存在凭据存储中
+    if let Err(e) = write_server_config(&ctx.config_path, &base_url, None) {
+        set_last_error(format!("Device registered but config.json was not updated: {}", e));
+        return false;
+    }
+    ctx.config.write().unwrap().server.url = base_url;
+    true
 }
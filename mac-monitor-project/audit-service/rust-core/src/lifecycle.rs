@@ -0,0 +1,117 @@
+//! 服务生命周期：后台任务的停止信号，以及 FFI / IPC 触发的写库任务跟踪
+//!
+//! 退出时先停止接收新任务，再等待已提交的任务完成，保证宿主进程退出前数据已落盘。
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::{watch, Notify};
+
+/// 广播停止信号，后台循环通过 `ShutdownSignal::wait` 感知
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    rx: watch::Receiver<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self { tx: Arc::new(watch::channel(false).0) }
+    }
+
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal { rx: self.tx.subscribe() }
+    }
+
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+}
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// 等待停止信号；`Shutdown` 被丢弃也视为停止
+    pub async fn wait(&mut self) {
+        let _ = self.rx.wait_for(|stopped| *stopped).await;
+    }
+}
+
+/// 跟踪正在执行的写库任务
+#[derive(Default)]
+pub struct TaskTracker {
+    active: AtomicUsize,
+    closed: AtomicBool,
+    idle: Notify,
+}
+
+struct ActiveGuard(Arc<TaskTracker>);
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        if self.0.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl TaskTracker {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// 在运行时上执行任务；已关闭时拒绝并返回 false
+    pub fn spawn<F>(self: &Arc<Self>, handle: &Handle, task: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        let guard = ActiveGuard(self.clone());
+        if self.closed.load(Ordering::SeqCst) {
+            return false;
+        }
+        handle.spawn(async move {
+            let _guard = guard;
+            task.await;
+        });
+        true
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// 停止接收新任务
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    /// 等待所有已提交的任务完成，超时返回 false
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.idle.notified();
+                if self.active() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+}
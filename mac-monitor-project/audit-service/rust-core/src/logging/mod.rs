@@ -0,0 +1,221 @@
+//! 结构化日志：每条记录一行 JSON，按大小轮转，按模块设置级别，写入前对敏感字段脱敏
+//!
+//! 级别使用类似 `RUST_LOG` 的写法：`info,audit_logic_core::uploader=debug,sqlx=warn`，
+//! 模块按最长前缀匹配；可通过 IPC `set_log_level` 在运行时修改。
+
+pub mod redact;
+pub mod rotation;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use self::redact::redact;
+use self::rotation::RotatingFile;
+
+pub const DEFAULT_LOG_PATH: &str = "/tmp/mac_monitor_audit_service.log";
+const DEFAULT_LEVEL: &str = "info";
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// 缺省为 /tmp/mac_monitor_audit_service.log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// 级别配置，如 `info,audit_logic_core::ipc=debug`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// 单个文件上限 (字节)，缺省 10 MiB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// 保留的历史文件数，缺省 5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+    /// 同时输出到 stderr (前台调试时使用)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub console: bool,
+}
+
+impl LoggingConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn path(&self) -> PathBuf {
+        PathBuf::from(self.path.as_deref().unwrap_or(DEFAULT_LOG_PATH))
+    }
+}
+
+/// 全局级别 + 按模块覆盖
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelSpec {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+impl LevelSpec {
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module.as_str()
+                    || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    /// 设置单个模块的级别；`module` 为空时修改全局级别
+    pub fn set(&mut self, module: Option<&str>, level: LevelFilter) {
+        match module.map(str::trim).filter(|m| !m.is_empty()) {
+            Some(module) => {
+                self.modules.insert(module.to_string(), level);
+            }
+            None => self.default = level,
+        }
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.values().copied().fold(self.default, Ord::max)
+    }
+}
+
+impl FromStr for LevelSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut parsed = LevelSpec { default: LevelFilter::Info, modules: BTreeMap::new() };
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (module, level) = match directive.split_once('=') {
+                Some((module, level)) => (Some(module), level),
+                None => (None, directive),
+            };
+            let level = parse_level(level)?;
+            parsed.set(module, level);
+        }
+        Ok(parsed)
+    }
+}
+
+impl std::fmt::Display for LevelSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{}={}", module, level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level.trim()).map_err(|_| format!("Invalid log level: {:?}", level))
+}
+
+struct JsonLogger {
+    levels: RwLock<LevelSpec>,
+    file: Mutex<Option<RotatingFile>>,
+    console: AtomicBool,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.levels.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        let line = serde_json::json!({
+            "ts": chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "msg": redact(&message),
+            "file": record.file(),
+            "line": record.line(),
+        })
+        .to_string();
+
+        if self.console.load(Ordering::Relaxed) {
+            eprintln!("{}", line);
+        }
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            if let Err(e) = file.write_line(&line) {
+                eprintln!("Failed to write log file {:?}: {}", file.path(), e);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+static LOGGER: OnceLock<JsonLogger> = OnceLock::new();
+
+/// 安装全局日志器；重复调用时按新配置重新打开日志文件并替换级别
+pub fn init(config: &LoggingConfig) -> Result<(), String> {
+    let levels: LevelSpec = config.level.as_deref().unwrap_or(DEFAULT_LEVEL).parse()?;
+    let path = config.path();
+    let file = RotatingFile::open(
+        &path,
+        config.max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+        config.max_files.unwrap_or(DEFAULT_MAX_FILES),
+    )
+    .map_err(|e| format!("Failed to open log file {:?}: {}", path, e))?;
+
+    let mut installed = false;
+    let logger = LOGGER.get_or_init(|| {
+        installed = true;
+        JsonLogger {
+            levels: RwLock::new(levels.clone()),
+            file: Mutex::new(None),
+            console: AtomicBool::new(config.console),
+        }
+    });
+    *logger.file.lock().unwrap() = Some(file);
+    logger.console.store(config.console, Ordering::Relaxed);
+    if installed {
+        log::set_logger(logger).map_err(|e| format!("Another logger is already installed: {}", e))?;
+    }
+    apply(logger, levels);
+    Ok(())
+}
+
+/// 当前级别配置
+pub fn levels() -> Option<LevelSpec> {
+    LOGGER.get().map(|logger| logger.levels.read().unwrap().clone())
+}
+
+/// 替换全部级别配置
+pub fn set_levels(spec: &str) -> Result<LevelSpec, String> {
+    let logger = LOGGER.get().ok_or_else(|| "Logging is not initialized".to_string())?;
+    let levels: LevelSpec = spec.parse()?;
+    apply(logger, levels.clone());
+    Ok(levels)
+}
+
+/// 修改单个模块 (或全局) 的级别，其他模块保持不变
+pub fn set_module_level(module: Option<&str>, level: &str) -> Result<LevelSpec, String> {
+    let logger = LOGGER.get().ok_or_else(|| "Logging is not initialized".to_string())?;
+    let mut levels = logger.levels.read().unwrap().clone();
+    levels.set(module, parse_level(level)?);
+    apply(logger, levels.clone());
+    Ok(levels)
+}
+
+fn apply(logger: &JsonLogger, levels: LevelSpec) {
+    log::set_max_level(levels.max_level());
+    let description = levels.to_string();
+    *logger.levels.write().unwrap() = levels;
+    log::info!("Log levels set to {}", description);
+}
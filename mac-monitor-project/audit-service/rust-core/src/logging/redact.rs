@@ -0,0 +1,39 @@
+//! 日志脱敏：PIN、应用密钥、访问令牌等不得以明文写入日志
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+const MASK: &str = "***";
+// 需要脱敏的字段名 (不区分大小写，兼容 snake_case / camelCase / 连字符)
+const SECRET_KEYS: &str = r"pin|app_?secret|secret|password|passwd|(?:visit|access|refresh)[-_]?token|token|credential";
+
+struct Patterns {
+    /// "pin":"1234" / pin: "1234" (JSON 与 Debug 输出)
+    quoted: Regex,
+    /// pin=1234 / visit-token: abc
+    bare: Regex,
+    /// Authorization: Bearer abc
+    bearer: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        quoted: Regex::new(&format!(r#"(?i)\b((?:{})"?\s*[=:]\s*")[^"]*""#, SECRET_KEYS)).unwrap(),
+        bare: Regex::new(&format!(r#"(?i)\b((?:{})"?\s*[=:]\s*)[^\s",;&}}\]]+"#, SECRET_KEYS)).unwrap(),
+        bearer: Regex::new(r"(?i)\b(Bearer\s+)[A-Za-z0-9._~+/=-]+").unwrap(),
+    })
+}
+
+/// 遮盖消息中的敏感字段值，无需修改时不分配新字符串
+pub fn redact(message: &str) -> Cow<'_, str> {
+    let p = patterns();
+    if !p.quoted.is_match(message) && !p.bare.is_match(message) && !p.bearer.is_match(message) {
+        return Cow::Borrowed(message);
+    }
+    let masked = p.bearer.replace_all(message, format!("${{1}}{}", MASK));
+    let masked = p.quoted.replace_all(&masked, format!("${{1}}{}\"", MASK));
+    let masked = p.bare.replace_all(&masked, format!("${{1}}{}", MASK));
+    Cow::Owned(masked.into_owned())
+}
@@ -0,0 +1,237 @@
+//! 运行指标：同步 / 心跳 / 扫描过程中累积的计数器，以及按需采集的存储状态
+//!
+//! 通过 IPC `status` 命令查看完整状态，可选导出为 Prometheus 文本格式，
+//! 每次心跳附带一份摘要供管理端判断终端健康状况。
+
+pub mod prometheus;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::clock::{format_ms, system_now_ms, LogicalClock};
+use crate::db::Database;
+use crate::models::PendingCounts;
+
+// 尚未发生时的占位值
+const NEVER: i64 = -1;
+
+pub struct Metrics {
+    db: Arc<Database>,
+    clock: Arc<LogicalClock>,
+    db_path: PathBuf,
+    screenshot_dir: PathBuf,
+    started_ms: i64,
+    sync_rounds: AtomicU64,
+    sync_failures: AtomicU64,
+    last_sync_ms: AtomicI64,
+    last_sync_error: Mutex<Option<String>>,
+    upload_failures: Mutex<BTreeMap<String, u64>>,
+    heartbeats: AtomicU64,
+    heartbeat_failures: AtomicU64,
+    last_heartbeat_ms: AtomicI64,
+    heartbeat_latency_ms: AtomicI64,
+    scans: AtomicU64,
+    scanner_duration_ms: AtomicI64,
+}
+
+/// IPC `status` 返回的完整状态
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub version: String,
+    pub uptime_secs: u64,
+    pub pending: PendingCounts,
+    pub pending_total: i64,
+    pub sync_rounds: u64,
+    pub sync_failures: u64,
+    /// 最近一次全部上传成功的同步 (逻辑时间)
+    pub last_sync_ms: Option<i64>,
+    pub last_sync_time: Option<String>,
+    pub last_sync_error: Option<String>,
+    /// 按接口统计的上传失败次数
+    pub upload_failures: BTreeMap<String, u64>,
+    pub heartbeats: u64,
+    pub heartbeat_failures: u64,
+    pub last_heartbeat_time: Option<String>,
+    pub heartbeat_latency_ms: Option<i64>,
+    pub clock_offset_ms: i64,
+    pub clock_synced: bool,
+    pub db_size_bytes: u64,
+    pub screenshot_dir_bytes: u64,
+    pub screenshot_files: u64,
+    pub scans: u64,
+    pub scanner_duration_ms: Option<i64>,
+}
+
+/// 随心跳上报的摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatSummary {
+    pub pending: i64,
+    pub last_sync_ms: Option<i64>,
+    pub upload_failures: u64,
+    pub heartbeat_latency_ms: Option<i64>,
+    pub clock_offset_ms: i64,
+    pub db_size_bytes: u64,
+    pub screenshot_dir_bytes: u64,
+    pub scanner_duration_ms: Option<i64>,
+}
+
+impl Metrics {
+    pub fn new(db: Arc<Database>, clock: Arc<LogicalClock>, db_path: PathBuf, screenshot_dir: PathBuf) -> Self {
+        Self {
+            db,
+            clock,
+            db_path,
+            screenshot_dir,
+            started_ms: system_now_ms(),
+            sync_rounds: AtomicU64::new(0),
+            sync_failures: AtomicU64::new(0),
+            last_sync_ms: AtomicI64::new(NEVER),
+            last_sync_error: Mutex::new(None),
+            upload_failures: Mutex::new(BTreeMap::new()),
+            heartbeats: AtomicU64::new(0),
+            heartbeat_failures: AtomicU64::new(0),
+            last_heartbeat_ms: AtomicI64::new(NEVER),
+            heartbeat_latency_ms: AtomicI64::new(NEVER),
+            scans: AtomicU64::new(0),
+            scanner_duration_ms: AtomicI64::new(NEVER),
+        }
+    }
+
+    /// 一轮同步结束；有任一记录上传失败时该轮不算成功
+    pub fn record_sync(&self, result: &Result<(), String>, had_failures: bool) {
+        self.sync_rounds.fetch_add(1, Ordering::Relaxed);
+        let error = match result {
+            Err(e) => Some(e.clone()),
+            Ok(()) if had_failures => Some("Some records failed to upload".to_string()),
+            Ok(()) => None,
+        };
+        match &error {
+            Some(_) => {
+                self.sync_failures.fetch_add(1, Ordering::Relaxed);
+            }
+            None => self.last_sync_ms.store(self.clock.now_ms(), Ordering::Relaxed),
+        }
+        *self.last_sync_error.lock().unwrap() = error;
+    }
+
+    pub fn record_upload_failure(&self, endpoint: &str) {
+        *self.upload_failures.lock().unwrap().entry(endpoint.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn upload_failure_total(&self) -> u64 {
+        self.upload_failures.lock().unwrap().values().sum()
+    }
+
+    /// 心跳结果，成功时记录往返耗时
+    pub fn record_heartbeat(&self, latency_ms: Option<i64>) {
+        self.heartbeats.fetch_add(1, Ordering::Relaxed);
+        match latency_ms {
+            Some(latency) => {
+                self.heartbeat_latency_ms.store(latency, Ordering::Relaxed);
+                self.last_heartbeat_ms.store(self.clock.now_ms(), Ordering::Relaxed);
+            }
+            None => {
+                self.heartbeat_failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn record_scan(&self, duration_ms: i64) {
+        self.scans.fetch_add(1, Ordering::Relaxed);
+        self.scanner_duration_ms.store(duration_ms, Ordering::Relaxed);
+    }
+
+    /// 采集完整状态 (查询数据库并统计截图目录大小)
+    pub async fn status(&self) -> Result<ServiceStatus, String> {
+        let pending = self.db.count_unsent().await.map_err(|e| e.to_string())?;
+        let (db_size_bytes, (screenshot_dir_bytes, screenshot_files)) = self.storage_usage().await;
+
+        let last_sync_ms = known(self.last_sync_ms.load(Ordering::Relaxed));
+        let last_heartbeat_ms = known(self.last_heartbeat_ms.load(Ordering::Relaxed));
+        Ok(ServiceStatus {
+            version: crate::APP_VERSION.to_string(),
+            uptime_secs: ((system_now_ms() - self.started_ms).max(0) / 1000) as u64,
+            pending,
+            pending_total: pending.total(),
+            sync_rounds: self.sync_rounds.load(Ordering::Relaxed),
+            sync_failures: self.sync_failures.load(Ordering::Relaxed),
+            last_sync_ms,
+            last_sync_time: last_sync_ms.map(format_ms),
+            last_sync_error: self.last_sync_error.lock().unwrap().clone(),
+            upload_failures: self.upload_failures.lock().unwrap().clone(),
+            heartbeats: self.heartbeats.load(Ordering::Relaxed),
+            heartbeat_failures: self.heartbeat_failures.load(Ordering::Relaxed),
+            last_heartbeat_time: last_heartbeat_ms.map(format_ms),
+            heartbeat_latency_ms: known(self.heartbeat_latency_ms.load(Ordering::Relaxed)),
+            clock_offset_ms: self.clock.offset_ms(),
+            clock_synced: self.clock.is_synced(),
+            db_size_bytes,
+            screenshot_dir_bytes,
+            screenshot_files,
+            scans: self.scans.load(Ordering::Relaxed),
+            scanner_duration_ms: known(self.scanner_duration_ms.load(Ordering::Relaxed)),
+        })
+    }
+
+    pub async fn heartbeat_summary(&self) -> Result<HeartbeatSummary, String> {
+        let status = self.status().await?;
+        Ok(HeartbeatSummary {
+            pending: status.pending_total,
+            last_sync_ms: status.last_sync_ms,
+            upload_failures: status.upload_failures.values().sum(),
+            heartbeat_latency_ms: status.heartbeat_latency_ms,
+            clock_offset_ms: status.clock_offset_ms,
+            db_size_bytes: status.db_size_bytes,
+            screenshot_dir_bytes: status.screenshot_dir_bytes,
+            scanner_duration_ms: status.scanner_duration_ms,
+        })
+    }
+
+    /// (数据库大小, (截图目录大小, 文件数))；截图较多时遍历目录较慢，放到阻塞线程执行
+    async fn storage_usage(&self) -> (u64, (u64, u64)) {
+        let db_path = self.db_path.clone();
+        let screenshot_dir = self.screenshot_dir.clone();
+        tokio::task::spawn_blocking(move || (database_size(&db_path), dir_usage(&screenshot_dir)))
+            .await
+            .unwrap_or_default()
+    }
+}
+
+fn known(value: i64) -> Option<i64> {
+    (value != NEVER).then_some(value)
+}
+
+/// 数据库文件及 WAL / SHM 文件的总大小
+fn database_size(path: &Path) -> u64 {
+    let file_size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    let mut total = file_size(path);
+    for suffix in ["-wal", "-shm"] {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        total += file_size(Path::new(&name));
+    }
+    total
+}
+
+/// 递归统计目录大小与文件数，目录不存在时为 0
+fn dir_usage(dir: &Path) -> (u64, u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (0, 0);
+    };
+    let (mut bytes, mut files) = (0, 0);
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            let (b, f) = dir_usage(&entry.path());
+            bytes += b;
+            files += f;
+        } else if file_type.is_file() {
+            bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files += 1;
+        }
+    }
+    (bytes, files)
+}
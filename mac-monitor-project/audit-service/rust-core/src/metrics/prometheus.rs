@@ -0,0 +1,202 @@
+//! Prometheus 文本格式导出
+//!
+//! 两种方式可同时启用：
+//! - 定期写入文件 (供 node_exporter textfile collector 采集)，先写临时文件再改名，避免读到半个文件
+//! - 监听地址提供 HTTP 抓取；以 `/` 开头时视为 Unix socket 路径，否则为 TCP 地址 (如 `127.0.0.1:9464`)
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{self, Duration};
+
+use super::{Metrics, ServiceStatus};
+use crate::lifecycle::ShutdownSignal;
+
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prometheus_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prometheus_listen: Option<String>,
+    /// 写文件的间隔 (秒)，缺省 30
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_interval_secs: Option<u64>,
+}
+
+impl MetricsConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// 按配置启动导出任务，需在 tokio 运行时内调用；收到停止信号后退出
+pub fn start_exporter(metrics: Arc<Metrics>, config: &MetricsConfig, shutdown: ShutdownSignal) {
+    if let Some(path) = &config.prometheus_file {
+        let interval = Duration::from_secs(config.export_interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS).max(1));
+        let mut stop = shutdown.clone();
+        let task = write_file_loop(metrics.clone(), PathBuf::from(path), interval);
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = task => {}
+                _ = stop.wait() => {}
+            }
+        });
+    }
+    if let Some(addr) = &config.prometheus_listen {
+        let addr = addr.clone();
+        let mut stop = shutdown;
+        tokio::spawn(async move {
+            tokio::select! {
+                result = serve(metrics, &addr) => {
+                    if let Err(e) = result {
+                        log::error!("Prometheus exporter on {} stopped: {}", addr, e);
+                    }
+                }
+                _ = stop.wait() => {
+                    if addr.starts_with('/') {
+                        let _ = std::fs::remove_file(&addr);
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn write_file_loop(metrics: Arc<Metrics>, path: PathBuf, interval: Duration) {
+    let mut ticker = time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let text = match metrics.status().await {
+            Ok(status) => render(&status),
+            Err(e) => {
+                log::warn!("Failed to collect metrics: {}", e);
+                continue;
+            }
+        };
+        let tmp = path.with_extension("prom.tmp");
+        let result = tokio::fs::write(&tmp, text).await;
+        if let Err(e) = match result {
+            Ok(()) => tokio::fs::rename(&tmp, &path).await,
+            Err(e) => Err(e),
+        } {
+            log::warn!("Failed to write metrics file {:?}: {}", path, e);
+        }
+    }
+}
+
+async fn serve(metrics: Arc<Metrics>, addr: &str) -> std::io::Result<()> {
+    if addr.starts_with('/') {
+        let _ = std::fs::remove_file(addr);
+        let listener = tokio::net::UnixListener::bind(addr)?;
+        log::info!("Prometheus metrics available on unix socket {}", addr);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(respond(stream, metrics.clone()));
+        }
+    } else {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        log::info!("Prometheus metrics available on http://{}/metrics", addr);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(respond(stream, metrics.clone()));
+        }
+    }
+}
+
+/// 最简 HTTP 响应：不区分路径，读完请求头后返回当前指标并关闭连接
+async fn respond<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, metrics: Arc<Metrics>) {
+    let mut buf = [0u8; 4096];
+    let mut request = Vec::new();
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 64 * 1024 {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let (status, body) = match metrics.status().await {
+        Ok(status) => ("200 OK", render(&status)),
+        Err(e) => ("503 Service Unavailable", format!("# metrics unavailable: {}\n", e)),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// 渲染为 Prometheus 文本格式 (0.0.4)
+pub fn render(status: &ServiceStatus) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, f64)]| {
+        let _ = writeln!(out, "# HELP audit_core_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE audit_core_{} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "audit_core_{}{} {}", name, labels, value);
+        }
+    };
+    let single = |value: f64| vec![(String::new(), value)];
+    let seconds = |ms: i64| ms as f64 / 1000.0;
+
+    metric(
+        "build_info",
+        "gauge",
+        "Audit core version.",
+        &[(format!("{{version=\"{}\"}}", escape(&status.version)), 1.0)],
+    );
+    metric("uptime_seconds", "gauge", "Seconds since the service started.", &single(status.uptime_secs as f64));
+    let pending: Vec<_> = status
+        .pending
+        .by_table()
+        .iter()
+        .map(|(table, count)| (format!("{{table=\"{}\"}}", table), *count as f64))
+        .collect();
+    metric("pending_rows", "gauge", "Rows waiting to be uploaded.", &pending);
+    metric("sync_rounds_total", "counter", "Completed sync rounds.", &single(status.sync_rounds as f64));
+    metric("sync_failures_total", "counter", "Sync rounds with at least one failure.", &single(status.sync_failures as f64));
+    if let Some(ms) = status.last_sync_ms {
+        metric(
+            "last_sync_timestamp_seconds",
+            "gauge",
+            "Logical time of the last fully successful sync.",
+            &single(seconds(ms)),
+        );
+    }
+    let failures: Vec<_> = status
+        .upload_failures
+        .iter()
+        .map(|(endpoint, count)| (format!("{{endpoint=\"{}\"}}", escape(endpoint)), *count as f64))
+        .collect();
+    metric("upload_failures_total", "counter", "Failed uploads by endpoint.", &failures);
+    metric("heartbeats_total", "counter", "Heartbeat attempts.", &single(status.heartbeats as f64));
+    metric("heartbeat_failures_total", "counter", "Failed heartbeats.", &single(status.heartbeat_failures as f64));
+    if let Some(ms) = status.heartbeat_latency_ms {
+        metric("heartbeat_latency_seconds", "gauge", "Round trip of the last successful heartbeat.", &single(seconds(ms)));
+    }
+    metric(
+        "clock_offset_seconds",
+        "gauge",
+        "Estimated server minus local clock offset.",
+        &single(seconds(status.clock_offset_ms)),
+    );
+    metric("clock_synced", "gauge", "Whether the logical clock has a server sample.", &single(status.clock_synced as u8 as f64));
+    metric("db_size_bytes", "gauge", "Size of the local database including WAL.", &single(status.db_size_bytes as f64));
+    metric("screenshot_dir_bytes", "gauge", "Size of the screenshot directory.", &single(status.screenshot_dir_bytes as f64));
+    metric("screenshot_files", "gauge", "Files in the screenshot directory.", &single(status.screenshot_files as f64));
+    metric("scans_total", "counter", "Completed process/application scans.", &single(status.scans as f64));
+    if let Some(ms) = status.scanner_duration_ms {
+        metric("scanner_duration_seconds", "gauge", "Duration of the last scan.", &single(seconds(ms)));
+    }
+    out
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
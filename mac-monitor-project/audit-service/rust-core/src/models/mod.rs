@@ -18,6 +18,9 @@ pub struct AuditLog {
     pub ip: String,
     pub mac: String,
     pub host_id: String,
+    /// 请求体字节数 (生产者提供时)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<i64>,
 }
 
 fn default_process_name() -> String {
@@ -58,6 +61,9 @@ pub struct ScreenshotLog {
     pub mac: String,
     pub ip: String,
     pub redaction_labels: Option<String>,
+    /// 策略判定结果，如 "capture" / "downgrade:outside_working_hours"
+    #[serde(default)]
+    pub policy_decision: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -75,6 +81,156 @@ pub struct ClipboardLog {
     pub host_id: String,
     pub mac: String,
     pub ip: String,
+    #[serde(default)]
+    pub policy_decision: Option<String>,
+}
+
+/// 文件操作记录，窗口期内同一进程对同一路径的同类操作合并为一条
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// 首次操作时间
+    pub op_time: String,
+    /// 合并后最后一次操作时间
+    pub last_op_time: String,
+    /// create / modify / rename / delete / copy_to_removable
+    pub op_type: String,
+    pub path: String,
+    #[serde(default)]
+    pub old_path: Option<String>,
+    #[serde(default)]
+    pub size: Option<i64>,
+    /// 文件内容 SHA-256 (删除或超过大小上限时为空)
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub process_name: String,
+    #[serde(default)]
+    pub pid: Option<i64>,
+    /// 合并的事件数
+    pub event_count: i64,
+    pub risk_level: i32,
+    #[serde(rename = "cpe_id")]
+    pub cpe_id: String,
+    pub host_id: String,
+    pub mac: String,
+    pub ip: String,
+}
+
+/// 聚合模式下一个时间桶内同一 (进程, 域名, 方法, 路径模板) 的请求汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// 时间桶起点
+    pub bucket_start: String,
+    pub bucket_secs: i64,
+    pub process_name: String,
+    pub domain: String,
+    pub method_type: String,
+    /// 数字、UUID、长十六进制等路径段替换为 `{id}`
+    pub path_template: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub request_count: i64,
+    /// 生产者提供的请求体字节数之和
+    pub bytes: i64,
+    pub risk_level: i32,
+    #[serde(rename = "cpe_id")]
+    pub cpe_id: String,
+    pub host_id: String,
+    pub mac: String,
+    pub ip: String,
+}
+
+/// 一段前台使用时间 (空闲、锁屏、睡眠期间不计)，跨零点时按日期拆分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppUsageSession {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub app_name: String,
+    #[serde(default)]
+    pub bundle_id: Option<String>,
+    pub start_time: String,
+    pub end_time: String,
+    pub duration_ms: i64,
+    /// 本地日期 (yyyy-MM-dd)
+    pub day: String,
+}
+
+/// 每日使用汇总，每天一条
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// 本地日期 (yyyy-MM-dd)
+    pub day: String,
+    pub active_secs: i64,
+    /// 各应用的使用时间、截图数与风险事件数 (`AppUsage` 数组的 JSON)
+    pub apps: String,
+    /// 当日各类风险事件数 (`RiskEventCounts` 的 JSON)
+    pub risk_events: String,
+    #[serde(rename = "cpe_id")]
+    pub cpe_id: String,
+    pub host_id: String,
+    pub mac: String,
+    pub ip: String,
+}
+
+/// 单个应用一天的使用情况
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppUsage {
+    pub app_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    pub active_secs: i64,
+    pub sessions: i64,
+    pub screenshots: i64,
+    /// 该应用的截图与剪贴板记录中 risk_level >= 1 的条数
+    pub risk_events: i64,
+}
+
+/// 一天内各表 risk_level >= 1 的记录数
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RiskEventCounts {
+    pub traffic: i64,
+    pub behavior: i64,
+    pub screenshot: i64,
+    pub clipboard: i64,
+    pub file: i64,
+    pub peripheral: i64,
+}
+
+/// 外设接入 / 拔出记录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeripheralLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub op_time: String,
+    /// attach / detach
+    pub action: String,
+    pub device_class: String,
+    #[serde(default)]
+    pub vendor_id: Option<String>,
+    #[serde(default)]
+    pub product_id: Option<String>,
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub volume_label: Option<String>,
+    #[serde(default)]
+    pub mount_point: Option<String>,
+    pub risk_level: i32,
+    /// 策略判定，如 "approved:0781:5567" / "unapproved:mass_storage"
+    #[serde(default)]
+    pub policy_decision: Option<String>,
+    #[serde(rename = "cpe_id")]
+    pub cpe_id: String,
+    pub host_id: String,
+    pub mac: String,
+    pub ip: String,
 }
 
 #[derive(Debug, Clone)]
@@ -86,6 +242,212 @@ pub struct DeviceInfo {
     pub ip: String,
 }
 
+impl DeviceInfo {
+    /// 序列号同时作为 pin 与 cpe_id 上报
+    pub fn from_identity(identity: &mac_monitor_common::identity::IdentitySnapshot) -> Self {
+        Self {
+            pin: identity.serial_number.clone(),
+            host_id: identity.host_name.clone(),
+            cpe_id: identity.serial_number.clone(),
+            mac: identity.mac.clone(),
+            ip: identity.ip.clone(),
+        }
+    }
+
+    /// 网络变化后只更新主机名 / MAC / IP；pin 与 cpe_id 可能已由注册写入，保持不变
+    pub fn refresh_network(&mut self, identity: &mac_monitor_common::identity::IdentitySnapshot) {
+        self.host_id = identity.host_name.clone();
+        self.mac = identity.mac.clone();
+        self.ip = identity.ip.clone();
+    }
+}
+
+/// 进行中的分块上传 (按文件 SHA-256 记录服务端确认的偏移，重启后续传)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub sha256: String,
+    pub upload_id: String,
+    pub file_path: String,
+    pub size: i64,
+    /// 服务端已确认的字节数
+    pub offset: i64,
+    pub chunk_size: i64,
+}
+
+/// 一轮同步中某台服务端在某个接口接收的记录数，上报后服务端按批次 id 去重
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadBatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// 同一轮同步的上传请求都带有该 id (`X-Upload-Batch`)
+    pub batch_id: String,
+    pub server_url: String,
+    pub endpoint: String,
+    pub records: i64,
+    pub accepted_at: String,
+}
+
+/// 各表尚未上传的记录数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingCounts {
+    pub audit: i64,
+    pub behavior: i64,
+    pub screenshot: i64,
+    pub clipboard: i64,
+    #[serde(default)]
+    pub file: i64,
+    #[serde(default)]
+    pub peripheral: i64,
+    #[serde(default)]
+    pub traffic_summary: i64,
+    #[serde(default)]
+    pub usage_summary: i64,
+}
+
+impl PendingCounts {
+    pub fn total(&self) -> i64 {
+        self.audit + self.behavior + self.screenshot + self.clipboard + self.file + self.peripheral + self.traffic_summary + self.usage_summary
+    }
+
+    /// (表名, 数量)，供指标导出使用
+    pub fn by_table(&self) -> [(&'static str, i64); 8] {
+        [
+            ("monitor_log_traffic", self.audit),
+            ("behavior_logs", self.behavior),
+            ("screenshot_logs", self.screenshot),
+            ("clipboard_logs", self.clipboard),
+            ("file_logs", self.file),
+            ("peripheral_logs", self.peripheral),
+            ("traffic_summaries", self.traffic_summary),
+            ("usage_summaries", self.usage_summary),
+        ]
+    }
+}
+
+/// 本地日志表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTable {
+    Audit,
+    Behavior,
+    Screenshot,
+    Clipboard,
+    File,
+    Peripheral,
+    TrafficSummary,
+    UsageSummary,
+}
+
+impl LogTable {
+    pub const ALL: [LogTable; 8] = [
+        LogTable::Audit,
+        LogTable::Behavior,
+        LogTable::Screenshot,
+        LogTable::Clipboard,
+        LogTable::File,
+        LogTable::Peripheral,
+        LogTable::TrafficSummary,
+        LogTable::UsageSummary,
+    ];
+
+    pub fn table_name(&self) -> &'static str {
+        match self {
+            LogTable::Audit => "monitor_log_traffic",
+            LogTable::Behavior => "behavior_logs",
+            LogTable::Screenshot => "screenshot_logs",
+            LogTable::Clipboard => "clipboard_logs",
+            LogTable::File => "file_logs",
+            LogTable::Peripheral => "peripheral_logs",
+            LogTable::TrafficSummary => "traffic_summaries",
+            LogTable::UsageSummary => "usage_summaries",
+        }
+    }
+
+    /// 用于按时间过滤 / 排序的列
+    pub fn time_column(&self) -> &'static str {
+        match self {
+            LogTable::Audit => "req_time",
+            LogTable::Behavior | LogTable::Clipboard | LogTable::File | LogTable::Peripheral => "op_time",
+            LogTable::Screenshot => "capture_time",
+            LogTable::TrafficSummary => "bucket_start",
+            LogTable::UsageSummary => "day",
+        }
+    }
+
+    pub fn from_table_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.table_name() == name)
+    }
+}
+
+impl std::str::FromStr for LogTable {
+    type Err = String;
+
+    /// 接受简称 (audit / behavior / screenshot / clipboard / file / peripheral / traffic_summary / usage_summary，audit 也可写作 traffic) 或表名
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "audit" | "traffic" => Ok(LogTable::Audit),
+            "behavior" => Ok(LogTable::Behavior),
+            "screenshot" => Ok(LogTable::Screenshot),
+            "clipboard" => Ok(LogTable::Clipboard),
+            "file" => Ok(LogTable::File),
+            "peripheral" => Ok(LogTable::Peripheral),
+            "traffic_summary" => Ok(LogTable::TrafficSummary),
+            "usage_summary" => Ok(LogTable::UsageSummary),
+            other => LogTable::from_table_name(other).ok_or_else(|| format!("Unknown log table: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LogTable::Audit => "audit",
+            LogTable::Behavior => "behavior",
+            LogTable::Screenshot => "screenshot",
+            LogTable::Clipboard => "clipboard",
+            LogTable::File => "file",
+            LogTable::Peripheral => "peripheral",
+            LogTable::TrafficSummary => "traffic_summary",
+            LogTable::UsageSummary => "usage_summary",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 本地日志查询条件 (IPC `query` 的载荷)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogQuery {
+    pub table: LogTable,
+    /// 时间下限 (含)，与记录时间按字符串比较，格式同记录 (如 `2024-05-01 08:00:00`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// 时间上限 (不含)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    /// 只返回尚未上传的记录
+    #[serde(default)]
+    pub unsent_only: bool,
+    /// 缺省 100，最大 10000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// 跳过的行数 (分页)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+impl LogQuery {
+    pub const DEFAULT_LIMIT: u32 = 100;
+    pub const MAX_LIMIT: u32 = 10_000;
+
+    pub fn new(table: LogTable) -> Self {
+        Self { table, since: None, until: None, unsent_only: false, limit: None, offset: None }
+    }
+
+    pub fn effective_limit(&self) -> u32 {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT).clamp(1, Self::MAX_LIMIT)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HeartbeatResponse {
     pub server_logic_clock: Option<u64>,
@@ -94,6 +456,9 @@ pub struct HeartbeatResponse {
     pub need_update: bool,            // Sync field
     #[serde(default)]
     pub commands: Vec<HeartbeatCommand>,        // Sync field
+    /// 服务端可解码的请求体编码 (如 ["zstd", "gzip"])，缺省表示不支持压缩
+    #[serde(default)]
+    pub content_encodings: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -115,14 +480,173 @@ pub struct UpdateInfo {
     pub has_update: bool,
     pub version: Option<String>,
     pub url: Option<String>,
+    /// 更新清单地址，缺省为 `{url}.manifest.json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_url: Option<String>,
+    /// 清单的分离签名地址，缺省为 `{manifest_url}.sig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_url: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PolicyConfig {
+    /// 服务端下发的策略版本 (可缺省，缺省时以 ETag 或内容摘要代替)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     #[serde(default)]
     pub process_blacklist: Vec<String>,
     #[serde(default)]
     pub app_blacklist: Vec<String>,
+    /// 工作时间段，为空表示全天采集
+    #[serde(default)]
+    pub working_hours: Vec<WorkingHours>,
+    /// 未命中 `app_capture_modes` 的应用使用的采集模式
+    #[serde(default)]
+    pub default_capture_mode: CaptureMode,
+    #[serde(default)]
+    pub app_capture_modes: Vec<AppCaptureRule>,
+    /// 同一应用两次非敏感截图的最小间隔 (秒)
+    #[serde(default)]
+    pub screenshot_interval_secs: Option<u32>,
+    #[serde(default = "default_true")]
+    pub clipboard_capture: bool,
+    /// 文件审计的路径 glob (`*` 不跨目录，`**` 跨任意层)，为空表示全部路径
+    #[serde(default)]
+    pub file_include_paths: Vec<String>,
+    /// 优先于 include 的排除 glob
+    #[serde(default)]
+    pub file_exclude_paths: Vec<String>,
+    /// 已批准的外设：`vvvv:pppp` 或 `vvvv:pppp:serial` (USB vendor / product id，可写 `*`)
+    #[serde(default)]
+    pub approved_devices: Vec<String>,
+    /// 未批准外设按类别定级，取第一条匹配的规则 (`*` 匹配任意类别)；缺省只标记移动存储
+    #[serde(default = "default_device_rules")]
+    pub unapproved_device_rules: Vec<DeviceRiskRule>,
+    /// 每日上传量上限，高风险记录不受限制
+    #[serde(default, skip_serializing_if = "UploadLimits::is_default")]
+    pub upload_limits: UploadLimits,
+    /// 网络流量入库规则，按顺序取第一条匹配的规则；无匹配时，存在 include 规则则丢弃，否则入库
+    #[serde(default = "default_traffic_rules")]
+    pub traffic_rules: Vec<TrafficRule>,
+    /// 流量聚合的时间桶 (秒)，缺省不聚合；带风险等级的请求始终保留完整记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traffic_aggregate_secs: Option<u32>,
+}
+
+/// 每日上传字节数上限 (按本地日期计算，缺省不限)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UploadLimits {
+    /// 日志等元数据
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_metadata_bytes: Option<u64>,
+    /// 截图等文件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_file_bytes: Option<u64>,
+}
+
+impl UploadLimits {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// 未批准外设的定级规则
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DeviceRiskRule {
+    /// mass_storage / hid / ... 或 `*`
+    pub device_class: String,
+    pub risk_level: i32,
+}
+
+/// 流量规则的动作
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficAction {
+    #[default]
+    Include,
+    Exclude,
+}
+
+/// 流量入库规则
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrafficRule {
+    /// `example.com` 匹配该域名及子域名，`*.example.com` 只匹配子域名，`*` 可出现在域名任意位置；
+    /// 可带路径 (`example.com/api/*`)，不含 `*` 的路径按前缀匹配
+    pub pattern: String,
+    #[serde(default)]
+    pub action: TrafficAction,
+    /// 命中 include 规则的记录至少标记为该风险等级
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_level: Option<i32>,
+}
+
+fn default_traffic_rules() -> Vec<TrafficRule> {
+    ["github.com", "google.com"]
+        .iter()
+        .map(|domain| TrafficRule { pattern: domain.to_string(), action: TrafficAction::Include, risk_level: None })
+        .collect()
+}
+
+fn default_device_rules() -> Vec<DeviceRiskRule> {
+    vec![DeviceRiskRule { device_class: "mass_storage".to_string(), risk_level: 2 }]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl PolicyConfig {
+    /// 首次启动且本地无缓存时使用的内置策略
+    pub fn builtin() -> Self {
+        Self {
+            version: None,
+            process_blacklist: vec!["clash".to_string(), "v2ray".to_string(), "clash-meta".to_string(), "proxyman".to_string()],
+            app_blacklist: vec!["clash".to_string(), "v2ray".to_string(), "proxyman".to_string()],
+            working_hours: Vec::new(),
+            default_capture_mode: CaptureMode::Always,
+            app_capture_modes: Vec::new(),
+            screenshot_interval_secs: None,
+            clipboard_capture: true,
+            file_include_paths: Vec::new(),
+            file_exclude_paths: ["**/.DS_Store", "**/*.swp", "**/.git/**", "**/node_modules/**"]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            approved_devices: Vec::new(),
+            unapproved_device_rules: default_device_rules(),
+            upload_limits: UploadLimits::default(),
+            traffic_rules: default_traffic_rules(),
+            traffic_aggregate_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    #[default]
+    Always,
+    OnSensitiveOnly,
+    Never,
+}
+
+/// 工作时间段，`start` 晚于 `end` 表示跨午夜 (如 22:00-06:00)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WorkingHours {
+    /// ISO 星期 (1 = 周一 ... 7 = 周日)，为空表示每天
+    #[serde(default)]
+    pub days: Vec<u8>,
+    /// "HH:MM"
+    pub start: String,
+    /// "HH:MM"
+    pub end: String,
+}
+
+/// 按应用名或 Bundle ID (不区分大小写) 指定采集模式
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AppCaptureRule {
+    pub app: String,
+    pub mode: CaptureMode,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,222 @@
+//! 外设 (U 盘、移动硬盘、HID 等) 接入 / 拔出审计
+//!
+//! 事件源实现 `PeripheralProducer`：Linux 使用内置的 udev 监听 (`udev::UdevProducer`)，
+//! macOS 由 Swift 宿主 (IOKit) 通过 FFI `log_peripheral_event` 提交，也可通过 IPC 同名命令提交。
+//! 未在策略 `approved_devices` 中的设备按 `unapproved_device_rules` 定级。
+
+#[cfg(target_os = "linux")]
+pub mod udev;
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use tokio::runtime::Handle;
+
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::Database;
+use crate::lifecycle::{ShutdownSignal, TaskTracker};
+use crate::models::{DeviceInfo, PeripheralLog, PolicyConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeripheralAction {
+    Attach,
+    Detach,
+}
+
+impl PeripheralAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PeripheralAction::Attach => "attach",
+            PeripheralAction::Detach => "detach",
+        }
+    }
+}
+
+/// 事件源提交的外设事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeripheralEvent {
+    pub action: PeripheralAction,
+    /// mass_storage / hid / audio / video / printer / wireless / usb 等
+    pub device_class: String,
+    /// USB vendor id，4 位十六进制 (如 "0781")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// 厂商 / 型号名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_point: Option<String>,
+    /// 事件时间 (逻辑时钟毫秒)，缺省为提交时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<i64>,
+}
+
+impl PeripheralEvent {
+    pub fn new(action: PeripheralAction, device_class: impl Into<String>) -> Self {
+        Self {
+            action,
+            device_class: device_class.into(),
+            vendor_id: None,
+            product_id: None,
+            serial: None,
+            name: None,
+            volume_label: None,
+            mount_point: None,
+            time_ms: None,
+        }
+    }
+
+    /// `vvvv:pppp[:serial]`，缺少 vendor / product id 时为 None
+    pub fn device_id(&self) -> Option<String> {
+        let (vendor, product) = (self.vendor_id.as_deref()?, self.product_id.as_deref()?);
+        let mut id = format!("{}:{}", vendor.to_lowercase(), product.to_lowercase());
+        if let Some(serial) = self.serial.as_deref().filter(|s| !s.is_empty()) {
+            id.push(':');
+            id.push_str(serial);
+        }
+        Some(id)
+    }
+}
+
+/// 外设事件源，收到停止信号后退出
+pub trait PeripheralProducer: Send {
+    fn name(&self) -> &'static str;
+
+    /// 启动后台监听，事件交给 `monitor.record`
+    fn start(self: Box<Self>, monitor: Arc<PeripheralMonitor>, shutdown: ShutdownSignal) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PeripheralConfig {
+    /// Linux 上监听 udev 事件；macOS 由 Swift 宿主提交，忽略此项
+    #[serde(default)]
+    pub watch_udev: bool,
+}
+
+impl PeripheralConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// 策略判定：(风险等级, 判定说明)
+pub fn evaluate(policy: &PolicyConfig, event: &PeripheralEvent) -> (i32, String) {
+    if let Some(id) = event.device_id() {
+        if let Some(pattern) = policy.approved_devices.iter().find(|p| device_id_matches(p, &id)) {
+            return (0, format!("approved:{}", pattern.trim()));
+        }
+    }
+    match policy
+        .unapproved_device_rules
+        .iter()
+        .find(|rule| rule.device_class == "*" || rule.device_class.eq_ignore_ascii_case(&event.device_class))
+    {
+        Some(rule) => (rule.risk_level, format!("unapproved:{}", rule.device_class)),
+        None => (0, "unapproved".to_string()),
+    }
+}
+
+/// 模式为 `vvvv:pppp` (任意序列号) 或 `vvvv:pppp:serial`，各段可写 `*`；id 部分不区分大小写
+fn device_id_matches(pattern: &str, id: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim().splitn(3, ':').collect();
+    let id: Vec<&str> = id.splitn(3, ':').collect();
+    if pattern.len() < 2 {
+        return false;
+    }
+    let segment = |i: usize, case_sensitive: bool| match (pattern.get(i), id.get(i)) {
+        (None, _) | (Some(&"*"), _) => true,
+        (Some(p), Some(v)) if case_sensitive => p == v,
+        (Some(p), Some(v)) => p.eq_ignore_ascii_case(v),
+        (Some(_), None) => false,
+    };
+    segment(0, false) && segment(1, false) && segment(2, true)
+}
+
+/// 校验策略中的设备 id 模式 (策略下发时调用)
+pub fn validate_device_pattern(pattern: &str) -> Result<(), String> {
+    let parts: Vec<&str> = pattern.trim().splitn(3, ':').collect();
+    if parts.len() < 2 {
+        return Err(format!("'{}' is not vvvv:pppp[:serial]", pattern));
+    }
+    for part in &parts[..2] {
+        if *part != "*" && !(part.len() == 4 && part.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err(format!("'{}' has an invalid vendor/product id '{}'", pattern, part));
+        }
+    }
+    Ok(())
+}
+
+/// 外设事件定级与落库
+pub struct PeripheralMonitor {
+    db: Arc<Database>,
+    policy: Arc<RwLock<PolicyConfig>>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+    tasks: Arc<TaskTracker>,
+    runtime_handle: Handle,
+}
+
+impl PeripheralMonitor {
+    pub fn new(
+        db: Arc<Database>,
+        policy: Arc<RwLock<PolicyConfig>>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+        tasks: Arc<TaskTracker>,
+        runtime_handle: Handle,
+    ) -> Arc<Self> {
+        Arc::new(Self { db, policy, device_info, clock, tasks, runtime_handle })
+    }
+
+    /// 异步写入 (事件源线程 / FFI / IPC 调用)；服务退出中返回 false
+    pub fn record(self: &Arc<Self>, event: PeripheralEvent) -> bool {
+        let monitor = self.clone();
+        self.tasks.spawn(&self.runtime_handle, async move {
+            if let Err(e) = monitor.save(event).await {
+                log::error!("Failed to save peripheral log: {}", e);
+            }
+        })
+    }
+
+    pub async fn save(&self, event: PeripheralEvent) -> Result<PeripheralLog, String> {
+        let (risk_level, decision) = evaluate(&self.policy.read().unwrap(), &event);
+        let device = self.device_info.read().unwrap().clone();
+        let log = PeripheralLog {
+            id: None,
+            op_time: format_ms(event.time_ms.unwrap_or_else(|| self.clock.now_ms())),
+            action: event.action.as_str().to_string(),
+            device_class: event.device_class,
+            vendor_id: event.vendor_id,
+            product_id: event.product_id,
+            serial: event.serial,
+            name: event.name,
+            volume_label: event.volume_label,
+            mount_point: event.mount_point,
+            risk_level,
+            policy_decision: Some(decision),
+            cpe_id: device.cpe_id,
+            host_id: device.host_id,
+            mac: device.mac,
+            ip: device.ip,
+        };
+        self.db.save_peripheral_log(&log).await.map_err(|e| e.to_string())?;
+        if risk_level > 0 {
+            log::warn!(
+                "🔌 Unapproved {} {} ({}): {}",
+                log.device_class,
+                log.action,
+                log.name.as_deref().unwrap_or("unknown device"),
+                log.policy_decision.as_deref().unwrap_or_default()
+            );
+        } else {
+            log::info!("🔌 {} {}", log.device_class, log.action);
+        }
+        Ok(log)
+    }
+}
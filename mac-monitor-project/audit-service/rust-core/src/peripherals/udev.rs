@@ -0,0 +1,247 @@
+//! Linux 外设事件源：监听 udev (NETLINK_KOBJECT_UEVENT) 的 USB 设备与 USB 块设备事件
+//!
+//! systemd-udevd 在运行时订阅其广播 (含 ID_VENDOR_ID / ID_FS_LABEL 等属性)，
+//! 否则 (容器等) 订阅内核广播，缺少的属性从 sysfs 读取。
+//! 卷通常在接入之后才被挂载，接入事件中的挂载点只在当时已挂载时才有。
+
+use std::collections::HashMap;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{PeripheralAction, PeripheralEvent, PeripheralMonitor, PeripheralProducer};
+use crate::lifecycle::ShutdownSignal;
+
+const KERNEL_GROUP: u32 = 1;
+const UDEV_GROUP: u32 = 2;
+const UDEV_MAGIC_PREFIX: &[u8] = b"libudev\0";
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct UdevProducer {
+    sysfs_root: PathBuf,
+    mounts_path: PathBuf,
+}
+
+impl Default for UdevProducer {
+    fn default() -> Self {
+        Self { sysfs_root: PathBuf::from("/sys"), mounts_path: PathBuf::from("/proc/self/mounts") }
+    }
+}
+
+impl PeripheralProducer for UdevProducer {
+    fn name(&self) -> &'static str {
+        "udev"
+    }
+
+    fn start(self: Box<Self>, monitor: Arc<PeripheralMonitor>, shutdown: ShutdownSignal) -> Result<(), String> {
+        let group = if Path::new("/run/udev/control").exists() { UDEV_GROUP } else { KERNEL_GROUP };
+        let socket = open_socket(group)?;
+        log::info!("Peripheral monitor listening for {} uevents", if group == UDEV_GROUP { "udev" } else { "kernel" });
+
+        std::thread::Builder::new()
+            .name("peripheral-udev".to_string())
+            .spawn(move || {
+                let mut buf = vec![0u8; 16 * 1024];
+                while !shutdown.is_triggered() {
+                    let n = unsafe { libc::recv(socket.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+                    if n < 0 {
+                        let err = std::io::Error::last_os_error();
+                        if err.kind() != std::io::ErrorKind::WouldBlock {
+                            log::error!("uevent socket read failed, peripheral monitor stopped: {}", err);
+                            return;
+                        }
+                        std::thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    let Some(props) = parse_message(&buf[..n as usize]) else { continue };
+                    let mounts = std::fs::read_to_string(&self.mounts_path).unwrap_or_default();
+                    if let Some(event) = event_from_properties(&props, &self.sysfs_root, &mounts) {
+                        if !monitor.record(event) {
+                            break;
+                        }
+                    }
+                }
+                log::info!("Peripheral udev monitor stopped");
+            })
+            .map_err(|e| format!("Failed to start udev thread: {}", e))?;
+        Ok(())
+    }
+}
+
+fn open_socket(group: u32) -> Result<OwnedFd, String> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(format!("Failed to open uevent socket: {}", std::io::Error::last_os_error()));
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = group;
+    let rc = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            (&addr as *const libc::sockaddr_nl).cast(),
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(format!("Failed to bind uevent socket: {}", std::io::Error::last_os_error()));
+    }
+    Ok(socket)
+}
+
+/// 解析一条 uevent 广播 (udev 带 `libudev` 头，内核以 `action@devpath` 开头) 为属性表
+pub fn parse_message(buf: &[u8]) -> Option<HashMap<String, String>> {
+    let properties = if buf.starts_with(UDEV_MAGIC_PREFIX) {
+        // prefix[8], magic, header_size, properties_off, properties_len (本机字节序)
+        let word = |i: usize| buf.get(8 + i * 4..12 + i * 4).map(|b| u32::from_ne_bytes(b.try_into().unwrap()) as usize);
+        let (offset, len) = (word(2)?, word(3)?);
+        buf.get(offset..offset.checked_add(len)?)?
+    } else {
+        let header_end = buf.iter().position(|b| *b == 0)?;
+        if !buf[..header_end].contains(&b'@') {
+            return None;
+        }
+        &buf[header_end + 1..]
+    };
+
+    let props: HashMap<String, String> = properties
+        .split(|b| *b == 0)
+        .filter_map(|entry| {
+            let entry = std::str::from_utf8(entry).ok()?;
+            let (key, value) = entry.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect();
+    props.contains_key("ACTION").then_some(props)
+}
+
+/// 只处理 USB 设备与 USB 块设备的接入 / 拔出，其余事件返回 None
+pub fn event_from_properties(props: &HashMap<String, String>, sysfs_root: &Path, mounts: &str) -> Option<PeripheralEvent> {
+    let action = match props.get("ACTION")?.as_str() {
+        "add" => PeripheralAction::Attach,
+        "remove" => PeripheralAction::Detach,
+        _ => return None,
+    };
+    let prop = |key: &str| props.get(key).filter(|v| !v.is_empty()).cloned();
+    let devpath = props.get("DEVPATH")?;
+    let sysfs_dir = sysfs_root.join(devpath.trim_start_matches('/'));
+
+    let mut event = match (props.get("SUBSYSTEM")?.as_str(), props.get("DEVTYPE").map(String::as_str)) {
+        ("usb", Some("usb_device")) => {
+            // TYPE=bDeviceClass/bDeviceSubClass/bDeviceProtocol (十进制)
+            let class_code = props.get("TYPE").and_then(|t| t.split('/').next()).and_then(|c| c.parse::<u8>().ok());
+            if class_code == Some(9) {
+                return None; // hub
+            }
+            let mut event = PeripheralEvent::new(action, usb_class_name(class_code.unwrap_or(0)));
+            // PRODUCT=vendor/product/bcdDevice (十六进制，无前导 0)
+            let mut product = props.get("PRODUCT").map(|p| p.split('/')).into_iter().flatten();
+            let (vendor, model) = (product.next(), product.next());
+            event.vendor_id = prop("ID_VENDOR_ID").or_else(|| vendor.and_then(pad_hex_id));
+            event.product_id = prop("ID_MODEL_ID").or_else(|| model.and_then(pad_hex_id));
+            event.serial = prop("ID_SERIAL_SHORT").or_else(|| sysfs_attr(&sysfs_dir, "serial"));
+            event.name = device_name(props, &sysfs_dir);
+            event
+        }
+        ("block", Some("disk" | "partition")) => {
+            let on_usb = props.get("ID_BUS").map(|b| b == "usb").unwrap_or_else(|| devpath.contains("/usb"));
+            if !on_usb {
+                return None;
+            }
+            let mut event = PeripheralEvent::new(action, "mass_storage");
+            let usb_dir = usb_device_ancestor(&sysfs_dir);
+            event.vendor_id = prop("ID_VENDOR_ID").or_else(|| usb_dir.as_deref().and_then(|d| sysfs_attr(d, "idVendor")));
+            event.product_id = prop("ID_MODEL_ID").or_else(|| usb_dir.as_deref().and_then(|d| sysfs_attr(d, "idProduct")));
+            event.serial = prop("ID_SERIAL_SHORT").or_else(|| usb_dir.as_deref().and_then(|d| sysfs_attr(d, "serial")));
+            event.name = device_name(props, usb_dir.as_deref().unwrap_or(&sysfs_dir));
+            event.volume_label = prop("ID_FS_LABEL");
+            if let Some(devname) = props.get("DEVNAME") {
+                let devnode = if devname.starts_with('/') { devname.clone() } else { format!("/dev/{}", devname) };
+                event.mount_point = mount_point(mounts, &devnode);
+            }
+            event
+        }
+        _ => return None,
+    };
+    event.vendor_id = event.vendor_id.map(|v| v.to_lowercase());
+    event.product_id = event.product_id.map(|p| p.to_lowercase());
+    Some(event)
+}
+
+fn usb_class_name(code: u8) -> &'static str {
+    match code {
+        0x01 => "audio",
+        0x02 | 0x0a => "communication",
+        0x03 => "hid",
+        0x06 => "imaging",
+        0x07 => "printer",
+        0x08 => "mass_storage",
+        0x0e => "video",
+        0xe0 => "wireless",
+        // 0 表示按接口区分类别
+        _ => "usb",
+    }
+}
+
+fn pad_hex_id(id: &str) -> Option<String> {
+    u16::from_str_radix(id, 16).ok().map(|v| format!("{:04x}", v))
+}
+
+fn sysfs_attr(dir: &Path, name: &str) -> Option<String> {
+    let value = std::fs::read_to_string(dir.join(name)).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// 块设备所属的 USB 设备目录 (向上查找含 idVendor 的目录)
+fn usb_device_ancestor(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find(|d| d.join("idVendor").is_file()).map(Path::to_path_buf)
+}
+
+fn device_name(props: &HashMap<String, String>, sysfs_dir: &Path) -> Option<String> {
+    let lookup = |db: &str, raw: &str, attr: &str| {
+        props.get(db).or_else(|| props.get(raw)).filter(|v| !v.is_empty()).cloned().or_else(|| sysfs_attr(sysfs_dir, attr))
+    };
+    let vendor = lookup("ID_VENDOR_FROM_DATABASE", "ID_VENDOR", "manufacturer");
+    let model = lookup("ID_MODEL_FROM_DATABASE", "ID_MODEL", "product");
+    match (vendor, model) {
+        (Some(v), Some(m)) => Some(format!("{} {}", v, m).replace('_', " ")),
+        (v, m) => v.or(m).map(|s| s.replace('_', " ")),
+    }
+}
+
+/// 在 /proc/self/mounts 内容中查找设备的挂载点 (空格等以八进制转义)
+fn mount_point(mounts: &str, devnode: &str) -> Option<String> {
+    mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        (fields.next()? == devnode).then(|| fields.next().map(unescape_mount))?
+    })
+}
+
+fn unescape_mount(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let code = std::str::from_utf8(&bytes[i + 1..i + 4]).ok().and_then(|s| u8::from_str_radix(s, 8).ok());
+            if let Some(code) = code {
+                out.push(code);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
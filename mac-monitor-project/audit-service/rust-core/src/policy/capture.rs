@@ -0,0 +1,250 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::models::{CaptureMode, PolicyConfig, WorkingHours};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureAction {
+    /// 完整记录
+    Capture,
+    /// 仅记录元数据 (不保存图片 / 剪贴板内容)
+    Downgrade,
+    /// 丢弃
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureDecision {
+    pub action: CaptureAction,
+    pub reason: &'static str,
+}
+
+impl CaptureDecision {
+    fn capture() -> Self {
+        Self { action: CaptureAction::Capture, reason: "" }
+    }
+
+    /// 策略排除的事件：敏感事件降级保留元数据，其余直接丢弃
+    fn excluded(reason: &'static str, is_sensitive: bool) -> Self {
+        let action = if is_sensitive { CaptureAction::Downgrade } else { CaptureAction::Drop };
+        Self { action, reason }
+    }
+
+    /// 写入日志记录的判定标签
+    pub fn label(&self) -> String {
+        match self.action {
+            CaptureAction::Capture => "capture".to_string(),
+            CaptureAction::Downgrade => format!("downgrade:{}", self.reason),
+            CaptureAction::Drop => format!("drop:{}", self.reason),
+        }
+    }
+}
+
+/// 按策略判定截图 / 剪贴板事件是否采集
+pub struct CaptureEvaluator {
+    policy: Arc<RwLock<PolicyConfig>>,
+    // 每个应用最近一次被完整采集的截图时间 (逻辑时钟毫秒)
+    last_screenshot: Mutex<HashMap<String, i64>>,
+}
+
+impl CaptureEvaluator {
+    pub fn new(policy: Arc<RwLock<PolicyConfig>>) -> Self {
+        Self {
+            policy,
+            last_screenshot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn screenshot_interval_secs(&self) -> Option<u32> {
+        self.policy.read().unwrap().screenshot_interval_secs
+    }
+
+    pub fn screenshot(&self, app_name: &str, is_sensitive: bool, now_ms: i64) -> CaptureDecision {
+        let (base, interval_secs) = {
+            let policy = self.policy.read().unwrap();
+            (evaluate(&policy, &[app_name], is_sensitive, now_ms), policy.screenshot_interval_secs)
+        };
+        if base.action != CaptureAction::Capture {
+            return base;
+        }
+
+        // 敏感截图不受采集间隔限制
+        let mut last = self.last_screenshot.lock().unwrap();
+        let key = app_name.to_lowercase();
+        if let (Some(interval), false) = (interval_secs, is_sensitive) {
+            if let Some(prev) = last.get(&key) {
+                if now_ms - prev < interval as i64 * 1000 {
+                    return CaptureDecision { action: CaptureAction::Drop, reason: "interval" };
+                }
+            }
+        }
+        last.insert(key, now_ms);
+        base
+    }
+
+    pub fn clipboard(&self, app_name: &str, bundle_id: &str, risk_level: i32, now_ms: i64) -> CaptureDecision {
+        let policy = self.policy.read().unwrap();
+        let is_sensitive = risk_level > 0;
+        if !policy.clipboard_capture {
+            return CaptureDecision::excluded("clipboard_disabled", is_sensitive);
+        }
+        evaluate(&policy, &[app_name, bundle_id], is_sensitive, now_ms)
+    }
+}
+
+fn evaluate(policy: &PolicyConfig, app_keys: &[&str], is_sensitive: bool, now_ms: i64) -> CaptureDecision {
+    let now = Local
+        .timestamp_millis_opt(now_ms)
+        .single()
+        .unwrap_or_else(Local::now);
+    if !policy.working_hours.is_empty() && !policy.working_hours.iter().any(|w| in_window(w, &now)) {
+        return CaptureDecision::excluded("outside_working_hours", is_sensitive);
+    }
+
+    match capture_mode(policy, app_keys) {
+        CaptureMode::Always => CaptureDecision::capture(),
+        CaptureMode::OnSensitiveOnly if is_sensitive => CaptureDecision::capture(),
+        CaptureMode::OnSensitiveOnly => CaptureDecision::excluded("app_sensitive_only", false),
+        CaptureMode::Never => CaptureDecision::excluded("app_never", is_sensitive),
+    }
+}
+
+fn capture_mode(policy: &PolicyConfig, app_keys: &[&str]) -> CaptureMode {
+    policy
+        .app_capture_modes
+        .iter()
+        .find(|rule| app_keys.iter().any(|key| key.eq_ignore_ascii_case(rule.app.trim())))
+        .map(|rule| rule.mode)
+        .unwrap_or(policy.default_capture_mode)
+}
+
+fn in_window(window: &WorkingHours, now: &DateTime<Local>) -> bool {
+    let (Some(start), Some(end)) = (parse_hhmm(&window.start), parse_hhmm(&window.end)) else {
+        return false;
+    };
+    let time = now.time();
+    let weekday = now.weekday().number_from_monday() as u8;
+
+    if start <= end {
+        day_matches(window, weekday) && time >= start && time < end
+    } else {
+        // 跨午夜：凌晨部分属于前一天的时间段
+        let yesterday = if weekday == 1 { 7 } else { weekday - 1 };
+        (day_matches(window, weekday) && time >= start) || (day_matches(window, yesterday) && time < end)
+    }
+}
+
+fn day_matches(window: &WorkingHours, weekday: u8) -> bool {
+    window.days.is_empty() || window.days.contains(&weekday)
+}
+
+pub(crate) fn parse_hhmm(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(value: serde_json::Value) -> Arc<RwLock<PolicyConfig>> {
+        Arc::new(RwLock::new(serde_json::from_value(value).unwrap()))
+    }
+
+    /// 本地时间 2026-01-05 (周一) 起第 `day` 天的 hh:mm
+    fn at(day: u32, hour: u32, minute: u32) -> i64 {
+        Local.with_ymd_and_hms(2026, 1, 5 + day, hour, minute, 0).single().unwrap().timestamp_millis()
+    }
+
+    fn action(decision: CaptureDecision) -> CaptureAction {
+        decision.action
+    }
+
+    #[test]
+    fn working_hours_limit_capture_by_day_and_time() {
+        let evaluator = CaptureEvaluator::new(policy(serde_json::json!({
+            "working_hours": [{ "days": [1, 2, 3, 4, 5], "start": "09:00", "end": "18:00" }]
+        })));
+        assert_eq!(action(evaluator.screenshot("Safari", false, at(0, 9, 0))), CaptureAction::Capture);
+        assert_eq!(action(evaluator.screenshot("Mail", false, at(0, 17, 59))), CaptureAction::Capture);
+        let late = evaluator.screenshot("Notes", false, at(0, 18, 0));
+        assert_eq!(late.label(), "drop:outside_working_hours");
+        // 周六不在工作日内；敏感截图降级保留元数据
+        assert_eq!(action(evaluator.screenshot("Xcode", false, at(5, 10, 0))), CaptureAction::Drop);
+        assert_eq!(evaluator.screenshot("Xcode", true, at(5, 10, 0)).label(), "downgrade:outside_working_hours");
+    }
+
+    #[test]
+    fn working_hours_can_cross_midnight() {
+        // 周五夜班 22:00-06:00：凌晨部分属于周五的时间段
+        let window = WorkingHours { days: vec![5], start: "22:00".to_string(), end: "06:00".to_string() };
+        let local = |ms: i64| Local.timestamp_millis_opt(ms).single().unwrap();
+        assert!(in_window(&window, &local(at(4, 22, 0))));
+        assert!(in_window(&window, &local(at(5, 5, 59))));
+        assert!(!in_window(&window, &local(at(5, 6, 0))));
+        assert!(!in_window(&window, &local(at(4, 21, 59))));
+        // 周四夜里与周六夜里不在时间段内
+        assert!(!in_window(&window, &local(at(4, 3, 0))));
+        assert!(!in_window(&window, &local(at(5, 23, 0))));
+
+        // 周日夜班延续到周一凌晨，周二凌晨不算
+        let sunday = WorkingHours { days: vec![7], start: "23:00".to_string(), end: "01:00".to_string() };
+        assert!(in_window(&sunday, &local(at(0, 0, 30))));
+        assert!(!in_window(&sunday, &local(at(1, 0, 30))));
+
+        let invalid = WorkingHours { days: vec![], start: "25:00".to_string(), end: "06:00".to_string() };
+        assert!(!in_window(&invalid, &local(at(0, 1, 0))));
+    }
+
+    #[test]
+    fn capture_modes_apply_per_app() {
+        let evaluator = CaptureEvaluator::new(policy(serde_json::json!({
+            "default_capture_mode": "on_sensitive_only",
+            "app_capture_modes": [
+                { "app": "Safari", "mode": "always" },
+                { "app": " com.apple.Terminal ", "mode": "never" }
+            ]
+        })));
+        let now = at(0, 10, 0);
+        // 应用名不区分大小写
+        assert_eq!(action(evaluator.screenshot("safari", false, now)), CaptureAction::Capture);
+        // never：普通截图丢弃，敏感截图降级
+        assert_eq!(evaluator.clipboard("Terminal", "com.apple.terminal", 0, now).label(), "drop:app_never");
+        assert_eq!(evaluator.clipboard("Terminal", "com.apple.Terminal", 1, now).label(), "downgrade:app_never");
+        // 未配置的应用按缺省模式：只采集敏感事件
+        assert_eq!(evaluator.screenshot("Notes", false, now).label(), "drop:app_sensitive_only");
+        assert_eq!(action(evaluator.screenshot("Notes", true, now)), CaptureAction::Capture);
+    }
+
+    #[test]
+    fn screenshot_interval_is_tracked_per_app() {
+        let evaluator = CaptureEvaluator::new(policy(serde_json::json!({ "screenshot_interval_secs": 60 })));
+        let start = at(0, 10, 0);
+        assert_eq!(evaluator.screenshot_interval_secs(), Some(60));
+        assert_eq!(action(evaluator.screenshot("Safari", false, start)), CaptureAction::Capture);
+        assert_eq!(evaluator.screenshot("SAFARI", false, start + 59_999).label(), "drop:interval");
+        // 其他应用与敏感截图不受限制
+        assert_eq!(action(evaluator.screenshot("Mail", false, start + 1_000)), CaptureAction::Capture);
+        assert_eq!(action(evaluator.screenshot("Safari", true, start + 30_000)), CaptureAction::Capture);
+        // 间隔从最近一次完整采集 (含敏感截图) 算起
+        assert_eq!(action(evaluator.screenshot("Safari", false, start + 60_000)), CaptureAction::Drop);
+        assert_eq!(action(evaluator.screenshot("Safari", false, start + 90_000)), CaptureAction::Capture);
+    }
+
+    #[test]
+    fn clipboard_rules_follow_the_policy() {
+        let disabled = CaptureEvaluator::new(policy(serde_json::json!({ "clipboard_capture": false })));
+        let now = at(0, 10, 0);
+        assert_eq!(disabled.clipboard("Notes", "com.apple.Notes", 0, now).label(), "drop:clipboard_disabled");
+        assert_eq!(disabled.clipboard("Notes", "com.apple.Notes", 2, now).label(), "downgrade:clipboard_disabled");
+
+        // 按 Bundle ID 也能匹配规则；剪贴板事件不受截图间隔限制
+        let evaluator = CaptureEvaluator::new(policy(serde_json::json!({
+            "screenshot_interval_secs": 60,
+            "app_capture_modes": [{ "app": "com.tinyspeck.slackmacgap", "mode": "never" }]
+        })));
+        assert_eq!(evaluator.clipboard("Slack", "com.tinyspeck.slackmacgap", 0, now).label(), "drop:app_never");
+        assert_eq!(action(evaluator.clipboard("Notes", "com.apple.Notes", 0, now)), CaptureAction::Capture);
+        assert_eq!(action(evaluator.clipboard("Notes", "com.apple.Notes", 0, now + 1)), CaptureAction::Capture);
+    }
+}
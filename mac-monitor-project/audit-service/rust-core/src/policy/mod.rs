@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::clock::LogicalClock;
+use crate::db::Database;
+use crate::models::{BehaviorLog, DeviceInfo, PolicyConfig};
+use crate::uploader::{PolicyFetch, Uploader};
+
+pub mod capture;
+
+const MAX_LIST_ENTRIES: usize = 1024;
+const MAX_ENTRY_LEN: usize = 256;
+
+/// 已生效的策略及其版本信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPolicy {
+    pub version: String,
+    #[serde(default)]
+    pub etag: Option<String>,
+    pub applied_at: String,
+    pub policy: PolicyConfig,
+}
+
+/// 磁盘缓存格式：当前版本 + 上一版本 (用于回滚)
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct PolicyCache {
+    current: Option<CachedPolicy>,
+    previous: Option<CachedPolicy>,
+    /// 被回滚掉的版本，服务端换成其他版本之前不再应用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rolled_back: Option<String>,
+}
+
+/// `apply` 在缓存锁内的判定结果
+enum ApplyOutcome {
+    Unchanged,
+    Rejected(Option<String>, String),
+    Applied(Option<String>),
+}
+
+/// 管理策略的持久化、版本切换与回滚
+///
+/// 扫描器等组件通过 `shared()` 读取当前生效的策略；
+/// 每一次切换 (下发 / 回滚 / 校验失败) 都作为行为日志记录，随日志同步上报。
+pub struct PolicyStore {
+    cache_path: PathBuf,
+    cache: Mutex<PolicyCache>,
+    active: Arc<RwLock<PolicyConfig>>,
+    db: Arc<Database>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+}
+
+impl PolicyStore {
+    /// 加载本地缓存的策略；缓存缺失或损坏时回退到内置策略
+    pub fn load(
+        cache_path: impl Into<PathBuf>,
+        db: Arc<Database>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+    ) -> Self {
+        let cache_path = cache_path.into();
+        let cache = match std::fs::read_to_string(&cache_path) {
+            Ok(content) => match serde_json::from_str::<PolicyCache>(&content) {
+                Ok(cache) => cache,
+                Err(e) => {
+                    log::error!("Policy cache {:?} is corrupt, ignoring: {}", cache_path, e);
+                    PolicyCache::default()
+                }
+            },
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::error!("Failed to read policy cache {:?}: {}", cache_path, e);
+                }
+                PolicyCache::default()
+            }
+        };
+
+        let active = match &cache.current {
+            Some(current) => {
+                log::info!("Loaded cached policy version {} (applied at {})", current.version, current.applied_at);
+                current.policy.clone()
+            }
+            None => {
+                log::info!("No cached policy, starting with built-in defaults");
+                PolicyConfig::builtin()
+            }
+        };
+
+        Self {
+            cache_path,
+            cache: Mutex::new(cache),
+            active: Arc::new(RwLock::new(active)),
+            db,
+            device_info,
+            clock,
+        }
+    }
+
+    /// 当前生效策略的共享句柄
+    pub fn shared(&self) -> Arc<RwLock<PolicyConfig>> {
+        self.active.clone()
+    }
+
+    pub fn current_version(&self) -> Option<String> {
+        self.cache.lock().unwrap().current.as_ref().map(|c| c.version.clone())
+    }
+
+    pub fn current(&self) -> Option<CachedPolicy> {
+        self.cache.lock().unwrap().current.clone()
+    }
+
+    pub fn previous(&self) -> Option<CachedPolicy> {
+        self.cache.lock().unwrap().previous.clone()
+    }
+
+    /// 条件拉取并应用最新策略，返回策略是否发生变化
+    pub async fn refresh(&self, uploader: &Uploader) -> Result<bool, String> {
+        let etag = self.cache.lock().unwrap().current.as_ref().and_then(|c| c.etag.clone());
+
+        match uploader.get_config(etag.as_deref()).await? {
+            PolicyFetch::NotModified => {
+                log::info!("Policy not modified (etag {:?})", etag);
+                Ok(false)
+            }
+            PolicyFetch::Updated { policy, etag } => self.apply(*policy, etag).await,
+        }
+    }
+
+    /// 校验并应用新策略，旧版本保留用于回滚；返回策略是否发生变化
+    ///
+    /// 版本比较与切换在同一把锁内完成；缓存写入磁盘成功后才切换版本。
+    pub async fn apply(&self, policy: PolicyConfig, etag: Option<String>) -> Result<bool, String> {
+        let version = policy
+            .version
+            .clone()
+            .or_else(|| etag.clone())
+            .unwrap_or_else(|| content_digest(&policy));
+        let validation = validate(&policy);
+
+        let outcome = {
+            let mut cache = self.cache.lock().unwrap();
+            let from_version = cache.current.as_ref().map(|c| c.version.clone());
+            if from_version.as_deref() == Some(version.as_str()) {
+                ApplyOutcome::Unchanged
+            } else if cache.rolled_back.as_deref() == Some(version.as_str()) {
+                log::info!("Policy {} was rolled back, waiting for a different version", version);
+                ApplyOutcome::Unchanged
+            } else if let Err(reason) = validation {
+                ApplyOutcome::Rejected(from_version, reason)
+            } else {
+                let mut next = cache.clone();
+                let previous = next.current.replace(CachedPolicy {
+                    version: version.clone(),
+                    etag,
+                    applied_at: self.clock.now_str(),
+                    policy: policy.clone(),
+                });
+                if previous.is_some() {
+                    next.previous = previous;
+                }
+                next.rolled_back = None;
+                persist(&self.cache_path, &next)
+                    .map_err(|e| format!("Failed to persist policy cache {:?}: {}", self.cache_path, e))?;
+                *cache = next;
+                *self.active.write().unwrap() = policy.clone();
+                ApplyOutcome::Applied(from_version)
+            }
+        };
+
+        let from_version = match outcome {
+            ApplyOutcome::Unchanged => return Ok(false),
+            ApplyOutcome::Rejected(from_version, reason) => {
+                self.record_transition("reject", from_version.as_deref(), &version, 1, &reason).await;
+                return Err(format!("Policy {} rejected: {}", version, reason));
+            }
+            ApplyOutcome::Applied(from_version) => from_version,
+        };
+
+        let detail = format!(
+            "{} processes, {} apps blacklisted",
+            policy.process_blacklist.len(),
+            policy.app_blacklist.len()
+        );
+        self.record_transition("apply", from_version.as_deref(), &version, 0, &detail).await;
+        Ok(true)
+    }
+
+    /// 回滚到上一版本策略，返回回滚后的版本号
+    ///
+    /// 被回滚的版本记入缓存，之后服务端仍下发该版本时不再应用。
+    pub async fn rollback(&self) -> Result<String, String> {
+        let (from_version, restored) = {
+            let mut cache = self.cache.lock().unwrap();
+            let mut next = cache.clone();
+            let previous = next.previous.take().ok_or_else(|| "No previous policy to roll back to".to_string())?;
+            let current = next.current.replace(previous.clone());
+            next.previous = current.clone();
+            next.rolled_back = current.as_ref().map(|c| c.version.clone());
+            persist(&self.cache_path, &next)
+                .map_err(|e| format!("Failed to persist policy cache {:?}: {}", self.cache_path, e))?;
+            *cache = next;
+            *self.active.write().unwrap() = previous.policy.clone();
+            (current.map(|c| c.version), previous)
+        };
+
+        self.record_transition("rollback", from_version.as_deref(), &restored.version, 1, "Rolled back to previous policy").await;
+        Ok(restored.version)
+    }
+
+    async fn record_transition(&self, action: &str, from: Option<&str>, to: &str, risk_level: i32, reason: &str) {
+        log::info!("Policy transition [{}]: {} -> {} ({})", action, from.unwrap_or("builtin"), to, reason);
+
+        let detail = serde_json::json!({
+            "action": action,
+            "from_version": from,
+            "to_version": to,
+            "reason": reason,
+        });
+        let device = self.device_info.read().unwrap().clone();
+        let log = BehaviorLog {
+            id: None,
+            proc: "audit-service".to_string(),
+            op_time: self.clock.now_str(),
+            cpe_id: device.cpe_id,
+            op_type: "PolicyTransition".to_string(),
+            detail: detail.to_string(),
+            risk_level,
+            host_id: device.host_id,
+            mac: device.mac,
+            ip: device.ip,
+        };
+        if let Err(e) = self.db.save_behavior_log(&log).await {
+            log::error!("Failed to record policy transition: {}", e);
+        }
+    }
+}
+
+fn validate(policy: &PolicyConfig) -> Result<(), String> {
+    for (name, list) in [("process_blacklist", &policy.process_blacklist), ("app_blacklist", &policy.app_blacklist)] {
+        if list.len() > MAX_LIST_ENTRIES {
+            return Err(format!("{} has {} entries (max {})", name, list.len(), MAX_LIST_ENTRIES));
+        }
+        for entry in list {
+            let trimmed = entry.trim();
+            if trimmed.is_empty() {
+                // 空串会匹配所有进程/应用
+                return Err(format!("{} contains an empty entry", name));
+            }
+            if trimmed.len() > MAX_ENTRY_LEN {
+                return Err(format!("{} entry exceeds {} characters", name, MAX_ENTRY_LEN));
+            }
+        }
+    }
+
+    for window in &policy.working_hours {
+        for value in [&window.start, &window.end] {
+            if capture::parse_hhmm(value).is_none() {
+                return Err(format!("working_hours has invalid time '{}' (expected HH:MM)", value));
+            }
+        }
+        if window.start.trim() == window.end.trim() {
+            return Err(format!("working_hours window {}-{} is empty", window.start, window.end));
+        }
+        if let Some(day) = window.days.iter().find(|d| !(1..=7).contains(*d)) {
+            return Err(format!("working_hours has invalid day {} (expected 1-7)", day));
+        }
+    }
+    for rule in &policy.app_capture_modes {
+        if rule.app.trim().is_empty() {
+            return Err("app_capture_modes contains an empty app".to_string());
+        }
+    }
+    for (name, list) in [("file_include_paths", &policy.file_include_paths), ("file_exclude_paths", &policy.file_exclude_paths)] {
+        if list.len() > MAX_LIST_ENTRIES {
+            return Err(format!("{} has {} entries (max {})", name, list.len(), MAX_LIST_ENTRIES));
+        }
+        if list.iter().any(|p| p.trim().is_empty()) {
+            return Err(format!("{} contains an empty entry", name));
+        }
+        crate::files::validate_globs(list).map_err(|e| format!("{} has an invalid glob {}", name, e))?;
+    }
+    if policy.approved_devices.len() > MAX_LIST_ENTRIES {
+        return Err(format!("approved_devices has {} entries (max {})", policy.approved_devices.len(), MAX_LIST_ENTRIES));
+    }
+    for pattern in &policy.approved_devices {
+        crate::peripherals::validate_device_pattern(pattern).map_err(|e| format!("approved_devices: {}", e))?;
+    }
+    for rule in &policy.unapproved_device_rules {
+        if rule.device_class.trim().is_empty() {
+            return Err("unapproved_device_rules contains an empty device_class".to_string());
+        }
+        if !(0..=2).contains(&rule.risk_level) {
+            return Err(format!("unapproved_device_rules risk_level {} out of range (0-2)", rule.risk_level));
+        }
+    }
+    if policy.traffic_rules.len() > MAX_LIST_ENTRIES {
+        return Err(format!("traffic_rules has {} entries (max {})", policy.traffic_rules.len(), MAX_LIST_ENTRIES));
+    }
+    for rule in &policy.traffic_rules {
+        crate::traffic::validate_traffic_pattern(&rule.pattern).map_err(|e| format!("traffic_rules: {}", e))?;
+        if let Some(risk) = rule.risk_level.filter(|r| !(0..=2).contains(r)) {
+            return Err(format!("traffic_rules risk_level {} out of range (0-2)", risk));
+        }
+    }
+    if let Some(secs) = policy.traffic_aggregate_secs.filter(|s| !(1..=86_400).contains(s)) {
+        return Err(format!("traffic_aggregate_secs {} out of range (1-86400)", secs));
+    }
+    let limits = &policy.upload_limits;
+    if limits.daily_metadata_bytes == Some(0) || limits.daily_file_bytes == Some(0) {
+        return Err("upload_limits daily caps must be greater than 0".to_string());
+    }
+    if policy.screenshot_interval_secs == Some(0) {
+        return Err("screenshot_interval_secs must be greater than 0".to_string());
+    }
+    Ok(())
+}
+
+fn content_digest(policy: &PolicyConfig) -> String {
+    let bytes = serde_json::to_vec(policy).unwrap_or_default();
+    let digest = Sha256::digest(&bytes);
+    format!("sha256:{}", &hex::encode(digest)[..16])
+}
+
+/// 先写临时文件再重命名，避免写到一半时崩溃留下损坏的缓存
+fn persist(path: &Path, cache: &PolicyCache) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_vec_pretty(cache)?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)
+}
@@ -1,24 +1,31 @@
 use std::sync::{Arc, RwLock};
 use sysinfo::System;
+use crate::clock::LogicalClock;
 use crate::db::Database;
 use crate::models::{PolicyConfig, BehaviorLog};
-use chrono::Local;
 use std::path::Path;
 
 pub struct Scanner {
     db: Arc<Database>,
     policy: Arc<RwLock<PolicyConfig>>,
     sys: System,
-    device_info: crate::models::DeviceInfo,
+    device_info: Arc<RwLock<crate::models::DeviceInfo>>,
+    clock: Arc<LogicalClock>,
 }
 
 impl Scanner {
-    pub fn new(db: Arc<Database>, policy: Arc<RwLock<PolicyConfig>>, device_info: crate::models::DeviceInfo) -> Self {
+    pub fn new(
+        db: Arc<Database>,
+        policy: Arc<RwLock<PolicyConfig>>,
+        device_info: Arc<RwLock<crate::models::DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+    ) -> Self {
         Self {
             db,
             policy,
             sys: System::new_all(),
             device_info,
+            clock,
         }
     }
 
@@ -91,17 +98,18 @@ impl Scanner {
         // 使用 log::warn 记录报警信息
         log::warn!("🚨 [Scanner ALARM] Type: {}, Detail: {}", op_type, detail);
 
+        let device = self.device_info.read().unwrap().clone();
         let log = BehaviorLog {
             id: None,
             proc: proc.to_string(),
-            op_time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-            cpe_id: self.device_info.cpe_id.clone(),
+            op_time: self.clock.now_str(),
+            cpe_id: device.cpe_id,
             op_type: op_type.to_string(),
             detail: detail.to_string(),
             risk_level: 2, // 2 = 高风险
-            host_id: self.device_info.host_id.clone(),
-            mac: self.device_info.mac.clone(),
-            ip: self.device_info.ip.clone(),
+            host_id: device.host_id,
+            mac: device.mac,
+            ip: device.ip,
         };
 
         if let Err(e) = self.db.save_behavior_log(&log).await {
@@ -0,0 +1,176 @@
+//! Linux 会话事件源：订阅 systemd-logind (org.freedesktop.login1) 的系统总线信号
+//!
+//! - Manager.SessionNew / SessionRemoved -> 登录 / 注销
+//! - Manager.PrepareForSleep(true / false) -> 睡眠 / 唤醒
+//! - Session 的 LockedHint 属性变化 -> 锁屏 / 解锁 (锁屏程序负责设置)，
+//!   Active 属性变化 -> 用户切换 (同一 seat 上只有一个会话处于前台)
+//!
+//! Session.Lock / Unlock 信号只是锁屏请求，不单独记录。
+
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use zbus::message::Type;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, MatchRule, Message, MessageStream};
+
+use super::{SessionEvent, SessionEventKind, SessionProducer, SessionTracker};
+use crate::lifecycle::ShutdownSignal;
+
+const LOGIN1_SERVICE: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const SOURCE: &str = "logind";
+
+#[derive(Default)]
+pub struct LogindProducer;
+
+impl SessionProducer for LogindProducer {
+    fn name(&self) -> &'static str {
+        "logind"
+    }
+
+    fn start(self: Box<Self>, tracker: Arc<SessionTracker>, shutdown: ShutdownSignal) -> Result<(), String> {
+        // 连接系统总线是异步的，失败时只记录日志，FFI / IPC 提交的事件仍会记录
+        tracker.runtime_handle().clone().spawn(async move {
+            if let Err(e) = run(tracker, shutdown).await {
+                log::error!("logind session monitor stopped: {}", e);
+            }
+        });
+        Ok(())
+    }
+}
+
+async fn run(tracker: Arc<SessionTracker>, mut shutdown: ShutdownSignal) -> Result<(), String> {
+    let conn = Connection::system().await.map_err(|e| format!("Failed to connect to the system bus: {}", e))?;
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .path_namespace(LOGIN1_PATH)
+        .map_err(|e| e.to_string())?
+        .build();
+    let mut stream =
+        MessageStream::for_match_rule(rule, &conn, None).await.map_err(|e| format!("Failed to subscribe to logind: {}", e))?;
+    log::info!("Session monitor listening for logind signals");
+
+    loop {
+        let msg = tokio::select! {
+            _ = shutdown.wait() => break,
+            msg = stream.next() => msg,
+        };
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => {
+                log::warn!("Malformed logind signal ignored: {}", e);
+                continue;
+            }
+            None => return Err("system bus connection closed".to_string()),
+        };
+        for mut event in events_from_signal(&msg) {
+            if event.kind == SessionEventKind::Login && event.user.is_none() {
+                event.user = session_user(&conn, &msg).await;
+            }
+            if !tracker.record(event) {
+                return Ok(());
+            }
+        }
+    }
+    log::info!("logind session monitor stopped");
+    Ok(())
+}
+
+fn events_from_signal(msg: &Message) -> Vec<SessionEvent> {
+    let header = msg.header();
+    let (Some(interface), Some(member), Some(path)) = (header.interface(), header.member(), header.path()) else {
+        return Vec::new();
+    };
+    let event = |kind: SessionEventKind, session_id: Option<String>| SessionEvent {
+        session_id,
+        source: SOURCE.to_string(),
+        ..SessionEvent::new(kind)
+    };
+
+    match (interface.as_str(), member.as_str()) {
+        (MANAGER_INTERFACE, "SessionNew" | "SessionRemoved") => {
+            let Ok((id, _path)) = msg.body().deserialize::<(String, zbus::zvariant::OwnedObjectPath)>() else {
+                return Vec::new();
+            };
+            let kind = if member.as_str() == "SessionNew" { SessionEventKind::Login } else { SessionEventKind::Logout };
+            vec![event(kind, Some(id))]
+        }
+        (MANAGER_INTERFACE, "PrepareForSleep") => match msg.body().deserialize::<bool>() {
+            Ok(true) => vec![event(SessionEventKind::Sleep, None)],
+            Ok(false) => vec![event(SessionEventKind::Wake, None)],
+            Err(_) => Vec::new(),
+        },
+        (PROPERTIES_INTERFACE, "PropertiesChanged") => {
+            let Some(session_id) = session_id_from_path(path.as_str()) else {
+                return Vec::new();
+            };
+            let Ok((iface, changed, _invalidated)) =
+                msg.body().deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+            else {
+                return Vec::new();
+            };
+            if iface != SESSION_INTERFACE {
+                return Vec::new();
+            }
+            let flag = |name: &str| changed.get(name).and_then(|v| bool::try_from(v).ok());
+            property_events(flag("LockedHint"), flag("Active"))
+                .into_iter()
+                .map(|kind| event(kind, Some(session_id.clone())))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Session 属性变化对应的事件：LockedHint -> 锁屏 / 解锁，Active -> 切入 / 切出
+pub fn property_events(locked_hint: Option<bool>, active: Option<bool>) -> Vec<SessionEventKind> {
+    let mut kinds = Vec::new();
+    match active {
+        Some(true) => kinds.push(SessionEventKind::SwitchIn),
+        Some(false) => kinds.push(SessionEventKind::SwitchOut),
+        None => {}
+    }
+    match locked_hint {
+        Some(true) => kinds.push(SessionEventKind::Lock),
+        Some(false) => kinds.push(SessionEventKind::Unlock),
+        None => {}
+    }
+    kinds
+}
+
+/// `/org/freedesktop/login1/session/_32` -> `2` (对象路径中非字母数字字符以 `_xx` 十六进制转义)
+pub fn session_id_from_path(path: &str) -> Option<String> {
+    let escaped = path.strip_prefix("/org/freedesktop/login1/session/")?;
+    if escaped.is_empty() || escaped.contains('/') {
+        return None;
+    }
+    let bytes = escaped.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'_' {
+            let code = bytes.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok())?;
+            out.push(code);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// 读取新会话的用户名 (Session.Name)，失败时返回 None
+async fn session_user(conn: &Connection, msg: &Message) -> Option<String> {
+    let (_, path) = msg.body().deserialize::<(String, zbus::zvariant::OwnedObjectPath)>().ok()?;
+    let reply = conn
+        .call_method(Some(LOGIN1_SERVICE), path.as_str(), Some(PROPERTIES_INTERFACE), "Get", &(SESSION_INTERFACE, "Name"))
+        .await
+        .ok()?;
+    let value = reply.body().deserialize::<OwnedValue>().ok()?;
+    String::try_from(value).ok().filter(|name| !name.is_empty())
+}
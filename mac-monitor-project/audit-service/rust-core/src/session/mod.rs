@@ -0,0 +1,266 @@
+//! 会话与锁屏状态审计：登录 / 注销、锁屏 / 解锁、睡眠 / 唤醒、快速用户切换
+//!
+//! 事件源实现 `SessionProducer`：Linux 使用 logind D-Bus 信号 (`logind::LogindProducer`)，
+//! macOS 由 Swift 宿主 (NSWorkspace / 锁屏通知) 通过 FFI `log_session_event` 提交。
+//! 事件写入行为日志 (op_type 见 `SessionEventKind::op_type`)；锁屏、睡眠或切出期间暂停截图。
+
+#[cfg(target_os = "linux")]
+pub mod logind;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::runtime::Handle;
+
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::Database;
+use crate::lifecycle::{ShutdownSignal, TaskTracker};
+use crate::models::{BehaviorLog, DeviceInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEventKind {
+    Login,
+    Logout,
+    Lock,
+    Unlock,
+    Sleep,
+    Wake,
+    /// 快速用户切换：切回本会话
+    SwitchIn,
+    /// 快速用户切换：切到其他用户
+    SwitchOut,
+}
+
+impl SessionEventKind {
+    /// 行为日志中的 op_type
+    pub fn op_type(&self) -> &'static str {
+        match self {
+            SessionEventKind::Login => "SessionLogin",
+            SessionEventKind::Logout => "SessionLogout",
+            SessionEventKind::Lock => "ScreenLock",
+            SessionEventKind::Unlock => "ScreenUnlock",
+            SessionEventKind::Sleep => "SystemSleep",
+            SessionEventKind::Wake => "SystemWake",
+            SessionEventKind::SwitchIn => "UserSwitchIn",
+            SessionEventKind::SwitchOut => "UserSwitchOut",
+        }
+    }
+}
+
+/// 事件源提交的会话事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub kind: SessionEventKind,
+    /// logind 会话 id / macOS audit session id；睡眠唤醒等系统级事件为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// 事件来源 (logind / loginwindow 等)，写入行为日志的 proc
+    #[serde(default = "default_source")]
+    pub source: String,
+    /// 事件时间 (逻辑时钟毫秒)，缺省为提交时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<i64>,
+}
+
+fn default_source() -> String {
+    "host".to_string()
+}
+
+impl SessionEvent {
+    pub fn new(kind: SessionEventKind) -> Self {
+        Self { kind, session_id: None, user: None, source: default_source(), time_ms: None }
+    }
+}
+
+/// 会话事件源，收到停止信号后退出
+pub trait SessionProducer: Send {
+    fn name(&self) -> &'static str;
+
+    /// 启动后台监听，事件交给 `tracker.record`
+    fn start(self: Box<Self>, tracker: Arc<SessionTracker>, shutdown: ShutdownSignal) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Linux 上监听 logind 信号；macOS 由 Swift 宿主提交，忽略此项
+    #[serde(default)]
+    pub watch_logind: bool,
+}
+
+impl SessionConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// 当前会话状态 (IPC get_session_state 返回)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub locked: bool,
+    pub asleep: bool,
+    pub switched_out: bool,
+    /// 已登录的会话 id -> 用户名 (用户未知时为空字符串)
+    pub sessions: HashMap<String, String>,
+    /// 最近一次状态变化的时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+}
+
+impl SessionState {
+    /// 锁屏、睡眠或切到其他用户期间不截图
+    pub fn capture_paused(&self) -> bool {
+        self.locked || self.asleep || self.switched_out
+    }
+
+    /// 应用事件，返回是否需要记录 (重复的锁屏 / 睡眠等状态事件忽略)
+    fn apply(&mut self, event: &mut SessionEvent) -> bool {
+        let flag = |value: &mut bool, new: bool| std::mem::replace(value, new) != new;
+        match event.kind {
+            SessionEventKind::Login => {
+                if let Some(id) = &event.session_id {
+                    self.sessions.insert(id.clone(), event.user.clone().unwrap_or_default());
+                }
+                true
+            }
+            SessionEventKind::Logout => {
+                // logind 注销信号不带用户名，沿用登录时记录的
+                if let Some(user) = event.session_id.as_ref().and_then(|id| self.sessions.remove(id)) {
+                    if event.user.is_none() && !user.is_empty() {
+                        event.user = Some(user);
+                    }
+                }
+                true
+            }
+            SessionEventKind::Lock => flag(&mut self.locked, true),
+            SessionEventKind::Unlock => flag(&mut self.locked, false),
+            SessionEventKind::Sleep => flag(&mut self.asleep, true),
+            SessionEventKind::Wake => flag(&mut self.asleep, false),
+            SessionEventKind::SwitchOut => flag(&mut self.switched_out, true),
+            SessionEventKind::SwitchIn => flag(&mut self.switched_out, false),
+        }
+    }
+}
+
+/// 会话状态跟踪与行为日志落库
+pub struct SessionTracker {
+    db: Arc<Database>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+    tasks: Arc<TaskTracker>,
+    runtime_handle: Handle,
+    state: RwLock<SessionState>,
+    pause_listeners: RwLock<Vec<PauseListener>>,
+}
+
+/// (是否暂停, 事件时间毫秒)
+type PauseListener = Box<dyn Fn(bool, i64) + Send + Sync>;
+
+impl SessionTracker {
+    pub fn new(
+        db: Arc<Database>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+        tasks: Arc<TaskTracker>,
+        runtime_handle: Handle,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            device_info,
+            clock,
+            tasks,
+            runtime_handle,
+            state: RwLock::new(SessionState::default()),
+            pause_listeners: RwLock::new(Vec::new()),
+        })
+    }
+
+    /// `capture_paused()` 变化时回调 (如前台使用时长在锁屏 / 睡眠期间停止计时)
+    pub fn on_pause_change(&self, listener: impl Fn(bool, i64) + Send + Sync + 'static) {
+        self.pause_listeners.write().unwrap().push(Box::new(listener));
+    }
+
+    pub fn runtime_handle(&self) -> &Handle {
+        &self.runtime_handle
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state.read().unwrap().clone()
+    }
+
+    pub fn capture_paused(&self) -> bool {
+        self.state.read().unwrap().capture_paused()
+    }
+
+    /// 立即更新状态 (截图随即暂停 / 恢复)，异步写入行为日志；服务退出中返回 false
+    pub fn record(self: &Arc<Self>, mut event: SessionEvent) -> bool {
+        if !self.apply(&mut event) {
+            return true;
+        }
+        let tracker = self.clone();
+        self.tasks.spawn(&self.runtime_handle, async move {
+            if let Err(e) = tracker.write(event).await {
+                log::error!("Failed to save session event: {}", e);
+            }
+        })
+    }
+
+    /// 更新状态并写入，重复的状态事件返回 None
+    pub async fn save(&self, mut event: SessionEvent) -> Result<Option<BehaviorLog>, String> {
+        if !self.apply(&mut event) {
+            return Ok(None);
+        }
+        self.write(event).await.map(Some)
+    }
+
+    fn apply(&self, event: &mut SessionEvent) -> bool {
+        let time_ms = *event.time_ms.get_or_insert_with(|| self.clock.now_ms());
+        let (changed, paused) = {
+            let mut state = self.state.write().unwrap();
+            let was_paused = state.capture_paused();
+            let changed = state.apply(event);
+            if changed {
+                state.since = Some(format_ms(time_ms));
+            } else {
+                log::debug!("Duplicate {} event ignored", event.kind.op_type());
+            }
+            (changed, Some(state.capture_paused()).filter(|p| *p != was_paused))
+        };
+        if let Some(paused) = paused {
+            for listener in self.pause_listeners.read().unwrap().iter() {
+                listener(paused, time_ms);
+            }
+        }
+        changed
+    }
+
+    async fn write(&self, event: SessionEvent) -> Result<BehaviorLog, String> {
+        let detail = serde_json::json!({
+            "session_id": event.session_id,
+            "user": event.user,
+        });
+        let device = self.device_info.read().unwrap().clone();
+        let log = BehaviorLog {
+            id: None,
+            proc: event.source,
+            op_time: format_ms(event.time_ms.unwrap_or_else(|| self.clock.now_ms())),
+            cpe_id: device.cpe_id,
+            op_type: event.kind.op_type().to_string(),
+            detail: detail.to_string(),
+            risk_level: 0,
+            host_id: device.host_id,
+            mac: device.mac,
+            ip: device.ip,
+        };
+        self.db.save_behavior_log(&log).await.map_err(|e| e.to_string())?;
+        log::info!(
+            "🔒 {} (session {}, user {})",
+            log.op_type,
+            event.session_id.as_deref().unwrap_or("-"),
+            event.user.as_deref().unwrap_or("-")
+        );
+        Ok(log)
+    }
+}
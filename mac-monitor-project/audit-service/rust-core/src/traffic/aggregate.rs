@@ -0,0 +1,217 @@
+//! 流量聚合模式
+//!
+//! 策略设置 `traffic_aggregate_secs` 后，通过过滤的请求按时间桶与 (进程, 域名, 方法, 路径模板) 汇总，
+//! 时间桶结束后写入 `traffic_summaries` 一行 (请求数、首末时间、字节数)，不再逐条写入 `monitor_log_traffic`。
+//! 带风险等级的请求 (命中带 risk_level 的规则，或生产者已标记) 仍保留完整记录。
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use super::{normalize_host, url_path};
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::Database;
+use crate::lifecycle::ShutdownSignal;
+use crate::models::{AuditLog, PolicyConfig, TrafficSummary};
+
+/// 待写入的时间桶条目上限，超出后新的请求按完整记录保存
+const DEFAULT_MAX_PENDING: usize = 10_000;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const ID_PLACEHOLDER: &str = "{id}";
+
+/// IPC get_traffic_stats 中的聚合部分
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregationStats {
+    /// 当前策略的时间桶，None 表示未开启
+    pub bucket_secs: Option<u32>,
+    /// 并入汇总的请求数
+    pub aggregated: u64,
+    /// 已写入的汇总行数
+    pub summaries: u64,
+    pub pending: usize,
+}
+
+/// (桶起点, 进程, 域名, 方法, 路径模板)
+type Key = (i64, String, String, String, String);
+
+struct Bucket {
+    end_ms: i64,
+    summary: TrafficSummary,
+}
+
+pub struct TrafficAggregator {
+    db: Arc<Database>,
+    policy: Arc<RwLock<PolicyConfig>>,
+    clock: Arc<LogicalClock>,
+    max_pending: usize,
+    pending: Mutex<HashMap<Key, Bucket>>,
+    aggregated: AtomicU64,
+    summaries: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl TrafficAggregator {
+    pub fn new(db: Arc<Database>, policy: Arc<RwLock<PolicyConfig>>, clock: Arc<LogicalClock>) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            policy,
+            clock,
+            max_pending: DEFAULT_MAX_PENDING,
+            pending: Mutex::new(HashMap::new()),
+            aggregated: AtomicU64::new(0),
+            summaries: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn bucket_secs(&self) -> Option<u32> {
+        self.policy.read().unwrap().traffic_aggregate_secs.filter(|s| *s > 0)
+    }
+
+    /// 并入当前时间桶；返回 false 表示未聚合 (未开启、带风险等级或已停止)，调用方按完整记录保存
+    pub fn absorb(&self, log: &AuditLog) -> bool {
+        let Some(bucket_secs) = self.bucket_secs() else {
+            return false;
+        };
+        if log.risk_level > 0 || self.closed.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        let bucket_ms = bucket_secs as i64 * 1000;
+        let start_ms = self.clock.now_ms().div_euclid(bucket_ms) * bucket_ms;
+        let key = (
+            start_ms,
+            log.process_name.clone(),
+            normalize_host(&log.domain),
+            log.method_type.to_uppercase(),
+            path_template(url_path(&log.url)),
+        );
+        let bytes = log.bytes.unwrap_or(0).max(0);
+
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(bucket) = pending.get_mut(&key) {
+            let summary = &mut bucket.summary;
+            summary.request_count += 1;
+            summary.bytes += bytes;
+            if log.req_time < summary.first_seen {
+                summary.first_seen = log.req_time.clone();
+            }
+            if log.req_time > summary.last_seen {
+                summary.last_seen = log.req_time.clone();
+            }
+        } else {
+            if pending.len() >= self.max_pending {
+                log::debug!("Traffic aggregation backlog is full, storing full record for {}", log.domain);
+                return false;
+            }
+            let summary = TrafficSummary {
+                id: None,
+                bucket_start: format_ms(start_ms),
+                bucket_secs: bucket_secs as i64,
+                process_name: key.1.clone(),
+                domain: key.2.clone(),
+                method_type: key.3.clone(),
+                path_template: key.4.clone(),
+                first_seen: log.req_time.clone(),
+                last_seen: log.req_time.clone(),
+                request_count: 1,
+                bytes,
+                risk_level: log.risk_level,
+                cpe_id: log.cpe_id.clone(),
+                host_id: log.host_id.clone(),
+                mac: log.mac.clone(),
+                ip: log.ip.clone(),
+            };
+            pending.insert(key, Bucket { end_ms: start_ms + bucket_ms, summary });
+        }
+        self.aggregated.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// 停止接收新请求 (退出流程)，已并入的由 `flush(true)` 写入
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// 定期写入已结束的时间桶，停止时写入全部
+    pub fn start(self: Arc<Self>, mut shutdown: ShutdownSignal) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
+                self.flush(false).await;
+            }
+            self.flush(true).await;
+        })
+    }
+
+    /// 写入时间桶，`all` 为 true 时不等待桶结束；返回写入的行数
+    pub async fn flush(&self, all: bool) -> usize {
+        let now = self.clock.now_ms();
+        let mut due: Vec<TrafficSummary> = {
+            let mut pending = self.pending.lock().unwrap();
+            let keys: Vec<Key> = pending.iter().filter(|(_, b)| all || b.end_ms <= now).map(|(k, _)| k.clone()).collect();
+            keys.into_iter().filter_map(|k| pending.remove(&k)).map(|b| b.summary).collect()
+        };
+        due.sort_by(|a, b| a.bucket_start.cmp(&b.bucket_start).then_with(|| a.first_seen.cmp(&b.first_seen)));
+
+        let mut written = 0;
+        for summary in due {
+            match self.db.save_traffic_summary(&summary).await {
+                Ok(()) => written += 1,
+                Err(e) => log::error!("Failed to save traffic summary for {}: {}", summary.domain, e),
+            }
+        }
+        if written > 0 {
+            self.summaries.fetch_add(written as u64, Ordering::Relaxed);
+            log::debug!("Saved {} traffic summaries", written);
+        }
+        written
+    }
+
+    pub fn stats(&self) -> AggregationStats {
+        AggregationStats {
+            bucket_secs: self.bucket_secs(),
+            aggregated: self.aggregated.load(Ordering::Relaxed),
+            summaries: self.summaries.load(Ordering::Relaxed),
+            pending: self.pending(),
+        }
+    }
+}
+
+/// 把路径中的标识段 (数字、UUID、长十六进制串、长令牌) 替换为 `{id}`，使同一接口的请求归为一组
+pub fn path_template(path: &str) -> String {
+    path.split('/')
+        .map(|segment| if is_identifier(segment) { ID_PLACEHOLDER } else { segment })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn is_identifier(segment: &str) -> bool {
+    if segment.is_empty() {
+        return false;
+    }
+    if segment.chars().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+    let hex = |s: &str| s.chars().all(|c| c.is_ascii_hexdigit());
+    let groups: Vec<&str> = segment.split('-').collect();
+    if groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12]) && groups.iter().all(|g| hex(g)) {
+        return true;
+    }
+    if segment.len() >= 16 && hex(segment) {
+        return true;
+    }
+    segment.len() >= 32
+        && segment.chars().any(|c| c.is_ascii_digit())
+        && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
@@ -0,0 +1,165 @@
+//! 网络流量入库过滤
+//!
+//! 宿主通过 IPC `log_traffic` 提交的请求记录按策略 `traffic_rules` 过滤：规则按顺序匹配，
+//! 第一条命中的 include 规则入库 (并按规则提升风险等级)，exclude 规则丢弃；
+//! 没有命中时，存在 include 规则则丢弃 (白名单)，否则入库。
+//! 每条记录都读取当前生效的策略，策略下发或回滚后立即生效。
+//! 开启聚合模式时，通过过滤的请求由 `aggregate::TrafficAggregator` 按时间桶汇总。
+
+pub mod aggregate;
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::models::{AuditLog, PolicyConfig, TrafficAction, TrafficRule};
+
+/// 规则匹配结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficVerdict {
+    pub store: bool,
+    /// 命中规则的序号
+    pub rule: Option<usize>,
+    pub risk_level: Option<i32>,
+}
+
+/// IPC get_traffic_stats 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct TrafficStats {
+    pub stored: u64,
+    pub dropped: u64,
+    /// 入库时被规则提升了风险等级的记录数
+    pub tagged: u64,
+    pub rules: usize,
+}
+
+/// 校验策略中的流量规则模式 (策略下发时调用)
+pub fn validate_traffic_pattern(pattern: &str) -> Result<(), String> {
+    let (host, _) = split_pattern(pattern);
+    if host.is_empty() {
+        return Err(format!("'{}' has an empty host", pattern));
+    }
+    if pattern.trim().chars().any(char::is_whitespace) {
+        return Err(format!("'{}' contains whitespace", pattern));
+    }
+    if host.contains(':') {
+        return Err(format!("'{}' must not contain a scheme or port", pattern));
+    }
+    Ok(())
+}
+
+/// 按规则判断一条请求是否入库
+pub fn evaluate(rules: &[TrafficRule], domain: &str, url: &str) -> TrafficVerdict {
+    let host = normalize_host(domain);
+    let path = url_path(url);
+    for (index, rule) in rules.iter().enumerate() {
+        if !rule_matches(&rule.pattern, &host, path) {
+            continue;
+        }
+        return match rule.action {
+            TrafficAction::Include => TrafficVerdict { store: true, rule: Some(index), risk_level: rule.risk_level },
+            TrafficAction::Exclude => TrafficVerdict { store: false, rule: Some(index), risk_level: None },
+        };
+    }
+    let whitelist = rules.iter().any(|r| r.action == TrafficAction::Include);
+    TrafficVerdict { store: !whitelist, rule: None, risk_level: None }
+}
+
+/// `log_traffic` 的过滤器，统计入库 / 丢弃数
+pub struct TrafficFilter {
+    policy: Arc<RwLock<PolicyConfig>>,
+    stored: AtomicU64,
+    dropped: AtomicU64,
+    tagged: AtomicU64,
+}
+
+impl TrafficFilter {
+    pub fn new(policy: Arc<RwLock<PolicyConfig>>) -> Self {
+        Self { policy, stored: AtomicU64::new(0), dropped: AtomicU64::new(0), tagged: AtomicU64::new(0) }
+    }
+
+    /// 返回 false 表示丢弃；入库的记录按命中规则提升风险等级
+    pub fn check(&self, log: &mut AuditLog) -> bool {
+        let verdict = evaluate(&self.policy.read().unwrap().traffic_rules, &log.domain, &log.url);
+        if !verdict.store {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if let Some(risk) = verdict.risk_level.filter(|r| *r > log.risk_level) {
+            log.risk_level = risk;
+            self.tagged.fetch_add(1, Ordering::Relaxed);
+        }
+        self.stored.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    pub fn stats(&self) -> TrafficStats {
+        TrafficStats {
+            stored: self.stored.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            tagged: self.tagged.load(Ordering::Relaxed),
+            rules: self.policy.read().unwrap().traffic_rules.len(),
+        }
+    }
+}
+
+fn rule_matches(pattern: &str, host: &str, path: &str) -> bool {
+    let (host_pattern, path_pattern) = split_pattern(pattern);
+    let host_pattern = host_pattern.to_lowercase();
+    let host_ok = if host_pattern.contains('*') {
+        wildcard(&host_pattern, host)
+    } else {
+        // 不带通配符时匹配该域名及其子域名
+        host == host_pattern || host.strip_suffix(host_pattern.as_str()).is_some_and(|rest| rest.ends_with('.'))
+    };
+    host_ok
+        && match path_pattern {
+            None => true,
+            Some(p) if p.contains('*') => wildcard(p, path),
+            Some(p) => path.starts_with(p),
+        }
+}
+
+/// (域名部分, 路径部分)，路径以 `/` 开头
+fn split_pattern(pattern: &str) -> (&str, Option<&str>) {
+    let pattern = pattern.trim();
+    match pattern.find('/') {
+        Some(i) => (&pattern[..i], Some(&pattern[i..])),
+        None => (pattern, None),
+    }
+}
+
+fn normalize_host(domain: &str) -> String {
+    let host = domain.trim().trim_end_matches('.').to_lowercase();
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.chars().all(|c| c.is_ascii_digit()) => name.to_string(),
+        _ => host,
+    }
+}
+
+/// URL 的路径部分 (不含查询串)，无路径时为 `/`
+fn url_path(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = rest.find('/').map_or("/", |i| &rest[i..]);
+    path.split(['?', '#']).next().unwrap_or("/")
+}
+
+/// `*` 匹配任意字符 (含空串)
+fn wildcard(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return text == first;
+    }
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
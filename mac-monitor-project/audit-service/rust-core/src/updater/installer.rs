@@ -0,0 +1,77 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use super::UpdateManifest;
+
+/// 安装已校验的更新包；安装程序通常会替换并重启本服务，调用方不应依赖返回后的状态
+pub trait InstallerHook: Send + Sync {
+    fn install(&self, package: &Path, manifest: &UpdateManifest) -> Result<(), String>;
+}
+
+/// 运行配置的安装命令，参数中的 `{package}` / `{version}` 会被替换
+pub struct CommandInstaller {
+    command: Vec<String>,
+}
+
+impl CommandInstaller {
+    pub fn new(command: Vec<String>) -> Self {
+        Self { command }
+    }
+}
+
+impl InstallerHook for CommandInstaller {
+    fn install(&self, package: &Path, manifest: &UpdateManifest) -> Result<(), String> {
+        let package = package.display().to_string();
+        let args: Vec<String> = self
+            .command
+            .iter()
+            .map(|arg| arg.replace("{package}", &package).replace("{version}", &manifest.version))
+            .collect();
+        let (program, args) = args.split_first().ok_or_else(|| "Empty installer command".to_string())?;
+        run(program, args)
+    }
+}
+
+/// macOS：通过系统 `installer` 安装 .pkg
+pub struct MacPkgInstaller;
+
+impl InstallerHook for MacPkgInstaller {
+    fn install(&self, package: &Path, _manifest: &UpdateManifest) -> Result<(), String> {
+        if package.extension().and_then(|e| e.to_str()) != Some("pkg") {
+            return Err(format!("Unsupported package type for macOS installer: {:?}", package));
+        }
+        let package = package.display().to_string();
+        run("/usr/sbin/installer", &["-pkg".to_string(), package, "-target".to_string(), "/".to_string()])
+    }
+}
+
+/// 配置了安装命令时使用该命令，否则使用平台默认方式 (Linux 无默认方式，仅暂存)
+pub fn platform_installer(command: &[String]) -> Option<Arc<dyn InstallerHook>> {
+    if !command.is_empty() {
+        return Some(Arc::new(CommandInstaller::new(command.to_vec())));
+    }
+    if cfg!(target_os = "macos") {
+        Some(Arc::new(MacPkgInstaller))
+    } else {
+        None
+    }
+}
+
+fn run(program: &str, args: &[String]) -> Result<(), String> {
+    log::info!("Running installer: {} {:?}", program, args);
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run installer {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Installer {} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
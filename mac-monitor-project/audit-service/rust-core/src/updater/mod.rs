@@ -0,0 +1,333 @@
+//! 自更新：检查更新 → 校验签名清单 → 断点续传下载安装包 → 校验摘要 → 交给安装钩子
+//!
+//! 安装包本身不签名，签名覆盖的是清单 (版本 / 文件名 / 大小 / SHA-256)，
+//! 清单验签通过后再用其中的摘要校验下载的安装包。
+
+pub mod installer;
+pub mod signature;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use self::installer::InstallerHook;
+use self::signature::{SignatureAlgorithm, SignatureVerifier};
+use crate::uploader::Uploader;
+
+// 下载中断后自动续传的次数
+const DOWNLOAD_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// 固定的发布公钥 (hex)；未配置时拒绝安装任何更新
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub signature_algorithm: SignatureAlgorithm,
+    /// 缺省为数据库同目录下的 updates/
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staging_dir: Option<String>,
+    /// 安装命令，`{package}` / `{version}` 会被替换；为空时使用平台默认安装方式
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub installer_command: Vec<String>,
+}
+
+/// 签名覆盖的更新清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateManifest {
+    pub version: String,
+    pub file_name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateState {
+    #[default]
+    Idle,
+    Checking,
+    UpToDate,
+    Downloading,
+    Verifying,
+    /// 已下载并校验，等待手动安装 (无可用的安装钩子)
+    Staged,
+    Installing,
+    Installed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateProgress {
+    pub state: UpdateState,
+    pub current_version: String,
+    pub target_version: Option<String>,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub package_path: Option<String>,
+    pub message: Option<String>,
+}
+
+pub struct Updater {
+    uploader: Arc<Uploader>,
+    config: UpdateConfig,
+    staging_dir: PathBuf,
+    installer: Option<Arc<dyn InstallerHook>>,
+    progress: RwLock<UpdateProgress>,
+    running: AtomicBool,
+}
+
+impl Updater {
+    pub fn new(uploader: Arc<Uploader>, config: UpdateConfig, default_staging_dir: PathBuf) -> Self {
+        let installer = installer::platform_installer(&config.installer_command);
+        Self::with_installer(uploader, config, default_staging_dir, installer)
+    }
+
+    pub fn with_installer(
+        uploader: Arc<Uploader>,
+        config: UpdateConfig,
+        default_staging_dir: PathBuf,
+        installer: Option<Arc<dyn InstallerHook>>,
+    ) -> Self {
+        let staging_dir = config
+            .staging_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or(default_staging_dir);
+        Self {
+            uploader,
+            config,
+            staging_dir,
+            installer,
+            progress: RwLock::new(UpdateProgress {
+                current_version: crate::APP_VERSION.to_string(),
+                ..Default::default()
+            }),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    pub fn progress(&self) -> UpdateProgress {
+        self.progress.read().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// 执行一次完整的更新流程，返回最终状态；同一时间只允许一个流程运行
+    pub async fn run(&self) -> Result<UpdateState, String> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("Update already in progress".to_string());
+        }
+
+        let result = self.run_inner().await;
+        match &result {
+            Ok(state) => self.set_state(*state, None),
+            Err(e) => {
+                log::error!("Update failed: {}", e);
+                self.set_state(UpdateState::Failed, Some(e.clone()));
+            }
+        }
+        self.running.store(false, Ordering::SeqCst);
+        result
+    }
+
+    async fn run_inner(&self) -> Result<UpdateState, String> {
+        {
+            let mut progress = self.progress.write().unwrap();
+            *progress = UpdateProgress {
+                state: UpdateState::Checking,
+                current_version: crate::APP_VERSION.to_string(),
+                ..Default::default()
+            };
+        }
+
+        let info = self.uploader.check_update().await?;
+        let (version, url) = match (info.has_update, info.version, info.url) {
+            (true, Some(version), Some(url)) if is_newer(&version, crate::APP_VERSION) => (version, url),
+            _ => {
+                log::info!("No update available (current version {})", crate::APP_VERSION);
+                return Ok(UpdateState::UpToDate);
+            }
+        };
+        self.progress.write().unwrap().target_version = Some(version.clone());
+
+        // 1. 获取并校验签名清单
+        let verifier = SignatureVerifier::from_config(&self.config)?;
+        let manifest_url = info.manifest_url.unwrap_or_else(|| format!("{}.manifest.json", url));
+        let signature_url = info.signature_url.unwrap_or_else(|| format!("{}.sig", manifest_url));
+        let manifest_bytes = self.uploader.fetch(&manifest_url).await?;
+        let signature = self.uploader.fetch(&signature_url).await?;
+        verifier.verify(&manifest_bytes, &signature)?;
+
+        let manifest: UpdateManifest =
+            serde_json::from_slice(&manifest_bytes).map_err(|e| format!("Invalid update manifest: {}", e))?;
+        if manifest.version != version {
+            return Err(format!(
+                "Manifest version {} does not match offered version {}",
+                manifest.version, version
+            ));
+        }
+        if manifest.file_name.is_empty()
+            || manifest.file_name.contains(['/', '\\'])
+            || manifest.file_name.starts_with('.')
+        {
+            return Err(format!("Invalid package file name in manifest: {:?}", manifest.file_name));
+        }
+        log::info!("Update manifest for {} verified ({} bytes)", version, manifest.size);
+
+        // 2. 下载到暂存目录 (已有完整且校验通过的包时跳过)
+        let dir = self.staging_dir.join(&version);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create staging dir {:?}: {}", dir, e))?;
+        let package = dir.join(&manifest.file_name);
+        let partial = dir.join(format!("{}.part", manifest.file_name));
+
+        if package.exists() && verify_package(&package, &manifest).is_ok() {
+            log::info!("Update package {:?} already staged", package);
+        } else {
+            let _ = std::fs::remove_file(&package);
+            self.download(&url, &partial, &manifest).await?;
+
+            // 3. 校验安装包
+            self.set_state(UpdateState::Verifying, None);
+            if let Err(e) = verify_package(&partial, &manifest) {
+                // 数据已损坏，续传无意义
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+            std::fs::rename(&partial, &package).map_err(|e| format!("Failed to stage package: {}", e))?;
+        }
+        {
+            let mut progress = self.progress.write().unwrap();
+            progress.package_path = Some(package.display().to_string());
+            progress.downloaded_bytes = manifest.size;
+            progress.total_bytes = Some(manifest.size);
+        }
+
+        // 4. 交给安装钩子
+        let Some(installer) = &self.installer else {
+            log::warn!("No installer hook configured, update {} staged at {:?}", version, package);
+            return Ok(UpdateState::Staged);
+        };
+        self.set_state(UpdateState::Installing, None);
+        installer.install(&package, &manifest)?;
+        log::info!("Update {} handed off to installer", version);
+        Ok(UpdateState::Installed)
+    }
+
+    /// 断点续传：`.part` 文件中已有的数据不再重新下载
+    async fn download(&self, url: &str, partial: &Path, manifest: &UpdateManifest) -> Result<(), String> {
+        let mut last_error = String::new();
+
+        for attempt in 1..=DOWNLOAD_ATTEMPTS {
+            let mut offset = std::fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+            if offset > manifest.size {
+                offset = 0;
+            }
+            if offset == manifest.size && offset > 0 {
+                return Ok(());
+            }
+            {
+                let mut progress = self.progress.write().unwrap();
+                progress.state = UpdateState::Downloading;
+                progress.downloaded_bytes = offset;
+                progress.total_bytes = Some(manifest.size);
+            }
+            if offset > 0 {
+                log::info!("Resuming update download at byte {} (attempt {})", offset, attempt);
+            }
+
+            match self.download_from(url, partial, offset).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    log::warn!("Update download attempt {} failed: {}", attempt, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(format!("Download failed after {} attempts: {}", DOWNLOAD_ATTEMPTS, last_error))
+    }
+
+    async fn download_from(&self, url: &str, partial: &Path, offset: u64) -> Result<(), String> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(partial)
+            .map_err(|e| format!("Failed to open {:?}: {}", partial, e))?;
+        let mut written = offset;
+
+        let response = self
+            .uploader
+            .download(url, offset, &mut |chunk: &[u8]| {
+                file.write_all(chunk).map_err(|e| format!("Failed to write update package: {}", e))?;
+                written += chunk.len() as u64;
+                self.progress.write().unwrap().downloaded_bytes = written;
+                Ok(())
+            })
+            .await?;
+
+        match response.status {
+            206 => Ok(()),
+            200 if offset == 0 => Ok(()),
+            // 服务端忽略了 Range (返回完整文件) 或已有数据无效，清空后从头下载
+            200 | 416 => {
+                let _ = file.set_len(0);
+                Err(format!("Ranged request rejected with status {}, restarting download", response.status))
+            }
+            status => Err(format!("Download failed with status: {}", status)),
+        }
+    }
+
+    fn set_state(&self, state: UpdateState, message: Option<String>) {
+        let mut progress = self.progress.write().unwrap();
+        progress.state = state;
+        progress.message = message;
+    }
+}
+
+fn verify_package(path: &Path, manifest: &UpdateManifest) -> Result<(), String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+
+    if size != manifest.size {
+        return Err(format!("Package size mismatch: expected {}, got {}", manifest.size, size));
+    }
+    let digest = hex::encode(hasher.finalize());
+    if !digest.eq_ignore_ascii_case(manifest.sha256.trim()) {
+        return Err(format!("Package SHA-256 mismatch: expected {}, got {}", manifest.sha256, digest));
+    }
+    Ok(())
+}
+
+/// 按数字段比较版本号 ("1.2.10" > "1.2.9")，无法解析的段按 0 处理
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Vec<u64> {
+        v.trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parse(candidate), parse(current));
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        let (x, y) = (a.get(i).copied().unwrap_or(0), b.get(i).copied().unwrap_or(0));
+        if x != y {
+            return x > y;
+        }
+    }
+    false
+}
@@ -0,0 +1,73 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use super::UpdateConfig;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureAlgorithm {
+    #[default]
+    Ed25519,
+    /// SM2 (默认用户 ID 1234567812345678)，签名为 DER 编码
+    Sm2,
+}
+
+/// 按固定公钥校验分离签名 (签名文件内容为 base64)
+pub struct SignatureVerifier {
+    algorithm: SignatureAlgorithm,
+    public_key: Vec<u8>,
+}
+
+impl SignatureVerifier {
+    pub fn new(algorithm: SignatureAlgorithm, public_key_hex: &str) -> Result<Self, String> {
+        let public_key = hex::decode(public_key_hex.trim())
+            .map_err(|e| format!("Invalid update public key: {}", e))?;
+        Ok(Self { algorithm, public_key })
+    }
+
+    pub fn from_config(config: &UpdateConfig) -> Result<Self, String> {
+        let key = config
+            .public_key
+            .as_deref()
+            .filter(|k| !k.trim().is_empty())
+            .ok_or_else(|| "No update public key configured, refusing to install updates".to_string())?;
+        Self::new(config.signature_algorithm, key)
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), String> {
+        let text = String::from_utf8_lossy(signature);
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(text.trim())
+            .map_err(|e| format!("Invalid update signature encoding: {}", e))?;
+
+        let valid = match self.algorithm {
+            SignatureAlgorithm::Ed25519 => {
+                let key: [u8; 32] = self
+                    .public_key
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| "Ed25519 public key must be 32 bytes".to_string())?;
+                let key = ed25519_dalek::VerifyingKey::from_bytes(&key)
+                    .map_err(|e| format!("Invalid Ed25519 public key: {}", e))?;
+                let signature = ed25519_dalek::Signature::from_slice(&signature)
+                    .map_err(|e| format!("Invalid Ed25519 signature: {}", e))?;
+                key.verify_strict(message, &signature).is_ok()
+            }
+            SignatureAlgorithm::Sm2 => {
+                let ctx = libsm::sm2::signature::SigCtx::new();
+                let key = ctx
+                    .load_pubkey(&self.public_key)
+                    .map_err(|e| format!("Invalid SM2 public key: {}", e))?;
+                let signature = libsm::sm2::signature::Signature::der_decode(&signature)
+                    .map_err(|e| format!("Invalid SM2 signature: {}", e))?;
+                ctx.verify(message, &key, &signature).unwrap_or(false)
+            }
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err("Update manifest signature verification failed".to_string())
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! 上传限速与受限网络模式
+//!
+//! 元数据 (日志) 与文件 (截图) 各有一个令牌桶，超出速率的请求等待令牌而不是失败；
+//! 策略 `upload_limits` 给出的每日上限按本地日期累计，超出后普通记录留到次日上传。
+//! 受限网络 (宿主检测到低数据模式 / 计费网络，或通过 IPC 手动设置) 下只上传元数据与高风险记录，
+//! 截图推迟到网络恢复后上传。高风险记录不受每日上限与受限模式影响，但仍然限速。
+
+#[cfg(target_os = "linux")]
+pub mod networkmanager;
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::clock::LogicalClock;
+use crate::models::UploadLimits;
+
+/// 高风险记录 (risk_level >= 2) 优先上传
+pub const HIGH_RISK_LEVEL: i32 = 2;
+/// 令牌桶容量 = 速率 × 该秒数，允许短时突发
+const BURST_SECS: f64 = 2.0;
+
+/// 限速配置 (config.json `server.bandwidth`)，缺省不限速
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_bytes_per_sec: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_bytes_per_sec: Option<u64>,
+    /// Linux：从 NetworkManager 读取计费网络 (Metered) 状态
+    #[serde(default)]
+    pub detect_metered: bool,
+}
+
+impl BandwidthConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficClass {
+    Metadata,
+    File,
+}
+
+/// 网络模式：自动 (按检测结果) / 强制受限 / 强制不受限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    #[default]
+    Auto,
+    Constrained,
+    Normal,
+}
+
+impl std::str::FromStr for NetworkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(NetworkMode::Auto),
+            "constrained" => Ok(NetworkMode::Constrained),
+            "normal" => Ok(NetworkMode::Normal),
+            other => Err(format!("Unknown network mode '{}' (expected auto / constrained / normal)", other)),
+        }
+    }
+}
+
+/// 推迟上传的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deferral {
+    Constrained,
+    DailyCap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_per_sec: Option<u64>,
+    pub used_today: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_cap: Option<u64>,
+    /// 累计推迟的记录数
+    pub deferred: u64,
+}
+
+/// IPC get_bandwidth_status 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct BandwidthStatus {
+    pub mode: NetworkMode,
+    /// 宿主 / NetworkManager 检测到受限或计费网络
+    pub detected: bool,
+    /// 当前是否按受限网络处理
+    pub constrained: bool,
+    pub day: String,
+    pub metadata: ClassStatus,
+    pub file: ClassStatus,
+}
+
+/// 令牌桶；令牌不足时记为欠账，调用方按欠账等待，保证大请求也能按速率发出
+struct TokenBucket {
+    rate: Option<u64>,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: Option<u64>) -> Self {
+        let mut bucket = Self { rate: None, tokens: 0.0, updated: Instant::now() };
+        bucket.set_rate(rate);
+        bucket
+    }
+
+    fn capacity(&self) -> f64 {
+        self.rate.map(|r| r as f64 * BURST_SECS).unwrap_or(0.0)
+    }
+
+    fn set_rate(&mut self, rate: Option<u64>) {
+        self.rate = rate.filter(|r| *r > 0);
+        self.tokens = self.capacity();
+        self.updated = Instant::now();
+    }
+
+    /// 取走 `bytes` 个令牌，返回需要等待的时间
+    fn reserve(&mut self, bytes: u64, now: Instant) -> Duration {
+        let Some(rate) = self.rate else {
+            return Duration::ZERO;
+        };
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(self.capacity());
+        self.updated = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate as f64)
+        }
+    }
+}
+
+/// 当日已上传字节数，日期变化时清零
+#[derive(Default)]
+struct DailyUsage {
+    day: String,
+    metadata: u64,
+    file: u64,
+}
+
+impl DailyUsage {
+    fn roll(&mut self, today: &str) {
+        if self.day != today {
+            *self = DailyUsage { day: today.to_string(), ..Default::default() };
+        }
+    }
+
+    fn get_mut(&mut self, class: TrafficClass) -> &mut u64 {
+        match class {
+            TrafficClass::Metadata => &mut self.metadata,
+            TrafficClass::File => &mut self.file,
+        }
+    }
+}
+
+pub struct Bandwidth {
+    metadata: Mutex<TokenBucket>,
+    file: Mutex<TokenBucket>,
+    usage: Mutex<DailyUsage>,
+    limits: RwLock<UploadLimits>,
+    mode: RwLock<NetworkMode>,
+    detected: AtomicBool,
+    deferred_metadata: AtomicU64,
+    deferred_file: AtomicU64,
+    /// 服务的共享逻辑时钟，每日上限按它的本地日期换日
+    clock: Arc<LogicalClock>,
+}
+
+impl Bandwidth {
+    pub fn new(clock: Arc<LogicalClock>) -> Self {
+        Self {
+            metadata: Mutex::new(TokenBucket::new(None)),
+            file: Mutex::new(TokenBucket::new(None)),
+            usage: Mutex::new(DailyUsage::default()),
+            limits: RwLock::new(UploadLimits::default()),
+            mode: RwLock::new(NetworkMode::Auto),
+            detected: AtomicBool::new(false),
+            deferred_metadata: AtomicU64::new(0),
+            deferred_file: AtomicU64::new(0),
+            clock,
+        }
+    }
+
+    pub fn configure(&self, config: &BandwidthConfig) {
+        self.metadata.lock().unwrap().set_rate(config.metadata_bytes_per_sec);
+        self.file.lock().unwrap().set_rate(config.file_bytes_per_sec);
+    }
+
+    /// 策略下发的每日上限
+    pub fn set_limits(&self, limits: UploadLimits) {
+        let mut current = self.limits.write().unwrap();
+        if *current != limits {
+            log::info!("Daily upload limits: {:?} -> {:?}", *current, limits);
+            *current = limits;
+        }
+    }
+
+    pub fn set_mode(&self, mode: NetworkMode) {
+        let mut current = self.mode.write().unwrap();
+        if *current != mode {
+            log::info!("Network mode set to {:?}", mode);
+            *current = mode;
+        }
+    }
+
+    /// 宿主或 NetworkManager 报告的网络状态
+    pub fn set_detected(&self, constrained: bool) {
+        if self.detected.swap(constrained, Ordering::Relaxed) != constrained {
+            log::info!("Network {} constrained / metered", if constrained { "is" } else { "is no longer" });
+        }
+    }
+
+    pub fn constrained(&self) -> bool {
+        match *self.mode.read().unwrap() {
+            NetworkMode::Auto => self.detected.load(Ordering::Relaxed),
+            NetworkMode::Constrained => true,
+            NetworkMode::Normal => false,
+        }
+    }
+
+    /// 是否现在上传该记录；推迟时计数，记录保留到下一轮
+    pub fn admit(&self, class: TrafficClass, risk_level: i32) -> Result<(), Deferral> {
+        if risk_level >= HIGH_RISK_LEVEL {
+            return Ok(());
+        }
+        let result = if class == TrafficClass::File && self.constrained() {
+            Err(Deferral::Constrained)
+        } else {
+            let cap = self.cap(class);
+            let mut usage = self.usage.lock().unwrap();
+            usage.roll(&self.today());
+            match cap {
+                Some(cap) if *usage.get_mut(class) >= cap => Err(Deferral::DailyCap),
+                _ => Ok(()),
+            }
+        };
+        if result.is_err() {
+            self.deferred_counter(class).fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// 计入当日用量并按速率等待
+    pub async fn throttle(&self, class: TrafficClass, bytes: usize) {
+        {
+            let mut usage = self.usage.lock().unwrap();
+            usage.roll(&self.today());
+            *usage.get_mut(class) += bytes as u64;
+        }
+        let wait = self.bucket(class).lock().unwrap().reserve(bytes as u64, Instant::now());
+        if !wait.is_zero() {
+            log::debug!("Throttling {:?} upload of {} bytes for {:?}", class, bytes, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn status(&self) -> BandwidthStatus {
+        let mut usage = self.usage.lock().unwrap();
+        usage.roll(&self.today());
+        let class_status = |class: TrafficClass, used: u64| ClassStatus {
+            bytes_per_sec: self.bucket(class).lock().unwrap().rate,
+            used_today: used,
+            daily_cap: self.cap(class),
+            deferred: self.deferred_counter(class).load(Ordering::Relaxed),
+        };
+        BandwidthStatus {
+            mode: *self.mode.read().unwrap(),
+            detected: self.detected.load(Ordering::Relaxed),
+            constrained: self.constrained(),
+            day: usage.day.clone(),
+            metadata: class_status(TrafficClass::Metadata, usage.metadata),
+            file: class_status(TrafficClass::File, usage.file),
+        }
+    }
+
+    fn cap(&self, class: TrafficClass) -> Option<u64> {
+        let limits = self.limits.read().unwrap();
+        match class {
+            TrafficClass::Metadata => limits.daily_metadata_bytes,
+            TrafficClass::File => limits.daily_file_bytes,
+        }
+    }
+
+    fn bucket(&self, class: TrafficClass) -> &Mutex<TokenBucket> {
+        match class {
+            TrafficClass::Metadata => &self.metadata,
+            TrafficClass::File => &self.file,
+        }
+    }
+
+    fn deferred_counter(&self, class: TrafficClass) -> &AtomicU64 {
+        match class {
+            TrafficClass::Metadata => &self.deferred_metadata,
+            TrafficClass::File => &self.deferred_file,
+        }
+    }
+
+    /// 本地日期 (每日上限的计算单位)
+    fn today(&self) -> String {
+        let now = self.clock.now_str();
+        now.get(..10).unwrap_or(&now).to_string()
+    }
+}
@@ -0,0 +1,53 @@
+//! Linux 计费网络检测：定期读取 NetworkManager 的 Metered 属性
+//!
+//! NMMetered：0 未知，1 是，2 否，3 推测是，4 推测否。NetworkManager 不可用时保持不受限。
+
+use std::sync::Arc;
+use std::time::Duration;
+use zbus::zvariant::OwnedValue;
+use zbus::Connection;
+
+use crate::lifecycle::ShutdownSignal;
+use crate::uploader::Uploader;
+
+const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn start(uploader: Arc<Uploader>, mut shutdown: ShutdownSignal) {
+    tokio::spawn(async move {
+        let conn = match Connection::system().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Metered network detection disabled: failed to connect to the system bus: {}", e);
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.wait() => break,
+            }
+            match metered(&conn).await {
+                Ok(value) => uploader.bandwidth().set_detected(is_metered(value)),
+                Err(e) => log::debug!("Failed to read NetworkManager Metered property: {}", e),
+            }
+        }
+    });
+}
+
+/// Metered 为 "是" 或 "推测是" 时按受限网络处理
+pub fn is_metered(value: u32) -> bool {
+    matches!(value, 1 | 3)
+}
+
+async fn metered(conn: &Connection) -> Result<u32, String> {
+    let reply = conn
+        .call_method(Some(NM_SERVICE), NM_PATH, Some(PROPERTIES_INTERFACE), "Get", &(NM_SERVICE, "Metered"))
+        .await
+        .map_err(|e| e.to_string())?;
+    let value = reply.body().deserialize::<OwnedValue>().map_err(|e| e.to_string())?;
+    u32::try_from(value).map_err(|e| e.to_string())
+}
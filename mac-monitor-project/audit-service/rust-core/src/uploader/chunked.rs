@@ -0,0 +1,232 @@
+//! 分块断点续传
+//!
+//! 1. `init`：提交文件名、大小与 SHA-256，服务端返回 upload_id 与分块大小；
+//! 2. `chunk`：按偏移逐块发送，服务端返回已确认的偏移 (偏移不一致时返回 409 和它记录的偏移)；
+//! 3. `complete`：服务端校验 SHA-256 后返回文件地址。
+//!
+//! 每块确认后的偏移写入数据库 (`upload_sessions`)，断线或重启后从该偏移继续。
+//! 服务端确认的偏移连续几次不前进时视为会话失效，重新 init (次数有限)。
+//! 服务端不支持 (init 返回 404 / 405 / 501) 时退回单次 multipart 上传。
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::Ordering;
+
+use super::bandwidth::TrafficClass;
+use super::transport::HttpRequest;
+use super::{endpoints, Uploader};
+use crate::db::Database;
+use crate::models::UploadSession;
+
+/// 服务端未指定时的分块大小
+pub const DEFAULT_CHUNK_SIZE: i64 = 256 * 1024;
+/// 续传会话失效 (服务端已丢弃) 时重新 init 的次数
+const MAX_RESTARTS: usize = 1;
+/// 服务端连续这么多次确认的偏移没有前进时放弃该会话，重新 init
+const MAX_STALLED_CHUNKS: usize = 3;
+
+#[derive(Serialize)]
+struct InitRequest<'a> {
+    #[serde(rename = "fileName")]
+    file_name: &'a str,
+    size: i64,
+    sha256: &'a str,
+    mime: &'a str,
+}
+
+#[derive(Serialize)]
+struct CompleteRequest<'a> {
+    #[serde(rename = "uploadId")]
+    upload_id: &'a str,
+    sha256: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkedResponse {
+    code: i32,
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    data: Option<ChunkedData>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChunkedData {
+    #[serde(default, alias = "uploadId")]
+    upload_id: Option<String>,
+    #[serde(default, alias = "chunkSize")]
+    chunk_size: Option<i64>,
+    /// 服务端已确认的字节数
+    #[serde(default)]
+    offset: Option<i64>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+enum Outcome {
+    Done(String),
+    /// 服务端不认识该 upload_id，需要重新 init
+    Expired,
+}
+
+impl Uploader {
+    /// 分块上传文件，返回服务端地址；服务端不支持分块时退回 `upload_file`
+    pub async fn upload_file_resumable(&self, file_path: &str, db: &Database) -> Result<String, String> {
+        if self.chunked_unsupported.load(Ordering::Relaxed) {
+            return self.upload_file(file_path).await;
+        }
+        let path = file_path.to_string();
+        let (size, sha256) = tokio::task::spawn_blocking(move || file_digest(&path))
+            .await
+            .map_err(|e| e.to_string())??;
+
+        for _ in 0..=MAX_RESTARTS {
+            let stored = db.get_upload_session(&sha256).await.map_err(|e| e.to_string())?;
+            let session = match stored {
+                Some(session) if session.size == size => {
+                    log::info!("Resuming upload of {} at {}/{} bytes", file_path, session.offset, size);
+                    session
+                }
+                _ => match self.init_upload(file_path, size, &sha256).await? {
+                    Some(session) => {
+                        db.save_upload_session(&session).await.map_err(|e| e.to_string())?;
+                        session
+                    }
+                    None => {
+                        log::info!("Server does not support chunked uploads, falling back to single requests");
+                        self.chunked_unsupported.store(true, Ordering::Relaxed);
+                        return self.upload_file(file_path).await;
+                    }
+                },
+            };
+
+            match self.send_chunks(session, file_path, db).await {
+                Ok(Outcome::Done(url)) => {
+                    db.delete_upload_session(&sha256).await.map_err(|e| e.to_string())?;
+                    return Ok(url);
+                }
+                Ok(Outcome::Expired) => {
+                    log::warn!("Upload session for {} expired on the server, starting over", file_path);
+                    db.delete_upload_session(&sha256).await.map_err(|e| e.to_string())?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(format!("Upload of {} could not be resumed", file_path))
+    }
+
+    /// 返回 None 表示服务端不支持分块上传
+    async fn init_upload(&self, file_path: &str, size: i64, sha256: &str) -> Result<Option<UploadSession>, String> {
+        let file_name = std::path::Path::new(file_path).file_name().and_then(|n| n.to_str()).unwrap_or("file.jpg");
+        let request = HttpRequest::post(self.url(endpoints::UPLOAD_INIT))
+            .json(&InitRequest { file_name, size, sha256, mime: "image/jpeg" })?;
+        let response = self.send_authorized(request).await?;
+        if matches!(response.status, 404 | 405 | 501) {
+            return Ok(None);
+        }
+        let res: ChunkedResponse = response.json()?;
+        let data = match res.code {
+            200 | 0 => res.data.unwrap_or_default(),
+            404 | 501 => return Ok(None),
+            _ => return Err(format!("Upload init failed: {}", res.msg)),
+        };
+        let upload_id = data.upload_id.ok_or_else(|| "No uploadId in upload init response".to_string())?;
+        Ok(Some(UploadSession {
+            sha256: sha256.to_string(),
+            upload_id,
+            file_path: file_path.to_string(),
+            size,
+            offset: data.offset.unwrap_or(0).clamp(0, size),
+            chunk_size: data.chunk_size.filter(|c| *c > 0).unwrap_or(DEFAULT_CHUNK_SIZE),
+        }))
+    }
+
+    async fn send_chunks(&self, mut session: UploadSession, file_path: &str, db: &Database) -> Result<Outcome, String> {
+        let mut stalled = 0;
+        while session.offset < session.size {
+            let len = session.chunk_size.min(session.size - session.offset);
+            let path = file_path.to_string();
+            let offset = session.offset;
+            let chunk = tokio::task::spawn_blocking(move || read_chunk(&path, offset, len as usize))
+                .await
+                .map_err(|e| e.to_string())??;
+
+            self.bandwidth.throttle(TrafficClass::File, chunk.len()).await;
+            let request = HttpRequest::post(self.url(endpoints::UPLOAD_CHUNK))
+                .query("uploadId", &session.upload_id)
+                .query("offset", &offset.to_string())
+                .bytes("application/octet-stream", chunk);
+            let response = self.send_authorized(request).await?;
+            if matches!(response.status, 404 | 410) {
+                return Ok(Outcome::Expired);
+            }
+            let res: ChunkedResponse = response.json()?;
+            let acked = match res.code {
+                200 | 0 => res.data.and_then(|d| d.offset).unwrap_or(offset + len),
+                // 偏移不一致 (如上次确认的响应丢失)，按服务端记录的偏移继续
+                409 => res.data.and_then(|d| d.offset).ok_or_else(|| format!("Upload chunk rejected: {}", res.msg))?,
+                404 | 410 => return Ok(Outcome::Expired),
+                _ => return Err(format!("Upload chunk failed at {}: {}", offset, res.msg)),
+            };
+            let acked = acked.clamp(0, session.size);
+            // 200 回显旧偏移或 409 给出不前进的偏移时不能无限重发同一块
+            if acked <= offset {
+                stalled += 1;
+                if stalled >= MAX_STALLED_CHUNKS {
+                    log::warn!("Upload of {} stalled at offset {} (server acked {}), starting over", file_path, offset, acked);
+                    return Ok(Outcome::Expired);
+                }
+            } else {
+                stalled = 0;
+            }
+            session.offset = acked;
+            db.update_upload_offset(&session.sha256, session.offset).await.map_err(|e| e.to_string())?;
+        }
+
+        let request = HttpRequest::post(self.url(endpoints::UPLOAD_COMPLETE))
+            .json(&CompleteRequest { upload_id: &session.upload_id, sha256: &session.sha256 })?;
+        let response = self.send_authorized(request).await?;
+        if matches!(response.status, 404 | 410) {
+            return Ok(Outcome::Expired);
+        }
+        let res: ChunkedResponse = response.json()?;
+        match res.code {
+            200 | 0 => res.data.and_then(|d| d.url).map(Outcome::Done).ok_or_else(|| "No URL in upload complete response".to_string()),
+            // 服务端收到的内容与摘要不一致，整个文件重新上传
+            400 | 404 | 410 | 422 => {
+                log::warn!("Upload of {} failed verification: {}", file_path, res.msg);
+                Ok(Outcome::Expired)
+            }
+            _ => Err(format!("Upload complete failed: {}", res.msg)),
+        }
+    }
+}
+
+/// (大小, SHA-256)，按块读取，不把整个文件读入内存
+fn file_digest(path: &str) -> Result<(i64, String), String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0i64;
+    loop {
+        let n = file.read(&mut buf).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as i64;
+    }
+    Ok((size, hex::encode(hasher.finalize())))
+}
+
+fn read_chunk(path: &str, offset: i64, len: usize) -> Result<Vec<u8>, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    file.seek(SeekFrom::Start(offset as u64)).map_err(|e| e.to_string())?;
+    let mut chunk = Vec::with_capacity(len);
+    file.take(len as u64).read_to_end(&mut chunk).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    if chunk.len() != len {
+        return Err(format!("{} changed during upload", path));
+    }
+    Ok(chunk)
+}
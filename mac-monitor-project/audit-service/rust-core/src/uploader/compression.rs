@@ -0,0 +1,139 @@
+//! 请求体压缩
+//!
+//! 客户端在心跳中声明支持的编码，服务端在心跳响应的 `content_encodings` 中返回它能解码的编码，
+//! 双方都支持时才压缩；服务端未返回该字段 (旧版本) 时一律发送原始数据。
+
+use serde::Serialize;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// 小于该大小的请求体压缩收益有限，直接发送
+pub const DEFAULT_MIN_BYTES: usize = 1024;
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    /// 按优先级排列的客户端支持列表
+    pub const SUPPORTED: [Encoding; 2] = [Encoding::Zstd, Encoding::Gzip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    pub fn encode(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Zstd => zstd::encode_all(data, ZSTD_LEVEL),
+            Encoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompressionStats {
+    pub encoding: Option<Encoding>,
+    /// 实际以压缩形式发送的请求数
+    pub compressed_requests: u64,
+    /// 压缩前 / 实际发送的请求体字节数 (含未压缩的请求)
+    pub raw_bytes: u64,
+    pub sent_bytes: u64,
+    pub bytes_saved: u64,
+}
+
+pub struct Compressor {
+    negotiated: RwLock<Option<Encoding>>,
+    min_bytes: RwLock<usize>,
+    compressed_requests: AtomicU64,
+    raw_bytes: AtomicU64,
+    sent_bytes: AtomicU64,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self {
+            negotiated: RwLock::new(None),
+            min_bytes: RwLock::new(DEFAULT_MIN_BYTES),
+            compressed_requests: AtomicU64::new(0),
+            raw_bytes: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Compressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_min_bytes(&self, min_bytes: usize) {
+        *self.min_bytes.write().unwrap() = min_bytes;
+    }
+
+    /// 根据服务端声明的编码选出双方都支持的第一个 (按客户端优先级)
+    pub fn negotiate(&self, server_encodings: &[String]) -> Option<Encoding> {
+        let chosen = Encoding::SUPPORTED
+            .into_iter()
+            .find(|e| server_encodings.iter().any(|s| s.trim().eq_ignore_ascii_case(e.as_str())));
+
+        let mut negotiated = self.negotiated.write().unwrap();
+        if *negotiated != chosen {
+            log::info!("Request compression negotiated: {:?} -> {:?}", *negotiated, chosen);
+            *negotiated = chosen;
+        }
+        chosen
+    }
+
+    pub fn encoding(&self) -> Option<Encoding> {
+        *self.negotiated.read().unwrap()
+    }
+
+    /// 按协商结果压缩请求体；未协商、低于阈值或压缩后没有变小时返回 `None`
+    pub fn compress(&self, data: &[u8]) -> Option<(Encoding, Vec<u8>)> {
+        let encoding = self.encoding()?;
+        if data.len() < *self.min_bytes.read().unwrap() {
+            return None;
+        }
+        match encoding.encode(data) {
+            Ok(compressed) if compressed.len() < data.len() => Some((encoding, compressed)),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Failed to {}-compress request body: {}", encoding.as_str(), e);
+                None
+            }
+        }
+    }
+
+    /// 记录一次请求的原始大小与实际发送大小
+    pub fn record(&self, raw_len: usize, sent_len: usize) {
+        self.raw_bytes.fetch_add(raw_len as u64, Ordering::Relaxed);
+        self.sent_bytes.fetch_add(sent_len as u64, Ordering::Relaxed);
+        if sent_len < raw_len {
+            self.compressed_requests.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> CompressionStats {
+        let raw_bytes = self.raw_bytes.load(Ordering::Relaxed);
+        let sent_bytes = self.sent_bytes.load(Ordering::Relaxed);
+        CompressionStats {
+            encoding: self.encoding(),
+            compressed_requests: self.compressed_requests.load(Ordering::Relaxed),
+            raw_bytes,
+            sent_bytes,
+            bytes_saved: raw_bytes.saturating_sub(sent_bytes),
+        }
+    }
+}
@@ -1,22 +1,170 @@
+pub mod bandwidth;
+pub mod chunked;
+pub mod compression;
+pub mod servers;
 pub mod sync;
+pub mod tls;
+pub mod transport;
 
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use self::bandwidth::{Bandwidth, TrafficClass};
+use self::compression::{CompressionStats, Compressor, Encoding};
+use self::servers::{FailoverConfig, ServerPool, ServerStatus};
+use self::tls::TlsConfig;
+use crate::clock::{format_ms, system_now_ms, LogicalClock};
+use crate::metrics::HeartbeatSummary;
+use crate::models::UploadBatch;
+use self::transport::{ChunkSink, HttpRequest, HttpResponse, RequestBody, ReqwestTransport, Transport, UnavailableTransport, TLS_ERROR_PREFIX};
+
+/// 管理端接口路径
+pub mod endpoints {
+    pub const LOGIN: &str = "/api/v1/login";
+    pub const ENROLL: &str = "/api/v1/device/enroll";
+    pub const UNENROLL: &str = "/api/v1/device/unenroll";
+    pub const HEARTBEAT: &str = "/api/v1/heartbeat";
+    pub const POLICY: &str = "/api/v1/config/policy";
+    pub const LOG_AUDIT: &str = "/api/v1/log/audit";
+    pub const LOG_BEHAVIOR: &str = "/api/v1/log/behavior";
+    pub const LOG_SCREENSHOT: &str = "/api/v1/log/screenshot";
+    pub const LOG_CLIPBOARD: &str = "/api/v1/log/clipboard";
+    pub const LOG_FILE: &str = "/api/v1/log/file";
+    pub const LOG_PERIPHERAL: &str = "/api/v1/log/peripheral";
+    pub const LOG_TRAFFIC_SUMMARY: &str = "/api/v1/log/traffic/summary";
+    pub const LOG_USAGE_DAILY: &str = "/api/v1/log/usage/daily";
+    pub const ALERT: &str = "/api/v1/alert";
+    pub const UPLOAD_SCREENSHOT: &str = "/api/v1/upload/screenshot";
+    pub const UPLOAD_INIT: &str = "/api/v1/upload/init";
+    pub const UPLOAD_CHUNK: &str = "/api/v1/upload/chunk";
+    pub const UPLOAD_COMPLETE: &str = "/api/v1/upload/complete";
+    pub const SERVER_TIME: &str = "/httpsaudit/zf/api/third/server/time";
+    pub const POP_LIST: &str = "/api/v1/pop/list";
+    pub const UPDATE: &str = "/api/v1/maintenance/update";
+    pub const CERT: &str = "/api/v1/maintenance/cert";
+    /// 备用服务端健康探测 (不需要登录)
+    pub const HEALTH: &str = "/api/v1/health";
+    /// 各轮同步由哪台服务端接收的记录
+    pub const LOG_BATCHES: &str = "/api/v1/log/batches";
+}
+
+/// 所有需要登录态的接口统一使用该请求头携带 token
+pub const AUTH_HEADER: &str = "visit-token";
+/// 登录响应未给出有效期时按此处理
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(3600);
+/// 到期前提前刷新的时间
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+/// 登录失败后匿名访问，间隔该时间再尝试登录 (被拒绝 401/403 时立即重试)
+const LOGIN_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// 上传请求携带本轮同步的批次 id，服务端据此去重
+pub const BATCH_HEADER: &str = "X-Upload-Batch";
 
 #[derive(Debug, Clone)]
 struct UploaderConfig {
     app_code: String,
     app_secret: String,
-    base_url: String,
     serial_number: String,
+    device_name: String,
+    os_version: String,
+    credential: Option<DeviceCredential>,
+}
+
+/// 注册后由管理端签发的设备凭据，登录时代替匿名访问
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceCredential {
+    pub device_id: String,
+    pub secret: String,
+}
+
+/// 当前一轮同步：批次 id 与各 (服务端, 接口) 接收的记录数
+struct UploadRound {
+    batch_id: String,
+    accepted: BTreeMap<(String, String), i64>,
 }
 
 pub struct Uploader {
-    client: Client,
+    transport: RwLock<Arc<dyn Transport>>,
     config: RwLock<UploaderConfig>,
-    visit_token: Arc<Mutex<Option<String>>>,
+    servers: ServerPool,
+    batch: Mutex<Option<UploadRound>>,
+    tls: RwLock<TlsConfig>,
+    auth: Mutex<AuthInner>,
+    // 串行化登录，避免并发请求同时刷新 token
+    login_lock: tokio::sync::Mutex<()>,
+    last_error: Mutex<Option<ConnectionError>>,
+    compressor: Compressor,
+    bandwidth: Bandwidth,
+    // 服务的共享逻辑时钟 (由心跳校准)，token 过期与错误时间都按它计算
+    clock: Arc<LogicalClock>,
+    // 服务端不支持分块上传时直接使用单次上传 (切换服务端后重新探测)
+    chunked_unsupported: AtomicBool,
+}
+
+/// 登录状态：匿名 (服务端允许) / 已登录 / 登录失败 (退回匿名访问)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthState {
+    Anonymous,
+    Authenticated,
+    Failed,
+}
+
+/// 登录状态，通过 IPC 提供给 GUI (不含 token)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthStatus {
+    pub state: AuthState,
+    pub header: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_login: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct CachedToken {
+    value: String,
+    expires_at: Instant,
+    expires_at_ms: i64,
+}
+
+struct AuthInner {
+    token: Option<CachedToken>,
+    state: AuthState,
+    last_login: Option<String>,
+    last_error: Option<String>,
+    retry_after: Option<Instant>,
+}
+
+impl AuthInner {
+    fn new() -> Self {
+        Self { token: None, state: AuthState::Anonymous, last_login: None, last_error: None, retry_after: None }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionError {
+    pub message: String,
+    /// 证书校验 / TLS 配置错误 (区别于普通网络错误)
+    pub tls: bool,
+    pub time: String,
+}
+
+/// 管理端连接状态，通过 IPC 提供给 GUI
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    pub base_url: String,
+    pub https: bool,
+    pub ca_bundle_path: Option<String>,
+    pub pinned_keys: usize,
+    pub mutual_tls: bool,
+    pub last_error: Option<ConnectionError>,
+    pub compression: CompressionStats,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,7 +185,18 @@ pub struct UploadResponse {
     pub code: i32,
     pub msg: String,
     pub url: Option<String>,
-    pub fileName: Option<String>,
+    #[serde(rename = "fileName")]
+    pub file_name: Option<String>,
+}
+
+/// 条件获取策略的结果
+#[derive(Debug)]
+pub enum PolicyFetch {
+    NotModified,
+    Updated {
+        policy: Box<crate::models::PolicyConfig>,
+        etag: Option<String>,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -50,6 +209,46 @@ pub struct MonitorDeviceLogin {
     pub os_version: String,
     #[serde(rename = "appVersion")]
     pub app_version: String,
+    #[serde(rename = "deviceId", skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+/// 设备注册请求：真实身份 + 管理员下发的 PIN
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrollRequest {
+    #[serde(rename = "serialNumber")]
+    pub serial_number: String,
+    #[serde(rename = "machineId", skip_serializing_if = "Option::is_none")]
+    pub machine_id: Option<String>,
+    #[serde(rename = "deviceName")]
+    pub device_name: String,
+    #[serde(rename = "osVersion")]
+    pub os_version: String,
+    #[serde(rename = "appVersion")]
+    pub app_version: String,
+    pub mac: String,
+    pub ip: String,
+    pub pin: String,
+    /// 重新注册时携带原设备 id，服务端可沿用
+    #[serde(rename = "previousDeviceId", skip_serializing_if = "Option::is_none")]
+    pub previous_device_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnrollResponse {
+    pub code: i32,
+    pub msg: String,
+    pub data: Option<EnrollData>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnrollData {
+    pub device_id: String,
+    pub credential: String,
+    #[serde(default)]
+    pub expires_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,106 +261,486 @@ pub struct LoginResponse {
 #[derive(Debug, Deserialize)]
 pub struct LoginData {
     pub token: String,
+    /// token 有效期 (秒)
+    #[serde(default, alias = "expiresIn")]
+    pub expires_in: Option<u64>,
 }
 
 impl Uploader {
-    pub fn new(app_code: &str, app_secret: &str, base_url: &str, serial_number: &str) -> Self {
+    pub fn new(app_code: &str, app_secret: &str, base_url: &str, serial_number: &str, clock: Arc<LogicalClock>) -> Self {
+        Self::with_transport(Arc::new(ReqwestTransport::new()), app_code, app_secret, base_url, serial_number, clock)
+    }
+
+    /// 使用自定义传输层 (如测试中的 mock 实现)
+    pub fn with_transport(
+        transport: Arc<dyn Transport>,
+        app_code: &str,
+        app_secret: &str,
+        base_url: &str,
+        serial_number: &str,
+        clock: Arc<LogicalClock>,
+    ) -> Self {
         Self {
-            client: Client::new(),
+            transport: RwLock::new(transport),
             config: RwLock::new(UploaderConfig {
                 app_code: app_code.to_string(),
                 app_secret: app_secret.to_string(),
-                base_url: base_url.to_string(),
                 serial_number: serial_number.to_string(),
+                device_name: "Unknown-Host".to_string(),
+                os_version: std::env::consts::OS.to_string(),
+                credential: None,
             }),
-            visit_token: Arc::new(Mutex::new(None)),
+            servers: ServerPool::new(base_url),
+            batch: Mutex::new(None),
+            tls: RwLock::new(TlsConfig::default()),
+            auth: Mutex::new(AuthInner::new()),
+            login_lock: tokio::sync::Mutex::new(()),
+            last_error: Mutex::new(None),
+            compressor: Compressor::new(),
+            bandwidth: Bandwidth::new(clock.clone()),
+            clock,
+            chunked_unsupported: AtomicBool::new(false),
         }
     }
 
+    /// 请求体压缩阈值 (字节)
+    pub fn set_compression_threshold(&self, min_bytes: usize) {
+        self.compressor.set_min_bytes(min_bytes);
+    }
+
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.compressor.stats()
+    }
+
+    /// 上传限速、每日上限与受限网络状态
+    pub fn bandwidth(&self) -> &Bandwidth {
+        &self.bandwidth
+    }
+
+    /// 服务的共享逻辑时钟
+    pub fn clock(&self) -> &Arc<LogicalClock> {
+        &self.clock
+    }
+
+    /// 按协商的编码压缩上传请求体，并记录节省的字节数
+    fn compressed(&self, mut request: HttpRequest) -> HttpRequest {
+        let Some((content_type, raw)) = request.body.to_bytes() else {
+            return request;
+        };
+        match self.compressor.compress(&raw) {
+            Some((encoding, data)) => {
+                self.compressor.record(raw.len(), data.len());
+                request.body = RequestBody::Raw { content_type, data };
+                request.header("Content-Encoding", encoding.as_str())
+            }
+            None => {
+                self.compressor.record(raw.len(), raw.len());
+                request
+            }
+        }
+    }
+
+    /// 应用 TLS 信任配置 (私有 CA / SPKI 指纹 / 设备证书)
+    ///
+    /// 配置无效时返回错误，并且之后的所有请求都会失败，不会退回到未校验的连接。
+    pub fn configure_tls(&self, tls: TlsConfig) -> Result<(), String> {
+        let result = tls.build_client();
+        let transport: Arc<dyn Transport> = match &result {
+            Ok(client) => Arc::new(ReqwestTransport::with_client(client.clone())),
+            Err(e) => {
+                log::error!("Invalid TLS configuration: {}", e);
+                self.record_error(&format!("{}: {}", TLS_ERROR_PREFIX, e));
+                Arc::new(UnavailableTransport::new(format!("{}: {}", TLS_ERROR_PREFIX, e)))
+            }
+        };
+        *self.transport.write().unwrap() = transport;
+        *self.tls.write().unwrap() = tls;
+        self.reset_auth();
+        result.map(|_| ())
+    }
+
+    pub fn connection_status(&self) -> ConnectionStatus {
+        let base_url = self.servers.active_url();
+        let tls = self.tls.read().unwrap();
+        ConnectionStatus {
+            https: base_url.starts_with("https://"),
+            base_url,
+            ca_bundle_path: tls.ca_bundle_path.clone(),
+            pinned_keys: tls.spki_pins.len(),
+            mutual_tls: tls.is_mutual(),
+            last_error: self.last_error.lock().unwrap().clone(),
+            compression: self.compressor.stats(),
+        }
+    }
+
+    fn record_error(&self, message: &str) {
+        *self.last_error.lock().unwrap() = Some(ConnectionError {
+            message: message.to_string(),
+            tls: message.starts_with(TLS_ERROR_PREFIX),
+            time: self.clock.now_str(),
+        });
+    }
+
     pub fn update_config(&self, app_code: &str, app_secret: &str, base_url: &str, serial_number: &str) {
         let mut config = self.config.write().unwrap();
         config.app_code = app_code.to_string();
         config.app_secret = app_secret.to_string();
-        config.base_url = base_url.to_string();
         config.serial_number = serial_number.to_string();
+        drop(config);
+        self.servers.set_primary(base_url);
 
         // Clear cached token on config change
-        let mut token = self.visit_token.lock().unwrap();
-        *token = None;
+        self.reset_auth();
+        self.chunked_unsupported.store(false, Ordering::Relaxed);
     }
 
-    async fn get_token(&self) -> String {
-        let (base_url, serial_number) = {
-            let config = self.config.read().unwrap();
-            (config.base_url.clone(), config.serial_number.clone())
-        };
+    /// 登录 / 注册时上报的主机名与系统版本
+    pub fn set_device_profile(&self, device_name: &str, os_version: &str) {
+        let mut config = self.config.write().unwrap();
+        config.device_name = device_name.to_string();
+        config.os_version = os_version.to_string();
+    }
 
-        {
-            let token = self.visit_token.lock().unwrap();
-            if let Some(t) = token.as_ref() {
-                return t.clone();
+    /// 设置 (或清除) 设备凭据，缓存的 token 随之失效
+    pub fn set_credential(&self, credential: Option<DeviceCredential>) {
+        self.config.write().unwrap().credential = credential;
+        self.reset_auth();
+    }
+
+    pub fn device_id(&self) -> Option<String> {
+        self.config.read().unwrap().credential.as_ref().map(|c| c.device_id.clone())
+    }
+
+    /// 配置的主服务端地址 (故障切换期间请求发往 `active_url`)
+    pub fn base_url(&self) -> String {
+        self.servers.primary_url()
+    }
+
+    /// 当前接收请求的服务端
+    pub fn active_url(&self) -> String {
+        self.servers.active_url()
+    }
+
+    /// 只切换主服务端地址，保留备用列表、app_code / app_secret 与设备凭据
+    pub fn set_base_url(&self, base_url: &str) {
+        self.servers.set_primary(base_url);
+        self.server_switched();
+    }
+
+    /// 设置主备服务端列表与切换条件，从主服务端开始
+    pub fn configure_servers(&self, primary: &str, standby: &[String], failover: FailoverConfig) {
+        self.servers.configure(primary, standby, failover);
+        self.server_switched();
+    }
+
+    pub fn has_standby_servers(&self) -> bool {
+        self.servers.has_standby()
+    }
+
+    pub fn server_status(&self) -> Vec<ServerStatus> {
+        self.servers.status()
+    }
+
+    /// token 与分块上传支持都按服务端区分，切换后重新登录 / 探测
+    fn server_switched(&self) {
+        self.reset_auth();
+        self.chunked_unsupported.store(false, Ordering::Relaxed);
+    }
+
+    /// 探测备用服务端 (任何非 5xx 响应视为可用)，用于故障切换与切回
+    pub async fn check_servers(&self) {
+        let transport = self.transport.read().unwrap().clone();
+        for url in self.servers.standby_urls() {
+            let started = Instant::now();
+            match transport.send(HttpRequest::get(format!("{}{}", url, endpoints::HEALTH))).await {
+                Ok(response) if response.status < 500 => self.servers.record_success(&url, started.elapsed()),
+                Ok(response) => {
+                    self.servers.record_failure(&url, &format!("Health check failed with status: {}", response.status));
+                }
+                Err(e) => {
+                    self.servers.record_failure(&url, &e);
+                }
             }
         }
+    }
 
-        // 调用登录接口尝试获取 Token (即使失败也继续，因为服务器现在允许匿名)
-        let login_info = MonitorDeviceLogin {
-            serial_number,
-            device_name: "Mac-Client".to_string(),
-            os_version: "macOS 15.1".to_string(),
-            app_version: "0.1.0".to_string(),
+    /// 开始一轮同步：按需切回更靠前的服务端，之后的上传都带上批次 id
+    pub fn begin_batch(&self) -> String {
+        if let Some(url) = self.servers.failback() {
+            log::info!("Management server {} is healthy again, switching back", url);
+            self.server_switched();
+        }
+        let batch_id = uuid::Uuid::new_v4().to_string();
+        *self.batch.lock().unwrap() = Some(UploadRound { batch_id: batch_id.clone(), accepted: BTreeMap::new() });
+        batch_id
+    }
+
+    /// 结束本轮同步，返回各服务端接收的记录数
+    pub fn end_batch(&self) -> Vec<UploadBatch> {
+        let Some(UploadRound { batch_id, accepted }) = self.batch.lock().unwrap().take() else {
+            return Vec::new();
         };
+        let accepted_at = format_ms(system_now_ms());
+        accepted
+            .into_iter()
+            .map(|((server_url, endpoint), records)| UploadBatch {
+                id: None,
+                batch_id: batch_id.clone(),
+                server_url,
+                endpoint,
+                records,
+                accepted_at: accepted_at.clone(),
+            })
+            .collect()
+    }
+
+    pub fn tls_config(&self) -> TlsConfig {
+        self.tls.read().unwrap().clone()
+    }
 
-        let url = format!("{}/api/v1/login", base_url);
-        let response = self.client.post(&url)
-            .json(&login_info)
-            .send()
-            .await;
-
-        match response {
-            Ok(resp) => {
-                if let Ok(res) = resp.json::<LoginResponse>().await {
-                    if (res.code == 200 || res.code == 0) && res.data.is_some() {
-                        let token = res.data.unwrap().token;
-                        let mut token_lock = self.visit_token.lock().unwrap();
-                        *token_lock = Some(token.clone());
-                        return token;
-                    }
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}{}", self.servers.active_url(), endpoint)
+    }
+
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, String> {
+        let transport = self.transport.read().unwrap().clone();
+        let url = request.url.clone();
+        let started = Instant::now();
+        let result = transport.send(request).await;
+        let switched = match &result {
+            Ok(response) if response.status >= 500 => {
+                self.servers.record_failure(&url, &format!("HTTP {}", response.status))
+            }
+            Ok(_) => {
+                self.servers.record_success(&url, started.elapsed());
+                None
+            }
+            Err(e) => self.servers.record_failure(&url, e),
+        };
+        if let Some(next) = switched {
+            log::warn!("Management server for {} keeps failing, switching to {}", url, next);
+            self.server_switched();
+        }
+        match result {
+            Ok(response) => {
+                *self.last_error.lock().unwrap() = None;
+                Ok(response)
+            }
+            Err(e) => {
+                if e.starts_with(TLS_ERROR_PREFIX) {
+                    log::error!("{}", e);
                 }
+                self.record_error(&e);
+                Err(e)
             }
-            Err(e) => eprintln!("Optional login failed: {}", e),
         }
+    }
 
-        "".to_string() // 登录失败返回空字符串，匿名访问
+    /// 管理端或凭据变化后需要重新登录
+    fn reset_auth(&self) {
+        *self.auth.lock().unwrap() = AuthInner::new();
     }
 
-    fn generate_signature(&self, _timestamp: u64, _nonce: &str) -> String {
-        "simple_sig".to_string()
+    pub fn auth_status(&self) -> AuthStatus {
+        let auth = self.auth.lock().unwrap();
+        AuthStatus {
+            state: auth.state,
+            header: AUTH_HEADER.to_string(),
+            device_id: self.device_id(),
+            expires_at: auth.token.as_ref().map(|t| crate::clock::format_ms(t.expires_at_ms)),
+            last_login: auth.last_login.clone(),
+            last_error: auth.last_error.clone(),
+        }
     }
 
-    pub async fn upload_data<T: Serialize>(&self, endpoint: &str, data: &T) -> Result<(), String> {
-        let base_url = {
-            self.config.read().unwrap().base_url.clone()
+    /// 丢弃当前 token，之后的请求按需重新登录
+    pub fn logout(&self) {
+        let mut auth = self.auth.lock().unwrap();
+        auth.token = None;
+        auth.state = AuthState::Anonymous;
+        auth.retry_after = None;
+    }
+
+    /// 立即重新登录 (IPC login)，登录失败时返回错误
+    pub async fn login(&self) -> Result<AuthStatus, String> {
+        let _login = self.login_lock.lock().await;
+        self.request_token().await?;
+        Ok(self.auth_status())
+    }
+
+    /// 有效 token；临近过期时提前刷新，刷新失败时在过期前继续使用旧 token。
+    /// 返回 None 表示匿名访问 (服务端允许)。
+    async fn get_token(&self) -> Option<String> {
+        if let Some(token) = self.cached_token(TOKEN_REFRESH_MARGIN) {
+            return Some(token);
+        }
+        let _login = self.login_lock.lock().await;
+        // 等锁期间其他请求可能已完成登录
+        if let Some(token) = self.cached_token(TOKEN_REFRESH_MARGIN) {
+            return Some(token);
+        }
+        let retry_after = self.auth.lock().unwrap().retry_after;
+        if retry_after.is_some_and(|t| Instant::now() < t) {
+            return self.cached_token(Duration::ZERO);
+        }
+        match self.request_token().await {
+            Ok(Some(token)) => Some(token),
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("Login failed, continuing anonymously: {}", e);
+                self.cached_token(Duration::ZERO)
+            }
+        }
+    }
+
+    /// 距过期超过 `margin` 的缓存 token
+    fn cached_token(&self, margin: Duration) -> Option<String> {
+        let auth = self.auth.lock().unwrap();
+        let token = auth.token.as_ref()?;
+        (Instant::now() + margin < token.expires_at).then(|| token.value.clone())
+    }
+
+    /// 调用登录接口；服务端未返回 token 时为匿名 (Ok(None))
+    async fn request_token(&self) -> Result<Option<String>, String> {
+        let config = self.config.read().unwrap().clone();
+        // 已注册的设备使用凭据登录
+        let login_info = MonitorDeviceLogin {
+            serial_number: config.serial_number,
+            device_name: config.device_name,
+            os_version: config.os_version,
+            app_version: crate::APP_VERSION.to_string(),
+            device_id: config.credential.as_ref().map(|c| c.device_id.clone()),
+            credential: config.credential.map(|c| c.secret),
         };
-        let url = format!("{}{}", base_url, endpoint);
 
+        let request = HttpRequest::post(self.url(endpoints::LOGIN)).json(&login_info)?;
+        let result = match self.send(request).await {
+            Ok(resp) if resp.is_success() => resp.json::<LoginResponse>(),
+            Ok(resp) => Err(format!("Login failed with status: {}", resp.status)),
+            Err(e) => Err(e),
+        };
+        let now = self.clock.now_str();
+        let mut auth = self.auth.lock().unwrap();
+        match result {
+            Ok(LoginResponse { code: 200 | 0, data: Some(data), .. }) => {
+                let ttl = data.expires_in.map(Duration::from_secs).unwrap_or(DEFAULT_TOKEN_TTL);
+                auth.token = Some(CachedToken {
+                    value: data.token.clone(),
+                    expires_at: Instant::now() + ttl,
+                    expires_at_ms: self.clock.now_ms() + ttl.as_millis() as i64,
+                });
+                auth.state = AuthState::Authenticated;
+                auth.last_login = Some(now);
+                auth.last_error = None;
+                auth.retry_after = None;
+                Ok(Some(data.token))
+            }
+            Ok(res) if login_info.device_id.is_none() => {
+                // 未注册的设备允许匿名访问
+                log::debug!("Login returned no token ({}), continuing anonymously", res.msg);
+                auth.token = None;
+                auth.state = AuthState::Anonymous;
+                auth.last_error = Some(res.msg);
+                auth.retry_after = Some(Instant::now() + LOGIN_RETRY_INTERVAL);
+                Ok(None)
+            }
+            Ok(res) => {
+                log::warn!("Device credential rejected by server: {}", res.msg);
+                auth.state = AuthState::Failed;
+                auth.last_error = Some(format!("Credential rejected: {}", res.msg));
+                auth.retry_after = Some(Instant::now() + LOGIN_RETRY_INTERVAL);
+                Err(format!("Credential rejected: {}", res.msg))
+            }
+            Err(e) => {
+                auth.state = AuthState::Failed;
+                auth.last_error = Some(e.clone());
+                auth.retry_after = Some(Instant::now() + LOGIN_RETRY_INTERVAL);
+                Err(e)
+            }
+        }
+    }
+
+    /// 携带 token 发送；被拒绝 (401/403) 时重新登录并重试一次
+    async fn send_authorized(&self, request: HttpRequest) -> Result<HttpResponse, String> {
         let token = self.get_token().await;
-        let mut request = self.client.post(&url);
-        if !token.is_empty() {
-            request = request.header("visit-token", token);
+        let response = self.send(with_auth(request.clone(), token.as_deref())).await?;
+        if !auth_rejected(&response) {
+            return Ok(response);
+        }
+
+        log::warn!("{} rejected with status {}, logging in again", request.url, response.status);
+        {
+            let mut auth = self.auth.lock().unwrap();
+            // 并发请求可能已经换了新 token
+            if auth.token.as_ref().map(|t| &t.value) == token.as_ref() {
+                auth.token = None;
+            }
+            auth.retry_after = None;
+        }
+        let token = self.get_token().await;
+        let response = self.send(with_auth(request, token.as_deref())).await?;
+        if auth_rejected(&response) {
+            let mut auth = self.auth.lock().unwrap();
+            auth.state = AuthState::Failed;
+            auth.last_error = Some(format!("Request rejected with status {} after re-login", response.status));
+        }
+        Ok(response)
+    }
+
+    /// 提交注册请求，返回服务端签发的设备 id 与凭据
+    pub async fn enroll(&self, request: &EnrollRequest) -> Result<EnrollData, String> {
+        let response = self.send(HttpRequest::post(self.url(endpoints::ENROLL)).json(request)?).await?;
+        if !response.is_success() {
+            return Err(format!("Enrollment failed with status: {}", response.status));
+        }
+        let res: EnrollResponse = response.json()?;
+        match (res.code, res.data) {
+            (200 | 0, Some(data)) if !data.device_id.is_empty() && !data.credential.is_empty() => Ok(data),
+            (200 | 0, _) => Err("Enrollment response has no device credential".to_string()),
+            (_, _) => Err(format!("Enrollment rejected: {}", res.msg)),
         }
+    }
+
+    /// 通知服务端注销设备 (使用当前凭据登录)
+    pub async fn unenroll(&self, device_id: &str) -> Result<(), String> {
+        let request = HttpRequest::post(self.url(endpoints::UNENROLL)).json(&serde_json::json!({ "deviceId": device_id }))?;
+        let response = self.send_authorized(request).await?;
+        if !response.is_success() {
+            return Err(format!("Unenrollment failed with status: {}", response.status));
+        }
+        let res: serde_json::Value = response.json().unwrap_or_default();
+        match res["code"].as_i64() {
+            Some(200 | 0) | None => Ok(()),
+            Some(_) => Err(format!("Unenrollment rejected: {}", res["msg"].as_str().unwrap_or_default())),
+        }
+    }
 
-        let response = request
-            .json(data)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+    #[allow(dead_code)]
+    fn generate_signature(&self, _timestamp: u64, _nonce: &str) -> String {
+        "simple_sig".to_string()
+    }
 
-        if response.status().is_success() {
+    pub async fn upload_data<T: Serialize>(&self, endpoint: &str, data: &T) -> Result<(), String> {
+        let server = self.servers.active_url();
+        let url = format!("{}{}", server, endpoint);
+        let mut request = HttpRequest::post(&url).json(data)?;
+        let batch_id = self.batch.lock().unwrap().as_ref().map(|round| round.batch_id.clone());
+        if let Some(batch_id) = &batch_id {
+            request = request.header(BATCH_HEADER, batch_id.as_str());
+        }
+        let request = self.compressed(request);
+        self.bandwidth.throttle(TrafficClass::Metadata, request.body.byte_len()).await;
+        let response = self.send_authorized(request).await?;
+
+        if response.is_success() {
+            if let Some(round) = self.batch.lock().unwrap().as_mut() {
+                if Some(&round.batch_id) == batch_id.as_ref() {
+                    *round.accepted.entry((server, endpoint.to_string())).or_default() += 1;
+                }
+            }
             Ok(())
         } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            Err(format!("Upload failed to {} with status: {}. Body: {}", url, status, body))
+            Err(format!("Upload failed to {} with status: {}. Body: {}", url, response.status, response.text()))
         }
     }
 
@@ -173,24 +752,13 @@ impl Uploader {
             .unwrap_or("file.jpg")
             .to_string();
 
-        let part = reqwest::multipart::Part::bytes(file_content)
-            .file_name(file_name)
-            .mime_str("image/jpeg")
-            .map_err(|e| e.to_string())?;
+        let request = HttpRequest::post(self.url(endpoints::UPLOAD_SCREENSHOT))
+            .file("file", &file_name, "image/jpeg", file_content);
+        let request = self.compressed(request);
+        self.bandwidth.throttle(TrafficClass::File, request.body.byte_len()).await;
+        let response = self.send_authorized(request).await?;
 
-        let form = reqwest::multipart::Form::new().part("file", part);
-
-        let base_url = {
-            self.config.read().unwrap().base_url.clone()
-        };
-        let url = format!("{}/api/v1/upload/screenshot", base_url);
-        let response = self.client.post(&url)
-            .multipart(form)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
-
-        let res: UploadResponse = response.json().await.map_err(|e| e.to_string())?;
+        let res: UploadResponse = response.json()?;
         if res.code == 200 || res.code == 0 {
             res.url.ok_or_else(|| "No URL in upload response".to_string())
         } else {
@@ -199,13 +767,7 @@ impl Uploader {
     }
 
     pub async fn get_server_time(&self) -> Result<u64, String> {
-        let base_url = {
-            self.config.read().unwrap().base_url.clone()
-        };
-        let response = self.client.get(format!("{}/httpsaudit/zf/api/third/server/time", base_url))
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let response = self.send(HttpRequest::get(self.url(endpoints::SERVER_TIME))).await?;
 
         #[derive(Deserialize)]
         struct TimeResponse {
@@ -216,122 +778,154 @@ impl Uploader {
             systime: String,
         }
 
-        let res: TimeResponse = response.json().await.map_err(|e| e.to_string())?;
+        let res: TimeResponse = response.json()?;
         // 尝试解析字符串时间为 Unix 时间戳，或直接要求后端返回数字
         res.data.systime.parse::<u64>().map_err(|_| "Invalid server time format".to_string())
     }
 
-    pub async fn heartbeat(&self, current_version: &str) -> Result<crate::models::HeartbeatResponse, String> {
-        let (base_url, serial_number) = {
-            let config = self.config.read().unwrap();
-            (config.base_url.clone(), config.serial_number.clone())
-        };
+    pub async fn heartbeat(
+        &self,
+        current_version: &str,
+        policy_version: Option<&str>,
+        metrics: Option<&HeartbeatSummary>,
+    ) -> Result<crate::models::HeartbeatResponse, String> {
+        let serial_number = self.config.read().unwrap().serial_number.clone();
 
         let data = serde_json::json!({
             "serialNumber": serial_number,
+            "deviceId": self.device_id(),
             "app_version": current_version,
+            "policyVersion": policy_version,
+            "supportedEncodings": Encoding::SUPPORTED.map(|e| e.as_str()),
+            "metrics": metrics,
             "timestamp": SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
         });
 
-        let token = self.get_token().await;
-
-        let mut request = self.client.post(format!("{}/api/v1/heartbeat", base_url));
-        if !token.is_empty() {
-            request = request.header("visit-token", token);
+        let request = HttpRequest::post(self.url(endpoints::HEARTBEAT)).json(&data)?;
+        let response = self.send_authorized(request).await?;
+        if !response.is_success() {
+            return Err(format!("Heartbeat failed with status: {}", response.status));
         }
 
-        let response = request.json(&data)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
-
-        response.json().await.map_err(|e| e.to_string())
+        let res: crate::models::HeartbeatResponse = response.json()?;
+        self.compressor.negotiate(&res.content_encodings);
+        Ok(res)
     }
 
     pub async fn get_pop_list(&self) -> Result<Vec<crate::models::PopNode>, String> {
-        let token = self.get_token().await;
-        let base_url = {
-            self.config.read().unwrap().base_url.clone()
-        };
-
-        let mut request = self.client.get(format!("{}/api/v1/pop/list", base_url));
-        if !token.is_empty() {
-            request = request.header("visit-token", token);
-        }
-
-        let response = request.send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let response = self.send_authorized(HttpRequest::get(self.url(endpoints::POP_LIST))).await?;
 
         #[derive(Deserialize)]
         struct PopListResponse {
             data: Vec<crate::models::PopNode>,
         }
 
-        let res: PopListResponse = response.json().await.map_err(|e| e.to_string())?;
+        let res: PopListResponse = response.json()?;
         Ok(res.data)
     }
 
     pub async fn check_update(&self) -> Result<crate::models::UpdateInfo, String> {
-        let base_url = {
-            self.config.read().unwrap().base_url.clone()
-        };
-        let response = self.client.get(format!("{}/api/v1/maintenance/update", base_url))
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let serial_number = self.config.read().unwrap().serial_number.clone();
+        let request = HttpRequest::get(self.url(endpoints::UPDATE))
+            .query("currentVersion", crate::APP_VERSION)
+            .query("serialNumber", &serial_number);
+        let response = self.send_authorized(request).await?;
 
         #[derive(Deserialize)]
         struct UpdateResponse {
             data: crate::models::UpdateInfo,
         }
 
-        let res: UpdateResponse = response.json().await.map_err(|e| e.to_string())?;
+        let res: UpdateResponse = response.json()?;
         Ok(res.data)
     }
 
-    pub async fn get_config(&self) -> Result<crate::models::PolicyConfig, String> {
-        let (base_url, serial_number) = {
-            let config = self.config.read().unwrap();
-            (config.base_url.clone(), config.serial_number.clone())
-        };
+    /// 条件获取策略：携带上次的 ETag，服务端未变更时返回 `NotModified`
+    pub async fn get_config(&self, etag: Option<&str>) -> Result<PolicyFetch, String> {
+        let serial_number = self.config.read().unwrap().serial_number.clone();
 
-        let token = self.get_token().await;
+        let mut request = HttpRequest::get(self.url(endpoints::POLICY))
+            .query("serialNumber", &serial_number);
+        if let Some(etag) = etag {
+            request = request.header("If-None-Match", etag);
+        }
 
-        let mut request = self.client.get(format!("{}/api/v1/config/policy", base_url))
-            .query(&[("serialNumber", &serial_number)]);
+        let response = self.send_authorized(request).await?;
 
-        if !token.is_empty() {
-            request = request.header("Authorization", format!("Bearer {}", token));
+        if response.status == 304 {
+            return Ok(PolicyFetch::NotModified);
         }
 
-        let response = request.send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let etag = response.header("ETag").map(|v| v.to_string());
 
-        let res: crate::models::ConfigResponse = response.json().await.map_err(|e| e.to_string())?;
+        let res: crate::models::ConfigResponse = response.json()?;
         if res.code == 200 || res.code == 0 {
-            res.data.ok_or_else(|| "No policy config in response".to_string())
+            let policy = res.data.ok_or_else(|| "No policy config in response".to_string())?;
+            Ok(PolicyFetch::Updated { policy: Box::new(policy), etag })
         } else {
             Err(format!("Get config failed: {}", res.msg))
         }
     }
 
+    /// 绝对地址原样返回，相对路径拼接到管理端地址上
+    pub fn resolve_url(&self, url: &str) -> String {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            url.to_string()
+        } else {
+            self.url(url)
+        }
+    }
+
+    /// 获取小文件 (如更新清单 / 签名)
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>, String> {
+        let url = self.resolve_url(url);
+        let response = self.send(HttpRequest::get(&url)).await?;
+        if response.is_success() {
+            Ok(response.body)
+        } else {
+            Err(format!("GET {} failed with status: {}", url, response.status))
+        }
+    }
+
+    /// 从 `offset` 处续传下载，数据块交给 `sink`；返回的响应仅含状态码与响应头
+    pub async fn download(&self, url: &str, offset: u64, sink: &mut ChunkSink<'_>) -> Result<HttpResponse, String> {
+        let mut request = HttpRequest::get(self.resolve_url(url));
+        if offset > 0 {
+            request = request.header("Range", format!("bytes={}-", offset));
+        }
+        let transport = self.transport.read().unwrap().clone();
+        transport.download(request, sink).await.inspect_err(|e| self.record_error(e))
+    }
+
     pub async fn get_cert_info(&self) -> Result<crate::models::CertInfo, String> {
-        let base_url = {
-            self.config.read().unwrap().base_url.clone()
-        };
-        let response = self.client.get(format!("{}/api/v1/maintenance/cert", base_url))
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let response = self.send(HttpRequest::get(self.url(endpoints::CERT))).await?;
 
         #[derive(Deserialize)]
         struct CertResponse {
             data: crate::models::CertInfo,
         }
 
-        let res: CertResponse = response.json().await.map_err(|e| e.to_string())?;
+        let res: CertResponse = response.json()?;
         Ok(res.data)
     }
 }
+
+fn with_auth(request: HttpRequest, token: Option<&str>) -> HttpRequest {
+    match token {
+        Some(token) => request.header(AUTH_HEADER, token),
+        None => request,
+    }
+}
+
+/// HTTP 401/403，或 HTTP 200 但响应体 code 为 401/403 (管理端的统一响应格式)
+fn auth_rejected(response: &HttpResponse) -> bool {
+    if matches!(response.status, 401 | 403) {
+        return true;
+    }
+    #[derive(Deserialize)]
+    struct Code {
+        code: Option<i64>,
+    }
+    response.body.len() < 4096
+        && response.json::<Code>().ok().and_then(|c| c.code).is_some_and(|code| code == 401 || code == 403)
+}
@@ -0,0 +1,220 @@
+//! 主备管理端：按配置顺序排列，记录每台的健康状况并在连续失败时切换
+//!
+//! 当前服务端健康时一直使用 (粘滞)，不会因为更靠前的服务端恢复就在一轮同步中途切回；
+//! 更靠前的服务端持续健康 `failback_secs` 后，在下一轮同步开始时切回。
+
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_FAILBACK_SECS: u64 = 300;
+/// 健康分按最近请求结果指数平滑，新结果占的权重
+const SCORE_WEIGHT: f64 = 0.2;
+
+/// 主备切换配置 (config.json `server.failover`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailoverConfig {
+    /// 当前服务端连续失败 (网络错误 / 超时 / 5xx) 达到该次数后切换
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// 更靠前的服务端持续健康该时间后切回
+    #[serde(default = "default_failback_secs")]
+    pub failback_secs: u64,
+}
+
+fn default_failure_threshold() -> u32 {
+    DEFAULT_FAILURE_THRESHOLD
+}
+
+fn default_failback_secs() -> u64 {
+    DEFAULT_FAILBACK_SECS
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self { failure_threshold: DEFAULT_FAILURE_THRESHOLD, failback_secs: DEFAULT_FAILBACK_SECS }
+    }
+}
+
+impl FailoverConfig {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// IPC get_servers 返回的单台服务端状态
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerStatus {
+    pub url: String,
+    pub active: bool,
+    pub healthy: bool,
+    /// 0-100，按最近请求 / 探测结果平滑
+    pub score: u8,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct Server {
+    url: String,
+    score: f64,
+    consecutive_failures: u32,
+    latency_ms: Option<u64>,
+    last_error: Option<String>,
+    healthy_since: Option<Instant>,
+}
+
+impl Server {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            score: 100.0,
+            consecutive_failures: 0,
+            latency_ms: None,
+            last_error: None,
+            healthy_since: Some(Instant::now()),
+        }
+    }
+
+    /// 请求地址是否发往这台服务端
+    fn serves(&self, url: &str) -> bool {
+        url.strip_prefix(self.url.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+    }
+}
+
+struct PoolInner {
+    servers: Vec<Server>,
+    active: usize,
+    config: FailoverConfig,
+}
+
+impl PoolInner {
+    fn healthy(&self, index: usize) -> bool {
+        self.servers[index].consecutive_failures < self.config.failure_threshold.max(1)
+    }
+}
+
+pub struct ServerPool {
+    inner: RwLock<PoolInner>,
+}
+
+impl ServerPool {
+    pub fn new(primary: &str) -> Self {
+        Self {
+            inner: RwLock::new(PoolInner {
+                servers: vec![Server::new(primary)],
+                active: 0,
+                config: FailoverConfig::default(),
+            }),
+        }
+    }
+
+    /// 替换服务端列表 (主 + 备)，从主服务端开始
+    pub fn configure(&self, primary: &str, standby: &[String], config: FailoverConfig) {
+        let mut servers = vec![Server::new(primary)];
+        for url in standby {
+            if !servers.iter().any(|s| s.url == url.trim_end_matches('/')) {
+                servers.push(Server::new(url));
+            }
+        }
+        *self.inner.write().unwrap() = PoolInner { servers, active: 0, config };
+    }
+
+    /// 替换主服务端 (注册到新的管理端)，保留备用列表，并切到主服务端
+    pub fn set_primary(&self, primary: &str) {
+        let mut inner = self.inner.write().unwrap();
+        inner.servers[0] = Server::new(primary);
+        inner.active = 0;
+    }
+
+    pub fn primary_url(&self) -> String {
+        self.inner.read().unwrap().servers[0].url.clone()
+    }
+
+    pub fn active_url(&self) -> String {
+        let inner = self.inner.read().unwrap();
+        inner.servers[inner.active].url.clone()
+    }
+
+    /// 是否配置了备用服务端
+    pub fn has_standby(&self) -> bool {
+        self.inner.read().unwrap().servers.len() > 1
+    }
+
+    /// 除当前服务端外的地址 (用于健康探测)
+    pub fn standby_urls(&self) -> Vec<String> {
+        let inner = self.inner.read().unwrap();
+        inner.servers.iter().enumerate().filter(|(i, _)| *i != inner.active).map(|(_, s)| s.url.clone()).collect()
+    }
+
+    /// 记录一次成功的请求 / 探测
+    pub fn record_success(&self, url: &str, latency: Duration) {
+        let mut inner = self.inner.write().unwrap();
+        let Some(server) = inner.servers.iter_mut().find(|s| s.serves(url)) else {
+            return;
+        };
+        server.score = server.score * (1.0 - SCORE_WEIGHT) + 100.0 * SCORE_WEIGHT;
+        server.consecutive_failures = 0;
+        server.latency_ms = Some(latency.as_millis() as u64);
+        server.healthy_since.get_or_insert_with(Instant::now);
+    }
+
+    /// 记录一次失败；当前服务端连续失败达到阈值时切到下一台，返回新的地址
+    pub fn record_failure(&self, url: &str, error: &str) -> Option<String> {
+        let mut inner = self.inner.write().unwrap();
+        let index = inner.servers.iter().position(|s| s.serves(url))?;
+        let server = &mut inner.servers[index];
+        server.score *= 1.0 - SCORE_WEIGHT;
+        server.consecutive_failures += 1;
+        server.last_error = Some(error.to_string());
+        if inner.healthy(index) {
+            return None;
+        }
+        inner.servers[index].healthy_since = None;
+        if index != inner.active {
+            return None;
+        }
+
+        // 优先选健康的，其次按健康分，同等条件按配置顺序
+        let next = (0..inner.servers.len()).filter(|i| *i != index).max_by(|a, b| {
+            (inner.healthy(*a), inner.servers[*a].score)
+                .partial_cmp(&(inner.healthy(*b), inner.servers[*b].score))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.cmp(a))
+        })?;
+        inner.active = next;
+        Some(inner.servers[next].url.clone())
+    }
+
+    /// 更靠前的服务端已持续健康 `failback_secs` 时切回，返回新的地址；只在两轮同步之间调用
+    pub fn failback(&self) -> Option<String> {
+        let mut inner = self.inner.write().unwrap();
+        let hold = Duration::from_secs(inner.config.failback_secs);
+        let preferred = (0..inner.active).find(|i| {
+            inner.healthy(*i) && inner.servers[*i].healthy_since.is_some_and(|since| since.elapsed() >= hold)
+        })?;
+        inner.active = preferred;
+        Some(inner.servers[preferred].url.clone())
+    }
+
+    pub fn status(&self) -> Vec<ServerStatus> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .servers
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ServerStatus {
+                url: s.url.clone(),
+                active: i == inner.active,
+                healthy: inner.healthy(i),
+                score: s.score.round() as u8,
+                consecutive_failures: s.consecutive_failures,
+                latency_ms: s.latency_ms,
+                last_error: s.last_error.clone(),
+            })
+            .collect()
+    }
+}
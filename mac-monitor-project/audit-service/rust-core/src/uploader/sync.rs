@@ -2,19 +2,26 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use tokio::time::{self, Duration};
 use crate::db::Database;
-use crate::uploader::Uploader;
-use crate::models::PolicyConfig;
+use crate::lifecycle::ShutdownSignal;
+use crate::metrics::Metrics;
+use crate::models::UploadBatch;
+use crate::uploader::bandwidth::TrafficClass;
+use crate::uploader::{endpoints, Uploader};
+use crate::policy::PolicyStore;
 use crate::scanner::Scanner;
 
-use crate::clock::LogicalClock;
+use crate::clock::{system_now_ms, LogicalClock};
 
 pub struct SyncService {
     db: Arc<Database>,
     uploader: Arc<Uploader>,
     clock: Arc<LogicalClock>,
-    policy: Arc<RwLock<PolicyConfig>>,
-    device_info: crate::models::DeviceInfo,
+    policy: Arc<PolicyStore>,
+    device_info: Arc<RwLock<crate::models::DeviceInfo>>,
     screenshot_dir: String,
+    metrics: Arc<Metrics>,
+    // 定时同步与 IPC `sync_now` 可能同时触发，同一时间只跑一轮，避免重复上传
+    round: tokio::sync::Mutex<()>,
 }
 
 impl SyncService {
@@ -22,73 +29,156 @@ impl SyncService {
         db: Arc<Database>,
         uploader: Arc<Uploader>,
         clock: Arc<LogicalClock>,
-        policy: Arc<RwLock<PolicyConfig>>,
-        device_info: crate::models::DeviceInfo,
+        policy: Arc<PolicyStore>,
+        device_info: Arc<RwLock<crate::models::DeviceInfo>>,
         screenshot_dir: String,
+        metrics: Arc<Metrics>,
     ) -> Self {
-        Self { db, uploader, clock, policy, device_info, screenshot_dir }
+        Self { db, uploader, clock, policy, device_info, screenshot_dir, metrics, round: tokio::sync::Mutex::new(()) }
     }
 
-    pub fn start(self) {
+    /// 启动后台同步循环，收到停止信号后在当前一轮结束时退出
+    pub fn start(self: Arc<Self>, mut shutdown: ShutdownSignal) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_secs(30)); // 每 30 秒同步一次
-            let mut scanner = Scanner::new(self.db.clone(), self.policy.clone(), self.device_info.clone());
+            let mut scanner = Scanner::new(
+                self.db.clone(),
+                self.policy.shared(),
+                self.device_info.clone(),
+                self.clock.clone(),
+            );
+            // 启动后首次心跳总是做一次条件拉取，确认本地缓存的策略仍是最新
+            let mut policy_checked = false;
             loop {
-                interval.tick().await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
 
                 // 1. 执行扫描检测异常进程和程序
+                let scan_started = std::time::Instant::now();
                 scanner.scan().await;
+                self.metrics.record_scan(scan_started.elapsed().as_millis() as i64);
 
                 // 2. 执行心跳与较时
-                if let Err(e) = self.do_heartbeat().await {
-                    eprintln!("Heartbeat failed: {}", e);
+                match self.do_heartbeat(!policy_checked).await {
+                    Ok(()) => policy_checked = true,
+                    Err(e) => log::warn!("Heartbeat failed: {}", e),
+                }
+
+                // 3. 探测备用服务端，供故障切换与切回参考
+                if self.uploader.has_standby_servers() {
+                    self.uploader.check_servers().await;
                 }
 
-                // 3. 同步日志
+                // 4. 同步日志
                 if let Err(e) = self.sync_logs().await {
-                    eprintln!("Sync failed: {}", e);
+                    log::warn!("Sync failed: {}", e);
                 }
             }
-        });
+            log::info!("Sync service stopped");
+        })
     }
 
-    async fn do_heartbeat(&self) -> Result<(), String> {
-        let res = self.uploader.heartbeat("0.1.0").await?;
-        
-        // 更新逻辑时钟
-        self.clock.update_offset(res.server_time.unwrap_or(0) as i64);
+    /// 心跳、较时，并按需刷新策略 / 执行远程指令
+    pub async fn do_heartbeat(&self, force_policy_refresh: bool) -> Result<(), String> {
+        let policy_version = self.policy.current_version();
+        let summary = match self.metrics.heartbeat_summary().await {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                log::warn!("Failed to collect heartbeat metrics: {}", e);
+                None
+            }
+        };
+        let sent_ms = system_now_ms();
+        let res = self
+            .uploader
+            .heartbeat(crate::APP_VERSION, policy_version.as_deref(), summary.as_ref())
+            .await
+            .inspect_err(|_| self.metrics.record_heartbeat(None))?;
+        let received_ms = system_now_ms();
+        self.metrics.record_heartbeat(Some(received_ms - sent_ms));
 
-        if res.need_update {
-            println!("Policy update needed, fetching latest config...");
-            match self.uploader.get_config().await {
-                Ok(new_policy) => {
-                    let mut p = self.policy.write().unwrap();
-                    *p = new_policy;
-                    println!("Policy updated: {} processes, {} apps blacklisted",
-                        p.process_blacklist.len(), p.app_blacklist.len());
-                }
-                Err(e) => eprintln!("Failed to fetch policy: {}", e),
+        // 更新逻辑时钟 (按 RTT 补偿，缺失 server_time 时保持原偏移)
+        let server_time = res.server_time.or(res.server_logic_clock).map(|t| t as i64);
+        if let Some(offset) = self.clock.record_exchange(sent_ms, server_time, received_ms) {
+            log::info!("Logical clock offset: {}ms (rtt {}ms)", offset, received_ms - sent_ms);
+        }
+
+        if res.need_update || force_policy_refresh {
+            log::info!("Checking for policy update (current version: {:?})...", policy_version);
+            match self.policy.refresh(&self.uploader).await {
+                Ok(true) => log::info!("Policy updated to version {:?}", self.policy.current_version()),
+                Ok(false) => {}
+                Err(e) => log::error!("Failed to refresh policy: {}", e),
             }
         }
 
         for cmd in res.commands {
-            println!("Received remote command: {} ({})", cmd.command_id, cmd.op_type);
-            // TODO: 处理远程指令
+            log::info!("Received remote command: {} ({})", cmd.command_id, cmd.op_type);
+            // TODO: 处理其他远程指令
+            if cmd.op_type == "policy_rollback" {
+                if let Err(e) = self.policy.rollback().await {
+                    log::error!("Remote policy rollback failed: {}", e);
+                }
+            }
         }
 
         Ok(())
     }
 
-    async fn sync_logs(&self) -> Result<(), String> {
+    /// 上传所有未同步的日志，失败的记录保留到下一轮重试
+    pub async fn sync_logs(&self) -> Result<(), String> {
+        let _round = self.round.lock().await;
+        let before = self.uploader.compression_stats();
+        let failures_before = self.metrics.upload_failure_total();
+        let limits = self.policy.shared().read().unwrap().upload_limits;
+        self.uploader.bandwidth().set_limits(limits);
+        let deferred_before = self.deferred_total();
+        self.uploader.begin_batch();
+        let result = self.upload_pending().await;
+        let batches = self.uploader.end_batch();
+        if self.uploader.has_standby_servers() {
+            if let Err(e) = self.report_batches(&batches).await {
+                log::warn!("Failed to report upload batches: {}", e);
+            }
+        }
+        let deferred = self.deferred_total() - deferred_before;
+        if deferred > 0 {
+            log::info!(
+                "Deferred {} records (constrained network: {})",
+                deferred,
+                self.uploader.bandwidth().constrained()
+            );
+        }
+        self.metrics.record_sync(&result, self.metrics.upload_failure_total() > failures_before);
+
+        let after = self.uploader.compression_stats();
+        let raw = after.raw_bytes - before.raw_bytes;
+        if raw > 0 {
+            let sent = after.sent_bytes - before.sent_bytes;
+            log::info!(
+                "Sync round uploaded {} bytes ({} on the wire, {} saved by {:?} compression; {} saved in total)",
+                raw, sent, raw - sent, after.encoding, after.bytes_saved
+            );
+        }
+        result
+    }
+
+    async fn upload_pending(&self) -> Result<(), String> {
         // 1. 同步审计日志 (即流量探测日志)
         let audit_logs = self.db.get_unsent_audit_logs().await.map_err(|e| e.to_string())?;
         for log in audit_logs {
-            match self.uploader.upload_data("/api/v1/log/audit", &log).await {
+            if self.defer(TrafficClass::Metadata, log.risk_level) {
+                continue;
+            }
+            match self.uploader.upload_data(endpoints::LOG_AUDIT, &log).await {
                 Ok(_) => {
                     self.db.mark_audit_log_sent(&log.id).await.map_err(|e| e.to_string())?;
                 }
                 Err(e) => {
-                    eprintln!("Failed to upload audit log {}: {}", log.id, e);
+                    self.metrics.record_upload_failure(endpoints::LOG_AUDIT);
+                    log::warn!("Failed to upload audit log {}: {}", log.id, e);
                 }
             }
         }
@@ -96,15 +186,19 @@ impl SyncService {
         // 2. 同步行为日志
         let behavior_logs = self.db.get_unsent_behavior_logs().await.map_err(|e| e.to_string())?;
         for log in behavior_logs {
+            if self.defer(TrafficClass::Metadata, log.risk_level) {
+                continue;
+            }
             // 注意：API 路径仅为示例，需根据实际接口文档调整
-            match self.uploader.upload_data("/api/v1/log/behavior", &log).await {
+            match self.uploader.upload_data(endpoints::LOG_BEHAVIOR, &log).await {
                 Ok(_) => {
                     if let Some(id) = log.id {
                          self.db.mark_behavior_log_sent(id).await.map_err(|e| e.to_string())?;
                     }
                 }
                 Err(e) => {
-                    eprintln!("Failed to upload behavior log {:?}: {}", log.id, e);
+                    self.metrics.record_upload_failure(endpoints::LOG_BEHAVIOR);
+                    log::warn!("Failed to upload behavior log {:?}: {}", log.id, e);
                 }
             }
         }
@@ -112,9 +206,27 @@ impl SyncService {
         // 3. 同步截图日志
         let screenshot_logs = self.db.get_unsent_screenshot_logs().await.map_err(|e| e.to_string())?;
         for mut log in screenshot_logs {
+            // 策略降级的截图只有元数据，没有图片文件
+            let class = if log.image_path.is_empty() { TrafficClass::Metadata } else { TrafficClass::File };
+            if self.defer(class, log.risk_level) {
+                continue;
+            }
+            if log.image_path.is_empty() {
+                match self.uploader.upload_data(endpoints::LOG_SCREENSHOT, &log).await {
+                    Ok(_) => {
+                        self.db.mark_screenshot_log_sent(&log.image_hash).await.map_err(|e| e.to_string())?;
+                    }
+                    Err(e) => {
+                        self.metrics.record_upload_failure(endpoints::LOG_SCREENSHOT);
+                        log::warn!("Failed to upload screenshot metadata {}: {}", log.image_hash, e);
+                    }
+                }
+                continue;
+            }
+
             let resolved_path = self.resolve_screenshot_path(&log.image_path);
             if !Path::new(&resolved_path).exists() {
-                eprintln!(
+                log::warn!(
                     "Screenshot file missing after path resolution, marking as sent: {}",
                     log.image_path
                 );
@@ -126,26 +238,28 @@ impl SyncService {
                 log.image_path = resolved_path;
             }
             // 3.1 首先上传真实的图片文件
-            match self.uploader.upload_file(&log.image_path).await {
+            match self.uploader.upload_file_resumable(&log.image_path, &self.db).await {
                 Ok(remote_url) => {
                     // 3.2 替换为服务器端的 URL
                     let local_path = log.image_path.clone();
                     log.image_path = remote_url;
 
                     // 3.3 上传元数据
-                    match self.uploader.upload_data("/api/v1/log/screenshot", &log).await {
+                    match self.uploader.upload_data(endpoints::LOG_SCREENSHOT, &log).await {
                         Ok(_) => {
                             self.db.mark_screenshot_log_sent(&log.image_hash).await.map_err(|e| e.to_string())?;
                         }
                         Err(e) => {
-                            eprintln!("Failed to upload screenshot metadata {}: {}", log.image_hash, e);
+                            self.metrics.record_upload_failure(endpoints::LOG_SCREENSHOT);
+                            log::warn!("Failed to upload screenshot metadata {}: {}", log.image_hash, e);
                             // 恢复本地路径，以便下次重试
                             log.image_path = local_path;
                         }
                     }
                 }
                 Err(e) => {
-                    eprintln!("Failed to upload screenshot file {}: {}", log.image_path, e);
+                    self.metrics.record_upload_failure(endpoints::UPLOAD_SCREENSHOT);
+                    log::warn!("Failed to upload screenshot file {}: {}", log.image_path, e);
                 }
             }
         }
@@ -153,14 +267,94 @@ impl SyncService {
         // 4. 同步剪贴板日志
         let clipboard_logs = self.db.get_unsent_clipboard_logs().await.map_err(|e| e.to_string())?;
         for log in clipboard_logs {
-            match self.uploader.upload_data("/api/v1/log/clipboard", &log).await {
+            if self.defer(TrafficClass::Metadata, log.risk_level) {
+                continue;
+            }
+            match self.uploader.upload_data(endpoints::LOG_CLIPBOARD, &log).await {
                 Ok(_) => {
                     if let Some(id) = log.id {
                         self.db.mark_clipboard_log_sent(id).await.map_err(|e| e.to_string())?;
                     }
                 }
                 Err(e) => {
-                    eprintln!("Failed to upload clipboard log {:?}: {}", log.id, e);
+                    self.metrics.record_upload_failure(endpoints::LOG_CLIPBOARD);
+                    log::warn!("Failed to upload clipboard log {:?}: {}", log.id, e);
+                }
+            }
+        }
+
+        // 5. 同步文件操作日志
+        let file_logs = self.db.get_unsent_file_logs().await.map_err(|e| e.to_string())?;
+        for log in file_logs {
+            if self.defer(TrafficClass::Metadata, log.risk_level) {
+                continue;
+            }
+            match self.uploader.upload_data(endpoints::LOG_FILE, &log).await {
+                Ok(_) => {
+                    if let Some(id) = log.id {
+                        self.db.mark_file_log_sent(id).await.map_err(|e| e.to_string())?;
+                    }
+                }
+                Err(e) => {
+                    self.metrics.record_upload_failure(endpoints::LOG_FILE);
+                    log::warn!("Failed to upload file log {:?}: {}", log.id, e);
+                }
+            }
+        }
+
+        // 6. 同步外设日志
+        let peripheral_logs = self.db.get_unsent_peripheral_logs().await.map_err(|e| e.to_string())?;
+        for log in peripheral_logs {
+            if self.defer(TrafficClass::Metadata, log.risk_level) {
+                continue;
+            }
+            match self.uploader.upload_data(endpoints::LOG_PERIPHERAL, &log).await {
+                Ok(_) => {
+                    if let Some(id) = log.id {
+                        self.db.mark_peripheral_log_sent(id).await.map_err(|e| e.to_string())?;
+                    }
+                }
+                Err(e) => {
+                    self.metrics.record_upload_failure(endpoints::LOG_PERIPHERAL);
+                    log::warn!("Failed to upload peripheral log {:?}: {}", log.id, e);
+                }
+            }
+        }
+
+        // 7. 同步流量聚合记录
+        let summaries = self.db.get_unsent_traffic_summaries().await.map_err(|e| e.to_string())?;
+        for summary in summaries {
+            if self.defer(TrafficClass::Metadata, summary.risk_level) {
+                continue;
+            }
+            match self.uploader.upload_data(endpoints::LOG_TRAFFIC_SUMMARY, &summary).await {
+                Ok(_) => {
+                    if let Some(id) = summary.id {
+                        self.db.mark_traffic_summary_sent(id).await.map_err(|e| e.to_string())?;
+                    }
+                }
+                Err(e) => {
+                    self.metrics.record_upload_failure(endpoints::LOG_TRAFFIC_SUMMARY);
+                    log::warn!("Failed to upload traffic summary {:?}: {}", summary.id, e);
+                }
+            }
+        }
+
+        // 8. 同步每日应用使用汇总 (每天一条)
+        let summaries = self.db.get_unsent_usage_summaries().await.map_err(|e| e.to_string())?;
+        for summary in summaries {
+            if self.defer(TrafficClass::Metadata, 0) {
+                continue;
+            }
+            match self.uploader.upload_data(endpoints::LOG_USAGE_DAILY, &summary).await {
+                Ok(_) => {
+                    if let Some(id) = summary.id {
+                        self.db.mark_usage_summary_sent(id).await.map_err(|e| e.to_string())?;
+                    }
+                }
+                Err(e) => {
+                    self.metrics.record_upload_failure(endpoints::LOG_USAGE_DAILY);
+                    log::warn!("Failed to upload usage summary for {}: {}", summary.day, e);
                 }
             }
         }
@@ -168,6 +362,28 @@ impl SyncService {
         Ok(())
     }
 
+    /// 记录本轮各服务端接收的记录数，并上报尚未上报的批次 (切回后服务端据此去重)
+    async fn report_batches(&self, batches: &[UploadBatch]) -> Result<(), String> {
+        self.db.save_upload_batches(batches).await.map_err(|e| e.to_string())?;
+        let pending = self.db.get_unreported_upload_batches().await.map_err(|e| e.to_string())?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.uploader.upload_data(endpoints::LOG_BATCHES, &serde_json::json!({ "batches": pending })).await?;
+        let ids: Vec<i64> = pending.iter().filter_map(|b| b.id).collect();
+        self.db.mark_upload_batches_reported(&ids).await.map_err(|e| e.to_string())
+    }
+
+    /// 受限网络或超过每日上限时推迟上传 (记录保持未上传状态)
+    fn defer(&self, class: TrafficClass, risk_level: i32) -> bool {
+        self.uploader.bandwidth().admit(class, risk_level).is_err()
+    }
+
+    fn deferred_total(&self) -> u64 {
+        let status = self.uploader.bandwidth().status();
+        status.metadata.deferred + status.file.deferred
+    }
+
     fn resolve_screenshot_path(&self, image_path: &str) -> String {
         let path = Path::new(image_path);
         if path.exists() {
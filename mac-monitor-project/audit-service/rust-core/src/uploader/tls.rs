@@ -0,0 +1,204 @@
+//! 管理端连接的 TLS 信任配置
+//!
+//! - `ca_bundle_path`：私有 CA 证书 (PEM，可包含多张)；配置后只信任这些 CA，不再信任公共根证书
+//! - `spki_pins`：服务端证书链中任一证书的 SPKI SHA-256 (base64，可带 "sha256/" 前缀) 必须命中
+//! - `client_cert_path` / `client_key_path`：设备证书与私钥 (PEM)，用于双向 TLS 认证
+
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spki_pins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key_path: Option<String>,
+}
+
+impl TlsConfig {
+    /// 未做任何定制时直接使用 reqwest 默认配置
+    pub fn is_default(&self) -> bool {
+        self == &TlsConfig::default()
+    }
+
+    pub fn is_mutual(&self) -> bool {
+        self.client_cert_path.is_some()
+    }
+
+    /// 按配置构建 HTTP 客户端；证书 / 私钥 / 指纹无效时返回明确的错误
+    pub fn build_client(&self) -> Result<reqwest::Client, String> {
+        if self.is_default() {
+            return Ok(reqwest::Client::new());
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = PinnedVerifier::new(self.root_store()?, self.parse_pins()?, provider.clone())?;
+
+        let builder = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("TLS setup failed: {}", e))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+
+        let config = match (&self.client_cert_path, &self.client_key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                let chain = load_certs(cert_path)?;
+                let key = PrivateKeyDer::from_pem_file(key_path)
+                    .map_err(|e| format!("Failed to load client key {}: {}", key_path, e))?;
+                builder
+                    .with_client_auth_cert(chain, key)
+                    .map_err(|e| format!("Client certificate {} rejected: {}", cert_path, e))?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => return Err("client_cert_path and client_key_path must be configured together".to_string()),
+        };
+
+        reqwest::Client::builder()
+            .use_preconfigured_tls(config)
+            .build()
+            .map_err(|e| format!("Failed to build HTTPS client: {}", e))
+    }
+
+    fn root_store(&self) -> Result<RootCertStore, String> {
+        let mut roots = RootCertStore::empty();
+        match &self.ca_bundle_path {
+            Some(path) => {
+                let certs = load_certs(path)?;
+                let (added, ignored) = roots.add_parsable_certificates(certs);
+                if added == 0 {
+                    return Err(format!("CA bundle {} contains no usable certificates", path));
+                }
+                if ignored > 0 {
+                    log::warn!("Ignored {} unparsable certificates in CA bundle {}", ignored, path);
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        Ok(roots)
+    }
+
+    fn parse_pins(&self) -> Result<Vec<[u8; 32]>, String> {
+        self.spki_pins
+            .iter()
+            .map(|pin| {
+                let encoded = pin.trim().trim_start_matches("sha256/");
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .map_err(|e| format!("Invalid SPKI pin '{}': {}", pin, e))?;
+                bytes
+                    .try_into()
+                    .map_err(|_| format!("Invalid SPKI pin '{}': expected a SHA-256 digest", pin))
+            })
+            .collect()
+    }
+}
+
+/// 服务端地址：未指定协议时默认使用 HTTPS
+pub fn build_base_url(host: &str, port: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.contains("://") {
+        format!("{}:{}", host, port)
+    } else {
+        format!("https://{}:{}", host, port)
+    }
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to load certificates from {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No PEM certificates found in {}", path));
+    }
+    Ok(certs)
+}
+
+/// 证书的 SubjectPublicKeyInfo SHA-256
+fn spki_digest(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+    Some(Sha256::digest(parsed.tbs_certificate.subject_pki.raw).into())
+}
+
+/// 先做常规的证书链校验，再检查 SPKI 指纹
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl PinnedVerifier {
+    fn new(roots: RootCertStore, pins: Vec<[u8; 32]>, provider: Arc<CryptoProvider>) -> Result<Self, String> {
+        let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .map_err(|e| format!("TLS verifier setup failed: {}", e))?;
+        Ok(Self { inner, pins })
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        if self.pins.is_empty() {
+            return Ok(verified);
+        }
+
+        let matched = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(spki_digest)
+            .any(|digest| self.pins.contains(&digest));
+        if matched {
+            Ok(verified)
+        } else {
+            let presented = spki_digest(end_entity)
+                .map(|d| base64::engine::general_purpose::STANDARD.encode(d))
+                .unwrap_or_default();
+            Err(rustls::Error::General(format!(
+                "server certificate public key does not match any pinned SPKI (presented sha256/{})",
+                presented
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
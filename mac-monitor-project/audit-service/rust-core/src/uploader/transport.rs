@@ -0,0 +1,312 @@
+//! HTTP 传输层抽象
+//!
+//! `Uploader` 只负责组装请求与解析业务响应，实际收发交给 `Transport`，
+//! 生产环境使用基于 reqwest 的实现，测试中可以替换为其他实现或指向本地 mock 服务。
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+#[derive(Debug, Clone)]
+pub enum RequestBody {
+    Empty,
+    /// 已序列化的 JSON
+    Json(Vec<u8>),
+    /// 单文件 multipart/form-data
+    File {
+        field: String,
+        file_name: String,
+        mime: String,
+        data: Vec<u8>,
+    },
+    /// 已编码好的请求体 (如压缩后的数据)
+    Raw {
+        content_type: String,
+        data: Vec<u8>,
+    },
+}
+
+impl RequestBody {
+    /// 编码为 (Content-Type, 字节)，`Empty` 返回 `None`
+    pub fn to_bytes(&self) -> Option<(String, Vec<u8>)> {
+        match self {
+            RequestBody::Empty => None,
+            RequestBody::Json(bytes) => Some(("application/json".to_string(), bytes.clone())),
+            RequestBody::File { field, file_name, mime, data } => {
+                let boundary = format!("----audit-core-{}", uuid::Uuid::new_v4().simple());
+                let mut body = Vec::with_capacity(data.len() + 256);
+                body.extend_from_slice(
+                    format!(
+                        "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                        boundary, field, file_name, mime
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(data);
+                body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+                Some((format!("multipart/form-data; boundary={}", boundary), body))
+            }
+            RequestBody::Raw { content_type, data } => Some((content_type.clone(), data.clone())),
+        }
+    }
+
+    /// 请求体的大致字节数 (multipart 不含分隔行)，用于限速计量
+    pub fn byte_len(&self) -> usize {
+        match self {
+            RequestBody::Empty => 0,
+            RequestBody::Json(bytes) => bytes.len(),
+            RequestBody::File { data, .. } => data.len(),
+            RequestBody::Raw { data, .. } => data.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: RequestBody,
+}
+
+impl HttpRequest {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::Get, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::Post, url)
+    }
+
+    fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: RequestBody::Empty,
+        }
+    }
+
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn json<T: serde::Serialize + ?Sized>(mut self, data: &T) -> Result<Self, String> {
+        self.body = RequestBody::Json(serde_json::to_vec(data).map_err(|e| e.to_string())?);
+        Ok(self)
+    }
+
+    /// 原始字节请求体 (如分块上传的数据块)
+    pub fn bytes(mut self, content_type: &str, data: Vec<u8>) -> Self {
+        self.body = RequestBody::Raw { content_type: content_type.to_string(), data };
+        self
+    }
+
+    pub fn file(mut self, field: &str, file_name: &str, mime: &str, data: Vec<u8>) -> Self {
+        self.body = RequestBody::File {
+            field: field.to_string(),
+            file_name: file_name.to_string(),
+            mime: mime.to_string(),
+            data,
+        };
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// 按名称 (不区分大小写) 取响应头
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_slice(&self.body).map_err(|e| e.to_string())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// 流式下载时接收数据块的回调
+pub type ChunkSink<'a> = dyn FnMut(&[u8]) -> Result<(), String> + Send + 'a;
+
+/// 发送一个 HTTP 请求；连接/IO 错误返回 `Err`，任意 HTTP 状态码都返回 `Ok`
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, String>;
+
+    /// 流式接收响应体 (大文件下载)，返回的 `HttpResponse.body` 为空
+    ///
+    /// 默认实现先完整读取再一次性交给 `sink`。
+    async fn download(&self, request: HttpRequest, sink: &mut ChunkSink<'_>) -> Result<HttpResponse, String> {
+        let mut response = self.send(request).await?;
+        if response.is_success() {
+            sink(&response.body)?;
+            response.body.clear();
+        }
+        Ok(response)
+    }
+}
+
+/// 基于 reqwest 的默认实现
+#[derive(Default)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, String> {
+        let response = self.request(request)?.send().await.map_err(|e| describe_error(&e))?;
+        let (status, headers) = response_head(&response);
+        let body = response.bytes().await.map_err(|e| describe_error(&e))?.to_vec();
+
+        Ok(HttpResponse { status, headers, body })
+    }
+
+    async fn download(&self, request: HttpRequest, sink: &mut ChunkSink<'_>) -> Result<HttpResponse, String> {
+        let mut response = self.request(request)?.send().await.map_err(|e| describe_error(&e))?;
+        let (status, headers) = response_head(&response);
+        if response.status().is_success() {
+            while let Some(chunk) = response.chunk().await.map_err(|e| describe_error(&e))? {
+                sink(&chunk)?;
+            }
+        }
+
+        Ok(HttpResponse { status, headers, body: Vec::new() })
+    }
+}
+
+impl ReqwestTransport {
+    fn request(&self, request: HttpRequest) -> Result<reqwest::RequestBuilder, String> {
+        let mut builder = match request.method {
+            Method::Get => self.client.get(&request.url),
+            Method::Post => self.client.post(&request.url),
+        };
+        if !request.query.is_empty() {
+            builder = builder.query(&request.query);
+        }
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        builder = match request.body {
+            RequestBody::Empty => builder,
+            RequestBody::Json(bytes) => builder
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(bytes),
+            RequestBody::File { field, file_name, mime, data } => {
+                let part = reqwest::multipart::Part::bytes(data)
+                    .file_name(file_name)
+                    .mime_str(&mime)
+                    .map_err(|e| e.to_string())?;
+                builder.multipart(reqwest::multipart::Form::new().part(field, part))
+            }
+            RequestBody::Raw { content_type, data } => builder
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(data),
+        };
+
+        Ok(builder)
+    }
+}
+
+fn response_head(response: &reqwest::Response) -> (u16, Vec<(String, String)>) {
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.as_str().to_string(), v.to_string())))
+        .collect();
+    (response.status().as_u16(), headers)
+}
+
+/// TLS 配置无效时使用：拒绝所有请求，而不是退回到未校验的连接
+pub struct UnavailableTransport {
+    reason: String,
+}
+
+impl UnavailableTransport {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self { reason: reason.into() }
+    }
+}
+
+#[async_trait]
+impl Transport for UnavailableTransport {
+    async fn send(&self, _request: HttpRequest) -> Result<HttpResponse, String> {
+        Err(self.reason.clone())
+    }
+}
+
+/// TLS 校验失败的错误信息都以此开头，便于上层识别
+pub const TLS_ERROR_PREFIX: &str = "TLS validation failed";
+
+/// reqwest 的错误信息只有一句 "error sending request"，展开错误链找出真正原因
+fn describe_error(err: &reqwest::Error) -> String {
+    let mut causes = Vec::new();
+    let mut tls_error = None;
+    let mut source: Option<&(dyn std::error::Error + 'static)> = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if let Some(e) = cause.downcast_ref::<rustls::Error>() {
+            tls_error = Some(e.to_string());
+        } else if let Some(e) = cause
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        {
+            tls_error = Some(e.to_string());
+        }
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+
+    // 默认客户端使用系统 TLS 后端，其错误类型不对外暴露，只能按描述识别
+    if tls_error.is_none() {
+        tls_error = causes.iter().find(|c| c.contains("certificate")).cloned();
+    }
+
+    let host = err.url().and_then(|u| u.host_str()).unwrap_or("server").to_string();
+    match tls_error {
+        Some(detail) => format!("{} for {}: {}", TLS_ERROR_PREFIX, host, detail),
+        None if causes.is_empty() => err.to_string(),
+        None => format!("{}: {}", err, causes.join(": ")),
+    }
+}
@@ -0,0 +1,333 @@
+//! 前台应用使用时长统计
+//!
+//! macOS 由 Swift 宿主 (NSWorkspace 前台切换 + HID 空闲检测) 通过 FFI `log_foreground_event` 提交事件，
+//! 也可通过 IPC 同名命令提交。前台应用切换、空闲、锁屏 / 睡眠 / 切出 (来自 `SessionTracker`) 时结束当前时段，
+//! 时段按本地日期拆分后写入 `app_usage_sessions`。每天结束后生成一条 `usage_summaries` 汇总并随日志同步上报。
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+use crate::clock::{format_ms, LogicalClock};
+use crate::db::Database;
+use crate::lifecycle::{ShutdownSignal, TaskTracker};
+use crate::models::{AppUsage, AppUsageSession, DeviceInfo, RiskEventCounts, UsageSummary};
+
+/// 检查跨日与生成汇总的间隔
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForegroundKind {
+    /// 前台应用切换 (视为用户活动)
+    Foreground,
+    /// 超过空闲阈值没有输入
+    Idle,
+    /// 空闲后恢复输入
+    Active,
+}
+
+/// 宿主提交的前台事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForegroundEvent {
+    pub kind: ForegroundKind,
+    /// foreground 事件必填
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_id: Option<String>,
+    /// 事件时间 (逻辑时钟毫秒)，缺省为提交时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<i64>,
+}
+
+impl ForegroundEvent {
+    pub fn new(kind: ForegroundKind) -> Self {
+        Self { kind, app_name: None, bundle_id: None, time_ms: None }
+    }
+
+    pub fn foreground(app_name: impl Into<String>) -> Self {
+        Self { app_name: Some(app_name.into()), ..Self::new(ForegroundKind::Foreground) }
+    }
+}
+
+/// IPC get_app_usage 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub day: String,
+    pub active_secs: i64,
+    pub apps: Vec<AppUsage>,
+    pub risk_events: RiskEventCounts,
+    /// 当前前台应用 (仅当天)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_app: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ForegroundApp {
+    name: String,
+    bundle_id: Option<String>,
+}
+
+/// 当前前台应用与计时状态；只有有前台应用、非空闲、未锁屏时计时
+#[derive(Debug, Default)]
+struct UsageState {
+    app: Option<ForegroundApp>,
+    idle: bool,
+    away: bool,
+    started_ms: Option<i64>,
+}
+
+impl UsageState {
+    /// 结束当前时段，返回按日期拆分后的记录
+    fn close(&mut self, at_ms: i64) -> Vec<AppUsageSession> {
+        match (self.started_ms.take(), &self.app) {
+            (Some(start), Some(app)) => split_by_day(app, start, at_ms.max(start)),
+            _ => Vec::new(),
+        }
+    }
+
+    fn resume(&mut self, at_ms: i64) {
+        if self.app.is_some() && !self.idle && !self.away && self.started_ms.is_none() {
+            self.started_ms = Some(at_ms);
+        }
+    }
+
+    fn apply(&mut self, event: &ForegroundEvent, at_ms: i64) -> Vec<AppUsageSession> {
+        match event.kind {
+            ForegroundKind::Foreground => {
+                let app = ForegroundApp {
+                    name: event.app_name.clone().unwrap_or_default(),
+                    bundle_id: event.bundle_id.clone(),
+                };
+                let closed = if self.app.as_ref() == Some(&app) { Vec::new() } else { self.close(at_ms) };
+                self.app = Some(app);
+                self.idle = false;
+                self.resume(at_ms);
+                closed
+            }
+            ForegroundKind::Idle => {
+                self.idle = true;
+                self.close(at_ms)
+            }
+            ForegroundKind::Active => {
+                self.idle = false;
+                self.resume(at_ms);
+                Vec::new()
+            }
+        }
+    }
+
+    fn set_away(&mut self, away: bool, at_ms: i64) -> Vec<AppUsageSession> {
+        self.away = away;
+        if away {
+            self.close(at_ms)
+        } else {
+            self.resume(at_ms);
+            Vec::new()
+        }
+    }
+}
+
+/// 前台使用时段统计与每日汇总
+pub struct UsageTracker {
+    db: Arc<Database>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+    tasks: Arc<TaskTracker>,
+    runtime_handle: Handle,
+    state: Mutex<UsageState>,
+}
+
+impl UsageTracker {
+    pub fn new(
+        db: Arc<Database>,
+        device_info: Arc<RwLock<DeviceInfo>>,
+        clock: Arc<LogicalClock>,
+        tasks: Arc<TaskTracker>,
+        runtime_handle: Handle,
+    ) -> Arc<Self> {
+        Arc::new(Self { db, device_info, clock, tasks, runtime_handle, state: Mutex::new(UsageState::default()) })
+    }
+
+    pub fn current_app(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        state.started_ms.and(state.app.as_ref()).map(|app| app.name.clone())
+    }
+
+    /// 立即更新状态，异步写入结束的时段；服务退出中返回 false
+    pub fn record(self: &Arc<Self>, event: ForegroundEvent) -> Result<bool, String> {
+        let closed = self.apply(&event)?;
+        Ok(self.persist(closed))
+    }
+
+    /// 更新状态并写入结束的时段，返回写入的记录数
+    pub async fn submit(&self, event: ForegroundEvent) -> Result<usize, String> {
+        let closed = self.apply(&event)?;
+        let count = closed.len();
+        self.db.save_app_usage_sessions(&closed).await.map_err(|e| e.to_string())?;
+        Ok(count)
+    }
+
+    /// 锁屏 / 睡眠 / 切出期间不计时 (由 `SessionTracker::on_pause_change` 调用)
+    pub fn set_away(self: &Arc<Self>, away: bool, time_ms: i64) {
+        let closed = self.state.lock().unwrap().set_away(away, time_ms);
+        self.persist(closed);
+    }
+
+    /// 某天的使用情况；当天包括进行中的时段
+    pub async fn report(&self, day: &str) -> Result<UsageReport, String> {
+        let mut apps = self.db.app_usage_for_day(day).await.map_err(|e| e.to_string())?;
+        let risk_events = self.db.risk_event_counts(day).await.map_err(|e| e.to_string())?;
+
+        let now = self.clock.now_ms();
+        let open = {
+            let state = self.state.lock().unwrap();
+            match (state.started_ms, &state.app) {
+                (Some(start), Some(app)) => Some((app.clone(), split_by_day(app, start, now.max(start)))),
+                _ => None,
+            }
+        };
+        let mut current_app = None;
+        if let Some((app, segments)) = open {
+            let open_ms: i64 = segments.iter().filter(|s| s.day == day).map(|s| s.duration_ms).sum();
+            if day == local_day(now) {
+                current_app = Some(app.name.clone());
+            }
+            if open_ms > 0 {
+                match apps.iter_mut().find(|a| a.app_name == app.name) {
+                    Some(usage) => {
+                        usage.active_secs += open_ms / 1000;
+                        usage.sessions += 1;
+                    }
+                    None => apps.push(AppUsage {
+                        app_name: app.name,
+                        bundle_id: app.bundle_id,
+                        active_secs: open_ms / 1000,
+                        sessions: 1,
+                        ..Default::default()
+                    }),
+                }
+                apps.sort_by_key(|a| std::cmp::Reverse(a.active_secs));
+            }
+        }
+
+        Ok(UsageReport {
+            day: day.to_string(),
+            active_secs: apps.iter().map(|a| a.active_secs).sum(),
+            apps,
+            risk_events,
+            current_app,
+        })
+    }
+
+    /// 跨日时先写入前一天的部分，再为已结束且未汇总的日期生成汇总；返回生成的条数
+    pub async fn summarize_completed(&self) -> Result<usize, String> {
+        let now = self.clock.now_ms();
+        let today = local_day(now);
+        let closed = {
+            let mut state = self.state.lock().unwrap();
+            match state.started_ms {
+                Some(start) if local_day(start) != today => {
+                    let closed = state.close(now);
+                    state.resume(now);
+                    closed
+                }
+                _ => Vec::new(),
+            }
+        };
+        self.db.save_app_usage_sessions(&closed).await.map_err(|e| e.to_string())?;
+
+        let days = self.db.get_unsummarized_usage_days(&today).await.map_err(|e| e.to_string())?;
+        for day in &days {
+            let report = self.report(day).await?;
+            let device = self.device_info.read().unwrap().clone();
+            let summary = UsageSummary {
+                id: None,
+                day: report.day,
+                active_secs: report.active_secs,
+                apps: serde_json::to_string(&report.apps).map_err(|e| e.to_string())?,
+                risk_events: serde_json::to_string(&report.risk_events).map_err(|e| e.to_string())?,
+                cpe_id: device.cpe_id,
+                host_id: device.host_id,
+                mac: device.mac,
+                ip: device.ip,
+            };
+            self.db.save_usage_summary(&summary).await.map_err(|e| e.to_string())?;
+            log::info!("Usage summary for {}: {}s active across {} apps", day, summary.active_secs, report.apps.len());
+        }
+        Ok(days.len())
+    }
+
+    /// 定期生成每日汇总；停止时写入进行中的时段
+    pub fn start(self: Arc<Self>, mut shutdown: ShutdownSignal) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SUMMARY_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait() => break,
+                }
+                if let Err(e) = self.summarize_completed().await {
+                    log::error!("Failed to generate usage summary: {}", e);
+                }
+            }
+            let closed = self.state.lock().unwrap().close(self.clock.now_ms());
+            if let Err(e) = self.db.save_app_usage_sessions(&closed).await {
+                log::error!("Failed to save app usage on shutdown: {}", e);
+            }
+        })
+    }
+
+    fn apply(&self, event: &ForegroundEvent) -> Result<Vec<AppUsageSession>, String> {
+        if event.kind == ForegroundKind::Foreground && event.app_name.as_deref().is_none_or(|n| n.trim().is_empty()) {
+            return Err("Foreground event has no app_name".to_string());
+        }
+        let time_ms = event.time_ms.unwrap_or_else(|| self.clock.now_ms());
+        Ok(self.state.lock().unwrap().apply(event, time_ms))
+    }
+
+    fn persist(&self, closed: Vec<AppUsageSession>) -> bool {
+        if closed.is_empty() {
+            return true;
+        }
+        let db = self.db.clone();
+        self.tasks.spawn(&self.runtime_handle, async move {
+            if let Err(e) = db.save_app_usage_sessions(&closed).await {
+                log::error!("Failed to save app usage sessions: {}", e);
+            }
+        })
+    }
+}
+
+/// 本地日期 (yyyy-MM-dd)
+pub fn local_day(time_ms: i64) -> String {
+    format_ms(time_ms)[..10].to_string()
+}
+
+/// 下一个本地零点
+fn next_midnight_ms(time_ms: i64) -> Option<i64> {
+    let date = Local.timestamp_millis_opt(time_ms).single()?.date_naive().succ_opt()?;
+    Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|t| t.timestamp_millis())
+}
+
+fn split_by_day(app: &ForegroundApp, start_ms: i64, end_ms: i64) -> Vec<AppUsageSession> {
+    let mut sessions = Vec::new();
+    let mut start = start_ms;
+    while start < end_ms {
+        let end = next_midnight_ms(start).filter(|m| *m > start).map_or(end_ms, |m| m.min(end_ms));
+        sessions.push(AppUsageSession {
+            id: None,
+            app_name: app.name.clone(),
+            bundle_id: app.bundle_id.clone(),
+            start_time: format_ms(start),
+            end_time: format_ms(end),
+            duration_ms: end - start,
+            day: local_day(start),
+        });
+        start = end;
+    }
+    sessions
+}
@@ -0,0 +1,150 @@
+mod support;
+
+use audit_logic_core::alerts::{AlertDispatcher, AlertStatus};
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::lifecycle::{Shutdown, TaskTracker};
+use audit_logic_core::metrics::Metrics;
+use audit_logic_core::models::{AuditLog, LogTable, TrafficSummary};
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::Uploader;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use support::{behavior_log, Fault, MockServer};
+
+fn traffic_summary(domain: &str, risk_level: i32) -> TrafficSummary {
+    TrafficSummary {
+        id: None,
+        bucket_start: "2026-01-01 09:00:00".to_string(),
+        bucket_secs: 60,
+        process_name: "curl".to_string(),
+        domain: domain.to_string(),
+        method_type: "POST".to_string(),
+        path_template: "/upload/{id}".to_string(),
+        first_seen: "2026-01-01 09:00:01".to_string(),
+        last_seen: "2026-01-01 09:00:59".to_string(),
+        request_count: 3,
+        bytes: 4096,
+        risk_level,
+        cpe_id: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn critical_records_are_pushed_immediately_and_retried() {
+    let server = MockServer::start().await;
+    let dir = std::env::temp_dir().join(format!("audit-core-alerts-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let clock = Arc::new(LogicalClock::new());
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+    let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+    let tasks = TaskTracker::new();
+    let alerts = AlertDispatcher::new(&db, uploader, metrics, clock, tasks.clone(), tokio::runtime::Handle::current());
+    let shutdown = Shutdown::new();
+    let task = alerts.clone().start(shutdown.signal());
+
+    // 只有风险等级 2 的记录触发告警
+    db.save_behavior_log(&behavior_log("ProcessWarning", 1)).await.unwrap();
+    let started = Instant::now();
+    db.save_behavior_log(&behavior_log("MaliciousProcess", 2)).await.unwrap();
+
+    let waiter = alerts.clone();
+    let raised = tokio::task::spawn_blocking(move || waiter.wait_after(0, Duration::from_secs(5))).await.unwrap();
+    assert_eq!(raised.len(), 1);
+    assert_eq!((raised[0].seq, raised[0].table, raised[0].risk_level), (1, LogTable::Behavior, 2));
+    assert_eq!(raised[0].record["op_type"], "MaliciousProcess");
+
+    while alerts.alerts_after(0)[0].status == AlertStatus::Pending {
+        assert!(started.elapsed() < Duration::from_secs(1), "alert was not delivered within a second");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let pushed = server.requests(endpoints::ALERT);
+    assert_eq!(pushed.len(), 1);
+    let body = pushed[0].json();
+    assert_eq!((body["table"].as_str(), body["record_id"].as_str()), (Some("behavior"), Some("2")));
+    assert_eq!(body["alert_id"].as_str(), Some(raised[0].alert_id.as_str()));
+    assert_eq!(body["record"]["op_type"], "MaliciousProcess");
+
+    // 推送失败按退避重试，告警 id 不变
+    server.fail_next(endpoints::ALERT, Fault::Status(503), 2);
+    db.save_behavior_log(&behavior_log("SensitiveUpload", 2)).await.unwrap();
+    // 告警由后台任务接收，推送任务稍后才创建，不能直接等 tasks 空闲
+    let retrying = Instant::now();
+    while alerts.alerts_after(1).first().is_none_or(|a| a.status == AlertStatus::Pending) {
+        assert!(retrying.elapsed() < Duration::from_secs(10), "alert retries did not finish");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(tasks.wait_idle(Duration::from_secs(1)).await);
+    let retried = &alerts.alerts_after(1)[0];
+    assert_eq!((retried.status, retried.attempts), (AlertStatus::Delivered, 3));
+    let pushed = server.requests(endpoints::ALERT);
+    assert_eq!(pushed.len(), 4);
+    assert!(pushed[1..].iter().all(|r| r.json()["alert_id"].as_str() == Some(retried.alert_id.as_str())));
+
+    let stats = alerts.stats();
+    assert_eq!((stats.raised, stats.delivered, stats.failed), (2, 2, 0));
+    // 没有新告警时长轮询超时返回空
+    assert!(alerts.wait_after(2, Duration::from_millis(50)).is_empty());
+
+    shutdown.trigger();
+    task.await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn critical_traffic_is_alerted_immediately() {
+    let server = MockServer::start().await;
+    let dir = std::env::temp_dir().join(format!("audit-core-alerts-traffic-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let clock = Arc::new(LogicalClock::new());
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+    let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+    let alerts = AlertDispatcher::new(&db, uploader, metrics, clock, TaskTracker::new(), tokio::runtime::Handle::current());
+    let shutdown = Shutdown::new();
+    let task = alerts.clone().start(shutdown.signal());
+
+    // 单条流量记录与聚合后的流量摘要都按风险等级触发告警
+    db.save_traffic_summary(&traffic_summary("github.com", 0)).await.unwrap();
+    db.save_audit_log(&AuditLog {
+        cpe_id: "TEST-SN".to_string(),
+        id: "t-1".to_string(),
+        url: "https://pastebin.com/raw".to_string(),
+        req_time: "2026-01-01 09:00:00".to_string(),
+        method_type: "POST".to_string(),
+        domain: "pastebin.com".to_string(),
+        process_name: "curl".to_string(),
+        risk_level: 2,
+        ip: "127.0.0.1".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        host_id: "test-host".to_string(),
+        bytes: Some(128),
+    })
+    .await
+    .unwrap();
+    db.save_traffic_summary(&traffic_summary("pastebin.com", 2)).await.unwrap();
+
+    let started = Instant::now();
+    while alerts.alerts_after(0).len() < 2 || alerts.alerts_after(0).iter().any(|a| a.status == AlertStatus::Pending) {
+        assert!(started.elapsed() < Duration::from_secs(5), "traffic alerts were not delivered");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let raised = alerts.alerts_after(0);
+    assert_eq!(raised.len(), 2);
+    assert_eq!((raised[0].table, raised[0].record_id.as_str()), (LogTable::Audit, "t-1"));
+    assert_eq!((raised[1].table, raised[1].record_id.as_str()), (LogTable::TrafficSummary, "2"));
+    assert_eq!(raised[1].record["domain"], "pastebin.com");
+
+    let pushed = server.requests(endpoints::ALERT);
+    assert_eq!(pushed.len(), 2);
+    let summary = pushed.iter().map(|r| r.json()).find(|body| body["table"] == "traffic_summary").unwrap();
+    assert_eq!(summary["record"]["request_count"], 3);
+
+    shutdown.trigger();
+    task.await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,92 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::uploader::{endpoints, AuthState, Uploader, AUTH_HEADER};
+use serde_json::json;
+use std::sync::Arc;
+use support::{Fault, MockServer, MOCK_TOKEN};
+
+fn uploader(server: &MockServer) -> Uploader {
+    Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()))
+}
+
+#[tokio::test]
+async fn rejected_requests_log_in_again_and_retry_once() {
+    let server = MockServer::start().await;
+    let uploader = uploader(&server);
+    assert_eq!(uploader.auth_status().state, AuthState::Anonymous);
+
+    uploader.upload_data(endpoints::LOG_AUDIT, &json!({ "n": 1 })).await.unwrap();
+    assert_eq!(uploader.auth_status().state, AuthState::Authenticated);
+    assert!(uploader.auth_status().expires_at.is_some());
+
+    // token 被服务端吊销：重新登录后用新 token 重试一次
+    server.fail_next(endpoints::LOG_AUDIT, Fault::Status(401), 1);
+    uploader.upload_data(endpoints::LOG_AUDIT, &json!({ "n": 2 })).await.unwrap();
+    assert_eq!(server.requests(endpoints::LOGIN).len(), 2);
+    let audit = server.requests(endpoints::LOG_AUDIT);
+    let tokens: Vec<_> = audit.iter().map(|r| r.header(AUTH_HEADER).unwrap().to_string()).collect();
+    let renewed = format!("{}-2", MOCK_TOKEN);
+    assert_eq!(tokens, [MOCK_TOKEN, MOCK_TOKEN, renewed.as_str()]);
+
+    // 重试后仍被拒绝：只重试一次，返回错误并标记登录失败
+    server.fail_next(endpoints::LOG_AUDIT, Fault::Status(403), 2);
+    assert!(uploader.upload_data(endpoints::LOG_AUDIT, &json!({ "n": 3 })).await.is_err());
+    assert_eq!(server.requests(endpoints::LOG_AUDIT).len(), 5);
+    assert_eq!(uploader.auth_status().state, AuthState::Failed);
+
+    // 策略接口与其他接口使用同一个请求头
+    server.set_policy(json!({ "version": "v1" }), "\"etag-v1\"");
+    uploader.get_config(None).await.unwrap();
+    let policy = server.requests(endpoints::POLICY).pop().unwrap();
+    assert!(policy.header(AUTH_HEADER).is_some());
+    assert!(policy.header("authorization").is_none());
+}
+
+#[tokio::test]
+async fn tokens_are_refreshed_before_expiry() {
+    let server = MockServer::start().await;
+    // 有效期短于提前刷新的时间，每次请求前都会重新登录
+    server.set_token_ttl(30);
+    let uploader = uploader(&server);
+    for _ in 0..2 {
+        uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    }
+    assert_eq!(server.requests(endpoints::LOGIN).len(), 2);
+    let last = server.requests(endpoints::HEARTBEAT).pop().unwrap();
+    assert_eq!(last.header(AUTH_HEADER), Some(format!("{}-2", MOCK_TOKEN).as_str()));
+
+    let server = MockServer::start().await;
+    server.set_token_ttl(3600);
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+    for _ in 0..3 {
+        uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    }
+    assert_eq!(server.requests(endpoints::LOGIN).len(), 1);
+}
+
+#[tokio::test]
+async fn failed_login_falls_back_to_anonymous_access() {
+    let server = MockServer::start().await;
+    let uploader = uploader(&server);
+    server.fail_next(endpoints::LOGIN, Fault::Status(500), 1);
+
+    uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    let status = uploader.auth_status();
+    assert_eq!(status.state, AuthState::Failed);
+    assert!(status.last_error.unwrap().contains("500"));
+    assert!(server.requests(endpoints::HEARTBEAT)[0].header(AUTH_HEADER).is_none());
+
+    // 退避期间不再尝试登录
+    uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    assert_eq!(server.requests(endpoints::LOGIN).len(), 1);
+
+    // 手动登录 (IPC login) 不受退避限制
+    assert_eq!(uploader.login().await.unwrap().state, AuthState::Authenticated);
+    uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    assert_eq!(server.requests(endpoints::HEARTBEAT).pop().unwrap().header(AUTH_HEADER), Some(MOCK_TOKEN));
+
+    uploader.logout();
+    assert_eq!(uploader.auth_status().state, AuthState::Anonymous);
+    assert!(uploader.auth_status().expires_at.is_none());
+}
@@ -0,0 +1,116 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::metrics::Metrics;
+use audit_logic_core::models::{PolicyConfig, ScreenshotLog};
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::uploader::bandwidth::{BandwidthConfig, NetworkMode};
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::sync::SyncService;
+use audit_logic_core::uploader::Uploader;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use support::{behavior_log, device_info, MockServer};
+
+fn screenshot(hash: &str, image_path: String, risk_level: i32) -> ScreenshotLog {
+    ScreenshotLog {
+        id: None,
+        capture_time: "2026-01-01 09:00:00".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        image_path,
+        ocr_text: None,
+        risk_level,
+        app_name: "Safari".to_string(),
+        image_hash: hash.to_string(),
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+        redaction_labels: None,
+        policy_decision: None,
+    }
+}
+
+#[tokio::test]
+async fn uploads_are_paced_by_the_token_bucket() {
+    let server = MockServer::start().await;
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+    uploader.bandwidth().configure(&BandwidthConfig { metadata_bytes_per_sec: Some(40_000), ..Default::default() });
+
+    // 桶容量为 2 秒的量 (80KB)，第三个 40KB 请求需要等待约 1 秒
+    let payload = json!({ "data": "x".repeat(40_000) });
+    let started = Instant::now();
+    for _ in 0..3 {
+        uploader.upload_data(endpoints::LOG_BEHAVIOR, &payload).await.unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(900), "elapsed {:?}", started.elapsed());
+
+    let status = uploader.bandwidth().status();
+    assert!(status.metadata.used_today >= 120_000);
+    assert_eq!(status.metadata.bytes_per_sec, Some(40_000));
+    assert_eq!((status.file.used_today, status.file.bytes_per_sec), (0, None));
+}
+
+#[tokio::test]
+async fn constrained_network_and_daily_caps_defer_low_risk_records() {
+    let server = MockServer::start().await;
+    let dir = std::env::temp_dir().join(format!("audit-core-bandwidth-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = device_info();
+    let clock = Arc::new(LogicalClock::new());
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+    let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+    let policy = Arc::new(PolicyStore::load(dir.join("policy_cache.json"), db.clone(), device_info.clone(), clock.clone()));
+    let sync = SyncService::new(
+        db.clone(),
+        uploader.clone(),
+        clock,
+        policy.clone(),
+        device_info,
+        dir.join("screenshots").display().to_string(),
+        metrics,
+    );
+
+    let image = dir.join("shot.jpg");
+    std::fs::write(&image, b"fake-jpeg-bytes").unwrap();
+    db.save_screenshot_log(&screenshot("low", image.display().to_string(), 1)).await.unwrap();
+    db.save_screenshot_log(&screenshot("high", image.display().to_string(), 2)).await.unwrap();
+    db.save_screenshot_log(&screenshot("meta-only", String::new(), 0)).await.unwrap();
+
+    // 受限网络：只上传元数据与高风险截图
+    uploader.bandwidth().set_mode(NetworkMode::Constrained);
+    sync.sync_logs().await.unwrap();
+    let pending = db.get_unsent_screenshot_logs().await.unwrap();
+    assert_eq!(pending.iter().map(|l| l.image_hash.as_str()).collect::<Vec<_>>(), ["low"]);
+    assert_eq!(server.requests(endpoints::UPLOAD_SCREENSHOT).len(), 1);
+    let status = uploader.bandwidth().status();
+    assert!(status.constrained);
+    assert_eq!(status.file.deferred, 1);
+
+    // 自动模式按检测结果，网络恢复后补传
+    uploader.bandwidth().set_mode(NetworkMode::Auto);
+    uploader.bandwidth().set_detected(true);
+    sync.sync_logs().await.unwrap();
+    assert_eq!(db.get_unsent_screenshot_logs().await.unwrap().len(), 1);
+    uploader.bandwidth().set_detected(false);
+    sync.sync_logs().await.unwrap();
+    assert!(db.get_unsent_screenshot_logs().await.unwrap().is_empty());
+
+    // 每日上限用尽后普通日志留到次日，高风险日志照常上传
+    let mut limited: PolicyConfig = serde_json::from_value(json!({ "version": "limited" })).unwrap();
+    limited.upload_limits.daily_metadata_bytes = Some(1);
+    policy.apply(limited, None).await.unwrap();
+    db.save_behavior_log(&behavior_log("ProcessDetected", 1)).await.unwrap();
+    db.save_behavior_log(&behavior_log("UsbStorage", 2)).await.unwrap();
+    sync.sync_logs().await.unwrap();
+    let pending = db.get_unsent_behavior_logs().await.unwrap();
+    assert!(pending.iter().all(|l| l.risk_level < 2) && !pending.is_empty());
+    let uploaded = server.requests(endpoints::LOG_BEHAVIOR);
+    assert!(uploaded.iter().any(|r| r.json()["op_type"] == "UsbStorage"));
+    assert!(!uploaded.iter().any(|r| r.json()["op_type"] == "ProcessDetected"));
+    assert_eq!(uploader.bandwidth().status().metadata.daily_cap, Some(1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,99 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::Uploader;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use support::{Fault, MockServer};
+
+const CONTENT: &[u8] = b"0123456789";
+
+async fn setup() -> (PathBuf, Database, String) {
+    let dir = std::env::temp_dir().join(format!("audit-core-chunked-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap();
+    let file = dir.join("shot.jpg");
+    std::fs::write(&file, CONTENT).unwrap();
+    (dir.clone(), db, file.display().to_string())
+}
+
+fn uploader(server: &MockServer) -> Uploader {
+    Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()))
+}
+
+fn chunk_offsets(server: &MockServer) -> Vec<String> {
+    let requests = server.requests(endpoints::UPLOAD_CHUNK);
+    requests.iter().map(|r| r.query.rsplit("offset=").next().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn chunked_upload_resumes_after_a_lost_acknowledgement() {
+    let server = MockServer::start().await;
+    server.enable_chunked_uploads(4);
+    let (dir, db, file) = setup().await;
+    let sha256 = hex::encode(Sha256::digest(CONTENT));
+
+    // 服务端收到第一块但响应丢失：数据库中仍是偏移 0
+    server.fail_next(endpoints::UPLOAD_CHUNK, Fault::Truncate(0), 1);
+    assert!(uploader(&server).upload_file_resumable(&file, &db).await.is_err());
+    let session = db.get_upload_session(&sha256).await.unwrap().unwrap();
+    assert_eq!((session.offset, session.size, session.chunk_size), (0, 10, 4));
+
+    // 重启后沿用会话：服务端以 409 告知已收到 4 字节，从该偏移继续
+    let url = uploader(&server).upload_file_resumable(&file, &db).await.unwrap();
+    assert_eq!(url, "http://files.mock/1.jpg");
+    assert_eq!(chunk_offsets(&server), ["0", "0", "4", "8"]);
+    assert_eq!(server.requests(endpoints::UPLOAD_INIT).len(), 1);
+    assert_eq!(server.stored_file("/files/1.jpg").unwrap(), CONTENT);
+    assert_eq!(server.requests(endpoints::UPLOAD_COMPLETE)[0].json()["sha256"], sha256.as_str());
+    assert!(db.get_upload_session(&sha256).await.unwrap().is_none());
+    assert!(server.requests(endpoints::UPLOAD_SCREENSHOT).is_empty());
+
+    // 服务端丢弃了未完成的上传：重新 init 并完整上传
+    server.fail_next(endpoints::UPLOAD_CHUNK, Fault::Status(503), 1);
+    let uploader = uploader(&server);
+    assert!(uploader.upload_file_resumable(&file, &db).await.is_err());
+    server.expire_chunked_uploads();
+    assert_eq!(uploader.upload_file_resumable(&file, &db).await.unwrap(), "http://files.mock/2.jpg");
+    assert_eq!(server.requests(endpoints::UPLOAD_INIT).len(), 3);
+    assert_eq!(server.stored_file("/files/2.jpg").unwrap(), CONTENT);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn servers_without_chunked_uploads_get_a_single_request() {
+    let server = MockServer::start().await;
+    let (dir, db, file) = setup().await;
+    let uploader = uploader(&server);
+
+    for n in 1..=2 {
+        assert_eq!(uploader.upload_file_resumable(&file, &db).await.unwrap(), format!("http://files.mock/{}.jpg", n));
+    }
+    // 只探测一次
+    assert_eq!(server.requests(endpoints::UPLOAD_INIT).len(), 1);
+    let uploads = server.requests(endpoints::UPLOAD_SCREENSHOT);
+    assert_eq!(uploads.len(), 2);
+    assert!(String::from_utf8_lossy(&uploads[0].body).contains("0123456789"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn chunked_upload_gives_up_when_the_server_offset_does_not_advance() {
+    let server = MockServer::start().await;
+    server.enable_chunked_uploads(4);
+    server.stall_chunked_uploads();
+    let (dir, db, file) = setup().await;
+
+    // 每个会话最多重发 3 次同一块，重新 init 一次后仍不前进则报错
+    assert!(uploader(&server).upload_file_resumable(&file, &db).await.is_err());
+    assert_eq!(chunk_offsets(&server), ["0"; 6]);
+    assert_eq!(server.requests(endpoints::UPLOAD_INIT).len(), 2);
+    assert!(server.requests(endpoints::UPLOAD_COMPLETE).is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
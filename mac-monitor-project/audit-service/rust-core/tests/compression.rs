@@ -0,0 +1,112 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::uploader::compression::Encoding;
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::Uploader;
+use serde_json::json;
+use std::sync::Arc;
+use support::MockServer;
+
+fn traffic_batch(entries: usize) -> serde_json::Value {
+    let logs: Vec<_> = (0..entries)
+        .map(|i| json!({ "url": format!("https://example.com/api/items/{}", i), "method_type": "GET", "domain": "example.com" }))
+        .collect();
+    json!({ "logs": logs })
+}
+
+#[tokio::test]
+async fn bodies_are_sent_uncompressed_until_negotiated() {
+    let server = MockServer::start().await;
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+
+    // 旧版服务端不返回 content_encodings
+    uploader.heartbeat(audit_logic_core::APP_VERSION, None, None).await.unwrap();
+    let advertised = &server.requests(endpoints::HEARTBEAT)[0].json()["supportedEncodings"];
+    assert_eq!(advertised, &json!(["zstd", "gzip"]));
+
+    uploader.upload_data(endpoints::LOG_AUDIT, &traffic_batch(200)).await.unwrap();
+    let request = &server.requests(endpoints::LOG_AUDIT)[0];
+    assert_eq!(request.header("content-encoding"), None);
+    assert_eq!(uploader.compression_stats().bytes_saved, 0);
+}
+
+#[tokio::test]
+async fn negotiated_encoding_compresses_bodies_above_threshold() {
+    let server = MockServer::start().await;
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+    server.set_content_encodings(&["gzip", "zstd"]);
+    uploader.heartbeat(audit_logic_core::APP_VERSION, None, None).await.unwrap();
+    assert_eq!(uploader.compression_stats().encoding, Some(Encoding::Zstd));
+
+    let batch = traffic_batch(200);
+    uploader.upload_data(endpoints::LOG_AUDIT, &batch).await.unwrap();
+    uploader.upload_data(endpoints::LOG_BEHAVIOR, &json!({ "op_type": "Small" })).await.unwrap();
+
+    let large = &server.requests(endpoints::LOG_AUDIT)[0];
+    assert_eq!(large.header("content-encoding"), Some("zstd"));
+    assert!(large.body.len() < large.decoded_body().len());
+    assert_eq!(large.json(), batch);
+
+    let small = &server.requests(endpoints::LOG_BEHAVIOR)[0];
+    assert_eq!(small.header("content-encoding"), None, "bodies below the threshold stay uncompressed");
+
+    let stats = uploader.compression_stats();
+    assert_eq!(stats.compressed_requests, 1);
+    assert!(stats.bytes_saved > 0);
+    assert_eq!(stats.raw_bytes - stats.sent_bytes, stats.bytes_saved);
+}
+
+#[tokio::test]
+async fn falls_back_to_gzip_and_disables_when_server_stops_advertising() {
+    let server = MockServer::start().await;
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+    server.set_content_encodings(&["gzip"]);
+    uploader.heartbeat(audit_logic_core::APP_VERSION, None, None).await.unwrap();
+
+    uploader.upload_data(endpoints::LOG_AUDIT, &traffic_batch(100)).await.unwrap();
+    let request = &server.requests(endpoints::LOG_AUDIT)[0];
+    assert_eq!(request.header("content-encoding"), Some("gzip"));
+    assert_eq!(request.json(), traffic_batch(100));
+
+    server.set_content_encodings(&[]);
+    uploader.heartbeat(audit_logic_core::APP_VERSION, None, None).await.unwrap();
+    uploader.upload_data(endpoints::LOG_AUDIT, &traffic_batch(100)).await.unwrap();
+    assert_eq!(server.requests(endpoints::LOG_AUDIT)[1].header("content-encoding"), None);
+}
+
+#[tokio::test]
+async fn incompressible_files_are_sent_as_is() {
+    let server = MockServer::start().await;
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+    server.set_content_encodings(&["zstd"]);
+    uploader.heartbeat(audit_logic_core::APP_VERSION, None, None).await.unwrap();
+
+    let dir = std::env::temp_dir().join(format!("audit-core-compress-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // 伪随机字节模拟已压缩的 JPEG
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let noise: Vec<u8> = (0..8192)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    std::fs::write(dir.join("noise.jpg"), &noise).unwrap();
+    std::fs::write(dir.join("flat.jpg"), vec![0u8; 8192]).unwrap();
+
+    uploader.upload_file(&dir.join("noise.jpg").display().to_string()).await.unwrap();
+    uploader.upload_file(&dir.join("flat.jpg").display().to_string()).await.unwrap();
+    let files = server.requests(endpoints::UPLOAD_SCREENSHOT);
+    assert_eq!(files[0].header("content-encoding"), None);
+    assert_eq!(files[1].header("content-encoding"), Some("zstd"));
+    let decoded = files[1].decoded_body();
+    assert!(String::from_utf8_lossy(&decoded).contains("filename=\"flat.jpg\""));
+    assert!(files[1]
+        .header("content-type")
+        .is_some_and(|ct| ct.starts_with("multipart/form-data; boundary=")));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,128 @@
+mod support;
+
+use audit_logic_core::ipc::client;
+use audit_logic_core::uploader::endpoints;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use support::MockServer;
+
+fn traffic(id: &str, req_time: &str, url: &str) -> Value {
+    json!({
+        "cpe_id": "TEST-SN",
+        "id": id,
+        "url": url,
+        "req_time": req_time,
+        "method_type": "GET",
+        "domain": "api.github.com",
+        "process_name": "curl",
+        "risk_level": 0,
+        "ip": "127.0.0.1",
+        "mac": "00:11:22:33:44:55",
+        "host_id": "test-host",
+    })
+}
+
+fn cli(socket: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_audit-daemon"))
+        .arg("--socket")
+        .arg(socket)
+        .args(args)
+        .output()
+        .expect("failed to run audit-daemon")
+}
+
+fn wait_for<T>(mut check: impl FnMut() -> Option<T>) -> T {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(value) = check() {
+            return value;
+        }
+        assert!(Instant::now() < deadline, "condition not met in time");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// 服务是进程内全局状态，整个流程放在一个测试里
+#[test]
+fn daemon_serves_cli_commands_over_ipc() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let server = rt.block_on(MockServer::start());
+    let dir: PathBuf = std::env::temp_dir().join(format!("audit-core-daemon-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket = dir.join("audit.sock");
+    let db_path = dir.join("audit.db");
+    let config_path = dir.join("config.json");
+    let config = json!({
+        "server": { "url": server.base_url(), "app_code": "mac_monitor", "app_secret": "secret" },
+        "storage": {
+            "screenshot_dir": dir.join("screenshots").display().to_string(),
+            "database_path": db_path.display().to_string(),
+        },
+        "ipc": { "socket_path": socket.display().to_string() },
+        "logging": { "path": dir.join("service.log").display().to_string() },
+    });
+    std::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap()).unwrap();
+
+    audit_logic_core::start_service(&config_path).unwrap();
+    let socket_str = socket.display().to_string();
+
+    for (id, time) in [("t-1", "2026-01-01 09:00:00"), ("t-2", "2026-01-02 09:00:00")] {
+        let payload = traffic(id, time, &format!("https://api.github.com/{}", id));
+        client::call(&socket_str, "log_traffic", payload).unwrap();
+    }
+    let query = json!({ "table": "audit", "since": "2026-01-02" });
+    let rows = wait_for(|| {
+        let rows = client::call(&socket_str, "query", query.clone()).ok()?.payload?;
+        (rows.as_array()?.len() == 1).then_some(rows)
+    });
+    assert_eq!(rows[0]["id"], "t-2");
+
+    // sync-now 上传后不再有待上传的流量记录
+    let status = cli(&socket, &["sync-now"]);
+    assert!(status.status.success(), "{}", String::from_utf8_lossy(&status.stderr));
+    let uploaded: Vec<String> = server
+        .requests(endpoints::LOG_AUDIT)
+        .iter()
+        .map(|r| r.json()["id"].as_str().unwrap_or_default().to_string())
+        .collect();
+    assert!(uploaded.contains(&"t-1".to_string()) && uploaded.contains(&"t-2".to_string()));
+    let status = client::call(&socket_str, "status", json!({})).unwrap().payload.unwrap();
+    assert_eq!(status["pending"]["audit"], 0);
+
+    // export 写出 CSV，字段按字母序
+    let csv_path = dir.join("audit.csv");
+    let export = cli(&socket, &["export", "--table", "audit", "--format", "csv", "--out", csv_path.to_str().unwrap()]);
+    assert!(export.status.success(), "{}", String::from_utf8_lossy(&export.stderr));
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next().unwrap(), "cpe_id,domain,host_id,id,ip,mac,method_type,process_name,req_time,risk_level,url");
+    assert_eq!(lines.count(), 2);
+
+    let verify = cli(&socket, &["verify-chain"]);
+    assert!(verify.status.success(), "{}", String::from_utf8_lossy(&verify.stderr));
+
+    // 直接改库后哈希链校验失败
+    rt.block_on(async {
+        let pool = sqlx::SqlitePool::connect(&format!("sqlite://{}", db_path.display())).await.unwrap();
+        sqlx::query("UPDATE monitor_log_traffic SET url = 'https://evil.example/' WHERE id = 't-1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+    });
+    let verify = cli(&socket, &["verify-chain"]);
+    assert_eq!(verify.status.code(), Some(2));
+    let report: Value = serde_json::from_slice(&verify.stdout).unwrap();
+    let breaks = report["breaks"].as_array().unwrap();
+    assert_eq!(breaks.len(), 1);
+    assert_eq!(breaks[0]["row_id"], "t-1");
+    assert_eq!(breaks[0]["reason"], "record modified");
+
+    assert!(audit_logic_core::stop_service(Duration::from_secs(5)).is_some());
+    assert!(!socket.exists(), "socket removed on shutdown");
+    assert!(client::request(&socket_str, "status", json!({})).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
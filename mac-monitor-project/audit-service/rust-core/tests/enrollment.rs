@@ -0,0 +1,110 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::enrollment::store::{CredentialStore, FileStore};
+use audit_logic_core::enrollment::{Enroller, Enrollment};
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::Uploader;
+use std::path::PathBuf;
+use std::sync::Arc;
+use support::{device_info, MockServer};
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("audit-core-enrollment-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn file_store_keeps_credentials_private() {
+    let dir = temp_dir();
+    let store = FileStore::new(dir.join("nested/enrollment.json"));
+    assert_eq!(store.load().unwrap(), None);
+
+    let enrollment = Enrollment {
+        device_id: "dev-1".to_string(),
+        credential: "s3cret".to_string(),
+        server_url: "https://audit.example.com".to_string(),
+        serial_number: "TEST-SN".to_string(),
+        enrolled_at: "2026-01-01 09:00:00".to_string(),
+        expires_at: None,
+    };
+    store.save(&enrollment).unwrap();
+    assert_eq!(store.load().unwrap(), Some(enrollment));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(store.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    store.clear().unwrap();
+    store.clear().unwrap();
+    assert_eq!(store.load().unwrap(), None);
+
+    std::fs::write(store.path(), b"{not json").unwrap();
+    assert!(store.load().is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn enrollment_issues_credentials_used_for_login() {
+    let server = MockServer::start().await;
+    let dir = temp_dir();
+    let store_path = dir.join("enrollment.json");
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new())));
+    uploader.set_device_profile("test-host", "Ubuntu 24.04 LTS");
+    let enroller = Enroller::new(
+        uploader.clone(),
+        device_info(),
+        Some("machine-1".to_string()),
+        "Ubuntu 24.04 LTS",
+        Box::new(FileStore::new(&store_path)),
+    );
+    assert!(!enroller.status().enrolled);
+
+    // 无效 PIN：不保存凭据
+    assert!(enroller.enroll("000000").await.unwrap_err().contains("invalid pin"));
+    assert!(!store_path.exists());
+
+    let status = enroller.enroll("123456").await.unwrap();
+    assert_eq!((status.enrolled, status.device_id.as_deref()), (true, Some("dev-TEST-SN")));
+    let request = server.requests(endpoints::ENROLL).pop().unwrap().json();
+    assert_eq!(request["machineId"], "machine-1");
+    assert_eq!(request["osVersion"], "Ubuntu 24.04 LTS");
+    assert_eq!(request["mac"], "00:11:22:33:44:55");
+    assert!(request.get("previousDeviceId").is_none());
+
+    // 之后的登录使用签发的凭据，上报真实系统版本
+    uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    let login = server.requests(endpoints::LOGIN).pop().unwrap().json();
+    assert_eq!((login["deviceId"].as_str(), login["credential"].as_str()), (Some("dev-TEST-SN"), Some("cred-1")));
+    assert_eq!((login["deviceName"].as_str(), login["osVersion"].as_str()), (Some("test-host"), Some("Ubuntu 24.04 LTS")));
+    assert_eq!(server.requests(endpoints::HEARTBEAT).pop().unwrap().json()["deviceId"], "dev-TEST-SN");
+
+    // 重新注册沿用设备 id，换新凭据；重启后从存储恢复
+    enroller.enroll("123456").await.unwrap();
+    assert_eq!(server.requests(endpoints::ENROLL).pop().unwrap().json()["previousDeviceId"], "dev-TEST-SN");
+    let restarted_uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new())));
+    let restarted =
+        Enroller::new(restarted_uploader.clone(), device_info(), None, "Ubuntu 24.04 LTS", Box::new(FileStore::new(&store_path)));
+    assert_eq!(restarted.status().device_id.as_deref(), Some("dev-TEST-SN"));
+    restarted_uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    assert_eq!(server.requests(endpoints::LOGIN).pop().unwrap().json()["credential"], "cred-2");
+
+    // 注册到不可达的服务端失败时保留原地址与凭据
+    let unreachable = "http://127.0.0.1:1";
+    assert!(enroller.register(unreachable, None, "123456").await.is_err());
+    assert_eq!(uploader.base_url(), server.base_url());
+    assert!(enroller.status().enrolled);
+
+    let status = enroller.unenroll(false).await.unwrap();
+    assert!(!status.enrolled);
+    assert!(!store_path.exists());
+    assert_eq!(server.requests(endpoints::UNENROLL).pop().unwrap().json()["deviceId"], "dev-TEST-SN");
+    assert!(enroller.unenroll(false).await.is_err());
+    uploader.heartbeat("1.0.0", None, None).await.unwrap();
+    assert!(server.requests(endpoints::LOGIN).pop().unwrap().json().get("deviceId").is_none());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,224 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::evidence::{verify_bundle, EvidenceExporter, ExportFormat, ExportRequest, Manifest};
+use audit_logic_core::models::{BehaviorLog, LogTable, ScreenshotLog};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use support::{behavior_log, device_info};
+
+// 导出窗口按 op_time 过滤；detail 含逗号与引号以覆盖 CSV 转义
+fn behavior(op_time: &str, op_type: &str) -> BehaviorLog {
+    BehaviorLog { op_time: op_time.to_string(), detail: "{\"note\": \"a, b\"}".to_string(), ..behavior_log(op_type, 1) }
+}
+
+fn screenshot(hash: &str, image_path: &Path) -> ScreenshotLog {
+    ScreenshotLog {
+        id: None,
+        capture_time: "2026-01-05 10:00:00".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        image_path: image_path.display().to_string(),
+        ocr_text: None,
+        risk_level: 0,
+        app_name: "Safari".to_string(),
+        image_hash: hash.to_string(),
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+        redaction_labels: None,
+        policy_decision: None,
+    }
+}
+
+/// 读出包内全部文件 (按包内顺序)
+fn entries(bundle: &Path) -> Vec<(String, Vec<u8>)> {
+    let file = std::fs::File::open(bundle).unwrap();
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            (name, data)
+        })
+        .collect()
+}
+
+fn repack(bundle: &Path, entries: &[(String, Vec<u8>)]) {
+    let file = std::fs::File::create(bundle).unwrap();
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice()).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+async fn setup(dir: &Path) -> (Arc<Database>, Arc<EvidenceExporter>) {
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = device_info();
+    let exporter = EvidenceExporter::new(
+        db.clone(),
+        device_info,
+        Arc::new(LogicalClock::new()),
+        dir.join("evidence_key.json"),
+        dir.join("evidence"),
+        dir.join("screenshots"),
+    );
+    (db, exporter)
+}
+
+#[tokio::test]
+async fn exported_bundles_are_signed_and_tampering_is_detected() {
+    let dir = std::env::temp_dir().join(format!("audit-core-evidence-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("screenshots")).unwrap();
+    let (db, exporter) = setup(&dir).await;
+
+    db.save_behavior_log(&behavior("2026-01-04 23:00:00", "Before")).await.unwrap();
+    db.save_behavior_log(&behavior("2026-01-05 09:00:00", "Inside")).await.unwrap();
+    db.save_behavior_log(&behavior("2026-01-06 00:00:00", "After")).await.unwrap();
+    let image = dir.join("screenshots").join("shot-1.jpg");
+    std::fs::write(&image, b"fake-jpeg-bytes").unwrap();
+    // 原路径已不存在时按文件名到截图目录下找
+    db.save_screenshot_log(&screenshot("h1", &PathBuf::from("/moved/away/shot-1.jpg"))).await.unwrap();
+    db.save_screenshot_log(&screenshot("h2", &dir.join("deleted.jpg"))).await.unwrap();
+
+    let mut request = ExportRequest::new("case-1.tar.gz");
+    request.tables = vec![LogTable::Behavior, LogTable::Screenshot];
+    request.since = Some("2026-01-05".to_string());
+    request.until = Some("2026-01-06".to_string());
+    let summary = exporter.export(&request).await.unwrap();
+    let bundle = dir.join("evidence").join("case-1.tar.gz");
+    assert_eq!(summary.path, bundle.display().to_string());
+    assert_eq!(summary.tables.iter().map(|t| t.rows).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!((summary.screenshots, summary.missing_screenshots), (1, 1));
+    assert_eq!(summary.public_key, exporter.public_key().unwrap());
+
+    let files = entries(&bundle);
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["data/behavior.jsonl", "data/screenshot.jsonl", "screenshots/shot-1.jpg", "manifest.json", "manifest.sig"]
+    );
+    let rows: Vec<serde_json::Value> =
+        files[0].1.split(|b| *b == b'\n').filter(|l| !l.is_empty()).map(|l| serde_json::from_slice(l).unwrap()).collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["op_type"], "Inside");
+    assert_eq!(files[2].1, b"fake-jpeg-bytes");
+    let manifest: Manifest = serde_json::from_slice(&files[3].1).unwrap();
+    assert_eq!(manifest.device.cpe_id, "TEST-SN");
+    assert_eq!(manifest.files.len(), 3);
+    assert_eq!(manifest.missing_screenshots, [dir.join("deleted.jpg").display().to_string()]);
+
+    let report = verify_bundle(&bundle, Some(&summary.public_key)).unwrap();
+    assert!(report.is_intact(), "{:?}", report.problems);
+    assert_eq!(report.files_checked, 3);
+
+    // 另一把密钥不被接受
+    let other = "0".repeat(64);
+    assert!(!verify_bundle(&bundle, Some(&other)).unwrap().is_intact());
+
+    // 修改数据文件：签名仍有效，但哈希不符
+    let mut tampered = files.clone();
+    tampered[0].1 = b"{\"op_type\":\"Forged\"}\n".to_vec();
+    repack(&bundle, &tampered);
+    let report = verify_bundle(&bundle, Some(&summary.public_key)).unwrap();
+    assert!(report.signature_valid && !report.is_intact());
+    assert_eq!(report.problems, ["File data/behavior.jsonl does not match the manifest"]);
+
+    // 连同 manifest 一起修改：签名失效
+    let mut forged: Manifest = manifest.clone();
+    forged.files[0].sha256 = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&tampered[0].1));
+    forged.files[0].size = tampered[0].1.len() as u64;
+    tampered[3].1 = serde_json::to_vec_pretty(&forged).unwrap();
+    repack(&bundle, &tampered);
+    let report = verify_bundle(&bundle, Some(&summary.public_key)).unwrap();
+    assert!(!report.signature_valid);
+    assert_eq!(report.problems, ["Manifest signature is invalid"]);
+
+    // 夹带未列出的文件
+    let mut extra = files.clone();
+    extra.push(("screenshots/extra.jpg".to_string(), b"x".to_vec()));
+    repack(&bundle, &extra);
+    let report = verify_bundle(&bundle, None).unwrap();
+    assert_eq!(report.problems, ["File screenshots/extra.jpg is not listed in the manifest"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn csv_exports_reuse_the_device_key() {
+    let dir = std::env::temp_dir().join(format!("audit-core-evidence-csv-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (db, exporter) = setup(&dir).await;
+    db.save_behavior_log(&behavior("2026-01-05 09:00:00", "Inside")).await.unwrap();
+
+    let mut request = ExportRequest::new("case-2.tar.gz");
+    request.format = ExportFormat::Csv;
+    let summary = exporter.export(&request).await.unwrap();
+    let bundle = exporter.bundle_path("case-2.tar.gz").unwrap();
+    // 缺省导出全部表
+    assert_eq!(summary.tables.len(), LogTable::ALL.len());
+    let files = entries(&bundle);
+    let csv = String::from_utf8(files[1].1.clone()).unwrap();
+    assert_eq!(files[1].0, "data/behavior.csv");
+    assert!(csv.lines().next().unwrap().starts_with("cpe_id,detail,"));
+    assert!(csv.contains("\"{\"\"note\"\": \"\"a, b\"\"}\""));
+
+    // 密钥保存后复用，重新创建导出器公钥不变
+    let (_db, reopened) = setup(&dir).await;
+    assert_eq!(reopened.public_key().unwrap(), summary.public_key);
+    assert!(verify_bundle(&bundle, Some(&summary.public_key)).unwrap().is_intact());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.join("evidence_key.json")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn bundles_stay_private_inside_the_export_directory() {
+    let dir = std::env::temp_dir().join(format!("audit-core-evidence-dir-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (_db, exporter) = setup(&dir).await;
+
+    // 只接受文件名：拒绝目录穿越、绝对路径与子目录
+    let victim = dir.join("victim.txt");
+    std::fs::write(&victim, b"keep me").unwrap();
+    for name in ["../victim.txt", "..", "/tmp/evidence.tar.gz", "sub/evidence.tar.gz", "", "."] {
+        let err = exporter.export(&ExportRequest::new(name)).await.unwrap_err();
+        assert!(err.starts_with("Invalid bundle name"), "{}: {}", name, err);
+    }
+    assert_eq!(std::fs::read(&victim).unwrap(), b"keep me");
+
+    exporter.export(&ExportRequest::new("case.tar.gz")).await.unwrap();
+    let bundle = exporter.bundle_path("case.tar.gz").unwrap();
+    let original = std::fs::read(&bundle).unwrap();
+    // 不覆盖已有文件，也不留下临时文件
+    let err = exporter.export(&ExportRequest::new("case.tar.gz")).await.unwrap_err();
+    assert!(err.contains("already exists"), "{}", err);
+    assert_eq!(std::fs::read(&bundle).unwrap(), original);
+    let names: Vec<String> =
+        std::fs::read_dir(exporter.export_dir()).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    assert_eq!(names, ["case.tar.gz"]);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&bundle).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::metadata(exporter.export_dir()).unwrap().permissions().mode() & 0o777, 0o700);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,104 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::metrics::Metrics;
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::uploader::servers::FailoverConfig;
+use audit_logic_core::uploader::sync::SyncService;
+use audit_logic_core::uploader::{endpoints, Uploader, BATCH_HEADER};
+use std::sync::Arc;
+use support::{behavior_log, device_info, Fault, MockServer};
+
+#[tokio::test]
+async fn repeated_errors_fail_over_to_standby_and_batches_record_the_accepting_server() {
+    let primary = MockServer::start().await;
+    let standby = MockServer::start().await;
+    let dir = std::env::temp_dir().join(format!("audit-core-failover-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = device_info();
+    let clock = Arc::new(LogicalClock::new());
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &primary.base_url(), "TEST-SN", clock.clone()));
+    uploader.configure_servers(
+        &primary.base_url(),
+        &[standby.base_url()],
+        FailoverConfig { failure_threshold: 2, failback_secs: 0 },
+    );
+    let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+    let policy = Arc::new(PolicyStore::load(dir.join("policy_cache.json"), db.clone(), device_info.clone(), clock.clone()));
+    let sync = SyncService::new(
+        db.clone(),
+        uploader.clone(),
+        clock,
+        policy,
+        device_info,
+        dir.join("screenshots").display().to_string(),
+        metrics,
+    );
+
+    // 主服务端连续两次 5xx 后切到备用，第三条由备用接收
+    for op_type in ["first", "second", "third"] {
+        db.save_behavior_log(&behavior_log(op_type, 1)).await.unwrap();
+    }
+    primary.fail_next(endpoints::LOG_BEHAVIOR, Fault::Status(503), 2);
+    sync.sync_logs().await.unwrap();
+    let status = uploader.server_status();
+    assert_eq!(uploader.active_url(), standby.base_url());
+    assert!(!status[0].active && !status[0].healthy && status[0].score < 100);
+    assert!(status[1].active && status[1].healthy);
+    assert_eq!(db.get_unsent_behavior_logs().await.unwrap().len(), 2);
+    let accepted = standby.requests(endpoints::LOG_BEHAVIOR);
+    assert_eq!(accepted.len(), 1);
+    let first_batch = accepted[0].header(BATCH_HEADER).unwrap().to_string();
+
+    let reported = standby.requests(endpoints::LOG_BATCHES);
+    assert_eq!(reported.len(), 1);
+    let batches = reported[0].json()["batches"].clone();
+    assert_eq!(batches.as_array().unwrap().len(), 1);
+    assert_eq!(batches[0]["batch_id"].as_str(), Some(first_batch.as_str()));
+    assert_eq!(batches[0]["server_url"].as_str(), Some(standby.base_url().as_str()));
+    assert_eq!((batches[0]["endpoint"].as_str(), batches[0]["records"].as_i64()), (Some(endpoints::LOG_BEHAVIOR), Some(1)));
+
+    // 主服务端探测恢复后，在下一轮同步开始时切回，剩余记录由主服务端接收
+    uploader.check_servers().await;
+    assert!(uploader.server_status()[0].healthy);
+    assert_eq!(uploader.active_url(), standby.base_url());
+    sync.sync_logs().await.unwrap();
+    assert_eq!(uploader.active_url(), primary.base_url());
+    assert!(db.get_unsent_behavior_logs().await.unwrap().is_empty());
+    assert_eq!(standby.requests(endpoints::LOG_BEHAVIOR).len(), 1);
+    let second_batch = primary.requests(endpoints::LOG_BEHAVIOR).last().unwrap().header(BATCH_HEADER).unwrap().to_string();
+    assert_ne!(second_batch, first_batch);
+
+    let reported = primary.requests(endpoints::LOG_BATCHES);
+    assert_eq!(reported.len(), 1);
+    let batches = reported[0].json()["batches"].clone();
+    assert_eq!(batches[0]["server_url"].as_str(), Some(primary.base_url().as_str()));
+    assert_eq!(batches[0]["records"].as_i64(), Some(2));
+    assert!(db.get_unreported_upload_batches().await.unwrap().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn single_server_setups_never_switch() {
+    let server = MockServer::start().await;
+    let uploader = Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()));
+    assert!(!uploader.has_standby_servers());
+
+    // 没有备用服务端时不切换，照常返回错误
+    server.fail_next(endpoints::LOG_BEHAVIOR, Fault::Status(503), 5);
+    for _ in 0..5 {
+        assert!(uploader.upload_data(endpoints::LOG_BEHAVIOR, &behavior_log("x", 1)).await.is_err());
+    }
+    assert_eq!(uploader.active_url(), server.base_url());
+    let status = uploader.server_status();
+    assert_eq!((status.len(), status[0].consecutive_failures), (1, 5));
+
+    uploader.begin_batch();
+    uploader.upload_data(endpoints::LOG_BEHAVIOR, &behavior_log("y", 1)).await.unwrap();
+    let batches = uploader.end_batch();
+    assert_eq!((batches.len(), batches[0].records), (1, 1));
+    assert!(uploader.server_status()[0].healthy);
+}
@@ -0,0 +1,210 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::files::batcher::Batcher;
+use audit_logic_core::files::{FileAuditConfig, FileAuditor, FileEvent, FileFilter, FileOp};
+use audit_logic_core::metrics::Metrics;
+use audit_logic_core::models::{DeviceInfo, LogQuery, LogTable};
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::sync::SyncService;
+use audit_logic_core::uploader::Uploader;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use support::MockServer;
+
+fn event(op: FileOp, path: &str, process: &str, time_ms: i64) -> FileEvent {
+    FileEvent { process_name: process.to_string(), time_ms: Some(time_ms), ..FileEvent::new(op, path) }
+}
+
+#[test]
+fn batcher_merges_events_within_window() {
+    let mut batcher = Batcher::new(1_000, 100);
+    assert!(batcher.push(event(FileOp::Create, "/tmp/a.txt", "vim", 0), 0).is_empty());
+    batcher.push(event(FileOp::Modify, "/tmp/a.txt", "vim", 100), 100);
+    batcher.push(event(FileOp::Modify, "/tmp/a.txt", "vim", 200), 200);
+    // 其他进程对同一文件的修改单独记录
+    batcher.push(event(FileOp::Modify, "/tmp/a.txt", "cp", 300), 300);
+    assert_eq!(batcher.len(), 2);
+
+    assert!(batcher.drain_due(900).is_empty());
+    let due = batcher.drain_due(1_000);
+    assert_eq!(due.len(), 1);
+    assert_eq!((due[0].op, due[0].count, due[0].first_ms, due[0].last_ms), (FileOp::Create, 3, 0, 200));
+    assert_eq!(batcher.drain_all()[0].process_name, "cp");
+
+    // 窗口为 0 时不合并
+    let mut immediate = Batcher::new(0, 100);
+    assert_eq!(immediate.push(event(FileOp::Delete, "/tmp/a.txt", "rm", 0), 0).len(), 1);
+}
+
+#[test]
+fn filter_applies_include_and_exclude_globs() {
+    let include = vec!["/home/*/Documents/**".to_string(), "/srv/**/*.csv".to_string()];
+    let exclude = vec!["**/.DS_Store".to_string(), "**/*.tmp".to_string()];
+    let filter = FileFilter::new(&include, &exclude).unwrap();
+
+    assert!(filter.allows("/home/alice/Documents/report.docx"));
+    assert!(filter.allows("/home/alice/Documents/q3/notes.md"));
+    assert!(filter.allows("/srv/export/2026/users.csv"));
+    assert!(!filter.allows("/home/alice/Downloads/report.docx"));
+    // `*` 不跨目录
+    assert!(!filter.allows("/home/alice/x/Documents/report.docx"));
+    assert!(!filter.allows("/home/alice/Documents/.DS_Store"));
+    assert!(!filter.allows("/home/alice/Documents/draft.tmp"));
+
+    let all = FileFilter::new(&[], &exclude).unwrap();
+    assert!(all.allows("/anything/at/all"));
+    assert!(FileFilter::new(&["/a/[".to_string()], &[]).is_err());
+}
+
+struct Harness {
+    server: MockServer,
+    db: Arc<Database>,
+    policy: Arc<PolicyStore>,
+    device_info: Arc<RwLock<DeviceInfo>>,
+    clock: Arc<LogicalClock>,
+    sync: SyncService,
+    dir: PathBuf,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        let server = MockServer::start().await;
+        let dir = std::env::temp_dir().join(format!("audit-core-files-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+        let device_info = Arc::new(RwLock::new(DeviceInfo {
+            pin: "TEST-SN".to_string(),
+            host_id: "test-host".to_string(),
+            cpe_id: "TEST-SN".to_string(),
+            mac: "00:11:22:33:44:55".to_string(),
+            ip: "127.0.0.1".to_string(),
+        }));
+        let clock = Arc::new(LogicalClock::new());
+        let policy = Arc::new(PolicyStore::load(dir.join("policy_cache.json"), db.clone(), device_info.clone(), clock.clone()));
+        let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+        let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+        let sync = SyncService::new(
+            db.clone(),
+            uploader,
+            clock.clone(),
+            policy.clone(),
+            device_info.clone(),
+            dir.join("screenshots").display().to_string(),
+            metrics,
+        );
+        Self { server, db, policy, device_info, clock, sync, dir }
+    }
+
+    fn auditor(&self, config: FileAuditConfig) -> Arc<FileAuditor> {
+        FileAuditor::new(self.db.clone(), self.policy.shared(), self.device_info.clone(), self.clock.clone(), config)
+    }
+
+    async fn file_rows(&self) -> Vec<serde_json::Value> {
+        self.db.query_logs(&LogQuery::new(LogTable::File)).await.unwrap()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test]
+async fn file_events_are_batched_hashed_and_uploaded() {
+    let h = Harness::new().await;
+    let docs = h.dir.join("docs");
+    let usb = h.dir.join("usb");
+    std::fs::create_dir_all(&docs).unwrap();
+    std::fs::create_dir_all(&usb).unwrap();
+    let config = FileAuditConfig {
+        batch_window_ms: Some(60_000),
+        removable_prefixes: Some(vec![format!("{}/", usb.display())]),
+        ..Default::default()
+    };
+    let files = h.auditor(config);
+
+    let report = docs.join("report.txt");
+    std::fs::write(&report, b"quarterly numbers").unwrap();
+    let path = report.display().to_string();
+    assert!(files.submit(FileEvent::new(FileOp::Create, &path)).unwrap());
+    assert!(files.submit(FileEvent::new(FileOp::Modify, &path)).unwrap());
+    // 内置策略排除 .DS_Store
+    assert!(!files.submit(FileEvent::new(FileOp::Create, docs.join(".DS_Store").display().to_string())).unwrap());
+    let copy = usb.join("report.txt");
+    std::fs::copy(&report, &copy).unwrap();
+    assert!(files.submit(FileEvent::new(FileOp::Create, copy.display().to_string())).unwrap());
+    assert_eq!(files.pending(), 2);
+
+    // 窗口期内不写入
+    assert_eq!(files.flush(false).await, 0);
+    assert_eq!(files.flush(true).await, 2);
+
+    let rows = h.file_rows().await;
+    let expected_hash = hex::encode(Sha256::digest(b"quarterly numbers"));
+    let created = rows.iter().find(|r| r["path"] == path.as_str()).unwrap();
+    assert_eq!(created["op_type"], "create");
+    assert_eq!(created["event_count"], 2);
+    assert_eq!(created["size"], 17);
+    assert_eq!(created["sha256"], expected_hash.as_str());
+    assert_eq!(created["risk_level"], 0);
+    let copied = rows.iter().find(|r| r["op_type"] == "copy_to_removable").unwrap();
+    assert_eq!(copied["risk_level"], 1);
+
+    // 策略 include 之外的路径被过滤
+    h.policy.shared().write().unwrap().file_include_paths = vec![format!("{}/**", usb.display())];
+    assert!(!files.submit(FileEvent::new(FileOp::Delete, &path)).unwrap());
+
+    h.sync.sync_logs().await.unwrap();
+    let uploaded = h.server.requests(endpoints::LOG_FILE);
+    assert_eq!(uploaded.len(), 2);
+    assert!(uploaded.iter().any(|r| r.json()["sha256"] == expected_hash.as_str()));
+    assert_eq!(h.db.count_unsent().await.unwrap().file, 0);
+    assert!(h.db.verify_chain().await.unwrap().is_intact());
+
+    files.close();
+    assert!(files.submit(FileEvent::new(FileOp::Delete, &path)).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn inotify_watcher_reports_creates_and_renames() {
+    let h = Harness::new().await;
+    let watched = h.dir.join("watched");
+    std::fs::create_dir_all(&watched).unwrap();
+    let files = h.auditor(FileAuditConfig { batch_window_ms: Some(0), ..Default::default() });
+    let shutdown = audit_logic_core::lifecycle::Shutdown::new();
+    let watcher =
+        audit_logic_core::files::linux::start(&[watched.display().to_string()], files.clone(), shutdown.signal()).unwrap();
+
+    // 启动后新建的子目录也会被监听
+    let sub = watched.join("sub");
+    std::fs::create_dir(&sub).unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    std::fs::write(sub.join("a.txt"), b"hello").unwrap();
+    std::fs::rename(sub.join("a.txt"), sub.join("b.txt")).unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    let rows = loop {
+        files.flush(true).await;
+        let rows = h.file_rows().await;
+        if rows.iter().any(|r| r["op_type"] == "rename") || std::time::Instant::now() > deadline {
+            break rows;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    };
+    shutdown.trigger();
+    watcher.join().unwrap();
+
+    let a = sub.join("a.txt").display().to_string();
+    let b = sub.join("b.txt").display().to_string();
+    assert!(rows.iter().any(|r| r["op_type"] == "create" && r["path"] == a.as_str()), "{:?}", rows);
+    let renamed = rows.iter().find(|r| r["op_type"] == "rename").expect("rename recorded");
+    assert_eq!(renamed["path"], b.as_str());
+    assert_eq!(renamed["old_path"], a.as_str());
+    assert_eq!(renamed["sha256"], hex::encode(Sha256::digest(b"hello")).as_str());
+}
@@ -0,0 +1,50 @@
+use audit_logic_core::lifecycle::{Shutdown, TaskTracker};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn tracker_waits_for_pending_writes_and_rejects_new_ones() {
+    let tracker = TaskTracker::new();
+    let handle = tokio::runtime::Handle::current();
+    let done = Arc::new(AtomicUsize::new(0));
+
+    for delay in [20u64, 60] {
+        let done = done.clone();
+        assert!(tracker.spawn(&handle, async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            done.fetch_add(1, Ordering::SeqCst);
+        }));
+    }
+    assert_eq!(tracker.active(), 2);
+
+    tracker.close();
+    assert!(!tracker.spawn(&handle, async {}));
+    assert_eq!(tracker.active(), 2, "rejected task is not counted");
+
+    assert!(tracker.wait_idle(Duration::from_secs(2)).await);
+    assert_eq!(done.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn tracker_wait_reports_timeout() {
+    let tracker = TaskTracker::new();
+    tracker.spawn(&tokio::runtime::Handle::current(), tokio::time::sleep(Duration::from_secs(5)));
+    assert!(!tracker.wait_idle(Duration::from_millis(50)).await);
+    assert_eq!(tracker.active(), 1);
+}
+
+#[tokio::test]
+async fn shutdown_reaches_every_signal() {
+    let shutdown = Shutdown::new();
+    let mut early = shutdown.signal();
+    let waiter = tokio::spawn(async move { early.wait().await });
+
+    shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    // 触发后订阅的信号同样处于停止状态
+    assert!(shutdown.signal().is_triggered());
+
+    let mut orphan = Shutdown::new().signal();
+    tokio::time::timeout(Duration::from_secs(1), orphan.wait()).await.expect("dropped sender counts as shutdown");
+}
@@ -0,0 +1,101 @@
+use audit_logic_core::logging::redact::redact;
+use audit_logic_core::logging::rotation::RotatingFile;
+use audit_logic_core::logging::{self, LevelSpec, LoggingConfig};
+use log::LevelFilter;
+
+fn temp_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("audit-core-logging-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn secrets_are_redacted() {
+    let cases = [
+        (r#"{"command":"register","payload":{"pin":"123456","server_ip":"10.0.0.1"}}"#,
+         r#"{"command":"register","payload":{"pin":"***","server_ip":"10.0.0.1"}}"#),
+        ("Registering device: PIN=123456, CPE=abc", "Registering device: PIN=***, CPE=abc"),
+        (r#"ServerConfig { app_code: "mac_monitor", app_secret: "s3cr3t" }"#,
+         r#"ServerConfig { app_code: "mac_monitor", app_secret: "***" }"#),
+        ("header visit-token: abc.def appSecret=xyz", "header visit-token: *** appSecret=***"),
+        ("Authorization: Bearer eyJhbGciOi.x-y_z", "Authorization: Bearer ***"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(redact(input), expected);
+    }
+    // 不含敏感字段时原样返回
+    assert_eq!(redact("ping 10.0.0.1: 3 tokens left"), "ping 10.0.0.1: 3 tokens left");
+}
+
+#[test]
+fn rotation_keeps_bounded_history() {
+    let dir = temp_dir();
+    let path = dir.join("audit.log");
+    let mut file = RotatingFile::open(&path, 100, 2).unwrap();
+    for i in 0..10 {
+        file.write_line(&format!("{:0>39}", i)).unwrap();
+    }
+    file.flush().unwrap();
+
+    // 每个文件最多放两行 (40 字节 / 行)
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read("audit.log").lines().count(), 2);
+    assert!(read("audit.log").ends_with(&format!("{:0>39}\n", 9)));
+    assert!(read("audit.log.1").starts_with(&format!("{:0>39}", 6)));
+    assert!(read("audit.log.2").starts_with(&format!("{:0>39}", 4)));
+    assert!(!dir.join("audit.log.3").exists());
+
+    // 重新打开时追加而不是截断
+    drop(file);
+    let mut file = RotatingFile::open(&path, 1000, 2).unwrap();
+    file.write_line("appended").unwrap();
+    assert_eq!(read("audit.log").lines().count(), 3);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn level_spec_uses_longest_module_prefix() {
+    let spec: LevelSpec = "warn,audit_logic_core=info,audit_logic_core::uploader=debug".parse().unwrap();
+    assert_eq!(spec.level_for("sqlx::query"), LevelFilter::Warn);
+    assert_eq!(spec.level_for("audit_logic_core::ipc"), LevelFilter::Info);
+    assert_eq!(spec.level_for("audit_logic_core::uploader::sync"), LevelFilter::Debug);
+    assert_eq!(spec.level_for("audit_logic_core_extra"), LevelFilter::Warn);
+    assert_eq!(spec.to_string(), "warn,audit_logic_core=info,audit_logic_core::uploader=debug");
+    assert!("info,foo=loud".parse::<LevelSpec>().is_err());
+}
+
+#[test]
+fn json_lines_follow_runtime_level_changes() {
+    let dir = temp_dir();
+    let path = dir.join("service.log");
+    logging::init(&LoggingConfig {
+        path: Some(path.display().to_string()),
+        level: Some("info".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+
+    log::debug!(target: "audit_logic_core::uploader", "hidden before change");
+    logging::set_module_level(Some("audit_logic_core::uploader"), "debug").unwrap();
+    log::debug!(target: "audit_logic_core::uploader", "login with pin=4321");
+    log::debug!(target: "audit_logic_core::ipc", "still hidden");
+
+    log::logger().flush();
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("every line is JSON"))
+        .collect();
+    let messages: Vec<&str> = lines.iter().map(|l| l["msg"].as_str().unwrap()).collect();
+    assert!(!messages.iter().any(|m| m.contains("hidden")));
+    let entry = lines.iter().find(|l| l["msg"] == "login with pin=***").expect("debug line after change");
+    assert_eq!(entry["level"], "DEBUG");
+    assert_eq!(entry["target"], "audit_logic_core::uploader");
+    assert_eq!(
+        logging::levels().unwrap().to_string(),
+        "info,audit_logic_core::uploader=debug"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
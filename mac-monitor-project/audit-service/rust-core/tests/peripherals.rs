@@ -0,0 +1,181 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::lifecycle::TaskTracker;
+use audit_logic_core::metrics::Metrics;
+use audit_logic_core::models::{DeviceInfo, DeviceRiskRule, PolicyConfig};
+use audit_logic_core::peripherals::{evaluate, validate_device_pattern, PeripheralAction, PeripheralEvent, PeripheralMonitor};
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::sync::SyncService;
+use audit_logic_core::uploader::Uploader;
+use std::sync::{Arc, RwLock};
+use support::MockServer;
+
+fn usb_stick(action: PeripheralAction, serial: &str) -> PeripheralEvent {
+    PeripheralEvent {
+        vendor_id: Some("0781".to_string()),
+        product_id: Some("5567".to_string()),
+        serial: Some(serial.to_string()),
+        name: Some("SanDisk Cruzer Blade".to_string()),
+        volume_label: Some("BACKUP".to_string()),
+        ..PeripheralEvent::new(action, "mass_storage")
+    }
+}
+
+#[test]
+fn policy_flags_unapproved_devices_by_class() {
+    let mut policy = PolicyConfig::builtin();
+    let stick = usb_stick(PeripheralAction::Attach, "4C530001");
+    assert_eq!(evaluate(&policy, &stick), (2, "unapproved:mass_storage".to_string()));
+    let keyboard = PeripheralEvent { vendor_id: Some("046d".into()), product_id: Some("c31c".into()), ..PeripheralEvent::new(PeripheralAction::Attach, "hid") };
+    assert_eq!(evaluate(&policy, &keyboard).0, 0);
+
+    // 只批准特定序列号的 U 盘
+    policy.approved_devices = vec!["0781:5567:4C530001".to_string()];
+    assert_eq!(evaluate(&policy, &stick), (0, "approved:0781:5567:4C530001".to_string()));
+    assert_eq!(evaluate(&policy, &usb_stick(PeripheralAction::Attach, "OTHER")).0, 2);
+    // vendor 级批准，id 不区分大小写
+    policy.approved_devices = vec!["0781:*".to_string()];
+    assert_eq!(evaluate(&policy, &usb_stick(PeripheralAction::Detach, "OTHER")).0, 0);
+
+    policy.unapproved_device_rules.push(DeviceRiskRule { device_class: "*".to_string(), risk_level: 1 });
+    assert_eq!(evaluate(&policy, &keyboard), (1, "unapproved:*".to_string()));
+
+    assert!(validate_device_pattern("0781:5567").is_ok());
+    assert!(validate_device_pattern("*:*:ABC").is_ok());
+    assert!(validate_device_pattern("0781").is_err());
+    assert!(validate_device_pattern("sandisk:5567").is_err());
+}
+
+#[cfg(target_os = "linux")]
+mod udev {
+    use audit_logic_core::peripherals::udev::{event_from_properties, parse_message};
+    use audit_logic_core::peripherals::PeripheralAction;
+    use std::path::Path;
+
+    fn kernel_message(header: &str, props: &[&str]) -> Vec<u8> {
+        let mut buf = format!("{}\0", header).into_bytes();
+        for prop in props {
+            buf.extend_from_slice(prop.as_bytes());
+            buf.push(0);
+        }
+        buf
+    }
+
+    fn udev_message(props: &[&str]) -> Vec<u8> {
+        let body: Vec<u8> = props.iter().flat_map(|p| p.bytes().chain(std::iter::once(0))).collect();
+        let header_size = 40u32;
+        let mut buf = b"libudev\0".to_vec();
+        for word in [0xfeedcafeu32.to_be(), header_size, header_size, body.len() as u32, 0, 0, 0, 0] {
+            buf.extend_from_slice(&word.to_ne_bytes());
+        }
+        buf.extend_from_slice(&body);
+        buf
+    }
+
+    #[test]
+    fn parses_kernel_usb_device_events_with_sysfs_fallback() {
+        let sysfs = std::env::temp_dir().join(format!("audit-core-sysfs-{}", uuid::Uuid::new_v4()));
+        let device = sysfs.join("devices/pci0000:00/0000:00:14.0/usb1/1-2");
+        std::fs::create_dir_all(&device).unwrap();
+        std::fs::write(device.join("serial"), "4C530001\n").unwrap();
+        std::fs::write(device.join("manufacturer"), "SanDisk\n").unwrap();
+        std::fs::write(device.join("product"), "Cruzer Blade\n").unwrap();
+
+        let msg = kernel_message(
+            "add@/devices/pci0000:00/0000:00:14.0/usb1/1-2",
+            &["ACTION=add", "DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2", "SUBSYSTEM=usb", "DEVTYPE=usb_device", "PRODUCT=781/5567/100", "TYPE=0/0/0"],
+        );
+        let props = parse_message(&msg).unwrap();
+        let event = event_from_properties(&props, &sysfs, "").unwrap();
+        assert_eq!(event.action, PeripheralAction::Attach);
+        assert_eq!(event.device_class, "usb");
+        assert_eq!((event.vendor_id.as_deref(), event.product_id.as_deref()), (Some("0781"), Some("5567")));
+        assert_eq!(event.serial.as_deref(), Some("4C530001"));
+        assert_eq!(event.name.as_deref(), Some("SanDisk Cruzer Blade"));
+
+        // hub 与非 USB 块设备不记录
+        let hub = kernel_message("add@/devices/usb1", &["ACTION=add", "DEVPATH=/devices/usb1", "SUBSYSTEM=usb", "DEVTYPE=usb_device", "TYPE=9/0/1"]);
+        assert!(event_from_properties(&parse_message(&hub).unwrap(), &sysfs, "").is_none());
+        let nvme = kernel_message("add@/devices/nvme0n1", &["ACTION=add", "DEVPATH=/devices/pci0000:00/nvme/nvme0n1", "SUBSYSTEM=block", "DEVTYPE=disk"]);
+        assert!(event_from_properties(&parse_message(&nvme).unwrap(), &sysfs, "").is_none());
+        assert!(parse_message(b"not a uevent").is_none());
+
+        let _ = std::fs::remove_dir_all(&sysfs);
+    }
+
+    #[test]
+    fn parses_udev_block_events_with_label_and_mount_point() {
+        let msg = udev_message(&[
+            "ACTION=add",
+            "DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/host6/target6:0:0/6:0:0:0/block/sdb/sdb1",
+            "SUBSYSTEM=block",
+            "DEVTYPE=partition",
+            "DEVNAME=/dev/sdb1",
+            "ID_BUS=usb",
+            "ID_VENDOR_ID=0781",
+            "ID_MODEL_ID=5567",
+            "ID_SERIAL_SHORT=4C530001",
+            "ID_VENDOR=SanDisk",
+            "ID_MODEL=Cruzer_Blade",
+            "ID_FS_LABEL=BACKUP",
+        ]);
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n/dev/sdb1 /media/alice/MY\\040BACKUP vfat rw 0 0\n";
+        let event = event_from_properties(&parse_message(&msg).unwrap(), Path::new("/nonexistent"), mounts).unwrap();
+        assert_eq!(event.device_class, "mass_storage");
+        assert_eq!(event.device_id().as_deref(), Some("0781:5567:4C530001"));
+        assert_eq!(event.name.as_deref(), Some("SanDisk Cruzer Blade"));
+        assert_eq!(event.volume_label.as_deref(), Some("BACKUP"));
+        assert_eq!(event.mount_point.as_deref(), Some("/media/alice/MY BACKUP"));
+    }
+}
+
+#[tokio::test]
+async fn peripheral_events_are_rated_and_uploaded() {
+    let server = MockServer::start().await;
+    let dir = std::env::temp_dir().join(format!("audit-core-peripherals-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = Arc::new(RwLock::new(DeviceInfo {
+        pin: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }));
+    let clock = Arc::new(LogicalClock::new());
+    let policy = Arc::new(PolicyStore::load(dir.join("policy_cache.json"), db.clone(), device_info.clone(), clock.clone()));
+    let tasks = TaskTracker::new();
+    let monitor = PeripheralMonitor::new(
+        db.clone(),
+        policy.shared(),
+        device_info.clone(),
+        clock.clone(),
+        tasks.clone(),
+        tokio::runtime::Handle::current(),
+    );
+
+    let saved = monitor.save(usb_stick(PeripheralAction::Attach, "4C530001")).await.unwrap();
+    assert_eq!((saved.risk_level, saved.policy_decision.as_deref()), (2, Some("unapproved:mass_storage")));
+    assert!(monitor.record(usb_stick(PeripheralAction::Detach, "4C530001")));
+    assert!(tasks.wait_idle(std::time::Duration::from_secs(5)).await);
+
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+    let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+    let sync = SyncService::new(db.clone(), uploader, clock, policy, device_info, dir.join("screenshots").display().to_string(), metrics);
+    sync.sync_logs().await.unwrap();
+
+    let uploaded = server.requests(endpoints::LOG_PERIPHERAL);
+    let actions: Vec<String> = uploaded.iter().map(|r| r.json()["action"].as_str().unwrap().to_string()).collect();
+    assert_eq!(actions, ["attach", "detach"]);
+    assert_eq!(uploaded[0].json()["vendor_id"], "0781");
+    assert_eq!(uploaded[0].json()["volume_label"], "BACKUP");
+    assert_eq!(db.count_unsent().await.unwrap().peripheral, 0);
+    assert!(db.verify_chain().await.unwrap().is_intact());
+
+    tasks.close();
+    assert!(!monitor.record(usb_stick(PeripheralAction::Attach, "4C530001")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
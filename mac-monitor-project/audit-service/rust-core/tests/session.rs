@@ -0,0 +1,107 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::lifecycle::TaskTracker;
+use audit_logic_core::metrics::Metrics;
+use audit_logic_core::models::DeviceInfo;
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::session::{SessionEvent, SessionEventKind, SessionTracker};
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::sync::SyncService;
+use audit_logic_core::uploader::Uploader;
+use std::sync::{Arc, RwLock};
+use support::MockServer;
+
+fn event(kind: SessionEventKind, session_id: &str) -> SessionEvent {
+    SessionEvent { session_id: Some(session_id.to_string()), source: "loginwindow".to_string(), ..SessionEvent::new(kind) }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn logind_paths_and_properties_map_to_events() {
+    use audit_logic_core::session::logind::{property_events, session_id_from_path};
+
+    assert_eq!(session_id_from_path("/org/freedesktop/login1/session/_32").as_deref(), Some("2"));
+    assert_eq!(session_id_from_path("/org/freedesktop/login1/session/c1").as_deref(), Some("c1"));
+    assert_eq!(session_id_from_path("/org/freedesktop/login1/session/_31_30").as_deref(), Some("10"));
+    assert_eq!(session_id_from_path("/org/freedesktop/login1/session/self"), Some("self".to_string()));
+    assert!(session_id_from_path("/org/freedesktop/login1/seat/seat0").is_none());
+    assert!(session_id_from_path("/org/freedesktop/login1/session/_3").is_none());
+
+    assert_eq!(property_events(Some(true), None), [SessionEventKind::Lock]);
+    assert_eq!(property_events(Some(false), Some(true)), [SessionEventKind::SwitchIn, SessionEventKind::Unlock]);
+    assert!(property_events(None, None).is_empty());
+}
+
+#[tokio::test]
+async fn session_events_pause_capture_and_are_uploaded() {
+    let server = MockServer::start().await;
+    let dir = std::env::temp_dir().join(format!("audit-core-session-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = Arc::new(RwLock::new(DeviceInfo {
+        pin: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }));
+    let clock = Arc::new(LogicalClock::new());
+    let tasks = TaskTracker::new();
+    let session =
+        SessionTracker::new(db.clone(), device_info.clone(), clock.clone(), tasks.clone(), tokio::runtime::Handle::current());
+
+    let login = SessionEvent { user: Some("alice".to_string()), ..event(SessionEventKind::Login, "257") };
+    assert_eq!(session.save(login).await.unwrap().unwrap().op_type, "SessionLogin");
+    assert!(!session.capture_paused());
+
+    // 状态立即生效，写库异步完成
+    assert!(session.record(event(SessionEventKind::Lock, "257")));
+    assert!(session.capture_paused());
+    // 重复的锁屏事件不再记录
+    assert!(session.save(event(SessionEventKind::Lock, "257")).await.unwrap().is_none());
+    assert!(session.record(event(SessionEventKind::Unlock, "257")));
+    assert!(!session.capture_paused());
+
+    assert!(session.record(SessionEvent::new(SessionEventKind::Sleep)));
+    assert!(session.capture_paused());
+    assert!(session.record(SessionEvent::new(SessionEventKind::Wake)));
+    assert!(session.record(event(SessionEventKind::SwitchOut, "257")));
+    assert!(session.state().switched_out && session.capture_paused());
+    assert!(session.record(event(SessionEventKind::SwitchIn, "257")));
+    // 注销时沿用登录记录的用户名
+    assert!(session.record(event(SessionEventKind::Logout, "257")));
+    assert!(tasks.wait_idle(std::time::Duration::from_secs(5)).await);
+    assert!(session.state().sessions.is_empty());
+
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+    let metrics = Arc::new(Metrics::new(db.clone(), clock.clone(), dir.join("audit.db"), dir.join("screenshots")));
+    let policy = Arc::new(PolicyStore::load(dir.join("policy_cache.json"), db.clone(), device_info.clone(), clock.clone()));
+    let sync = SyncService::new(db.clone(), uploader, clock, policy, device_info, dir.join("screenshots").display().to_string(), metrics);
+    sync.sync_logs().await.unwrap();
+
+    let uploaded = server.requests(endpoints::LOG_BEHAVIOR);
+    let op_types: Vec<String> = uploaded.iter().map(|r| r.json()["op_type"].as_str().unwrap().to_string()).collect();
+    assert_eq!(
+        op_types,
+        [
+            "SessionLogin",
+            "ScreenLock",
+            "ScreenUnlock",
+            "SystemSleep",
+            "SystemWake",
+            "UserSwitchOut",
+            "UserSwitchIn",
+            "SessionLogout"
+        ]
+    );
+    let logout: serde_json::Value = serde_json::from_str(uploaded[7].json()["detail"].as_str().unwrap()).unwrap();
+    assert_eq!((logout["session_id"].as_str(), logout["user"].as_str()), (Some("257"), Some("alice")));
+    assert_eq!(uploaded[1].json()["proc"], "loginwindow");
+    assert!(db.verify_chain().await.unwrap().is_intact());
+
+    tasks.close();
+    assert!(!session.record(event(SessionEventKind::Lock, "257")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
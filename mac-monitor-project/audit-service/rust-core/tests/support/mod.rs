@@ -0,0 +1,499 @@
+//! 进程内 mock 管理端：监听本地临时端口，实现登录 / 心跳 / 策略 / 日志上报 / 文件上传接口，
+//! 可按接口预设失败响应，并记录收到的所有请求。另有各测试共用的记录 / 设备信息构造函数。
+
+#![allow(dead_code)]
+
+use audit_logic_core::models::{BehaviorLog, DeviceInfo};
+use audit_logic_core::uploader::endpoints;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+
+pub const MOCK_TOKEN: &str = "mock-visit-token";
+
+/// 预设的故障
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    /// 返回指定 HTTP 状态码
+    Status(u16),
+    /// 读完请求后直接断开连接，不返回任何响应
+    Disconnect,
+    /// 正常返回响应头，但只发送前 n 字节响应体后断开
+    Truncate(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(|v| v.as_str())
+    }
+
+    /// 按 Content-Encoding 解码后的请求体
+    pub fn decoded_body(&self) -> Vec<u8> {
+        match self.header("content-encoding") {
+            Some("zstd") => zstd::decode_all(self.body.as_slice()).expect("invalid zstd body"),
+            Some("gzip") => {
+                let mut out = Vec::new();
+                flate2::read::GzDecoder::new(self.body.as_slice())
+                    .read_to_end(&mut out)
+                    .expect("invalid gzip body");
+                out
+            }
+            _ => self.body.clone(),
+        }
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.decoded_body()).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<RecordedRequest>,
+    faults: HashMap<String, VecDeque<Fault>>,
+    policy: Option<(Value, String)>,
+    need_update: bool,
+    commands: Vec<Value>,
+    content_encodings: Vec<String>,
+    uploaded_files: usize,
+    update_info: Option<Value>,
+    files: HashMap<String, Vec<u8>>,
+    enrollments: usize,
+    token_ttl: Option<u64>,
+    logins: usize,
+    /// 启用分块上传时的分块大小
+    chunk_size: Option<usize>,
+    chunked_uploads: HashMap<String, ChunkedUpload>,
+    /// 分块确认但不保存，始终回显旧偏移
+    chunks_stalled: bool,
+}
+
+struct ChunkedUpload {
+    sha256: String,
+    data: Vec<u8>,
+}
+
+pub struct MockServer {
+    addr: std::net::SocketAddr,
+    tls: bool,
+    state: Arc<Mutex<State>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        Self::spawn(None).await
+    }
+
+    /// HTTPS 版本，证书由调用方提供
+    pub async fn start_tls(acceptor: TlsAcceptor) -> Self {
+        Self::spawn(Some(acceptor)).await
+    }
+
+    async fn spawn(acceptor: Option<TlsAcceptor>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock server");
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+        let tls = acceptor.is_some();
+
+        let accept_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = accept_state.clone();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    match acceptor {
+                        Some(acceptor) => {
+                            if let Ok(stream) = acceptor.accept(stream).await {
+                                let _ = handle_connection(stream, state).await;
+                            }
+                        }
+                        None => {
+                            let _ = handle_connection(stream, state).await;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self { addr, tls, state, handle }
+    }
+
+    pub fn base_url(&self) -> String {
+        if self.tls {
+            format!("https://localhost:{}", self.addr.port())
+        } else {
+            format!("http://{}", self.addr)
+        }
+    }
+
+    /// 接下来 `times` 次访问 `path` 时注入故障
+    pub fn fail_next(&self, path: &str, fault: Fault, times: usize) {
+        let mut state = self.state.lock().unwrap();
+        let queue = state.faults.entry(path.to_string()).or_default();
+        queue.extend(std::iter::repeat_n(fault, times));
+    }
+
+    pub fn set_policy(&self, policy: Value, etag: &str) {
+        self.state.lock().unwrap().policy = Some((policy, etag.to_string()));
+    }
+
+    /// 支持分块上传 (缺省不支持，init 返回 404)
+    pub fn enable_chunked_uploads(&self, chunk_size: usize) {
+        self.state.lock().unwrap().chunk_size = Some(chunk_size);
+    }
+
+    /// 分块上传完成后保存的文件内容 (`/files/<n>.jpg`)
+    pub fn stored_file(&self, path: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().files.get(path).cloned()
+    }
+
+    /// 服务端丢弃所有未完成的分块上传
+    pub fn expire_chunked_uploads(&self) {
+        self.state.lock().unwrap().chunked_uploads.clear();
+    }
+
+    /// 之后的分块只确认不保存 (响应中的偏移不前进)
+    pub fn stall_chunked_uploads(&self) {
+        self.state.lock().unwrap().chunks_stalled = true;
+    }
+
+    /// 登录响应中的 token 有效期 (秒)，未设置时不返回
+    pub fn set_token_ttl(&self, seconds: u64) {
+        self.state.lock().unwrap().token_ttl = Some(seconds);
+    }
+
+    pub fn set_need_update(&self, need_update: bool) {
+        self.state.lock().unwrap().need_update = need_update;
+    }
+
+    /// 心跳响应中声明可解码的请求体编码
+    pub fn set_content_encodings(&self, encodings: &[&str]) {
+        self.state.lock().unwrap().content_encodings = encodings.iter().map(|e| e.to_string()).collect();
+    }
+
+    /// 更新检查接口返回的 `data`
+    pub fn set_update(&self, info: Value) {
+        self.state.lock().unwrap().update_info = Some(info);
+    }
+
+    /// 在 `path` 提供静态文件下载，支持 `Range: bytes=N-`
+    pub fn serve_file(&self, path: &str, data: &[u8]) {
+        self.state.lock().unwrap().files.insert(path.to_string(), data.to_vec());
+    }
+
+    /// 下一次心跳下发的远程指令
+    pub fn push_command(&self, op_type: &str) {
+        let mut state = self.state.lock().unwrap();
+        let id = format!("cmd-{}", state.commands.len() + 1);
+        state.commands.push(json!({ "command_id": id, "op_type": op_type }));
+    }
+
+    pub fn requests(&self, path: &str) -> Vec<RecordedRequest> {
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|r| r.path == path)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// 测试设备 TEST-SN 上的一条行为日志
+pub fn behavior_log(op_type: &str, risk_level: i32) -> BehaviorLog {
+    BehaviorLog {
+        id: None,
+        proc: "clash".to_string(),
+        op_time: "2026-01-01 09:00:00".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        op_type: op_type.to_string(),
+        detail: "detected".to_string(),
+        risk_level,
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }
+}
+
+/// 测试设备 TEST-SN 的设备信息
+pub fn device_info() -> Arc<RwLock<DeviceInfo>> {
+    Arc::new(RwLock::new(DeviceInfo {
+        pin: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }))
+}
+
+async fn handle_connection<S>(stream: S, state: Arc<Mutex<State>>) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).await? == 0 {
+            return Ok(());
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default().to_string();
+        let (path, query) = match target.split_once('?') {
+            Some((p, q)) => (p.to_string(), q.to_string()),
+            None => (target.clone(), String::new()),
+        };
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let len: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body).await?;
+
+        let request = RecordedRequest { method, path, query, headers, body };
+        let (response, truncate) = {
+            let mut state = state.lock().unwrap();
+            state.requests.push(request.clone());
+            let fault = state.faults.get_mut(&request.path).and_then(|q| q.pop_front());
+            match fault {
+                Some(Fault::Disconnect) => (None, None),
+                Some(Fault::Status(status)) => {
+                    let body = json!({ "code": status, "msg": "injected failure" });
+                    (Some((status, Vec::new(), serde_json::to_vec(&body).unwrap())), None)
+                }
+                Some(Fault::Truncate(n)) => (Some(respond(&mut state, &request)), Some(n)),
+                None => (Some(respond(&mut state, &request)), None),
+            }
+        };
+
+        let Some((status, extra_headers, body)) = response else {
+            return Ok(());
+        };
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+            status,
+            reason(status),
+            body.len()
+        );
+        if !extra_headers.iter().any(|(name, _)| name == "Content-Type") {
+            head.push_str("Content-Type: application/json\r\n");
+        }
+        for (name, value) in extra_headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        let stream = reader.get_mut();
+        stream.write_all(head.as_bytes()).await?;
+        if let Some(n) = truncate {
+            stream.write_all(&body[..n.min(body.len())]).await?;
+            stream.flush().await?;
+            return Ok(());
+        }
+        stream.write_all(&body).await?;
+        stream.flush().await?;
+    }
+}
+
+fn respond(state: &mut State, request: &RecordedRequest) -> (u16, Vec<(String, String)>, Vec<u8>) {
+    if let Some(data) = state.files.get(&request.path) {
+        return serve_file(data, request.header("range"));
+    }
+    let (status, headers, body) = route(state, request);
+    let body = if status == 304 { Vec::new() } else { serde_json::to_vec(&body).unwrap() };
+    (status, headers, body)
+}
+
+fn serve_file(data: &[u8], range: Option<&str>) -> (u16, Vec<(String, String)>, Vec<u8>) {
+    let mut headers = vec![("Content-Type".to_string(), "application/octet-stream".to_string())];
+    let start = range
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.strip_suffix('-'))
+        .and_then(|r| r.parse::<usize>().ok());
+    match start {
+        None => (200, headers, data.to_vec()),
+        Some(start) if start >= data.len() => {
+            headers.push(("Content-Range".to_string(), format!("bytes */{}", data.len())));
+            (416, headers, Vec::new())
+        }
+        Some(start) => {
+            headers.push((
+                "Content-Range".to_string(),
+                format!("bytes {}-{}/{}", start, data.len() - 1, data.len()),
+            ));
+            (206, headers, data[start..].to_vec())
+        }
+    }
+}
+
+fn route(state: &mut State, request: &RecordedRequest) -> (u16, Vec<(String, String)>, Value) {
+    let ok = json!({ "code": 200, "msg": "操作成功" });
+    match request.path.as_str() {
+        // 第一次登录签发 MOCK_TOKEN，之后每次登录签发新 token
+        endpoints::LOGIN => {
+            state.logins += 1;
+            let token = if state.logins == 1 { MOCK_TOKEN.to_string() } else { format!("{}-{}", MOCK_TOKEN, state.logins) };
+            let mut data = json!({ "token": token });
+            if let Some(ttl) = state.token_ttl {
+                data["expires_in"] = json!(ttl);
+            }
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "data": data }))
+        }
+        // PIN 000000 视为无效；重新注册沿用原设备 id，每次签发新凭据
+        endpoints::ENROLL => {
+            let body = request.json();
+            if body["pin"] == "000000" {
+                return (200, Vec::new(), json!({ "code": 403, "msg": "invalid pin" }));
+            }
+            state.enrollments += 1;
+            let device_id = match body["previousDeviceId"].as_str() {
+                Some(id) => id.to_string(),
+                None => format!("dev-{}", body["serialNumber"].as_str().unwrap_or_default()),
+            };
+            let credential = format!("cred-{}", state.enrollments);
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "data": { "device_id": device_id, "credential": credential } }))
+        }
+        endpoints::UNENROLL => (200, Vec::new(), ok),
+        endpoints::HEARTBEAT => {
+            let commands: Vec<Value> = state.commands.drain(..).collect();
+            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+            (200, Vec::new(), json!({
+                "server_time": now_ms,
+                "need_update": state.need_update,
+                "commands": commands,
+                "content_encodings": state.content_encodings,
+            }))
+        }
+        endpoints::POLICY => match &state.policy {
+            Some((_, etag)) if request.header("if-none-match") == Some(etag.as_str()) => {
+                (304, vec![("ETag".to_string(), etag.clone())], Value::Null)
+            }
+            Some((policy, etag)) => (
+                200,
+                vec![("ETag".to_string(), etag.clone())],
+                json!({ "code": 200, "msg": "操作成功", "data": policy }),
+            ),
+            None => (200, Vec::new(), json!({ "code": 500, "msg": "no policy configured" })),
+        },
+        endpoints::UPDATE => {
+            let data = state.update_info.clone().unwrap_or_else(|| json!({ "has_update": false }));
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "data": data }))
+        }
+        endpoints::UPLOAD_INIT | endpoints::UPLOAD_CHUNK | endpoints::UPLOAD_COMPLETE if state.chunk_size.is_some() => {
+            chunked_upload(state, request)
+        }
+        endpoints::UPLOAD_SCREENSHOT => {
+            state.uploaded_files += 1;
+            let name = format!("{}.jpg", state.uploaded_files);
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "url": format!("http://files.mock/{}", name), "fileName": name }))
+        }
+        endpoints::LOG_AUDIT
+        | endpoints::LOG_BEHAVIOR
+        | endpoints::LOG_SCREENSHOT
+        | endpoints::LOG_CLIPBOARD
+        | endpoints::LOG_FILE
+        | endpoints::LOG_PERIPHERAL
+        | endpoints::LOG_TRAFFIC_SUMMARY
+        | endpoints::LOG_USAGE_DAILY
+        | endpoints::ALERT
+        | endpoints::LOG_BATCHES
+        | endpoints::HEALTH => (200, Vec::new(), ok),
+        _ => (404, Vec::new(), json!({ "code": 404, "msg": "not found" })),
+    }
+}
+
+/// 分块上传：偏移不一致返回 409 与已收到的字节数，complete 时校验 SHA-256
+fn chunked_upload(state: &mut State, request: &RecordedRequest) -> (u16, Vec<(String, String)>, Value) {
+    use sha2::{Digest, Sha256};
+
+    let query: HashMap<&str, &str> = request.query.split('&').filter_map(|p| p.split_once('=')).collect();
+    match request.path.as_str() {
+        endpoints::UPLOAD_INIT => {
+            let body = request.json();
+            let id = format!("up-{}", state.chunked_uploads.len() + 1);
+            let sha256 = body["sha256"].as_str().unwrap_or_default().to_string();
+            state.chunked_uploads.insert(id.clone(), ChunkedUpload { sha256, data: Vec::new() });
+            let data = json!({ "uploadId": id, "chunkSize": state.chunk_size, "offset": 0 });
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "data": data }))
+        }
+        endpoints::UPLOAD_CHUNK => {
+            let id = query.get("uploadId").copied().unwrap_or_default();
+            let offset: usize = query.get("offset").and_then(|o| o.parse().ok()).unwrap_or(usize::MAX);
+            let Some(upload) = state.chunked_uploads.get_mut(id) else {
+                return (404, Vec::new(), json!({ "code": 404, "msg": "unknown upload" }));
+            };
+            if offset != upload.data.len() {
+                let data = json!({ "offset": upload.data.len() });
+                return (200, Vec::new(), json!({ "code": 409, "msg": "offset mismatch", "data": data }));
+            }
+            if !state.chunks_stalled {
+                upload.data.extend_from_slice(&request.body);
+            }
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "data": { "offset": upload.data.len() } }))
+        }
+        _ => {
+            let body = request.json();
+            let id = body["uploadId"].as_str().unwrap_or_default();
+            let Some(upload) = state.chunked_uploads.remove(id) else {
+                return (404, Vec::new(), json!({ "code": 404, "msg": "unknown upload" }));
+            };
+            let digest = hex::encode(Sha256::digest(&upload.data));
+            if digest != upload.sha256 || body["sha256"] != upload.sha256.as_str() {
+                return (200, Vec::new(), json!({ "code": 422, "msg": "sha256 mismatch" }));
+            }
+            state.uploaded_files += 1;
+            let name = format!("{}.jpg", state.uploaded_files);
+            state.files.insert(format!("/files/{}", name), upload.data);
+            (200, Vec::new(), json!({ "code": 200, "msg": "操作成功", "data": { "url": format!("http://files.mock/{}", name) } }))
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        304 => "Not Modified",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
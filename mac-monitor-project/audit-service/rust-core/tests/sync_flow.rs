@@ -0,0 +1,357 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::metrics::{prometheus, Metrics};
+use audit_logic_core::models::{AuditLog, ClipboardLog, ScreenshotLog};
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::sync::SyncService;
+use audit_logic_core::uploader::Uploader;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use support::{behavior_log, device_info, Fault, MockServer, MOCK_TOKEN};
+
+struct Harness {
+    server: MockServer,
+    db: Arc<Database>,
+    policy: Arc<PolicyStore>,
+    clock: Arc<LogicalClock>,
+    metrics: Arc<Metrics>,
+    sync: SyncService,
+    dir: PathBuf,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        let server = MockServer::start().await;
+        let dir = std::env::temp_dir().join(format!("audit-core-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let db = Arc::new(
+            Database::new(&format!("sqlite://{}", dir.join("audit.db").display()))
+                .await
+                .unwrap(),
+        );
+        let device_info = device_info();
+        let clock = Arc::new(LogicalClock::new());
+        let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", clock.clone()));
+        let policy = Arc::new(PolicyStore::load(
+            dir.join("policy_cache.json"),
+            db.clone(),
+            device_info.clone(),
+            clock.clone(),
+        ));
+        let metrics = Arc::new(Metrics::new(
+            db.clone(),
+            clock.clone(),
+            dir.join("audit.db"),
+            dir.join("screenshots"),
+        ));
+        let sync = SyncService::new(
+            db.clone(),
+            uploader,
+            clock.clone(),
+            policy.clone(),
+            device_info,
+            dir.join("screenshots").display().to_string(),
+            metrics.clone(),
+        );
+
+        Self { server, db, policy, clock, metrics, sync, dir }
+    }
+
+    async fn pending_behavior_logs(&self) -> usize {
+        self.db.get_unsent_behavior_logs().await.unwrap().len()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn policy_json(version: &str) -> serde_json::Value {
+    json!({
+        "version": version,
+        "process_blacklist": ["clash"],
+        "app_blacklist": ["proxyman"],
+    })
+}
+
+#[tokio::test]
+async fn sync_uploads_pending_logs_and_marks_them_sent() {
+    let h = Harness::new().await;
+    h.db.save_audit_log(&AuditLog {
+        cpe_id: "TEST-SN".to_string(),
+        id: "traffic-1".to_string(),
+        url: "https://example.com/".to_string(),
+        req_time: "2026-01-01 09:00:00".to_string(),
+        method_type: "GET".to_string(),
+        domain: "example.com".to_string(),
+        process_name: "curl".to_string(),
+        risk_level: 0,
+        ip: "127.0.0.1".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        host_id: "test-host".to_string(),
+        bytes: None,
+    })
+    .await
+    .unwrap();
+    h.db.save_behavior_log(&behavior_log("ProcessDetected", 1)).await.unwrap();
+    h.db.save_clipboard_log(&ClipboardLog {
+        id: None,
+        app_name: "Notes".to_string(),
+        bundle_id: "com.apple.Notes".to_string(),
+        op_time: "2026-01-01 09:00:00".to_string(),
+        content: "hello".to_string(),
+        content_type: "text/plain".to_string(),
+        risk_level: 0,
+        cpe_id: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+        policy_decision: Some("capture".to_string()),
+    })
+    .await
+    .unwrap();
+
+    h.sync.sync_logs().await.unwrap();
+
+    assert_eq!(h.server.requests(endpoints::LOG_AUDIT).len(), 1);
+    let behavior = h.server.requests(endpoints::LOG_BEHAVIOR);
+    assert_eq!(behavior.len(), 1);
+    assert_eq!(behavior[0].header("visit-token"), Some(MOCK_TOKEN));
+    assert_eq!(behavior[0].json()["op_type"], "ProcessDetected");
+    assert_eq!(h.server.requests(endpoints::LOG_CLIPBOARD)[0].json()["content"], "hello");
+    assert_eq!(h.server.requests(endpoints::LOGIN).len(), 1, "token should be cached");
+
+    assert!(h.db.get_unsent_audit_logs().await.unwrap().is_empty());
+    assert_eq!(h.pending_behavior_logs().await, 0);
+    assert!(h.db.get_unsent_clipboard_logs().await.unwrap().is_empty());
+
+    // 已上报的记录不会重复发送
+    h.sync.sync_logs().await.unwrap();
+    assert_eq!(h.server.requests(endpoints::LOG_BEHAVIOR).len(), 1);
+}
+
+#[tokio::test]
+async fn failed_uploads_are_retried_on_next_sync() {
+    let h = Harness::new().await;
+    h.db.save_behavior_log(&behavior_log("ProcessDetected", 1)).await.unwrap();
+    h.db.save_behavior_log(&behavior_log("AppDetected", 1)).await.unwrap();
+    h.server.fail_next(endpoints::LOG_BEHAVIOR, Fault::Status(500), 1);
+    h.server.fail_next(endpoints::LOG_BEHAVIOR, Fault::Disconnect, 1);
+
+    h.sync.sync_logs().await.unwrap();
+    assert_eq!(h.pending_behavior_logs().await, 2);
+
+    h.sync.sync_logs().await.unwrap();
+    assert_eq!(h.pending_behavior_logs().await, 0);
+    assert_eq!(h.server.requests(endpoints::LOG_BEHAVIOR).len(), 4);
+}
+
+#[tokio::test]
+async fn screenshot_file_is_uploaded_before_metadata() {
+    let h = Harness::new().await;
+    let image_path = h.dir.join("shot.jpg");
+    std::fs::write(&image_path, b"fake-jpeg-bytes").unwrap();
+    let screenshot = |hash: &str, path: String| ScreenshotLog {
+        id: None,
+        capture_time: "2026-01-01 09:00:00".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        image_path: path,
+        ocr_text: None,
+        risk_level: 1,
+        app_name: "Safari".to_string(),
+        image_hash: hash.to_string(),
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+        redaction_labels: None,
+        policy_decision: None,
+    };
+    h.db.save_screenshot_log(&screenshot("hash-full", image_path.display().to_string())).await.unwrap();
+    // 策略降级的截图只有元数据
+    h.db.save_screenshot_log(&screenshot("hash-meta", String::new())).await.unwrap();
+
+    h.server.fail_next(endpoints::UPLOAD_SCREENSHOT, Fault::Status(503), 1);
+    h.sync.sync_logs().await.unwrap();
+    let pending = h.db.get_unsent_screenshot_logs().await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].image_hash, "hash-full");
+
+    h.sync.sync_logs().await.unwrap();
+    assert!(h.db.get_unsent_screenshot_logs().await.unwrap().is_empty());
+
+    let files = h.server.requests(endpoints::UPLOAD_SCREENSHOT);
+    assert_eq!(files.len(), 2);
+    assert!(String::from_utf8_lossy(&files[1].body).contains("fake-jpeg-bytes"));
+
+    let metadata = h.server.requests(endpoints::LOG_SCREENSHOT);
+    assert_eq!(metadata.len(), 2);
+    assert_eq!(metadata[0].json()["image_path"], "");
+    assert_eq!(metadata[1].json()["image_hash"], "hash-full");
+    assert_eq!(metadata[1].json()["image_path"], "http://files.mock/1.jpg");
+}
+
+#[tokio::test]
+async fn heartbeat_refreshes_policy_with_conditional_fetch() {
+    let h = Harness::new().await;
+    h.server.set_policy(policy_json("v1"), "\"etag-v1\"");
+
+    h.sync.do_heartbeat(true).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v1"));
+    assert!(h.clock.is_synced(), "heartbeat server_time should sync the clock");
+    assert_eq!(h.server.requests(endpoints::HEARTBEAT)[0].json()["policyVersion"], serde_json::Value::Null);
+
+    // 未变更时服务端返回 304，策略保持不变
+    h.sync.do_heartbeat(true).await.unwrap();
+    let fetches = h.server.requests(endpoints::POLICY);
+    assert_eq!(fetches.len(), 2);
+    assert_eq!(fetches[1].header("if-none-match"), Some("\"etag-v1\""));
+    assert_eq!(h.server.requests(endpoints::HEARTBEAT)[1].json()["policyVersion"], "v1");
+
+    // 不强制刷新且服务端未要求更新时不拉取策略
+    h.server.set_policy(policy_json("v2"), "\"etag-v2\"");
+    h.sync.do_heartbeat(false).await.unwrap();
+    assert_eq!(h.server.requests(endpoints::POLICY).len(), 2);
+    assert_eq!(h.policy.current_version().as_deref(), Some("v1"));
+
+    h.server.set_need_update(true);
+    h.sync.do_heartbeat(false).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v2"));
+    assert_eq!(h.policy.previous().map(|p| p.version).as_deref(), Some("v1"));
+}
+
+#[tokio::test]
+async fn invalid_policy_is_rejected_and_remote_rollback_restores_previous() {
+    let h = Harness::new().await;
+    h.server.set_policy(policy_json("v1"), "\"etag-v1\"");
+    h.sync.do_heartbeat(true).await.unwrap();
+    h.server.set_policy(policy_json("v2"), "\"etag-v2\"");
+    h.sync.do_heartbeat(true).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v2"));
+
+    let mut invalid = policy_json("v3");
+    invalid["working_hours"] = json!([{ "days": [1], "start": "25:00", "end": "18:00" }]);
+    h.server.set_policy(invalid, "\"etag-v3\"");
+    h.sync.do_heartbeat(true).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v2"));
+
+    h.server.set_policy(policy_json("v2"), "\"etag-v2\"");
+    h.server.push_command("policy_rollback");
+    h.sync.do_heartbeat(false).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v1"));
+
+    // 服务端仍下发被回滚的版本时不再应用，直到出现新版本 (重启后同样生效)
+    h.sync.do_heartbeat(true).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v1"));
+    let reloaded = PolicyStore::load(
+        h.dir.join("policy_cache.json"),
+        h.db.clone(),
+        device_info(),
+        h.clock.clone(),
+    );
+    assert!(!reloaded.apply(serde_json::from_value(policy_json("v2")).unwrap(), None).await.unwrap());
+    h.server.set_policy(policy_json("v4"), "\"etag-v4\"");
+    h.sync.do_heartbeat(true).await.unwrap();
+    assert_eq!(h.policy.current_version().as_deref(), Some("v4"));
+
+    // 每次切换都记录为行为日志并随同步上报
+    h.sync.sync_logs().await.unwrap();
+    let actions: Vec<String> = h
+        .server
+        .requests(endpoints::LOG_BEHAVIOR)
+        .iter()
+        .map(|r| r.json())
+        .filter(|log| log["op_type"] == "PolicyTransition")
+        .map(|log| serde_json::from_str::<serde_json::Value>(log["detail"].as_str().unwrap()).unwrap()["action"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(actions, ["apply", "apply", "reject", "rollback", "apply"]);
+}
+
+#[tokio::test]
+async fn policy_is_not_switched_when_the_cache_cannot_be_written() {
+    let h = Harness::new().await;
+    // 缓存路径的上级是普通文件，无法写入
+    std::fs::write(h.dir.join("blocker"), b"").unwrap();
+    let policy = PolicyStore::load(
+        h.dir.join("blocker").join("policy_cache.json"),
+        h.db.clone(),
+        device_info(),
+        h.clock.clone(),
+    );
+    let before = policy.shared().read().unwrap().clone();
+
+    let err = policy.apply(serde_json::from_value(policy_json("v1")).unwrap(), None).await.unwrap_err();
+    assert!(err.contains("Failed to persist policy cache"), "{}", err);
+    assert_eq!(policy.current_version(), None);
+    assert_eq!(*policy.shared().read().unwrap(), before);
+}
+
+#[tokio::test]
+async fn heartbeat_failure_is_reported() {
+    let h = Harness::new().await;
+    h.server.fail_next(endpoints::HEARTBEAT, Fault::Status(500), 1);
+    assert!(h.sync.do_heartbeat(true).await.is_err());
+    assert!(h.server.requests(endpoints::POLICY).is_empty());
+
+    h.server.fail_next(endpoints::HEARTBEAT, Fault::Disconnect, 1);
+    assert!(h.sync.do_heartbeat(true).await.is_err());
+
+    h.sync.do_heartbeat(true).await.unwrap();
+}
+
+#[tokio::test]
+async fn status_tracks_pending_rows_and_failures_by_endpoint() {
+    let h = Harness::new().await;
+    h.db.save_behavior_log(&behavior_log("ProcessDetected", 1)).await.unwrap();
+    h.db.save_behavior_log(&behavior_log("AppDetected", 1)).await.unwrap();
+    std::fs::create_dir_all(h.dir.join("screenshots")).unwrap();
+    std::fs::write(h.dir.join("screenshots").join("a.jpg"), vec![0u8; 300]).unwrap();
+
+    h.server.fail_next(endpoints::LOG_BEHAVIOR, Fault::Status(500), 1);
+    h.sync.sync_logs().await.unwrap();
+
+    let status = h.metrics.status().await.unwrap();
+    assert_eq!(status.pending.behavior, 1);
+    assert_eq!(status.pending_total, 1);
+    assert_eq!(status.upload_failures.get(endpoints::LOG_BEHAVIOR), Some(&1));
+    assert_eq!((status.sync_rounds, status.sync_failures), (1, 1));
+    assert!(status.last_sync_ms.is_none() && status.last_sync_error.is_some());
+    assert_eq!((status.screenshot_dir_bytes, status.screenshot_files), (300, 1));
+    assert!(status.db_size_bytes > 0);
+
+    h.sync.sync_logs().await.unwrap();
+    let status = h.metrics.status().await.unwrap();
+    assert_eq!(status.pending_total, 0);
+    assert!(status.last_sync_ms.is_some() && status.last_sync_error.is_none());
+
+    let text = prometheus::render(&status);
+    assert!(text.contains("audit_core_pending_rows{table=\"behavior_logs\"} 0\n"));
+    assert!(text.contains(&format!("audit_core_upload_failures_total{{endpoint=\"{}\"}} 1\n", endpoints::LOG_BEHAVIOR)));
+    assert!(text.contains("# TYPE audit_core_sync_rounds_total counter\n"));
+}
+
+#[tokio::test]
+async fn heartbeat_carries_metrics_summary() {
+    let h = Harness::new().await;
+    h.db.save_behavior_log(&behavior_log("ProcessDetected", 1)).await.unwrap();
+
+    h.sync.do_heartbeat(false).await.unwrap();
+    h.sync.do_heartbeat(false).await.unwrap();
+
+    let heartbeats = h.server.requests(endpoints::HEARTBEAT);
+    let first = &heartbeats[0].json()["metrics"];
+    assert_eq!(first["pending"], 1);
+    assert_eq!(first["heartbeat_latency_ms"], serde_json::Value::Null);
+    // 第二次心跳上报上一次的往返耗时
+    assert!(heartbeats[1].json()["metrics"]["heartbeat_latency_ms"].is_i64());
+
+    let status = h.metrics.status().await.unwrap();
+    assert_eq!((status.heartbeats, status.heartbeat_failures), (2, 0));
+    assert!(status.clock_synced);
+}
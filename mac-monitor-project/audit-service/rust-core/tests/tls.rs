@@ -0,0 +1,195 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::uploader::endpoints;
+use audit_logic_core::uploader::tls::TlsConfig;
+use audit_logic_core::uploader::Uploader;
+use base64::Engine;
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use support::MockServer;
+use tokio_rustls::TlsAcceptor;
+
+struct Pki {
+    dir: PathBuf,
+    ca: Certificate,
+    ca_key: KeyPair,
+    server: Certificate,
+    server_key: KeyPair,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("audit-core-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.distinguished_name.push(DnType::CommonName, "Mac Monitor Test CA");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let server = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+        Self { dir, ca, ca_key, server, server_key }
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.join(name).display().to_string()
+    }
+
+    /// 签发设备证书，返回 (证书路径, 私钥路径)
+    fn issue_client(&self) -> (String, String) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.distinguished_name.push(DnType::CommonName, "TEST-SN");
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+        std::fs::write(self.dir.join("client.pem"), cert.pem()).unwrap();
+        std::fs::write(self.dir.join("client.key"), key.serialize_pem()).unwrap();
+        (self.path("client.pem"), self.path("client.key"))
+    }
+
+    fn server_pin(&self) -> String {
+        let (_, cert) = x509_parser::parse_x509_certificate(self.server.der()).unwrap();
+        let digest = Sha256::digest(cert.tbs_certificate.subject_pki.raw);
+        format!("sha256/{}", base64::engine::general_purpose::STANDARD.encode(digest))
+    }
+
+    fn acceptor(&self, require_client_cert: bool) -> TlsAcceptor {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap();
+        let builder = if require_client_cert {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.add(self.ca.der().clone()).unwrap();
+            let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .unwrap();
+            builder.with_client_cert_verifier(verifier)
+        } else {
+            builder.with_no_client_auth()
+        };
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.server_key.serialize_der()));
+        let config = builder
+            .with_single_cert(vec![CertificateDer::from(self.server.der().to_vec())], key)
+            .unwrap();
+        TlsAcceptor::from(Arc::new(config))
+    }
+}
+
+impl Drop for Pki {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn uploader_for(server: &MockServer) -> Uploader {
+    Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new()))
+}
+
+async fn upload(uploader: &Uploader) -> Result<(), String> {
+    uploader.upload_data(endpoints::LOG_BEHAVIOR, &json!({ "op_type": "Test" })).await
+}
+
+#[tokio::test]
+async fn private_ca_bundle_is_trusted() {
+    let pki = Pki::new();
+    let server = MockServer::start_tls(pki.acceptor(false)).await;
+    let uploader = uploader_for(&server);
+
+    // 默认只信任公共根证书，私有 CA 签发的证书被拒绝
+    let err = upload(&uploader).await.unwrap_err();
+    assert!(err.starts_with("TLS validation failed"), "{}", err);
+    let status = uploader.connection_status();
+    assert!(status.https);
+    assert!(status.last_error.as_ref().is_some_and(|e| e.tls));
+
+    uploader
+        .configure_tls(TlsConfig { ca_bundle_path: Some(pki.path("ca.pem")), ..Default::default() })
+        .unwrap();
+    upload(&uploader).await.unwrap();
+    assert!(uploader.connection_status().last_error.is_none());
+    assert_eq!(server.requests(endpoints::LOG_BEHAVIOR).len(), 1);
+}
+
+#[tokio::test]
+async fn spki_pin_must_match_server_key() {
+    let pki = Pki::new();
+    let server = MockServer::start_tls(pki.acceptor(false)).await;
+    let uploader = uploader_for(&server);
+
+    let wrong_pin = format!("sha256/{}", base64::engine::general_purpose::STANDARD.encode([7u8; 32]));
+    uploader
+        .configure_tls(TlsConfig {
+            ca_bundle_path: Some(pki.path("ca.pem")),
+            spki_pins: vec![wrong_pin],
+            ..Default::default()
+        })
+        .unwrap();
+    let err = upload(&uploader).await.unwrap_err();
+    assert!(err.contains("does not match any pinned SPKI"), "{}", err);
+    assert!(err.contains(&pki.server_pin()), "error should report the presented key: {}", err);
+
+    uploader
+        .configure_tls(TlsConfig {
+            ca_bundle_path: Some(pki.path("ca.pem")),
+            spki_pins: vec![pki.server_pin()],
+            ..Default::default()
+        })
+        .unwrap();
+    upload(&uploader).await.unwrap();
+}
+
+#[tokio::test]
+async fn client_certificate_is_presented_for_mutual_tls() {
+    let pki = Pki::new();
+    let server = MockServer::start_tls(pki.acceptor(true)).await;
+    let uploader = uploader_for(&server);
+
+    uploader
+        .configure_tls(TlsConfig { ca_bundle_path: Some(pki.path("ca.pem")), ..Default::default() })
+        .unwrap();
+    assert!(upload(&uploader).await.is_err());
+
+    let (cert, key) = pki.issue_client();
+    uploader
+        .configure_tls(TlsConfig {
+            ca_bundle_path: Some(pki.path("ca.pem")),
+            client_cert_path: Some(cert),
+            client_key_path: Some(key),
+            ..Default::default()
+        })
+        .unwrap();
+    upload(&uploader).await.unwrap();
+    assert!(uploader.connection_status().mutual_tls);
+}
+
+#[tokio::test]
+async fn invalid_tls_config_fails_closed() {
+    let pki = Pki::new();
+    let server = MockServer::start_tls(pki.acceptor(false)).await;
+    let uploader = uploader_for(&server);
+
+    let err = uploader
+        .configure_tls(TlsConfig { ca_bundle_path: Some(pki.path("missing.pem")), ..Default::default() })
+        .unwrap_err();
+    assert!(err.contains("missing.pem"), "{}", err);
+    assert!(uploader
+        .configure_tls(TlsConfig { spki_pins: vec!["not-base64!".to_string()], ..Default::default() })
+        .is_err());
+
+    // 不会退回到未校验的连接
+    assert!(upload(&uploader).await.is_err());
+    assert!(server.requests(endpoints::LOG_BEHAVIOR).is_empty());
+    assert!(uploader.connection_status().last_error.is_some_and(|e| e.tls));
+}
@@ -0,0 +1,157 @@
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::models::{AuditLog, DeviceInfo, LogQuery, LogTable, PolicyConfig, TrafficAction, TrafficRule};
+use audit_logic_core::policy::PolicyStore;
+use audit_logic_core::traffic::aggregate::{path_template, TrafficAggregator};
+use audit_logic_core::traffic::{evaluate, TrafficFilter};
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+fn rule(pattern: &str, action: TrafficAction, risk_level: Option<i32>) -> TrafficRule {
+    TrafficRule { pattern: pattern.to_string(), action, risk_level }
+}
+
+fn request(domain: &str, url: &str) -> AuditLog {
+    AuditLog {
+        cpe_id: "TEST-SN".to_string(),
+        id: uuid::Uuid::new_v4().to_string(),
+        url: url.to_string(),
+        req_time: "2026-01-01 09:00:00".to_string(),
+        method_type: "GET".to_string(),
+        domain: domain.to_string(),
+        process_name: "Safari".to_string(),
+        risk_level: 0,
+        ip: "127.0.0.1".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        host_id: "test-host".to_string(),
+        bytes: None,
+    }
+}
+
+#[test]
+fn rules_match_by_suffix_wildcard_and_path() {
+    // 内置策略保持原来的白名单：github.com / google.com 及其子域名
+    let builtin = PolicyConfig::builtin().traffic_rules;
+    assert!(evaluate(&builtin, "api.GitHub.com", "https://api.github.com/repos").store);
+    assert!(evaluate(&builtin, "google.com:443", "https://google.com/").store);
+    assert!(!evaluate(&builtin, "notgithub.com", "https://notgithub.com/").store);
+    assert!(!evaluate(&builtin, "example.com", "https://example.com/").store);
+
+    let rules = vec![
+        rule("*.internal.example.com", TrafficAction::Exclude, None),
+        rule("example.com/upload", TrafficAction::Include, Some(2)),
+        rule("cdn-*.example.com", TrafficAction::Include, Some(1)),
+        rule("example.com/*/export/*", TrafficAction::Include, Some(1)),
+    ];
+    assert_eq!(evaluate(&rules, "git.internal.example.com", "https://git.internal.example.com/upload").rule, Some(0));
+    assert!(!evaluate(&rules, "git.internal.example.com", "/").store);
+    // `*.` 只匹配子域名
+    assert_eq!(evaluate(&rules, "internal.example.com", "https://internal.example.com/x").rule, None);
+
+    let upload = evaluate(&rules, "www.example.com", "https://www.example.com/upload/file?id=1");
+    assert_eq!((upload.store, upload.rule, upload.risk_level), (true, Some(1), Some(2)));
+    assert_eq!(evaluate(&rules, "cdn-eu.example.com", "https://cdn-eu.example.com/a.js").rule, Some(2));
+    assert_eq!(evaluate(&rules, "example.com", "https://example.com/team/export/report.csv").rule, Some(3));
+    assert!(!evaluate(&rules, "example.com", "https://example.com/export").store);
+
+    // 只有 exclude 规则时未命中的请求入库
+    let blacklist = vec![rule("ads.example.com", TrafficAction::Exclude, None)];
+    assert!(evaluate(&blacklist, "example.org", "https://example.org/").store);
+    assert!(!evaluate(&blacklist, "x.ads.example.com", "https://x.ads.example.com/").store);
+    assert!(evaluate(&[], "example.org", "https://example.org/").store);
+}
+
+#[tokio::test]
+async fn filter_follows_policy_updates_and_counts_records() {
+    let dir = std::env::temp_dir().join(format!("audit-core-traffic-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = Arc::new(RwLock::new(DeviceInfo {
+        pin: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }));
+    let policy = PolicyStore::load(dir.join("policy_cache.json"), db, device_info, Arc::new(LogicalClock::new()));
+    let filter = TrafficFilter::new(policy.shared());
+
+    assert!(filter.check(&mut request("github.com", "https://github.com/")));
+    assert!(!filter.check(&mut request("pastebin.com", "https://pastebin.com/raw/1")));
+
+    let update = json!({
+        "version": "traffic-v2",
+        "traffic_rules": [
+            { "pattern": "pastebin.com", "risk_level": 2 },
+            { "pattern": "*", "action": "exclude" }
+        ]
+    });
+    policy.apply(serde_json::from_value::<PolicyConfig>(update).unwrap(), None).await.unwrap();
+    let mut paste = request("pastebin.com", "https://pastebin.com/raw/1");
+    assert!(filter.check(&mut paste));
+    assert_eq!(paste.risk_level, 2);
+    assert!(!filter.check(&mut request("github.com", "https://github.com/")));
+
+    let stats = filter.stats();
+    assert_eq!((stats.stored, stats.dropped, stats.tagged, stats.rules), (2, 2, 1, 2));
+
+    // 非法规则的策略被拒绝，原策略继续生效
+    let invalid = json!({ "version": "traffic-v3", "traffic_rules": [{ "pattern": "https://example.com" }] });
+    assert!(policy.apply(serde_json::from_value::<PolicyConfig>(invalid).unwrap(), None).await.is_err());
+    let invalid = json!({ "version": "traffic-v4", "traffic_rules": [{ "pattern": "example.com", "risk_level": 5 }] });
+    assert!(policy.apply(serde_json::from_value::<PolicyConfig>(invalid).unwrap(), None).await.is_err());
+    assert_eq!(policy.current_version().as_deref(), Some("traffic-v2"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn aggregation_rolls_up_requests_and_keeps_flagged_records() {
+    assert_eq!(path_template("/repos/42/issues/0f3a9c2e4b5d6e7f"), "/repos/{id}/issues/{id}");
+    assert_eq!(path_template("/v1/items/3fa85f64-5717-4562-b3fc-2c963f66afa6/raw"), "/v1/items/{id}/raw");
+    assert_eq!(path_template("/api/v2/status"), "/api/v2/status");
+
+    let dir = std::env::temp_dir().join(format!("audit-core-traffic-agg-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let policy = Arc::new(RwLock::new(PolicyConfig::builtin()));
+    let aggregator = TrafficAggregator::new(db.clone(), policy.clone(), Arc::new(LogicalClock::new()));
+
+    // 未开启时不聚合
+    assert!(!aggregator.absorb(&request("github.com", "https://github.com/")));
+    policy.write().unwrap().traffic_aggregate_secs = Some(3600);
+
+    for (id, time) in [(1, "09:00:05"), (2, "09:00:01"), (3, "09:00:09")] {
+        let mut poll = request("API.github.com", &format!("https://api.github.com/repos/{}/events?page=1", id));
+        poll.req_time = format!("2026-01-01 {}", time);
+        poll.bytes = Some(100);
+        assert!(aggregator.absorb(&poll));
+    }
+    let mut post = request("api.github.com", "https://api.github.com/repos/1/events");
+    post.method_type = "POST".to_string();
+    assert!(aggregator.absorb(&post));
+    // 带风险等级的请求保留完整记录
+    let mut flagged = request("api.github.com", "https://api.github.com/repos/1/events");
+    flagged.risk_level = 2;
+    assert!(!aggregator.absorb(&flagged));
+
+    // 时间桶未结束时不写入，退出时全部写入
+    assert_eq!(aggregator.flush(false).await, 0);
+    assert_eq!(aggregator.pending(), 2);
+    assert_eq!(aggregator.flush(true).await, 2);
+
+    let rows = db.query_logs(&LogQuery::new(LogTable::TrafficSummary)).await.unwrap();
+    let get = rows.iter().find(|r| r["method_type"] == "GET").unwrap();
+    assert_eq!(get["domain"], "api.github.com");
+    assert_eq!(get["path_template"], "/repos/{id}/events");
+    assert_eq!((get["request_count"].as_i64(), get["bytes"].as_i64()), (Some(3), Some(300)));
+    assert_eq!((get["first_seen"].as_str(), get["last_seen"].as_str()), (Some("2026-01-01 09:00:01"), Some("2026-01-01 09:00:09")));
+    assert_eq!(get["bucket_secs"], 3600);
+
+    let stats = aggregator.stats();
+    assert_eq!((stats.bucket_secs, stats.aggregated, stats.summaries, stats.pending), (Some(3600), 4, 2, 0));
+    assert_eq!(db.count_unsent().await.unwrap().traffic_summary, 2);
+    assert!(db.verify_chain().await.unwrap().is_intact());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
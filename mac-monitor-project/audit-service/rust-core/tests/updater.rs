@@ -0,0 +1,179 @@
+mod support;
+
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::updater::installer::InstallerHook;
+use audit_logic_core::updater::{UpdateConfig, UpdateManifest, UpdateState, Updater};
+use audit_logic_core::uploader::Uploader;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use support::{Fault, MockServer};
+
+const PACKAGE_PATH: &str = "/files/audit-service-9.9.9.pkg";
+const MANIFEST_PATH: &str = "/files/audit-service-9.9.9.pkg.manifest.json";
+const SIGNATURE_PATH: &str = "/files/audit-service-9.9.9.pkg.manifest.json.sig";
+
+#[derive(Default)]
+struct RecordingInstaller {
+    installed: Mutex<Vec<(PathBuf, String)>>,
+}
+
+impl InstallerHook for RecordingInstaller {
+    fn install(&self, package: &Path, manifest: &UpdateManifest) -> Result<(), String> {
+        self.installed.lock().unwrap().push((package.to_path_buf(), manifest.version.clone()));
+        Ok(())
+    }
+}
+
+struct Release {
+    key: SigningKey,
+    package: Vec<u8>,
+}
+
+impl Release {
+    fn new() -> Self {
+        let package = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        Self { key: SigningKey::from_bytes(&[7u8; 32]), package }
+    }
+
+    fn manifest(&self) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "version": "9.9.9",
+            "file_name": "audit-service-9.9.9.pkg",
+            "size": self.package.len(),
+            "sha256": hex::encode(Sha256::digest(&self.package)),
+        }))
+        .unwrap()
+    }
+
+    fn sign(&self, manifest: &[u8]) -> Vec<u8> {
+        let signature = self.key.sign(manifest);
+        base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()).into_bytes()
+    }
+
+    /// 发布到 mock 服务端；`package` 为实际提供下载的内容
+    fn publish(&self, server: &MockServer, package: &[u8], manifest: &[u8], signature: &[u8]) {
+        server.set_update(json!({ "has_update": true, "version": "9.9.9", "url": PACKAGE_PATH }));
+        server.serve_file(PACKAGE_PATH, package);
+        server.serve_file(MANIFEST_PATH, manifest);
+        server.serve_file(SIGNATURE_PATH, signature);
+    }
+
+    fn config(&self, staging: &Path) -> UpdateConfig {
+        UpdateConfig {
+            public_key: Some(hex::encode(self.key.verifying_key().to_bytes())),
+            staging_dir: Some(staging.display().to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+fn staging_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("audit-core-update-{}", uuid::Uuid::new_v4()))
+}
+
+fn updater(server: &MockServer, config: UpdateConfig, installer: Arc<RecordingInstaller>) -> Updater {
+    let uploader = Arc::new(Uploader::new("mac_monitor", "secret", &server.base_url(), "TEST-SN", Arc::new(LogicalClock::new())));
+    Updater::with_installer(uploader, config, staging_dir(), Some(installer))
+}
+
+#[tokio::test]
+async fn verified_update_resumes_partial_download_and_installs() {
+    let server = MockServer::start().await;
+    let release = Release::new();
+    let manifest = release.manifest();
+    release.publish(&server, &release.package, &manifest, &release.sign(&manifest));
+
+    // 上次下载中断，留下前一半数据
+    let staging = staging_dir();
+    let version_dir = staging.join("9.9.9");
+    std::fs::create_dir_all(&version_dir).unwrap();
+    std::fs::write(version_dir.join("audit-service-9.9.9.pkg.part"), &release.package[..80_000]).unwrap();
+    // 本次传输再次中断一次
+    server.fail_next(PACKAGE_PATH, Fault::Truncate(50_000), 1);
+
+    let installer = Arc::new(RecordingInstaller::default());
+    let updater = updater(&server, release.config(&staging), installer.clone());
+    assert_eq!(updater.run().await, Ok(UpdateState::Installed));
+
+    let downloads = server.requests(PACKAGE_PATH);
+    assert_eq!(downloads.len(), 2);
+    assert_eq!(downloads[0].header("range"), Some("bytes=80000-"));
+    assert!(downloads[1].header("range").is_some(), "retry must resume instead of restarting");
+
+    let package = version_dir.join("audit-service-9.9.9.pkg");
+    assert_eq!(std::fs::read(&package).unwrap(), release.package);
+    assert_eq!(*installer.installed.lock().unwrap(), vec![(package.clone(), "9.9.9".to_string())]);
+
+    let progress = updater.progress();
+    assert_eq!(progress.state, UpdateState::Installed);
+    assert_eq!(progress.downloaded_bytes, release.package.len() as u64);
+    assert_eq!(progress.package_path, Some(package.display().to_string()));
+
+    let _ = std::fs::remove_dir_all(&staging);
+}
+
+#[tokio::test]
+async fn tampered_manifest_is_rejected_before_download() {
+    let server = MockServer::start().await;
+    let release = Release::new();
+    let manifest = release.manifest();
+    let signature = release.sign(&manifest);
+    let mut tampered = manifest.clone();
+    tampered.extend_from_slice(b" ");
+    release.publish(&server, &release.package, &tampered, &signature);
+
+    let staging = staging_dir();
+    let installer = Arc::new(RecordingInstaller::default());
+    let updater = updater(&server, release.config(&staging), installer.clone());
+    let err = updater.run().await.unwrap_err();
+
+    assert!(err.contains("signature verification failed"), "unexpected error: {}", err);
+    assert!(server.requests(PACKAGE_PATH).is_empty());
+    assert!(installer.installed.lock().unwrap().is_empty());
+    assert_eq!(updater.progress().state, UpdateState::Failed);
+
+    let _ = std::fs::remove_dir_all(&staging);
+}
+
+#[tokio::test]
+async fn package_digest_mismatch_is_discarded() {
+    let server = MockServer::start().await;
+    let release = Release::new();
+    let manifest = release.manifest();
+    let mut corrupted = release.package.clone();
+    corrupted[1234] ^= 0xff;
+    release.publish(&server, &corrupted, &manifest, &release.sign(&manifest));
+
+    let staging = staging_dir();
+    let installer = Arc::new(RecordingInstaller::default());
+    let updater = updater(&server, release.config(&staging), installer.clone());
+    let err = updater.run().await.unwrap_err();
+
+    assert!(err.contains("SHA-256 mismatch"), "unexpected error: {}", err);
+    assert!(installer.installed.lock().unwrap().is_empty());
+    let version_dir = staging.join("9.9.9");
+    assert!(!version_dir.join("audit-service-9.9.9.pkg").exists());
+    assert!(!version_dir.join("audit-service-9.9.9.pkg.part").exists());
+
+    let _ = std::fs::remove_dir_all(&staging);
+}
+
+#[tokio::test]
+async fn updates_are_refused_without_a_pinned_key() {
+    let server = MockServer::start().await;
+    let release = Release::new();
+    let manifest = release.manifest();
+    release.publish(&server, &release.package, &manifest, &release.sign(&manifest));
+
+    let staging = staging_dir();
+    let config = UpdateConfig { staging_dir: Some(staging.display().to_string()), ..Default::default() };
+    let updater = updater(&server, config, Arc::new(RecordingInstaller::default()));
+    let err = updater.run().await.unwrap_err();
+
+    assert!(err.contains("No update public key"), "unexpected error: {}", err);
+    assert!(server.requests(MANIFEST_PATH).is_empty());
+}
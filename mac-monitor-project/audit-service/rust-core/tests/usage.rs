@@ -0,0 +1,142 @@
+use audit_logic_core::clock::LogicalClock;
+use audit_logic_core::db::Database;
+use audit_logic_core::lifecycle::TaskTracker;
+use audit_logic_core::models::{AppUsage, DeviceInfo, ScreenshotLog};
+use audit_logic_core::session::{SessionEvent, SessionEventKind, SessionTracker};
+use audit_logic_core::usage::{ForegroundEvent, ForegroundKind, UsageTracker};
+use chrono::{Local, NaiveDate, TimeZone};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// 本地时间 2026-01-{day} {hour}:{minute}
+fn at(day: u32, hour: u32, minute: u32) -> i64 {
+    let time = NaiveDate::from_ymd_opt(2026, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap();
+    Local.from_local_datetime(&time).earliest().unwrap().timestamp_millis()
+}
+
+fn foreground(app: &str, time_ms: i64) -> ForegroundEvent {
+    ForegroundEvent {
+        bundle_id: Some(format!("com.example.{}", app.to_lowercase())),
+        time_ms: Some(time_ms),
+        ..ForegroundEvent::foreground(app)
+    }
+}
+
+fn kind(kind: ForegroundKind, time_ms: i64) -> ForegroundEvent {
+    ForegroundEvent { time_ms: Some(time_ms), ..ForegroundEvent::new(kind) }
+}
+
+async fn setup(name: &str) -> (std::path::PathBuf, Arc<Database>, Arc<TaskTracker>, Arc<SessionTracker>, Arc<UsageTracker>) {
+    let dir = std::env::temp_dir().join(format!("audit-core-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::new(&format!("sqlite://{}", dir.join("audit.db").display())).await.unwrap());
+    let device_info = Arc::new(RwLock::new(DeviceInfo {
+        pin: "TEST-SN".to_string(),
+        host_id: "test-host".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+    }));
+    let clock = Arc::new(LogicalClock::new());
+    let tasks = TaskTracker::new();
+    let handle = tokio::runtime::Handle::current();
+    let session = SessionTracker::new(db.clone(), device_info.clone(), clock.clone(), tasks.clone(), handle.clone());
+    let usage = UsageTracker::new(db.clone(), device_info, clock, tasks.clone(), handle);
+    let listener = usage.clone();
+    session.on_pause_change(move |paused, time_ms| listener.set_away(paused, time_ms));
+    (dir, db, tasks, session, usage)
+}
+
+#[tokio::test]
+async fn usage_excludes_idle_and_locked_time_and_summarizes_each_day() {
+    let (dir, db, tasks, session, usage) = setup("usage").await;
+
+    assert_eq!(usage.submit(foreground("Safari", at(5, 9, 0))).await.unwrap(), 0);
+    assert_eq!(usage.current_app().as_deref(), Some("Safari"));
+    // 同一应用重复上报不切分时段
+    assert_eq!(usage.submit(foreground("Safari", at(5, 9, 5))).await.unwrap(), 0);
+    assert_eq!(usage.submit(foreground("Xcode", at(5, 9, 10))).await.unwrap(), 1);
+    // 空闲期间不计时
+    assert_eq!(usage.submit(kind(ForegroundKind::Idle, at(5, 9, 40))).await.unwrap(), 1);
+    assert_eq!(usage.submit(kind(ForegroundKind::Active, at(5, 9, 45))).await.unwrap(), 0);
+    assert_eq!(usage.submit(foreground("Safari", at(5, 10, 0))).await.unwrap(), 1);
+
+    // 锁屏期间不计时
+    let lock = SessionEvent { time_ms: Some(at(5, 10, 5)), ..SessionEvent::new(SessionEventKind::Lock) };
+    session.save(lock).await.unwrap();
+    assert!(usage.current_app().is_none());
+    let unlock = SessionEvent { time_ms: Some(at(5, 11, 0)), ..SessionEvent::new(SessionEventKind::Unlock) };
+    session.save(unlock).await.unwrap();
+    assert_eq!(usage.current_app().as_deref(), Some("Safari"));
+    // 跨过零点的时段按日期拆分
+    assert_eq!(usage.submit(kind(ForegroundKind::Idle, at(6, 0, 20))).await.unwrap(), 2);
+    assert!(tasks.wait_idle(Duration::from_secs(5)).await);
+
+    db.save_screenshot_log(&ScreenshotLog {
+        id: None,
+        capture_time: "2026-01-05 10:01:00".to_string(),
+        cpe_id: "TEST-SN".to_string(),
+        image_path: "/tmp/shot.jpg".to_string(),
+        ocr_text: None,
+        risk_level: 1,
+        app_name: "Safari".to_string(),
+        image_hash: "hash".to_string(),
+        host_id: "test-host".to_string(),
+        mac: "00:11:22:33:44:55".to_string(),
+        ip: "127.0.0.1".to_string(),
+        redaction_labels: None,
+        policy_decision: None,
+    })
+    .await
+    .unwrap();
+
+    let report = usage.report("2026-01-05").await.unwrap();
+    let safari = AppUsage {
+        app_name: "Safari".to_string(),
+        bundle_id: Some("com.example.safari".to_string()),
+        // 10 分钟 + 5 分钟 + 13 小时，锁屏的 55 分钟不计
+        active_secs: (10 + 5 + 13 * 60) * 60,
+        sessions: 3,
+        screenshots: 1,
+        risk_events: 1,
+    };
+    let xcode = AppUsage {
+        app_name: "Xcode".to_string(),
+        bundle_id: Some("com.example.xcode".to_string()),
+        active_secs: (30 + 15) * 60,
+        sessions: 2,
+        ..Default::default()
+    };
+    assert_eq!(report.apps, vec![safari, xcode]);
+    assert_eq!(report.active_secs, (10 + 5 + 13 * 60 + 45) * 60);
+    assert_eq!(report.risk_events.screenshot, 1);
+    assert_eq!(usage.report("2026-01-06").await.unwrap().active_secs, 20 * 60);
+
+    // 已结束的每一天生成一条汇总，只生成一次
+    assert_eq!(usage.summarize_completed().await.unwrap(), 2);
+    assert_eq!(usage.summarize_completed().await.unwrap(), 0);
+    let summary = db.get_usage_summary("2026-01-05").await.unwrap().unwrap();
+    assert_eq!(summary.active_secs, report.active_secs);
+    let apps: Vec<AppUsage> = serde_json::from_str(&summary.apps).unwrap();
+    assert_eq!(apps, report.apps);
+    assert_eq!(summary.cpe_id, "TEST-SN");
+    assert_eq!(db.count_unsent().await.unwrap().usage_summary, 2);
+    assert!(db.verify_chain().await.unwrap().is_intact());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn foreground_events_require_an_app_name() {
+    let (dir, _db, _tasks, _session, usage) = setup("usage-invalid").await;
+
+    assert!(usage.submit(ForegroundEvent::new(ForegroundKind::Foreground)).await.is_err());
+    assert!(usage.submit(ForegroundEvent::foreground("  ")).await.is_err());
+    let json = r#"{"kind": "foreground", "app_name": "Terminal", "bundle_id": "com.apple.Terminal"}"#;
+    let event: ForegroundEvent = serde_json::from_str(json).unwrap();
+    assert!(usage.record(event).unwrap());
+    assert_eq!(usage.current_app().as_deref(), Some("Terminal"));
+    assert!(serde_json::from_str::<ForegroundEvent>(r#"{"kind": "focus"}"#).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
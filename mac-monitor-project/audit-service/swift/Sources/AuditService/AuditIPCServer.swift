@@ -116,8 +116,9 @@ class AuditIPCServer {
             if let payloadDict = payload as? [String: Any],
                let serverIp = payloadDict["server_ip"] as? String,
                let serverPort = payloadDict["server_port"] as? String,
-               let cpeId = payloadDict["cpe_id"] as? String,
                let pin = payloadDict["pin"] as? String {
+                // 设备标识由 Rust Core 取本机序列号，cpe_id 仅为兼容保留
+                let cpeId = payloadDict["cpe_id"] as? String ?? ""
 
                 let success = rust_register_device(serverIp, serverPort, cpeId, pin)
 
@@ -126,9 +127,9 @@ class AuditIPCServer {
                     {"status":"ok","message":"Registration successful","payload":null}
                     """
                 } else {
-                    return """
-                    {"status":"error","message":"Registration failed in core"}
-                    """
+                    let response: [String: Any] = ["status": "error", "message": "Registration failed: \(auditCoreLastError())"]
+                    let data = (try? JSONSerialization.data(withJSONObject: response)) ?? Data()
+                    return String(data: data, encoding: .utf8) ?? "{\"status\":\"error\"}"
                 }
             } else {
                 return """
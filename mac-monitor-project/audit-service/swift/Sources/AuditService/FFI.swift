@@ -1,7 +1,26 @@
 import Foundation
 
 @_silgen_name("init_audit_core")
-func rust_init_audit_core()
+func rust_init_audit_core() -> Int32
+
+@_silgen_name("shutdown_audit_core")
+func rust_shutdown_audit_core(_ timeout_ms: UInt32) -> Int32
+
+@_silgen_name("reload_audit_core_config")
+func rust_reload_audit_core_config() -> Int32
+
+@_silgen_name("audit_core_last_error")
+func rust_audit_core_last_error() -> UnsafeMutablePointer<CChar>?
+
+@_silgen_name("audit_core_free_string")
+func rust_audit_core_free_string(_ ptr: UnsafeMutablePointer<CChar>?)
+
+/// Rust Core 最近一次失败的原因
+func auditCoreLastError() -> String {
+    guard let ptr = rust_audit_core_last_error() else { return "unknown error" }
+    defer { rust_audit_core_free_string(ptr) }
+    return String(cString: ptr)
+}
 
 @_silgen_name("analyze_enhanced_image")
 func rust_analyze_enhanced_image(
@@ -38,8 +57,42 @@ func rust_log_clipboard_event(
     _ risk_level: Int32
 )
 
-@_silgen_name("update_redaction_status")
-func rust_update_redaction_status(_ enabled: Bool) {
+/// 文件操作事件 JSON：{"op": "create|modify|rename|delete|copy_to_removable", "path", "old_path"?, "process_name"?, "pid"?, "size"?}
+/// 返回 0 已接收，1 被策略过滤，-1 失败
+@_silgen_name("log_file_event")
+func rust_log_file_event(_ event_json: UnsafePointer<CChar>) -> Int32
+
+/// 外设事件 JSON：{"action": "attach|detach", "device_class", "vendor_id"?, "product_id"?, "serial"?, "name"?, "volume_label"?, "mount_point"?}
+/// 返回 0 已接收，-1 失败
+@_silgen_name("log_peripheral_event")
+func rust_log_peripheral_event(_ event_json: UnsafePointer<CChar>) -> Int32
+
+/// 会话事件 JSON：{"kind": "login|logout|lock|unlock|sleep|wake|switch_in|switch_out", "session_id"?, "user"?, "source"?}
+/// 返回 0 已接收，-1 失败
+@_silgen_name("log_session_event")
+func rust_log_session_event(_ event_json: UnsafePointer<CChar>) -> Int32
+
+/// 前台应用事件 JSON：{"kind": "foreground|idle|active", "app_name"?, "bundle_id"?, "time_ms"?}
+/// foreground 事件必须带 app_name；返回 0 已接收，-1 失败
+@_silgen_name("log_foreground_event")
+func rust_log_foreground_event(_ event_json: UnsafePointer<CChar>) -> Int32
+
+/// 锁屏 / 睡眠 / 切到其他用户期间为 true，此时不截图
+@_silgen_name("audit_core_capture_paused")
+func rust_audit_core_capture_paused() -> Bool
+
+/// 低数据模式 / 计费网络 (NWPath.isConstrained / isExpensive)；受限时截图推迟上传
+@_silgen_name("audit_core_set_network_constrained")
+func rust_audit_core_set_network_constrained(_ constrained: Bool)
+
+@_silgen_name("get_screenshot_interval_secs")
+func rust_get_screenshot_interval_secs() -> UInt32
+
+@_silgen_name("register_redaction_callback")
+func rust_register_redaction_callback(_ callback: (@convention(c) (Bool) -> Void)?)
+
+/// IPC set_redaction_status 的处理函数，在 init_audit_core 之前注册给 Rust Core
+func handleRedactionStatus(_ enabled: Bool) {
     if #available(macOS 12.3, *) {
         ScreenCapturer.shared.redactionEnabled = enabled
         print("🛡 Redaction status updated via IPC: \(enabled)")
@@ -0,0 +1,67 @@
+import Cocoa
+
+/// 将前台应用切换与空闲状态转发给 Rust Core (log_foreground_event)，用于统计各应用使用时长
+class ForegroundMonitor {
+    static let shared = ForegroundMonitor()
+
+    // 超过该时间没有键盘 / 鼠标输入视为空闲
+    private let idleThreshold: TimeInterval = 300
+    private let pollInterval: TimeInterval = 15
+
+    private var observer: NSObjectProtocol?
+    private var timer: Timer?
+    private var idle = false
+
+    private init() {}
+
+    func start() {
+        observer = NSWorkspace.shared.notificationCenter.addObserver(
+            forName: NSWorkspace.didActivateApplicationNotification, object: nil, queue: .main
+        ) { [weak self] note in
+            let app = note.userInfo?[NSWorkspace.applicationUserInfoKey] as? NSRunningApplication
+            self?.postForeground(app)
+        }
+        timer = Timer.scheduledTimer(withTimeInterval: pollInterval, repeats: true) { [weak self] _ in
+            self?.checkIdle()
+        }
+        postForeground(NSWorkspace.shared.frontmostApplication)
+        print("🪟 Foreground Monitor: Started")
+    }
+
+    func stop() {
+        if let observer = observer {
+            NSWorkspace.shared.notificationCenter.removeObserver(observer)
+        }
+        observer = nil
+        timer?.invalidate()
+        timer = nil
+        print("🪟 Foreground Monitor: Stopped")
+    }
+
+    private func checkIdle() {
+        let anyInput = CGEventType(rawValue: ~0)!
+        let seconds = CGEventSource.secondsSinceLastEventType(.combinedSessionState, eventType: anyInput)
+        let nowIdle = seconds >= idleThreshold
+        guard nowIdle != idle else { return }
+        idle = nowIdle
+        post(["kind": nowIdle ? "idle" : "active"])
+    }
+
+    private func postForeground(_ app: NSRunningApplication?) {
+        guard let app = app, let name = app.localizedName else { return }
+        idle = false
+        var event: [String: Any] = ["kind": "foreground", "app_name": name]
+        if let bundleId = app.bundleIdentifier {
+            event["bundle_id"] = bundleId
+        }
+        post(event)
+    }
+
+    private func post(_ event: [String: Any]) {
+        guard let data = try? JSONSerialization.data(withJSONObject: event),
+              let json = String(data: data, encoding: .utf8) else { return }
+        if rust_log_foreground_event(json) != 0 {
+            print("⚠️ Failed to log foreground event: \(auditCoreLastError())")
+        }
+    }
+}
@@ -0,0 +1,33 @@
+import Foundation
+import Network
+
+/// 把低数据模式 / 计费网络 (个人热点等) 状态转发给 Rust Core，受限时截图推迟上传
+class NetworkMonitor {
+    static let shared = NetworkMonitor()
+
+    private let monitor = NWPathMonitor()
+    private let queue = DispatchQueue(label: "com.macmonitor.audit.network")
+    private var constrained: Bool?
+
+    private init() {}
+
+    func start() {
+        monitor.pathUpdateHandler = { [weak self] path in
+            self?.update(path.isConstrained || path.isExpensive)
+        }
+        monitor.start(queue: queue)
+        print("📶 Network Monitor: Started")
+    }
+
+    func stop() {
+        monitor.cancel()
+        print("📶 Network Monitor: Stopped")
+    }
+
+    private func update(_ value: Bool) {
+        guard value != constrained else { return }
+        constrained = value
+        rust_audit_core_set_network_constrained(value)
+        print("📶 Network is \(value ? "constrained / metered" : "unconstrained")")
+    }
+}
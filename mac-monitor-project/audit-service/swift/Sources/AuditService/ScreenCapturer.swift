@@ -344,6 +344,9 @@ class ScreenCapturer: NSObject, SCStreamOutput, SCStreamDelegate {
         // 我们现在只处理 displayStream
         guard stream === displayStream else { return }
 
+        // 锁屏 / 睡眠 / 切到其他用户期间不采集
+        guard !rust_audit_core_capture_paused() else { return }
+
         let now = Date()
 
         // 1. 全屏截图频率控制
@@ -0,0 +1,59 @@
+import Cocoa
+
+/// 将登录 / 锁屏 / 睡眠 / 快速用户切换通知转发给 Rust Core (log_session_event)
+class SessionMonitor {
+    static let shared = SessionMonitor()
+
+    private var observers: [(NotificationCenter, NSObjectProtocol)] = []
+    // 当前 GUI 会话的 audit session id
+    private let sessionId = String(getsid(0))
+
+    private init() {}
+
+    func start() {
+        let workspace = NSWorkspace.shared.notificationCenter
+        let distributed = DistributedNotificationCenter.default()
+
+        observe(distributed, "com.apple.screenIsLocked", kind: "lock")
+        observe(distributed, "com.apple.screenIsUnlocked", kind: "unlock")
+        observe(workspace, NSWorkspace.willSleepNotification.rawValue, kind: "sleep")
+        observe(workspace, NSWorkspace.didWakeNotification.rawValue, kind: "wake")
+        observe(workspace, NSWorkspace.sessionDidResignActiveNotification.rawValue, kind: "switch_out")
+        observe(workspace, NSWorkspace.sessionDidBecomeActiveNotification.rawValue, kind: "switch_in")
+        // 注销 / 关机 / 重启前发出
+        observe(workspace, NSWorkspace.willPowerOffNotification.rawValue, kind: "logout")
+
+        // 服务随用户登录启动，启动即视为登录
+        post(kind: "login")
+        print("🔒 Session Monitor: Started")
+    }
+
+    func stop() {
+        for (center, observer) in observers {
+            center.removeObserver(observer)
+        }
+        observers.removeAll()
+        print("🔒 Session Monitor: Stopped")
+    }
+
+    private func observe(_ center: NotificationCenter, _ name: String, kind: String) {
+        let observer = center.addObserver(forName: NSNotification.Name(name), object: nil, queue: .main) { [weak self] _ in
+            self?.post(kind: kind)
+        }
+        observers.append((center, observer))
+    }
+
+    private func post(kind: String) {
+        let event: [String: Any] = [
+            "kind": kind,
+            "session_id": sessionId,
+            "user": NSUserName(),
+            "source": "loginwindow"
+        ]
+        guard let data = try? JSONSerialization.data(withJSONObject: event),
+              let json = String(data: data, encoding: .utf8) else { return }
+        if rust_log_session_event(json) != 0 {
+            print("⚠️ Failed to log session event \(kind): \(auditCoreLastError())")
+        }
+    }
+}
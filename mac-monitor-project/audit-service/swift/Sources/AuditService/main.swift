@@ -19,7 +19,11 @@ func startAuditServices() {
 
     // Initialize Rust Core (Starts Scanner and Sync Service)
     // Note: Rust Core also starts the IPC server on /tmp/mac_monitor_audit.sock
-    rust_init_audit_core()
+    rust_register_redaction_callback { enabled in handleRedactionStatus(enabled) }
+    if rust_init_audit_core() != 0 {
+        print("❌ Rust Core failed to initialize: \(auditCoreLastError())")
+        exit(1)
+    }
 
     // Start Screen Capturer (if macOS 12.3+)
     if #available(macOS 12.3, *) {
@@ -29,6 +33,18 @@ func startAuditServices() {
         print("⚠️ Screen Capture requires macOS 12.3+")
     }
 
+    // Start Session Monitor (锁屏 / 睡眠 / 用户切换)
+    print("🔒 Initializing Session Monitor...")
+    SessionMonitor.shared.start()
+
+    // Start Foreground Monitor (前台应用使用时长)
+    print("🪟 Initializing Foreground Monitor...")
+    ForegroundMonitor.shared.start()
+
+    // Start Network Monitor (低数据模式 / 计费网络时推迟截图上传)
+    print("📶 Initializing Network Monitor...")
+    NetworkMonitor.shared.start()
+
     // Start Clipboard Monitor
     print("📋 Initializing Clipboard Monitor...")
     ClipboardMonitor.shared.start()
@@ -37,17 +53,45 @@ func startAuditServices() {
     print("📍 Socket location: /tmp/mac_monitor_audit.sock")
 }
 
-// Set up signal handling for graceful shutdown
-signal(SIGINT) { _ in
-    print("\n🛑 Received SIGINT, shutting down...")
-    exit(0)
+// Set up signal handling for graceful shutdown.
+// 信号在主队列上处理，这样可以安全地调用 Rust Core 的阻塞接口
+var signalSources: [DispatchSourceSignal] = []
+
+func handleSignal(_ sig: Int32, _ handler: @escaping () -> Void) {
+    signal(sig, SIG_IGN)
+    let source = DispatchSource.makeSignalSource(signal: sig, queue: .main)
+    source.setEventHandler(handler: handler)
+    source.resume()
+    signalSources.append(source)
 }
 
-signal(SIGTERM) { _ in
-    print("\n🛑 Received SIGTERM, shutting down...")
+func shutdownAndExit(_ name: String) {
+    print("\n🛑 Received \(name), shutting down...")
+    ClipboardMonitor.shared.stop()
+    ForegroundMonitor.shared.stop()
+    SessionMonitor.shared.stop()
+    NetworkMonitor.shared.stop()
+    // 等待未写完的数据落盘并尝试最后一次同步，最多 10 秒
+    switch rust_shutdown_audit_core(10_000) {
+    case 0:
+        print("✅ Rust Core stopped")
+    case 2:
+        print("⚠️ Rust Core stopped with errors: \(auditCoreLastError())")
+    default:
+        break
+    }
     exit(0)
 }
 
+handleSignal(SIGINT) { shutdownAndExit("SIGINT") }
+handleSignal(SIGTERM) { shutdownAndExit("SIGTERM") }
+handleSignal(SIGHUP) {
+    print("🔄 Received SIGHUP, reloading config...")
+    if rust_reload_audit_core_config() != 0 {
+        print("❌ Config reload failed: \(auditCoreLastError())")
+    }
+}
+
 startAuditServices()
 
 print("⏳ Entering main dispatch loop (dispatchMain)...")
@@ -0,0 +1,298 @@
+//! 逻辑时钟：以管理端时间为基准，所有组件的日志时间戳都由此生成
+//!
+//! 偏移量按 NTP 方式估算：请求发出时刻 t0、服务端时间 ts、响应到达时刻 t3，
+//! 假设链路对称，则 offset = ts - (t0 + t3) / 2，误差不超过 RTT / 2。
+//! 保留最近若干个样本，取 RTT 最小的一半样本的偏移中位数，
+//! 单次网络抖动或异常的服务端时间不会让时钟跳变。
+//! 只有服务端时间、没有 RTT 的样本不参与 RTT 筛选，仅在没有可测 RTT 的样本时使用。
+//!
+//! 偏移回调 (或本地时间倒退) 时不回退时间戳：逻辑时间以一半速率前进，
+//! 追上新的估计后恢复正常，既不倒退也不停住。
+
+use chrono::{Local, TimeZone};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_SAMPLES: usize = 8;
+// RTT 超过该值的样本误差过大，直接丢弃
+const MAX_RTT_MS: i64 = 10_000;
+// 小于该值的时间戳视为秒级 (毫秒级时间戳在 1973 年之后都大于该值)
+const SECONDS_THRESHOLD: i64 = 100_000_000_000;
+// 追赶回调偏移期间，逻辑时间按本地时间流逝的 1/SLEW_DIVISOR 前进
+const SLEW_DIVISOR: i64 = 2;
+
+#[derive(Debug, Clone, Copy)]
+struct ClockSample {
+    offset_ms: i64,
+    /// None 表示无法测量 RTT (只有服务端时间)
+    rtt_ms: Option<i64>,
+}
+
+/// 已发出的最大时间戳；`anchor` 为开始追赶时的 (本地时间, 逻辑时间)
+#[derive(Debug, Clone, Copy)]
+struct Issued {
+    last_ms: i64,
+    anchor: Option<(i64, i64)>,
+}
+
+pub struct LogicalClock {
+    offset_ms: AtomicI64,
+    issued: Mutex<Issued>,
+    synced: AtomicBool,
+    samples: Mutex<VecDeque<ClockSample>>,
+}
+
+static GLOBAL_CLOCK: LogicalClock = LogicalClock::new();
+
+impl Default for LogicalClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogicalClock {
+    pub const fn new() -> Self {
+        Self {
+            offset_ms: AtomicI64::new(0),
+            issued: Mutex::new(Issued { last_ms: 0, anchor: None }),
+            synced: AtomicBool::new(false),
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// 进程级共享时钟，供不便传递实例的独立进程 (流量代理 / 网络扩展 / GUI) 使用；审计服务传递自己的实例
+    pub fn global() -> &'static LogicalClock {
+        &GLOBAL_CLOCK
+    }
+
+    /// 记录一次请求/响应交换
+    ///
+    /// `sent_ms` / `received_ms` 为本地系统时间 (`system_now_ms`)，
+    /// `server_time` 为服务端返回的时间 (秒或毫秒均可)；缺失时保持当前偏移不变。
+    /// 返回更新后的偏移估计。
+    pub fn record_exchange(&self, sent_ms: i64, server_time: Option<i64>, received_ms: i64) -> Option<i64> {
+        let server_ms = match server_time {
+            Some(t) if t > 0 => normalize_epoch_ms(t),
+            _ => {
+                log::warn!(
+                    "Server time missing in response, keeping clock offset {}ms",
+                    self.offset_ms()
+                );
+                return None;
+            }
+        };
+
+        let rtt_ms = received_ms - sent_ms;
+        if !(0..=MAX_RTT_MS).contains(&rtt_ms) {
+            log::warn!("Discarding clock sample with RTT {}ms", rtt_ms);
+            return None;
+        }
+
+        let sample = ClockSample {
+            offset_ms: server_ms - (sent_ms + received_ms) / 2,
+            rtt_ms: Some(rtt_ms),
+        };
+        Some(self.add_sample(sample))
+    }
+
+    /// 仅有服务端时间、无法测量 RTT 时使用；此类样本不参与 RTT 筛选
+    pub fn record_server_time(&self, server_time: i64) -> Option<i64> {
+        if server_time <= 0 {
+            return None;
+        }
+        let sample = ClockSample {
+            offset_ms: normalize_epoch_ms(server_time) - system_now_ms(),
+            rtt_ms: None,
+        };
+        Some(self.add_sample(sample))
+    }
+
+    fn add_sample(&self, sample: ClockSample) -> i64 {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+
+        let estimate = estimate_offset(samples.make_contiguous());
+        let previous = self.offset_ms.swap(estimate, Ordering::SeqCst);
+        self.synced.store(true, Ordering::SeqCst);
+
+        log::debug!(
+            "Clock sample: offset={}ms rtt={:?}ms, estimate {}ms -> {}ms ({} samples)",
+            sample.offset_ms, sample.rtt_ms, previous, estimate, samples.len()
+        );
+        estimate
+    }
+
+    /// 当前估计的偏移 (服务端时间 - 本地时间)
+    pub fn offset_ms(&self) -> i64 {
+        self.offset_ms.load(Ordering::SeqCst)
+    }
+
+    /// 是否已至少获得过一次有效的服务端时间
+    pub fn is_synced(&self) -> bool {
+        self.synced.load(Ordering::SeqCst)
+    }
+
+    /// 当前逻辑时间 (毫秒)，单调不减：偏移回调时以一半速率追赶，不倒退也不停住
+    pub fn now_ms(&self) -> i64 {
+        self.logical_at(system_now_ms())
+    }
+
+    fn logical_at(&self, local_ms: i64) -> i64 {
+        let candidate = local_ms + self.offset_ms();
+        let mut issued = self.issued.lock().unwrap();
+        if candidate >= issued.last_ms {
+            issued.last_ms = candidate;
+            issued.anchor = None;
+            return candidate;
+        }
+
+        let last_ms = issued.last_ms;
+        let (anchor_local, anchor_logical) = *issued.anchor.get_or_insert((local_ms, last_ms));
+        let slewed = anchor_logical + (local_ms - anchor_local).max(0) / SLEW_DIVISOR;
+        issued.last_ms = slewed.max(last_ms);
+        issued.last_ms
+    }
+
+    /// 当前逻辑时间 (秒)
+    pub fn now_secs(&self) -> i64 {
+        self.now_ms() / 1000
+    }
+
+    /// 日志记录使用的时间格式 "yyyy-MM-dd HH:mm:ss"
+    pub fn now_str(&self) -> String {
+        format_ms(self.now_ms())
+    }
+}
+
+/// 本地系统时间 (毫秒)，用于记录请求发出/响应到达时刻
+pub fn system_now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// 服务端时间可能是秒级或毫秒级，统一为毫秒
+pub fn normalize_epoch_ms(value: i64) -> i64 {
+    if value < SECONDS_THRESHOLD {
+        value * 1000
+    } else {
+        value
+    }
+}
+
+pub fn format_ms(timestamp_ms: i64) -> String {
+    Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .unwrap_or_else(Local::now)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// 取 RTT 最小的一半样本，返回其偏移中位数；没有可测 RTT 的样本时取全部样本的中位数
+fn estimate_offset(samples: &[ClockSample]) -> i64 {
+    let mut by_rtt: Vec<ClockSample> = samples.iter().filter(|s| s.rtt_ms.is_some()).copied().collect();
+    if by_rtt.is_empty() {
+        by_rtt = samples.to_vec();
+    } else {
+        by_rtt.sort_by_key(|s| s.rtt_ms);
+        by_rtt.truncate(by_rtt.len().div_ceil(2));
+    }
+
+    let mut offsets: Vec<i64> = by_rtt.iter().map(|s| s.offset_ms).collect();
+    offsets.sort_unstable();
+    let mid = offsets.len() / 2;
+    if offsets.len().is_multiple_of(2) {
+        (offsets[mid - 1] + offsets[mid]) / 2
+    } else {
+        offsets[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: i64 = 1_767_000_000_000;
+
+    #[test]
+    fn lowest_rtt_half_wins_over_jittery_samples() {
+        let clock = LogicalClock::new();
+        // 两个低 RTT 样本偏移约 +500ms，两个高 RTT 样本被网络抖动带偏
+        clock.record_exchange(BASE, Some(BASE + 510), BASE + 20);
+        clock.record_exchange(BASE, Some(BASE + 515), BASE + 30);
+        clock.record_exchange(BASE, Some(BASE + 4_000), BASE + 3_000);
+        let estimate = clock.record_exchange(BASE, Some(BASE - 2_000), BASE + 5_000);
+        assert_eq!(estimate, Some(500));
+        // RTT 过大或为负的样本直接丢弃
+        assert_eq!(clock.record_exchange(BASE, Some(BASE), BASE + MAX_RTT_MS + 1), None);
+        assert_eq!(clock.record_exchange(BASE, Some(BASE), BASE - 1), None);
+        assert_eq!(clock.offset_ms(), 500);
+    }
+
+    #[test]
+    fn samples_without_rtt_do_not_outrank_measured_ones() {
+        let clock = LogicalClock::new();
+        clock.record_exchange(BASE, Some(BASE + 1_000), BASE + 200);
+        clock.record_exchange(BASE, Some(BASE + 1_010), BASE + 220);
+        clock.record_server_time(system_now_ms() - 60_000);
+        clock.record_server_time(system_now_ms() - 60_000);
+        clock.record_server_time(system_now_ms() - 60_000);
+        assert_eq!(clock.offset_ms(), 900);
+
+        // 只有服务端时间时按其中位数估计 (秒级时间戳同样接受)
+        let unmeasured = LogicalClock::new();
+        let offset = unmeasured.record_server_time((system_now_ms() + 30_000) / 1000).unwrap();
+        assert!((28_000..=31_000).contains(&offset), "{}", offset);
+        assert_eq!(unmeasured.record_server_time(0), None);
+    }
+
+    #[test]
+    fn missing_server_time_keeps_the_offset() {
+        let clock = LogicalClock::new();
+        assert_eq!(clock.record_exchange(BASE, None, BASE + 10), None);
+        assert_eq!(clock.record_exchange(BASE, Some(0), BASE + 10), None);
+        assert!(!clock.is_synced());
+        assert_eq!(clock.offset_ms(), 0);
+        assert_eq!(clock.logical_at(BASE), BASE);
+
+        clock.record_exchange(BASE, Some(BASE + 300), BASE);
+        assert_eq!(clock.record_exchange(BASE, None, BASE + 10), None);
+        assert!(clock.is_synced());
+        assert_eq!(clock.offset_ms(), 300);
+    }
+
+    #[test]
+    fn negative_offset_slews_without_freezing() {
+        let clock = LogicalClock::new();
+        clock.record_exchange(BASE, Some(BASE + 10_000), BASE);
+        assert_eq!(clock.logical_at(BASE), BASE + 10_000);
+
+        // 服务端时间回调 10s：逻辑时间不倒退，按一半速率继续前进
+        for _ in 0..MAX_SAMPLES {
+            clock.record_exchange(BASE, Some(BASE), BASE);
+        }
+        assert_eq!(clock.offset_ms(), 0);
+        let mut previous = clock.logical_at(BASE);
+        assert_eq!(previous, BASE + 10_000);
+        for step in 1..=10 {
+            let now = clock.logical_at(BASE + step * 1_000);
+            assert_eq!(now, BASE + 10_000 + step * 500);
+            assert!(now > previous);
+            previous = now;
+        }
+
+        // 追上新的估计后恢复按本地时间 + 偏移计时
+        assert_eq!(clock.logical_at(BASE + 20_000), BASE + 20_000);
+        assert_eq!(clock.logical_at(BASE + 20_001), BASE + 20_001);
+        // 本地时间倒退时也不回退
+        assert_eq!(clock.logical_at(BASE + 15_000), BASE + 20_001);
+        assert_eq!(clock.logical_at(BASE + 17_000), BASE + 21_001);
+    }
+}
@@ -0,0 +1,283 @@
+use super::DeviceIdentity;
+use std::ffi::CStr;
+use std::io;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MACHINE_ID_PATHS: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+// product_serial 通常仅 root 可读，普通用户下回退到主板序列号 / UUID
+const DMI_SERIAL_PATHS: &[&str] = &[
+    "/sys/class/dmi/id/product_serial",
+    "/sys/class/dmi/id/board_serial",
+    "/sys/class/dmi/id/product_uuid",
+];
+// 厂商未填写时常见的占位值
+const DMI_PLACEHOLDERS: &[&str] = &[
+    "",
+    "0",
+    "none",
+    "default string",
+    "to be filled by o.e.m.",
+    "not specified",
+    "system serial number",
+    "not applicable",
+];
+const RTF_UP: u32 = 0x0001;
+// 一次网络切换 (如 DHCP 续约) 会产生一串 netlink 消息，等待其平息后再采集
+const CHANGE_SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// 基于 procfs / sysfs / netlink 的 Linux 实现
+#[derive(Default)]
+pub struct LinuxIdentity {
+    netlink: Mutex<Option<NetlinkSocket>>,
+}
+
+impl LinuxIdentity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DeviceIdentity for LinuxIdentity {
+    fn serial_number(&self) -> Option<String> {
+        DMI_SERIAL_PATHS.iter().find_map(|path| {
+            let value = read_trimmed(path)?;
+            if DMI_PLACEHOLDERS.contains(&value.to_lowercase().as_str()) {
+                None
+            } else {
+                Some(value)
+            }
+        })
+    }
+
+    fn machine_id(&self) -> Option<String> {
+        MACHINE_ID_PATHS
+            .iter()
+            .find_map(|path| read_trimmed(path).filter(|id| !id.is_empty()))
+    }
+
+    fn os_version(&self) -> Option<String> {
+        ["/etc/os-release", "/usr/lib/os-release"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| parse_os_release(&content))
+    }
+
+    fn primary_interface(&self) -> Option<String> {
+        let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+        parse_default_route(&routes)
+    }
+
+    fn ip_for_interface(&self, interface: &str) -> Option<String> {
+        ipv4_for_interface(interface).map(|ip| ip.to_string())
+    }
+
+    fn mac_for_interface(&self, interface: &str) -> Option<String> {
+        let mac = read_trimmed(&format!("/sys/class/net/{}/address", interface))?;
+        if mac.is_empty() || mac == super::FALLBACK_MAC {
+            None
+        } else {
+            Some(mac)
+        }
+    }
+
+    fn wait_for_network_change(&self, timeout: Duration) {
+        let mut guard = self.netlink.lock().unwrap();
+        if guard.is_none() {
+            match NetlinkSocket::open() {
+                Ok(socket) => *guard = Some(socket),
+                Err(e) => {
+                    log::warn!("Failed to open netlink socket, falling back to polling: {}", e);
+                    drop(guard);
+                    std::thread::sleep(timeout);
+                    return;
+                }
+            }
+        }
+
+        let socket = guard.as_ref().unwrap();
+        if socket.wait(timeout) {
+            std::thread::sleep(CHANGE_SETTLE_DELAY);
+            socket.drain();
+        }
+    }
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// os-release 中的 PRETTY_NAME，缺省时拼接 NAME 与 VERSION
+fn parse_os_release(content: &str) -> Option<String> {
+    let field = |key: &str| {
+        content.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix('=')?;
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            (!value.is_empty()).then(|| value.to_string())
+        })
+    };
+    field("PRETTY_NAME").or_else(|| match (field("NAME"), field("VERSION")) {
+        (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+        (name, _) => name,
+    })
+}
+
+/// 从 `/proc/net/route` 中选出 metric 最小且处于 UP 状态的默认路由网卡
+fn parse_default_route(routes: &str) -> Option<String> {
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 7 || cols[1] != "00000000" {
+                return None;
+            }
+            let flags = u32::from_str_radix(cols[3], 16).ok()?;
+            if flags & RTF_UP == 0 {
+                return None;
+            }
+            let metric: u32 = cols[6].parse().ok()?;
+            Some((metric, cols[0].to_string()))
+        })
+        .min_by_key(|(metric, _)| *metric)
+        .map(|(_, iface)| iface)
+}
+
+fn ipv4_for_interface(interface: &str) -> Option<Ipv4Addr> {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return None;
+    }
+
+    let mut found = None;
+    let mut cursor = addrs;
+    while !cursor.is_null() {
+        let ifa = unsafe { &*cursor };
+        if !ifa.ifa_addr.is_null() && !ifa.ifa_name.is_null() {
+            let name = unsafe { CStr::from_ptr(ifa.ifa_name) };
+            let family = unsafe { (*ifa.ifa_addr).sa_family } as libc::c_int;
+            if family == libc::AF_INET && name.to_bytes() == interface.as_bytes() {
+                let sin = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+                found = Some(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)));
+                break;
+            }
+        }
+        cursor = ifa.ifa_next;
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+    found
+}
+
+/// 订阅链路、地址与路由变化的 rtnetlink socket
+struct NetlinkSocket {
+    fd: libc::c_int,
+}
+
+impl NetlinkSocket {
+    fn open() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = Self { fd };
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = (libc::RTMGRP_LINK
+            | libc::RTMGRP_IPV4_IFADDR
+            | libc::RTMGRP_IPV4_ROUTE
+            | libc::RTMGRP_IPV6_IFADDR) as u32;
+
+        let ret = unsafe {
+            libc::bind(
+                socket.fd,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(socket)
+    }
+
+    /// 等待事件到达，超时返回 false
+    fn wait(&self, timeout: Duration) -> bool {
+        let mut pfd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+        let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        let ret = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+        ret > 0 && (pfd.revents & libc::POLLIN) != 0
+    }
+
+    /// 丢弃已排队的消息，我们只关心"发生了变化"
+    fn drain(&self) {
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = unsafe {
+                libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), libc::MSG_DONTWAIT)
+            };
+            if n <= 0 {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for NetlinkSocket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTE_HEADER: &str =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n";
+
+    #[test]
+    fn default_route_prefers_the_lowest_metric_up_interface() {
+        let routes = format!(
+            "{}{}{}{}{}",
+            ROUTE_HEADER,
+            "wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n",
+            "eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0\n",
+            // 未 UP 的默认路由即使 metric 更小也不选
+            "tun0\t00000000\t00000000\t0000\t0\t0\t50\t00000000\t0\t0\t0\n",
+            "eth0\t0000000A\t00000000\t0001\t0\t0\t0\t000000FF\t0\t0\t0\n",
+        );
+        assert_eq!(parse_default_route(&routes), Some("eth0".to_string()));
+    }
+
+    #[test]
+    fn no_default_route_yields_none() {
+        let routes = format!(
+            "{}{}",
+            ROUTE_HEADER, "eth0\t0000000A\t00000000\t0001\t0\t0\t0\t000000FF\t0\t0\t0\n"
+        );
+        assert_eq!(parse_default_route(&routes), None);
+        assert_eq!(parse_default_route(ROUTE_HEADER), None);
+        assert_eq!(parse_default_route(""), None);
+    }
+
+    #[test]
+    fn os_release_uses_pretty_name_then_name_and_version() {
+        let ubuntu = "NAME=\"Ubuntu\"\nVERSION=\"24.04 LTS (Noble Numbat)\"\nPRETTY_NAME=\"Ubuntu 24.04 LTS\"\nID=ubuntu\n";
+        assert_eq!(parse_os_release(ubuntu), Some("Ubuntu 24.04 LTS".to_string()));
+
+        let minimal = "NAME='Alpine Linux'\nVERSION=3.20\nPRETTY_NAME=\"\"\n";
+        assert_eq!(parse_os_release(minimal), Some("Alpine Linux 3.20".to_string()));
+        assert_eq!(parse_os_release("NAME=Arch\n"), Some("Arch".to_string()));
+        // PRETTY_NAME_EXTRA 之类的前缀不会被误认
+        assert_eq!(parse_os_release("PRETTY_NAME_EXTRA=x\nID=none\n"), None);
+    }
+}
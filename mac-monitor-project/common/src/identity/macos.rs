@@ -0,0 +1,119 @@
+use super::DeviceIdentity;
+use std::process::Command;
+
+/// 基于系统命令 (`ioreg` / `route` / `ipconfig` / `ifconfig`) 的 macOS 实现
+#[derive(Default)]
+pub struct MacosIdentity;
+
+impl MacosIdentity {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn platform_property(&self, key: &str) -> Option<String> {
+        let output = Command::new("ioreg")
+            .args(["-c", "IOPlatformExpertDevice", "-d", "2"])
+            .output();
+
+        let output = match output {
+            Ok(o) => o,
+            Err(e) => {
+                log::error!("Failed to execute ioreg: {}", e);
+                return None;
+            }
+        };
+
+        let s = String::from_utf8_lossy(&output.stdout);
+        for line in s.lines() {
+            if line.contains(key) {
+                let parts: Vec<&str> = line.split('=').collect();
+                if parts.len() == 2 {
+                    let value = parts[1].trim().trim_matches(|c| c == '"' || c == ' ');
+                    if !value.is_empty() {
+                        return Some(value.to_string());
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl DeviceIdentity for MacosIdentity {
+    fn serial_number(&self) -> Option<String> {
+        self.platform_property("IOPlatformSerialNumber")
+    }
+
+    fn machine_id(&self) -> Option<String> {
+        self.platform_property("IOPlatformUUID")
+    }
+
+    fn os_version(&self) -> Option<String> {
+        let output = Command::new("sw_vers").arg("-productVersion").output().ok()?;
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || version.is_empty() {
+            return None;
+        }
+        Some(format!("macOS {}", version))
+    }
+
+    fn primary_interface(&self) -> Option<String> {
+        let output = Command::new("route")
+            .args(["-n", "get", "default"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let s = String::from_utf8_lossy(&output.stdout);
+        for line in s.lines() {
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix("interface:") {
+                let iface = rest.trim();
+                if !iface.is_empty() {
+                    return Some(iface.to_string());
+                }
+            }
+        }
+        None
+    }
+
+    fn ip_for_interface(&self, interface: &str) -> Option<String> {
+        let output = Command::new("ipconfig")
+            .args(["getifaddr", interface])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let ip = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if ip.is_empty() {
+            None
+        } else {
+            Some(ip)
+        }
+    }
+
+    fn mac_for_interface(&self, interface: &str) -> Option<String> {
+        let output = Command::new("ifconfig")
+            .arg(interface)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let s = String::from_utf8_lossy(&output.stdout);
+        for line in s.lines() {
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix("ether ") {
+                let mac = rest.split_whitespace().next().unwrap_or("").trim();
+                if !mac.is_empty() {
+                    return Some(mac.to_string());
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,249 @@
+//! 设备身份识别 (序列号 / 机器 ID / 主网卡 IP 与 MAC)
+//!
+//! 各平台的采集方式差异较大，统一抽象为 `DeviceIdentity` trait：
+//! - macOS: `ioreg` / `route` / `ipconfig` / `ifconfig`
+//! - Linux: `/etc/machine-id`、DMI (`/sys/class/dmi/id`)、`/proc/net/route`、sysfs 与 netlink
+//!
+//! `IdentityMonitor` 在网络变化时刷新快照，供审计核心与 GUI 共享。
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "linux")]
+pub use linux::LinuxIdentity;
+#[cfg(target_os = "macos")]
+pub use macos::MacosIdentity;
+
+use serde::{Deserialize, Serialize};
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub const UNKNOWN_SERIAL: &str = "UNKNOWN_SERIAL";
+pub const FALLBACK_IP: &str = "127.0.0.1";
+pub const FALLBACK_MAC: &str = "00:00:00:00:00:00";
+
+/// 某一时刻的设备身份
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentitySnapshot {
+    pub serial_number: String,
+    pub machine_id: Option<String>,
+    pub host_name: String,
+    /// 如 "macOS 15.1" / "Ubuntu 24.04.1 LTS"
+    #[serde(default)]
+    pub os_version: String,
+    pub interface: Option<String>,
+    pub ip: String,
+    pub mac: String,
+}
+
+/// 平台相关的设备身份采集接口
+pub trait DeviceIdentity: Send + Sync {
+    /// 硬件序列号
+    fn serial_number(&self) -> Option<String>;
+
+    /// 稳定的机器标识 (macOS: IOPlatformUUID, Linux: machine-id)
+    fn machine_id(&self) -> Option<String>;
+
+    /// 操作系统名称与版本
+    fn os_version(&self) -> Option<String> {
+        None
+    }
+
+    /// 默认路由所在的网卡名
+    fn primary_interface(&self) -> Option<String>;
+
+    fn ip_for_interface(&self, interface: &str) -> Option<String>;
+
+    fn mac_for_interface(&self, interface: &str) -> Option<String>;
+
+    /// 阻塞直到网络配置可能发生变化或超时。
+    /// 默认实现为定时轮询，平台支持事件通知时应覆盖此方法。
+    fn wait_for_network_change(&self, timeout: Duration) {
+        std::thread::sleep(timeout);
+    }
+
+    /// 采集完整快照，各字段在平台接口失败时依次回退
+    fn snapshot(&self) -> IdentitySnapshot {
+        let machine_id = self.machine_id();
+        let serial_number = self
+            .serial_number()
+            .or_else(|| machine_id.clone())
+            .unwrap_or_else(|| UNKNOWN_SERIAL.to_string());
+        let interface = self.primary_interface();
+
+        let ip = interface
+            .as_deref()
+            .and_then(|iface| self.ip_for_interface(iface))
+            .or_else(fallback_ip)
+            .unwrap_or_else(|| FALLBACK_IP.to_string());
+
+        let mac = interface
+            .as_deref()
+            .and_then(|iface| self.mac_for_interface(iface))
+            .or_else(fallback_mac)
+            .unwrap_or_else(|| FALLBACK_MAC.to_string());
+
+        IdentitySnapshot {
+            serial_number,
+            machine_id,
+            host_name: host_name().unwrap_or_else(|| "Unknown-Host".to_string()),
+            os_version: self.os_version().unwrap_or_else(|| std::env::consts::OS.to_string()),
+            interface,
+            ip,
+            mac,
+        }
+    }
+}
+
+/// 当前平台的默认实现
+pub fn platform_identity() -> Arc<dyn DeviceIdentity> {
+    #[cfg(target_os = "macos")]
+    {
+        Arc::new(MacosIdentity::new())
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Arc::new(LinuxIdentity::new())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Arc::new(GenericIdentity)
+    }
+}
+
+/// 不支持的平台上仅依赖通用回退逻辑
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub struct GenericIdentity;
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+impl DeviceIdentity for GenericIdentity {
+    fn serial_number(&self) -> Option<String> {
+        None
+    }
+
+    fn machine_id(&self) -> Option<String> {
+        None
+    }
+
+    fn primary_interface(&self) -> Option<String> {
+        None
+    }
+
+    fn ip_for_interface(&self, _interface: &str) -> Option<String> {
+        None
+    }
+
+    fn mac_for_interface(&self, _interface: &str) -> Option<String> {
+        None
+    }
+}
+
+/// 持有最新的身份快照，并在网络变化时刷新
+pub struct IdentityMonitor {
+    provider: Arc<dyn DeviceIdentity>,
+    current: Arc<RwLock<IdentitySnapshot>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl IdentityMonitor {
+    pub fn new(provider: Arc<dyn DeviceIdentity>) -> Self {
+        let initial = provider.snapshot();
+        log::info!(
+            "Device identity: serial={}, host={}, interface={:?}, ip={}, mac={}",
+            initial.serial_number, initial.host_name, initial.interface, initial.ip, initial.mac
+        );
+        Self {
+            provider,
+            current: Arc::new(RwLock::new(initial)),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn current(&self) -> IdentitySnapshot {
+        self.current.read().unwrap().clone()
+    }
+
+    /// 重新采集快照，返回是否发生变化
+    pub fn refresh(&self) -> bool {
+        refresh_snapshot(self.provider.as_ref(), &self.current).is_some()
+    }
+
+    /// 启动后台线程监听网络变化，身份变化时回调 `on_change`
+    pub fn watch<F>(&self, poll_interval: Duration, on_change: F) -> JoinHandle<()>
+    where
+        F: Fn(&IdentitySnapshot) + Send + 'static,
+    {
+        let provider = self.provider.clone();
+        let current = self.current.clone();
+        let stopped = self.stopped.clone();
+
+        std::thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                provider.wait_for_network_change(poll_interval);
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Some(snapshot) = refresh_snapshot(provider.as_ref(), &current) {
+                    on_change(&snapshot);
+                }
+            }
+        })
+    }
+
+    /// 通知监听线程退出 (在下一次等待返回后生效)
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+fn refresh_snapshot(
+    provider: &dyn DeviceIdentity,
+    current: &RwLock<IdentitySnapshot>,
+) -> Option<IdentitySnapshot> {
+    let snapshot = provider.snapshot();
+    let mut guard = current.write().unwrap();
+    if *guard == snapshot {
+        return None;
+    }
+    log::info!(
+        "Device identity changed: interface {:?} -> {:?}, ip {} -> {}, mac {} -> {}",
+        guard.interface, snapshot.interface, guard.ip, snapshot.ip, guard.mac, snapshot.mac
+    );
+    *guard = snapshot.clone();
+    Some(snapshot)
+}
+
+/// 通过 UDP socket 推导出口 IP (不会真正发包)
+fn fallback_ip() -> Option<String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    let addr = socket.local_addr().ok()?;
+    log::info!("Local IP resolved via UDP fallback: {}", addr.ip());
+    Some(addr.ip().to_string())
+}
+
+fn fallback_mac() -> Option<String> {
+    let mac = mac_address::get_mac_address().ok()??.to_string();
+    log::info!("Local MAC resolved via mac_address: {}", mac);
+    Some(mac)
+}
+
+fn host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let name = String::from_utf8_lossy(&buf[..len]).trim().to_string();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
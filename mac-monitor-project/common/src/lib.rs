@@ -0,0 +1,4 @@
+//! Mac Monitor 各组件 (审计核心 / GUI / 流量代理 / 网络扩展) 共享的基础模块
+
+pub mod clock;
+pub mod identity;
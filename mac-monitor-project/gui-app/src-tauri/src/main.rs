@@ -6,7 +6,7 @@ use std::sync::{Arc, Mutex};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    Manager, State,
+    Emitter, Manager, State,
 };
 use tauri_plugin_shell::ShellExt;
 use tauri_plugin_autostart::MacosLauncher;
@@ -14,6 +14,11 @@ use tauri_plugin_autostart::ManagerExt;
 use tokio::time::{self, Duration};
 use interprocess::local_socket::LocalSocketStream;
 use std::io::{Read, Write};
+use mac_monitor_common::clock::{system_now_ms, LogicalClock};
+use mac_monitor_common::identity::{platform_identity, IdentityMonitor, IdentitySnapshot};
+
+// traffic-proxy 读取的设备信息
+const DEVICE_INFO_PATH: &str = "/tmp/mac_monitor_device_info.json";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct AppState {
@@ -27,7 +32,6 @@ struct AppState {
     current_pop: Option<PopNode>,
     available_pops: Vec<PopNode>,
     audit_policy_json: Option<String>,
-    logic_clock: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -195,8 +199,11 @@ async fn check_for_updates() -> Result<serde_json::Value, String> {
 async fn register(payload: RegisterPayload, state: State<'_, ManagedState>) -> Result<String, String> {
     println!("Registering device: {:?}", payload);
 
-    let _ipc_res = send_ipc_command("register", serde_json::to_value(&payload).unwrap()).await?;
-    // println!("Audit service response: {}", ipc_res); // Removed as per new code
+    let ipc_res = send_ipc_command("register", serde_json::to_value(&payload).unwrap()).await?;
+    let res: IpcResponse = serde_json::from_str(&ipc_res).map_err(|e| e.to_string())?;
+    if res.status != "ok" {
+        return Err(format!("注册失败: {}", res.message));
+    }
 
     let mut s = state.0.lock().unwrap();
     s.server_ip = payload.server_ip;
@@ -213,16 +220,14 @@ async fn login(payload: LoginPayload, state: State<'_, ManagedState>) -> Result<
 
     let ipc_res = send_ipc_command("login", serde_json::to_value(&payload).unwrap()).await?;
     let res: IpcResponse = serde_json::from_str(&ipc_res).map_err(|e| e.to_string())?;
+    if res.status != "ok" {
+        return Err(format!("登录失败: {}", res.message));
+    }
 
     let mut s = state.0.lock().unwrap();
     s.pin = payload.pin;
+    // token 由审计服务管理，不再返回给 GUI；登录状态通过 get_auth_status 查询
     s.is_logged_in = true;
-    
-    if let Some(payload) = res.payload {
-        if let Some(token) = payload["token"].as_str() {
-            s.token = token.to_string();
-        }
-    }
 
     Ok("登录成功".to_string())
 }
@@ -238,9 +243,8 @@ async fn set_device_info(
     // std::fs::write(&path, data).map_err(|e| format!("写入配置失败: {}", e))?; // Removed as per new code
 
     // Write to /tmp/mac_monitor_device_info.json for traffic-proxy
-    let path = "/tmp/mac_monitor_device_info.json";
     let data = serde_json::to_string(&payload).map_err(|e| format!("序列化失败: {}", e))?;
-    std::fs::write(path, data).map_err(|e| format!("写入共享配置失败: {}", e))?;
+    std::fs::write(DEVICE_INFO_PATH, data).map_err(|e| format!("写入共享配置失败: {}", e))?;
 
     let mut s = state.0.lock().unwrap();
     s.device_info = Some(payload);
@@ -251,106 +255,97 @@ async fn set_device_info(
 #[tauri::command]
 async fn get_system_device_info() -> Result<DeviceInfoPayload, String> {
     println!("Called get_system_device_info");
-    let mut sys = sysinfo::System::new_all();
-    sys.refresh_all();
-
-    let host_name = sysinfo::System::host_name().unwrap_or_else(|| "Unknown-Mac".to_string());
-    let serial_number = get_macos_serial_number().unwrap_or_else(|| "UNKNOWN_SERIAL".to_string());
-    let ip_addr = get_local_ip().unwrap_or_else(|| "127.0.0.1".to_string());
-    let mac_addr = get_local_mac().unwrap_or_else(|| "00:00:00:00:00:00".to_string());
+    let identity = platform_identity().snapshot();
 
-    println!("Device Info Found: Host={}, Serial={}, IP={}, MAC={}", host_name, serial_number, ip_addr, mac_addr);
+    println!(
+        "Device Info Found: Host={}, Serial={}, IP={}, MAC={}",
+        identity.host_name, identity.serial_number, identity.ip, identity.mac
+    );
 
-    Ok(DeviceInfoPayload {
-        pin_number: serial_number.clone(),
-        ip: ip_addr,
-        mac: mac_addr,
-        cpe_id: serial_number,
-        host_id: host_name,
-    })
+    Ok(DeviceInfoPayload::from(&identity))
 }
 
-fn get_macos_serial_number() -> Option<String> {
-    use std::process::Command;
-    let output = Command::new("ioreg")
-        .args(&["-c", "IOPlatformExpertDevice", "-d", "2"])
-        .output()
-        .ok()?;
-
-    let s = String::from_utf8_lossy(&output.stdout);
-    for line in s.lines() {
-        if line.contains("IOPlatformSerialNumber") {
-            let parts: Vec<&str> = line.split('=').collect();
-            if parts.len() == 2 {
-                return Some(parts[1].trim().trim_matches(|c| c == '"' || c == ' ').to_string());
-            }
+impl From<&IdentitySnapshot> for DeviceInfoPayload {
+    fn from(identity: &IdentitySnapshot) -> Self {
+        Self {
+            pin_number: identity.serial_number.clone(),
+            ip: identity.ip.clone(),
+            mac: identity.mac.clone(),
+            cpe_id: identity.serial_number.clone(),
+            host_id: identity.host_name.clone(),
         }
     }
-    None
 }
 
-fn get_primary_interface_name() -> Option<String> {
-    use std::process::Command;
-    let output = Command::new("route")
-        .args(&["-n", "get", "default"])
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
-    }
+// 网络切换后 IP / MAC 会变化，刷新共享状态并通知前端
+fn start_identity_watch(app_handle: tauri::AppHandle) {
+    let monitor = IdentityMonitor::new(platform_identity());
+    monitor.watch(Duration::from_secs(30), move |identity| {
+        log::info!("Device identity changed: IP={}, MAC={}", identity.ip, identity.mac);
 
-    let s = String::from_utf8_lossy(&output.stdout);
-    for line in s.lines() {
-        let line = line.trim();
-        if let Some(rest) = line.strip_prefix("interface:") {
-            let iface = rest.trim();
-            if !iface.is_empty() {
-                return Some(iface.to_string());
-            }
-        }
-    }
-    None
-}
-
-fn get_local_ip() -> Option<String> {
-    if let Some(interface) = get_primary_interface_name() {
-        use std::process::Command;
-        let output = Command::new("ipconfig")
-            .args(&["getifaddr", &interface])
-            .output()
-            .ok()?;
-        if output.status.success() {
-            let ip = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !ip.is_empty() {
-                return Some(ip);
+        // 只更新自动采集的字段，保留用户设置的 pin_number / cpe_id
+        let updated = {
+            let state = app_handle.state::<ManagedState>();
+            let mut s = state.0.lock().unwrap();
+            s.device_info.as_mut().map(|info| {
+                info.ip = identity.ip.clone();
+                info.mac = identity.mac.clone();
+                info.host_id = identity.host_name.clone();
+                info.clone()
+            })
+        };
+        let Some(payload) = updated else {
+            let _ = app_handle.emit("device-info-changed", DeviceInfoPayload::from(identity));
+            return;
+        };
+
+        // 与 set_device_info 一样同步给 traffic-proxy
+        match serde_json::to_string(&payload) {
+            Ok(data) => {
+                if let Err(e) = std::fs::write(DEVICE_INFO_PATH, data) {
+                    log::error!("Failed to write {}: {}", DEVICE_INFO_PATH, e);
+                }
             }
+            Err(e) => log::error!("Failed to serialize device info: {}", e),
         }
-    }
-    None
+        let _ = app_handle.emit("device-info-changed", payload);
+    });
 }
 
-fn get_local_mac() -> Option<String> {
-    let interface = get_primary_interface_name()?;
-    use std::process::Command;
-    let output = Command::new("ifconfig")
-        .arg(&interface)
-        .output()
-        .ok()?;
-    if !output.status.success() {
-        return None;
-    }
-
-    let s = String::from_utf8_lossy(&output.stdout);
-    for line in s.lines() {
-        let line = line.trim();
-        if let Some(rest) = line.strip_prefix("ether ") {
-            let mac = rest.split_whitespace().next().unwrap_or("").trim();
-            if !mac.is_empty() {
-                return Some(mac.to_string());
+// 长轮询审计服务的高风险告警 (get_alerts)，逐条转发给前端
+fn start_alert_watch(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut after: Option<u64> = None;
+        loop {
+            let payload = match after {
+                Some(seq) => serde_json::json!({ "after": seq, "wait_ms": 30_000 }),
+                // 首次只取当前进度，不重放 GUI 启动前的告警
+                None => serde_json::json!({}),
+            };
+            let res = match send_ipc_command("get_alerts", payload).await {
+                Ok(res) => serde_json::from_str::<serde_json::Value>(&res).unwrap_or_default(),
+                Err(_) => {
+                    time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            if res["status"] != "ok" {
+                time::sleep(Duration::from_secs(30)).await;
+                continue;
             }
+            let Some(seq) = after else {
+                after = res["payload"]["stats"]["raised"].as_u64();
+                continue;
+            };
+            let mut last = seq;
+            for alert in res["payload"]["alerts"].as_array().into_iter().flatten() {
+                last = last.max(alert["seq"].as_u64().unwrap_or(0));
+                log::warn!("Critical alert: {}", alert["table"]);
+                let _ = app_handle.emit("critical-alert", alert.clone());
+            }
+            after = Some(last);
         }
-    }
-    None
+    });
 }
 
 #[tauri::command]
@@ -424,17 +419,15 @@ fn start_heartbeat_loop(app_handle: tauri::AppHandle) {
         
         loop {
             interval.tick().await;
-            let (is_logged, token, server_ip, server_port, pop_id, clock) = {
+            let (is_logged, token, server_ip, server_port, pop_id) = {
                 let state = app_handle.state::<ManagedState>();
-                let mut s = state.0.lock().unwrap();
-                s.logic_clock += 1; // 递增本地逻辑时钟
+                let s = state.0.lock().unwrap();
                 (
-                    s.is_logged_in, 
-                    s.token.clone(), 
-                    s.server_ip.clone(), 
+                    s.is_logged_in,
+                    s.token.clone(),
+                    s.server_ip.clone(),
                     s.server_port.clone(),
                     s.current_pop.as_ref().map(|p| p.id.clone()),
-                    s.logic_clock
                 )
             };
 
@@ -442,26 +435,26 @@ fn start_heartbeat_loop(app_handle: tauri::AppHandle) {
                 let url = format!("http://{}:{}/api/v1/heartbeat", server_ip, server_port);
                 let payload = serde_json::json!({
                     "token": token,
-                    "logic_clock": clock,
+                    "logic_clock": LogicalClock::global().now_ms(),
                     "pop_id": pop_id,
                     "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
                 });
 
                 println!("💓 Heartbeat: sending to {}", url);
-                
+
+                let sent_ms = system_now_ms();
                 match client.post(&url).json(&payload).send().await {
                     Ok(resp) => {
+                        let received_ms = system_now_ms();
                         if resp.status().is_success() {
                             if let Ok(data) = resp.json::<serde_json::Value>().await {
-                                // 处理服务端逻辑时钟校准
-                                if let Some(srv_clock) = data["server_logic_clock"].as_u64() {
-                                    let state = app_handle.state::<ManagedState>();
-                                    let mut s = state.0.lock().unwrap();
-                                    if srv_clock > s.logic_clock {
-                                        s.logic_clock = srv_clock;
-                                    }
+                                // 按往返时延校准逻辑时钟 (兼容 AjaxResult 包装的 data.serverTime)
+                                let server_time = data["server_time"].as_i64()
+                                    .or_else(|| data["server_logic_clock"].as_i64())
+                                    .or_else(|| data["data"]["serverTime"].as_i64());
+                                if let Some(offset) = LogicalClock::global().record_exchange(sent_ms, server_time, received_ms) {
+                                    println!("✅ Heartbeat success: clock offset {}ms", offset);
                                 }
-                                println!("✅ Heartbeat success: server sync clock");
                             }
                         } else {
                             eprintln!("❌ Heartbeat failed with status: {}", resp.status());
@@ -616,6 +609,19 @@ async fn set_redaction_status(enabled: bool) -> Result<String, String> {
     }
 }
 
+/// 审计服务的登录状态：anonymous / authenticated / failed
+#[tauri::command]
+async fn get_auth_status() -> Result<serde_json::Value, String> {
+    let res_str = send_ipc_command("get_auth_status", serde_json::Value::Null).await?;
+    let res: IpcResponse = serde_json::from_str(&res_str).map_err(|e| e.to_string())?;
+
+    if res.status == "ok" {
+        Ok(res.payload.unwrap_or(serde_json::json!({ "state": "anonymous" })))
+    } else {
+        Err(res.message)
+    }
+}
+
 #[tauri::command]
 async fn get_screenshot_logs() -> Result<serde_json::Value, String> {
     let res_str = send_ipc_command("get_screenshot_logs", serde_json::Value::Null).await?;
@@ -654,12 +660,12 @@ fn main() {
             PopNode { id: "sg-01".into(), name: "新加坡 BGP 01".into(), latency: 45 },
             PopNode { id: "jp-01".into(), name: "东京 NTT 01".into(), latency: 60 },
         ],
-        logic_clock: 0,
         token: String::new(),
         audit_policy_json: None,
     })));
 
     tauri::Builder::default()
+        .plugin(tauri_plugin_log::Builder::new().level(log::LevelFilter::Info).build())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_autostart::init(MacosLauncher::LaunchAgent, Some(vec![])))
         .manage(app_state)
@@ -743,6 +749,8 @@ fn main() {
                 .build(app)?;
 
             start_heartbeat_loop(handle.clone());
+            start_identity_watch(handle.clone());
+            start_alert_watch(handle.clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -752,7 +760,7 @@ fn main() {
             enable_proxy, disable_proxy,
             enable_monitoring, disable_monitoring,
             get_screenshot_logs, get_clipboard_logs,
-            get_system_device_info
+            get_system_device_info, get_auth_status
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
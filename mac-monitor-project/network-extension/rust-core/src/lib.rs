@@ -96,9 +96,12 @@ pub extern "C" fn shutdown_stack() {
     log::info!("Network stack shutdown");
 }
 
+/// 宿主只传入服务端时间 (秒或毫秒)，无法测量往返时延
 #[no_mangle]
 pub extern "C" fn sync_logic_clock(server_time: i64) {
-    LogicalClock::sync(server_time);
+    if let Some(offset) = LogicalClock::global().record_server_time(clock::normalize_epoch_ms(server_time)) {
+        log::info!("Logical clock synced. Offset: {}ms", offset);
+    }
 }
 
 #[no_mangle]
@@ -184,7 +187,7 @@ pub extern "C" fn analyze_enhanced_image(
     
     // 构造审计日志
     // 实际生产中应配合 image 库保存 ptr 指向的像素数据为 JPG
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let timestamp = LogicalClock::global().now_str();
     let filename = format!("{}_{}.jpg", app_name, timestamp.replace(" ", "_"));
     let path = format!("/Users/adolf/Desktop/mac-monitor/screenshots/{}", filename);
 
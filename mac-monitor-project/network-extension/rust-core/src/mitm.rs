@@ -1,5 +1,4 @@
 use crate::clock::LogicalClock;
-use chrono::{Local, TimeZone};
 use interprocess::local_socket::LocalSocketStream;
 use lazy_static::lazy_static;
 use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType};
@@ -141,7 +140,7 @@ impl MitmProxy {
 
 fn next_log_id() -> String {
     let seq = LOG_COUNTER.fetch_add(1, Ordering::Relaxed);
-    format!("{}-{}", LogicalClock::now_ms(), seq)
+    format!("{}-{}", LogicalClock::global().now_ms(), seq)
 }
 
 pub fn set_device_info(info: DeviceInfo) {
@@ -233,15 +232,6 @@ pub fn response_body_for_url(url: &str, body: &[u8]) -> Option<String> {
     None
 }
 
-fn format_logical_time(timestamp_secs: i64) -> String {
-    Local
-        .timestamp_opt(timestamp_secs, 0)
-        .single()
-        .unwrap_or_else(Local::now)
-        .format("%Y-%m-%d %H:%M:%S")
-        .to_string()
-}
-
 #[derive(Debug, Clone, Serialize)]
 pub struct HttpRequest {
     pub method: String,
@@ -297,8 +287,8 @@ impl MitmProxy {
             return Ok(client_stream.to_vec());
         }
 
-        let req_time = format_logical_time(LogicalClock::now());
-        let resp_time = format_logical_time(LogicalClock::now());
+        let req_time = LogicalClock::global().now_str();
+        let resp_time = LogicalClock::global().now_str();
 
         // Resolve process name
         let process_name = resolve_process_name(src_port).unwrap_or_else(|| "unknown".to_string());
@@ -1,30 +1,33 @@
-use std::sync::atomic::{AtomicI64, Ordering};
-use chrono::Utc;
+use log::{debug, info};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-static CLOCK_OFFSET: AtomicI64 = AtomicI64::new(0);
+pub use mac_monitor_common::clock::{system_now_ms, LogicalClock};
 
-pub struct LogicalClock;
+const AUDIT_SOCKET: &str = "/tmp/mac_monitor_audit.sock";
 
-impl LogicalClock {
-    /// 获取当前逻辑时间（Unix 时间戳，秒）
-    pub fn now() -> i64 {
-        let system_now = Utc::now().timestamp();
-        let offset = CLOCK_OFFSET.load(Ordering::Relaxed);
-        system_now + offset
-    }
+/// 以审计服务 (已与管理端校时) 为时间源校准本进程的逻辑时钟
+pub async fn sync_from_audit_service() -> Result<(), String> {
+    let sent_ms = system_now_ms();
+    let mut stream = tokio::net::UnixStream::connect(AUDIT_SOCKET)
+        .await
+        .map_err(|e| format!("Audit IPC not available: {}", e))?;
+
+    let command = serde_json::json!({ "command": "get_logical_time", "payload": null });
+    stream.write_all(command.to_string().as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.map_err(|e| e.to_string())?;
+    let received_ms = system_now_ms();
 
-    /// 获取当前逻辑时间（Unix 时间戳，毫秒）
-    pub fn now_ms() -> i64 {
-        let system_now = Utc::now().timestamp_millis();
-        let offset = CLOCK_OFFSET.load(Ordering::Relaxed);
-        system_now + (offset * 1000)
+    let value: serde_json::Value = serde_json::from_slice(&response).map_err(|e| e.to_string())?;
+    let payload = &value["payload"];
+    if !payload["synced"].as_bool().unwrap_or(false) {
+        debug!("Audit service clock not synced yet, skipping sample");
+        return Ok(());
     }
 
-    /// 根据管理端时间校准时钟
-    pub fn sync(server_time: i64) {
-        let system_now = Utc::now().timestamp();
-        let offset = server_time - system_now;
-        CLOCK_OFFSET.store(offset, Ordering::Relaxed);
-        log::info!("Logical clock synced. Offset: {}s", offset);
+    if let Some(offset) = LogicalClock::global().record_exchange(sent_ms, payload["logical_ms"].as_i64(), received_ms) {
+        info!("Logical clock synced via audit service. Offset: {}ms", offset);
     }
+    Ok(())
 }
@@ -1,10 +1,8 @@
-use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::env;
 
 use bytes::Bytes;
-use http_body_util::{BodyExt, Full, Empty};
+use http_body_util::{BodyExt, Full};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Method, Request, Response, StatusCode, body::Incoming};
@@ -40,6 +38,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Initial setup (Mock for now, should read from files)
     init_config();
 
+    // Periodic logical clock sync against the audit service
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = crate::clock::sync_from_audit_service().await {
+                debug!("Logical clock sync failed: {}", e);
+            }
+        }
+    });
+
     // Periodic device info refresh
     tokio::task::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
@@ -118,7 +127,7 @@ async fn tunnel(upgraded: hyper::upgrade::Upgraded, host: String, src_port: u16)
     let server_config = {
         let mut proxy = MITM_PROXY.lock().unwrap();
         proxy.generate_cert_for_domain(&host)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?
+            .map_err(|e| std::io::Error::other(e.to_string()))?
     };
 
     let acceptor = TlsAcceptor::from(server_config);
@@ -157,7 +166,7 @@ async fn handle_proxied_request(req: Request<Incoming>, domain: String, src_port
     let body_bytes = body.collect().await?.to_bytes();
     
     let log_payload = {
-        let mut proxy = MITM_PROXY.lock().unwrap();
+        let proxy = MITM_PROXY.lock().unwrap();
         proxy.prepare_audit_log(&domain, &method, &path, &body_bytes, src_port).unwrap_or(None)
     };
     
@@ -252,7 +261,7 @@ async fn handle_http_request(req: Request<Incoming>, src_port: u16) -> Result<Re
     
     let path_str = uri.path().to_string();
     let log_payload = {
-        let mut proxy = MITM_PROXY.lock().unwrap();
+        let proxy = MITM_PROXY.lock().unwrap();
         proxy.prepare_audit_log(&host, method.as_str(), &path_str, &body_bytes, src_port).unwrap_or(None)
     };
     
@@ -317,7 +326,7 @@ fn init_config() {
     // Load device info etc.
     if let Err(e) = crate::mitm::update_device_info_from_file("/tmp/mac_monitor_device_info.json") {
         info!("Device info file not found or invalid ({}), using defaults.", e);
-        let _ = crate::mitm::set_device_info(crate::mitm::DeviceInfo {
+        crate::mitm::set_device_info(crate::mitm::DeviceInfo {
             ip: "127.0.0.1".into(),
             mac: "00:00:00:00:00:00".into(),
             pin_number: "unknown".into(),
@@ -1,12 +1,10 @@
 use crate::clock::LogicalClock;
-use chrono::{Local, TimeZone};
 use lazy_static::lazy_static;
 use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType, KeyPair};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use rustls::ServerConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
@@ -105,7 +103,7 @@ impl MitmProxy {
         params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
         params.serial_number = Some(123456789.into()); // Constant serial
 
-        let cert = Certificate::from_params(params).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let cert = Certificate::from_params(params).map_err(std::io::Error::other)?;
         let cert_pem = cert.serialize_pem().unwrap();
         let key_pem = cert.serialize_private_key_pem();
 
@@ -160,8 +158,8 @@ impl MitmProxy {
         }
 
         let device_info = current_device_info();
-        let req_time = format_logical_time(LogicalClock::now());
-        let resp_time = format_logical_time(LogicalClock::now());
+        let req_time = LogicalClock::global().now_str();
+        let resp_time = LogicalClock::global().now_str();
 
         // Resolve process name
         let process_name = resolve_process_name(src_port).unwrap_or_else(|| "unknown".to_string());
@@ -183,6 +181,7 @@ impl MitmProxy {
             response_body: None,
             title: None,
             process_name,
+            bytes: body.len() as i64,
         };
 
         let command = serde_json::json!({
@@ -198,15 +197,15 @@ fn resolve_process_name(port: u16) -> Option<String> {
     use std::process::Command;
     // Command: lsof -i :<port> -sTCP:ESTABLISHED -F c
     let output = Command::new("lsof")
-        .args(&["-i", &format!(":{}", port), "-sTCP:ESTABLISHED", "-F", "c"])
+        .args(["-i", &format!(":{}", port), "-sTCP:ESTABLISHED", "-F", "c"])
         .output()
         .ok()?;
 
     if output.status.success() {
         let s = String::from_utf8_lossy(&output.stdout);
         for line in s.lines() {
-            if line.starts_with('c') {
-                return Some(line[1..].to_string());
+            if let Some(name) = line.strip_prefix('c') {
+                return Some(name.to_string());
             }
         }
     }
@@ -215,7 +214,7 @@ fn resolve_process_name(port: u16) -> Option<String> {
 
 fn next_log_id() -> String {
     let seq = LOG_COUNTER.fetch_add(1, Ordering::Relaxed);
-    format!("{}-{}", LogicalClock::now_ms(), seq)
+    format!("{}-{}", LogicalClock::global().now_ms(), seq)
 }
 
 pub fn set_device_info(info: DeviceInfo) {
@@ -234,6 +233,7 @@ pub fn update_device_info_from_file(path: &str) -> Result<()> {
     Ok(())
 }
 
+#[allow(dead_code)]
 pub fn set_audit_policy_json(json: &str) -> Result<()> {
     let policy: AuditPolicy = serde_json::from_str(json)?;
     let mut current = GLOBAL_AUDIT_POLICY.lock().unwrap();
@@ -251,8 +251,7 @@ fn should_log_request(domain: &str, url: &str) -> bool {
             if !entry.enabled { continue; }
             let target = entry.domain.trim().to_ascii_lowercase();
             if target.is_empty() { continue; }
-            if target.starts_with("*.") {
-                 let suffix = &target[2..];
+            if let Some(suffix) = target.strip_prefix("*.") {
                  if domain_lower == suffix || domain_lower.ends_with(&format!(".{}", suffix)) {
                      matched = true; break;
                  }
@@ -293,6 +292,7 @@ fn should_log_request(domain: &str, url: &str) -> bool {
     true
 }
 
+#[allow(dead_code)]
 pub fn response_body_for_url(url: &str, body: &[u8]) -> Option<String> {
     if body.is_empty() { return None; }
     let policy = GLOBAL_AUDIT_POLICY.lock().unwrap();
@@ -309,10 +309,6 @@ pub fn response_body_for_url(url: &str, body: &[u8]) -> Option<String> {
     None
 }
 
-fn format_logical_time(timestamp_secs: i64) -> String {
-    Local.timestamp_opt(timestamp_secs, 0).single().unwrap_or_else(Local::now).format("%Y-%m-%d %H:%M:%S").to_string()
-}
-
 #[derive(Debug, Clone, Serialize)]
 pub struct AuditLog {
     pub pin_number: String,
@@ -334,9 +330,12 @@ pub struct AuditLog {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     pub process_name: String,
+    /// 请求体字节数，审计服务聚合模式下累计
+    pub bytes: i64,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
+#[allow(dead_code)]
 struct AuditPolicy {
     #[serde(default)]
     white_domains: Vec<WhiteDomain>,
@@ -347,6 +346,7 @@ struct AuditPolicy {
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
 struct WhiteDomain {
     domain: String,
     #[serde(default)]
@@ -361,6 +361,7 @@ struct TargetDomain {
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
 struct ResponseConfig {
     #[serde(default)]
     rspbodylength: usize,